@@ -0,0 +1,85 @@
+use compression::{parallel_zstd_encoder, ParallelGzEncoder};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use flate2::{write::GzEncoder, Compression};
+use std::io::Write;
+
+/// Log-like input that compresses about as well as real log bundles.
+fn fixture_logs(size: usize) -> Vec<u8> {
+    let mut logs = Vec::with_capacity(size);
+    let mut line = 0u64;
+    while logs.len() < size {
+        writeln!(
+            logs,
+            "2024-10-{:02}T12:{:02}:{:02}Z INFO worker-{} served request {} in {}ms",
+            line % 28 + 1,
+            line % 60,
+            (line * 7) % 60,
+            line % 16,
+            line.wrapping_mul(2_654_435_761) % 1_000_000,
+            line % 250
+        )
+        .unwrap();
+        line += 1;
+    }
+    logs.truncate(size);
+    logs
+}
+
+fn bench_gzip(c: &mut Criterion) {
+    let input = fixture_logs(32 * 1024 * 1024);
+    let mut group = c.benchmark_group("gzip");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.sample_size(10);
+
+    group.bench_function("single_threaded", |b| {
+        b.iter(|| {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&input).unwrap();
+            encoder.finish().unwrap()
+        })
+    });
+
+    for threads in [2, 4, 8] {
+        group.bench_with_input(
+            BenchmarkId::new("parallel", threads),
+            &threads,
+            |b, &threads| {
+                b.iter(|| {
+                    let mut encoder =
+                        ParallelGzEncoder::new(Vec::new(), Compression::default(), threads)
+                            .unwrap();
+                    encoder.write_all(&input).unwrap();
+                    encoder.finish().unwrap()
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn bench_zstd(c: &mut Criterion) {
+    let input = fixture_logs(32 * 1024 * 1024);
+    let mut group = c.benchmark_group("zstd");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.sample_size(10);
+
+    for threads in [0, 2, 4, 8] {
+        group.bench_with_input(
+            BenchmarkId::new("workers", threads),
+            &threads,
+            |b, &threads| {
+                b.iter(|| {
+                    let mut encoder = parallel_zstd_encoder(Vec::new(), 3, threads).unwrap();
+                    encoder.write_all(&input).unwrap();
+                    encoder.finish().unwrap()
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_gzip, bench_zstd);
+criterion_main!(benches);
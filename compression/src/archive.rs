@@ -0,0 +1,720 @@
+use crate::{parallel_zstd_encoder, safe_extraction::sanitize, ParallelGzEncoder};
+use bzip2::{read::BzDecoder, write::BzEncoder};
+use flate2::{read::GzDecoder, write::GzEncoder};
+use std::{
+    fs::{self, DirBuilder, File},
+    io::{self, BufReader, Error, ErrorKind, Read, Seek, SeekFrom, Write},
+    ops::RangeInclusive,
+    path::{Component, Path, PathBuf},
+};
+use tar::{Archive, Builder};
+use walkdir::WalkDir;
+use xz2::{read::XzDecoder, write::XzEncoder};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+/// Archive formats supported by the compression crate.
+///
+/// # Examples
+///
+/// ```
+/// use compression::ArchiveFormat;
+///
+/// let format = ArchiveFormat::from_extension("logs.tar.zst").unwrap();
+/// assert_eq!(format, ArchiveFormat::TarZst);
+/// assert_eq!(format.extension(), "tar.zst");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArchiveFormat {
+    TarGz,
+    TarBz2,
+    TarXz,
+    TarZst,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// All the supported formats.
+    pub const ALL: [ArchiveFormat; 5] = [
+        ArchiveFormat::TarGz,
+        ArchiveFormat::TarBz2,
+        ArchiveFormat::TarXz,
+        ArchiveFormat::TarZst,
+        ArchiveFormat::Zip,
+    ];
+
+    /// Detect the format from the magic bytes at the start of an archive
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The first bytes of the archive, at least 6 are needed to recognize every format
+    ///
+    /// # Returns
+    ///
+    /// * `Some(ArchiveFormat)` if the bytes match a known signature, `None` otherwise
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compression::ArchiveFormat;
+    ///
+    /// assert_eq!(ArchiveFormat::from_magic(&[0x1f, 0x8b, 0x08]), Some(ArchiveFormat::TarGz));
+    /// assert_eq!(ArchiveFormat::from_magic(b"PK\x03\x04"), Some(ArchiveFormat::Zip));
+    /// assert_eq!(ArchiveFormat::from_magic(b"plain text"), None);
+    /// ```
+    pub fn from_magic(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0x1f, 0x8b, ..] => Some(ArchiveFormat::TarGz),
+            [b'B', b'Z', b'h', ..] => Some(ArchiveFormat::TarBz2),
+            [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => Some(ArchiveFormat::TarXz),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(ArchiveFormat::TarZst),
+            [b'P', b'K', 0x03, 0x04, ..] | [b'P', b'K', 0x05, 0x06, ..] => Some(ArchiveFormat::Zip),
+            _ => None,
+        }
+    }
+
+    /// Detect the format of an archive by peeking at its magic bytes
+    ///
+    /// The reader is rewound to the position it had before the call.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - A seekable reader positioned at the start of the archive
+    ///
+    /// # Returns
+    ///
+    /// * The detected format, or an `InvalidData` error if the signature is unknown
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compression::ArchiveFormat;
+    /// use std::io::Cursor;
+    ///
+    /// let mut reader = Cursor::new(vec![0x28, 0xb5, 0x2f, 0xfd, 0x00, 0x00]);
+    /// assert_eq!(ArchiveFormat::detect(&mut reader).unwrap(), ArchiveFormat::TarZst);
+    /// assert_eq!(reader.position(), 0);
+    /// ```
+    pub fn detect<R: Read + Seek>(reader: &mut R) -> Result<Self, Error> {
+        let start = reader.stream_position()?;
+        let mut magic = Vec::with_capacity(6);
+        reader.by_ref().take(6).read_to_end(&mut magic)?;
+        reader.seek(SeekFrom::Start(start))?;
+
+        Self::from_magic(&magic)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Unknown archive format"))
+    }
+
+    /// Guess the format from a file name
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A path whose extension is one of `tar.gz`, `tgz`, `tar.bz2`, `tbz2`, `tar.xz`, `txz`, `tar.zst`, `tzst` or `zip`
+    ///
+    /// # Returns
+    ///
+    /// * `Some(ArchiveFormat)` if the extension is known, `None` otherwise
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compression::ArchiveFormat;
+    ///
+    /// assert_eq!(ArchiveFormat::from_extension("backup.tgz"), Some(ArchiveFormat::TarGz));
+    /// assert_eq!(ArchiveFormat::from_extension("backup.tar"), None);
+    /// ```
+    pub fn from_extension<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let name = path.as_ref().file_name()?.to_str()?.to_lowercase();
+        let ends_with_any = |extensions: &[&str]| extensions.iter().any(|ext| name.ends_with(ext));
+
+        if ends_with_any(&[".tar.gz", ".tgz"]) {
+            Some(ArchiveFormat::TarGz)
+        } else if ends_with_any(&[".tar.bz2", ".tbz2"]) {
+            Some(ArchiveFormat::TarBz2)
+        } else if ends_with_any(&[".tar.xz", ".txz"]) {
+            Some(ArchiveFormat::TarXz)
+        } else if ends_with_any(&[".tar.zst", ".tzst"]) {
+            Some(ArchiveFormat::TarZst)
+        } else if ends_with_any(&[".zip"]) {
+            Some(ArchiveFormat::Zip)
+        } else {
+            None
+        }
+    }
+
+    /// The canonical file extension of the format, without the leading dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::TarBz2 => "tar.bz2",
+            ArchiveFormat::TarXz => "tar.xz",
+            ArchiveFormat::TarZst => "tar.zst",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+
+    /// The compression levels accepted by the codec of the format
+    ///
+    /// Level 0 stores zip entries without compression.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compression::ArchiveFormat;
+    ///
+    /// assert_eq!(ArchiveFormat::TarZst.level_range(), 1..=22);
+    /// ```
+    pub fn level_range(&self) -> RangeInclusive<u32> {
+        match self {
+            ArchiveFormat::TarBz2 => 1..=9,
+            ArchiveFormat::TarZst => 1..=22,
+            ArchiveFormat::TarGz | ArchiveFormat::TarXz | ArchiveFormat::Zip => 0..=9,
+        }
+    }
+
+    /// The compression level used when none is given to the builder
+    pub fn default_level(&self) -> u32 {
+        match self {
+            ArchiveFormat::TarZst => 3,
+            _ => 6,
+        }
+    }
+
+    /// Whether the format is a compressed tarball
+    pub fn is_tar(&self) -> bool {
+        !matches!(self, ArchiveFormat::Zip)
+    }
+}
+
+/// A compressing writer for the tar based formats.
+pub(crate) enum Encoder<W: Write> {
+    Gz(GzEncoder<W>),
+    ParallelGz(ParallelGzEncoder<W>),
+    Bz2(BzEncoder<W>),
+    Xz(XzEncoder<W>),
+    Zst(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> Encoder<W> {
+    pub(crate) fn new(
+        writer: W,
+        format: ArchiveFormat,
+        level: u32,
+        threads: Option<u32>,
+    ) -> Result<Self, Error> {
+        let encoder = match (format, threads) {
+            (ArchiveFormat::TarGz, None) => {
+                Encoder::Gz(GzEncoder::new(writer, flate2::Compression::new(level)))
+            }
+            (ArchiveFormat::TarGz, Some(threads)) => Encoder::ParallelGz(ParallelGzEncoder::new(
+                writer,
+                flate2::Compression::new(level),
+                threads as usize,
+            )?),
+            (ArchiveFormat::TarZst, Some(threads)) => {
+                Encoder::Zst(parallel_zstd_encoder(writer, level as i32, threads)?)
+            }
+            (ArchiveFormat::TarBz2, _) => {
+                Encoder::Bz2(BzEncoder::new(writer, bzip2::Compression::new(level)))
+            }
+            (ArchiveFormat::TarXz, _) => Encoder::Xz(XzEncoder::new(writer, level)),
+            (ArchiveFormat::TarZst, None) => {
+                Encoder::Zst(zstd::Encoder::new(writer, level as i32)?)
+            }
+            (ArchiveFormat::Zip, _) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Zip archives are not a compressed stream",
+                ))
+            }
+        };
+        Ok(encoder)
+    }
+
+    /// Flush the compressed trailer and return the underlying writer.
+    pub(crate) fn finish(self) -> Result<W, Error> {
+        match self {
+            Encoder::Gz(encoder) => encoder.finish(),
+            Encoder::ParallelGz(encoder) => encoder.finish(),
+            Encoder::Bz2(encoder) => encoder.finish(),
+            Encoder::Xz(encoder) => encoder.finish(),
+            Encoder::Zst(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::Gz(encoder) => encoder.write(buf),
+            Encoder::ParallelGz(encoder) => encoder.write(buf),
+            Encoder::Bz2(encoder) => encoder.write(buf),
+            Encoder::Xz(encoder) => encoder.write(buf),
+            Encoder::Zst(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::Gz(encoder) => encoder.flush(),
+            Encoder::ParallelGz(encoder) => encoder.flush(),
+            Encoder::Bz2(encoder) => encoder.flush(),
+            Encoder::Xz(encoder) => encoder.flush(),
+            Encoder::Zst(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// A decompressing reader for the tar based formats.
+pub(crate) enum Decoder<R: Read> {
+    Gz(GzDecoder<R>),
+    Bz2(BzDecoder<R>),
+    Xz(XzDecoder<R>),
+    Zst(zstd::Decoder<'static, BufReader<R>>),
+}
+
+impl<R: Read> Decoder<R> {
+    pub(crate) fn new(reader: R, format: ArchiveFormat) -> Result<Self, Error> {
+        let decoder = match format {
+            ArchiveFormat::TarGz => Decoder::Gz(GzDecoder::new(reader)),
+            ArchiveFormat::TarBz2 => Decoder::Bz2(BzDecoder::new(reader)),
+            ArchiveFormat::TarXz => Decoder::Xz(XzDecoder::new(reader)),
+            ArchiveFormat::TarZst => Decoder::Zst(zstd::Decoder::new(reader)?),
+            ArchiveFormat::Zip => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Zip archives are not a compressed stream",
+                ))
+            }
+        };
+        Ok(decoder)
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Decoder::Gz(decoder) => decoder.read(buf),
+            Decoder::Bz2(decoder) => decoder.read(buf),
+            Decoder::Xz(decoder) => decoder.read(buf),
+            Decoder::Zst(decoder) => decoder.read(buf),
+        }
+    }
+}
+
+/// Builder that selects the format and compression level of a new archive
+///
+/// # Examples
+///
+/// ```
+/// use compression::{ArchiveBuilder, ArchiveFormat};
+/// use std::fs::remove_file;
+///
+/// let mut writer = ArchiveBuilder::new(ArchiveFormat::TarXz)
+///     .level(9)
+///     .create("test_builder.tar.xz")
+///     .unwrap();
+/// writer.append_dir_all("backup/src", "src").unwrap();
+/// writer.finish().unwrap();
+/// remove_file("test_builder.tar.xz").unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArchiveBuilder {
+    format: ArchiveFormat,
+    level: Option<u32>,
+    threads: Option<u32>,
+}
+
+impl ArchiveBuilder {
+    /// Create a builder for the given format using its default compression level
+    pub fn new(format: ArchiveFormat) -> Self {
+        ArchiveBuilder {
+            format,
+            level: None,
+            threads: None,
+        }
+    }
+
+    /// Set the compression level, it must be inside `ArchiveFormat::level_range`
+    pub fn level(mut self, level: u32) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Compress on a pool of `threads` workers, `0` uses one per logical CPU
+    ///
+    /// tar.gz archives are written with `ParallelGzEncoder` and tar.zst archives
+    /// with the zstd multi-threaded encoder; the other formats ignore this setting.
+    pub fn threads(mut self, threads: u32) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// The format the archive will be written in
+    pub fn format(&self) -> ArchiveFormat {
+        self.format
+    }
+
+    /// Create the archive file and return a writer for it
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The path of the archive to create, truncated if it exists
+    ///
+    /// # Returns
+    ///
+    /// * An `ArchiveWriter` that must be closed with `finish`
+    pub fn create<P: AsRef<Path>>(&self, file: P) -> Result<ArchiveWriter<File>, Error> {
+        self.build(File::create(file)?)
+    }
+
+    /// Wrap an arbitrary writer in an archive writer
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The destination of the compressed archive
+    ///
+    /// # Returns
+    ///
+    /// * An `ArchiveWriter`, or an `InvalidInput` error if the level is out of range
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compression::{ArchiveBuilder, ArchiveFormat};
+    /// use std::io::Cursor;
+    ///
+    /// let mut writer = ArchiveBuilder::new(ArchiveFormat::Zip)
+    ///     .build(Cursor::new(Vec::new()))
+    ///     .unwrap();
+    /// writer.append_file("Cargo.toml", "Cargo.toml").unwrap();
+    /// let bytes = writer.finish().unwrap().into_inner();
+    /// assert_eq!(&bytes[..2], b"PK");
+    ///
+    /// assert!(ArchiveBuilder::new(ArchiveFormat::TarGz)
+    ///     .level(42)
+    ///     .build(Cursor::new(Vec::new()))
+    ///     .is_err());
+    /// ```
+    pub fn build<W: Write + Seek>(&self, writer: W) -> Result<ArchiveWriter<W>, Error> {
+        let level = self.level.unwrap_or(self.format.default_level());
+        if !self.format.level_range().contains(&level) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Compression level {} is outside of {:?} for {}",
+                    level,
+                    self.format.level_range(),
+                    self.format.extension()
+                ),
+            ));
+        }
+
+        let inner = match self.format {
+            ArchiveFormat::Zip => WriterInner::Zip {
+                writer: ZipWriter::new(writer),
+                options: match level {
+                    0 => SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+                    level => SimpleFileOptions::default()
+                        .compression_method(CompressionMethod::Deflated)
+                        .compression_level(Some(level as i64)),
+                },
+            },
+            format => WriterInner::Tar(Builder::new(Encoder::new(
+                writer,
+                format,
+                level,
+                self.threads,
+            )?)),
+        };
+
+        Ok(ArchiveWriter {
+            format: self.format,
+            inner,
+        })
+    }
+}
+
+enum WriterInner<W: Write + Seek> {
+    Tar(Builder<Encoder<W>>),
+    Zip {
+        writer: ZipWriter<W>,
+        options: SimpleFileOptions,
+    },
+}
+
+/// Streaming writer for any `ArchiveFormat`, created by `ArchiveBuilder`
+///
+/// Entries are compressed as they are appended; call `finish` to write the
+/// trailers, otherwise the archive will be truncated.
+pub struct ArchiveWriter<W: Write + Seek> {
+    format: ArchiveFormat,
+    inner: WriterInner<W>,
+}
+
+impl<W: Write + Seek> ArchiveWriter<W> {
+    /// The format the archive is being written in
+    pub fn format(&self) -> ArchiveFormat {
+        self.format
+    }
+
+    /// Recursively add a directory to the archive
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path the directory will have inside the archive
+    ///
+    /// * `src_path` - The directory on disk to add
+    pub fn append_dir_all<P: AsRef<Path>, Q: AsRef<Path>>(
+        &mut self,
+        path: P,
+        src_path: Q,
+    ) -> Result<(), Error> {
+        match &mut self.inner {
+            WriterInner::Tar(builder) => builder.append_dir_all(path, src_path),
+            WriterInner::Zip { writer, options } => {
+                let (path, src_path) = (path.as_ref(), src_path.as_ref());
+                for entry in WalkDir::new(src_path)
+                    .follow_links(true)
+                    .sort_by_file_name()
+                {
+                    let entry = entry.map_err(Error::other)?;
+                    let name =
+                        path.join(entry.path().strip_prefix(src_path).map_err(Error::other)?);
+                    if entry.file_type().is_dir() {
+                        let options =
+                            with_metadata(*options, &entry.metadata().map_err(Error::other)?);
+                        writer
+                            .add_directory(zip_entry_name(&name), options)
+                            .map_err(Error::other)?;
+                    } else {
+                        append_zip_file(writer, *options, &name, entry.path())?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Add a single file to the archive
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path the file will have inside the archive
+    ///
+    /// * `src_path` - The file on disk to add
+    pub fn append_file<P: AsRef<Path>, Q: AsRef<Path>>(
+        &mut self,
+        path: P,
+        src_path: Q,
+    ) -> Result<(), Error> {
+        match &mut self.inner {
+            WriterInner::Tar(builder) => {
+                builder.append_path_with_name(src_path.as_ref(), path.as_ref())
+            }
+            WriterInner::Zip { writer, options } => {
+                append_zip_file(writer, *options, path.as_ref(), src_path.as_ref())
+            }
+        }
+    }
+
+    /// Write the archive trailers and return the underlying writer
+    pub fn finish(self) -> Result<W, Error> {
+        match self.inner {
+            WriterInner::Tar(builder) => builder.into_inner()?.finish(),
+            WriterInner::Zip { writer, .. } => writer.finish().map_err(Error::other),
+        }
+    }
+}
+
+/// Zip entry names always use forward slashes.
+fn zip_entry_name(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn with_metadata(options: SimpleFileOptions, metadata: &fs::Metadata) -> SimpleFileOptions {
+    #[cfg(unix)]
+    let options = {
+        use std::os::unix::fs::PermissionsExt;
+        options.unix_permissions(metadata.permissions().mode())
+    };
+
+    options.large_file(metadata.len() >= u32::MAX as u64)
+}
+
+fn append_zip_file<W: Write + Seek>(
+    writer: &mut ZipWriter<W>,
+    options: SimpleFileOptions,
+    path: &Path,
+    src_path: &Path,
+) -> Result<(), Error> {
+    let mut file = File::open(src_path)?;
+    let options = with_metadata(options, &file.metadata()?);
+    writer
+        .start_file(zip_entry_name(path), options)
+        .map_err(Error::other)?;
+    io::copy(&mut file, writer)?;
+    Ok(())
+}
+
+/// Reader for any `ArchiveFormat`, detecting the format from its magic bytes
+///
+/// # Examples
+///
+/// ```
+/// use compression::{ArchiveBuilder, ArchiveFormat, ArchiveReader};
+/// use std::fs::{remove_dir_all, remove_file};
+///
+/// let mut writer = ArchiveBuilder::new(ArchiveFormat::TarBz2)
+///     .create("test_reader.archive")
+///     .unwrap();
+/// writer.append_dir_all("backup/src", "src").unwrap();
+/// writer.finish().unwrap();
+///
+/// let reader = ArchiveReader::open("test_reader.archive").unwrap();
+/// assert_eq!(reader.format(), ArchiveFormat::TarBz2);
+/// let extracted = reader
+///     .extract_removing_prefix("backup", "output_dir_reader")
+///     .unwrap();
+/// assert!(extracted.iter().any(|path| path.ends_with("lib.rs")));
+///
+/// remove_dir_all("output_dir_reader").unwrap();
+/// remove_file("test_reader.archive").unwrap();
+/// ```
+pub struct ArchiveReader<R: Read + Seek> {
+    pub(crate) format: ArchiveFormat,
+    pub(crate) reader: R,
+}
+
+impl ArchiveReader<BufReader<File>> {
+    /// Open an archive file, detecting its format
+    pub fn open<P: AsRef<Path>>(file: P) -> Result<Self, Error> {
+        Self::new(BufReader::new(File::open(file)?))
+    }
+}
+
+impl<R: Read + Seek> ArchiveReader<R> {
+    /// Wrap a reader, detecting the archive format from its magic bytes
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let format = ArchiveFormat::detect(&mut reader)?;
+        Ok(Self::with_format(reader, format))
+    }
+
+    /// Wrap a reader whose archive format is already known
+    pub fn with_format(reader: R, format: ArchiveFormat) -> Self {
+        ArchiveReader { format, reader }
+    }
+
+    /// The format of the archive
+    pub fn format(&self) -> ArchiveFormat {
+        self.format
+    }
+
+    /// Consume the archive reader, returning the underlying reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Extract every entry into a directory, creating it if needed
+    ///
+    /// # Arguments
+    ///
+    /// * `output_dir` - The directory to extract the archive into
+    pub fn extract<P: AsRef<Path>>(self, output_dir: P) -> Result<(), Error> {
+        DirBuilder::new().recursive(true).create(&output_dir)?;
+
+        match self.format {
+            ArchiveFormat::Zip => ZipArchive::new(self.reader)
+                .and_then(|mut archive| archive.extract(output_dir))
+                .map_err(Error::other),
+            format => Archive::new(Decoder::new(self.reader, format)?).unpack(output_dir),
+        }
+    }
+
+    /// Extract the entries under `prefix` into a directory, removing the prefix from their paths
+    ///
+    /// Entries outside of `prefix` are skipped, as are absolute paths and paths with `..`.
+    /// Entries reaching outside of `output_dir` through an extracted symlink are an error.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The prefix to be removed from the extracted entries
+    ///
+    /// * `output_dir` - The directory to extract the entries into
+    ///
+    /// # Returns
+    ///
+    /// * The paths of the extracted entries, relative to `output_dir`
+    pub fn extract_removing_prefix<P: AsRef<Path>, Q: AsRef<Path>>(
+        self,
+        prefix: P,
+        output_dir: Q,
+    ) -> Result<Vec<PathBuf>, Error> {
+        let (prefix, output_dir) = (prefix.as_ref(), output_dir.as_ref());
+        DirBuilder::new().recursive(true).create(output_dir)?;
+        let mut extracted = Vec::new();
+
+        match self.format {
+            ArchiveFormat::Zip => {
+                let mut archive = ZipArchive::new(self.reader).map_err(Error::other)?;
+                for index in 0..archive.len() {
+                    let mut file = archive.by_index(index).map_err(Error::other)?;
+                    let path = match file.enclosed_name() {
+                        Some(name) => match name.strip_prefix(prefix) {
+                            Ok(path) => path.to_owned(),
+                            Err(_) => continue,
+                        },
+                        None => continue,
+                    };
+                    let out_path = output_dir.join(&path);
+                    if file.is_dir() {
+                        DirBuilder::new().recursive(true).create(&out_path)?;
+                    } else {
+                        if let Some(parent) = out_path.parent() {
+                            DirBuilder::new().recursive(true).create(parent)?;
+                        }
+                        io::copy(&mut file, &mut File::create(&out_path)?)?;
+                        #[cfg(unix)]
+                        if let Some(mode) = file.unix_mode() {
+                            use std::os::unix::fs::PermissionsExt;
+                            fs::set_permissions(&out_path, fs::Permissions::from_mode(mode))?;
+                        }
+                    }
+                    extracted.push(path);
+                }
+            }
+            format => {
+                let root = output_dir.canonicalize()?;
+                let mut archive = Archive::new(Decoder::new(self.reader, format)?);
+                for entry in archive.entries()? {
+                    let mut entry = entry?;
+                    // Like the enclosed names of zip, absolute paths and `..` are skipped.
+                    let path = match entry.path()?.strip_prefix(prefix).ok().and_then(sanitize) {
+                        Some(path) => path,
+                        None => continue,
+                    };
+                    if let Some(parent) = path.parent() {
+                        let parent = output_dir.join(parent);
+                        DirBuilder::new().recursive(true).create(&parent)?;
+                        // A symlink extracted earlier must not lead outside of output_dir.
+                        if !parent.canonicalize()?.starts_with(&root) {
+                            return Err(Error::new(
+                                ErrorKind::InvalidData,
+                                format!("{} is outside of the output directory", path.display()),
+                            ));
+                        }
+                    }
+                    entry.unpack(output_dir.join(&path))?;
+                    extracted.push(path);
+                }
+            }
+        }
+
+        Ok(extracted)
+    }
+}
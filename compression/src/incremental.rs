@@ -0,0 +1,507 @@
+use crate::{ArchiveBuilder, ArchiveReader, ExtractLimits};
+use data_encoding::HEXLOWER;
+use serde::{Deserialize, Serialize};
+use shared::compute_digest;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::{BufReader, BufWriter, Error, ErrorKind},
+    path::{Component, Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+use walkdir::WalkDir;
+
+/// A file recorded in a `Manifest`.
+///
+/// # Examples
+///
+/// ```
+/// use compression::ManifestEntry;
+///
+/// let entry = ManifestEntry {
+///     path: "app/app.log".to_string(),
+///     size: 7,
+///     mtime: 1_700_000_000,
+///     sha256: "a1".repeat(32),
+/// };
+/// assert_eq!(entry.path, "app/app.log");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Path relative to the archived directory, using `/` as separator
+    pub path: String,
+    pub size: u64,
+    /// Modification time in seconds since the Unix epoch
+    pub mtime: u64,
+    /// Lowercase hexadecimal SHA-256 of the file contents
+    pub sha256: String,
+}
+
+/// Snapshot of a directory written alongside each archive.
+///
+/// `entries` always describes the whole directory at archiving time, while
+/// `archived` lists the files actually stored in the archive and `deleted`
+/// the files that disappeared since the base manifest.
+///
+/// # Examples
+///
+/// ```
+/// use compression::Manifest;
+///
+/// let manifest = Manifest::scan("src", None).unwrap();
+/// assert!(manifest.entries.contains_key("lib.rs"));
+/// assert!(manifest.base.is_none());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// File name of the archive this one is incremental to, `None` for full archives
+    pub base: Option<String>,
+    pub entries: BTreeMap<String, ManifestEntry>,
+    pub archived: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+impl Manifest {
+    /// Describe every file under a directory
+    ///
+    /// Files whose size and modification time match the `base` manifest reuse its
+    /// digest instead of being hashed again.
+    ///
+    /// # Arguments
+    ///
+    /// * `src_path` - The directory to scan
+    ///
+    /// * `base` - An optional previous manifest of the same directory
+    ///
+    /// # Returns
+    ///
+    /// * A manifest with every file in `entries` and nothing archived or deleted yet
+    pub fn scan<P: AsRef<Path>>(src_path: P, base: Option<&Manifest>) -> Result<Self, Error> {
+        let src_path = src_path.as_ref();
+        let mut entries = BTreeMap::new();
+
+        for entry in WalkDir::new(src_path)
+            .follow_links(true)
+            .sort_by_file_name()
+        {
+            let entry = entry.map_err(Error::other)?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let path =
+                manifest_path_name(entry.path().strip_prefix(src_path).map_err(Error::other)?);
+            let metadata = entry.metadata().map_err(Error::other)?;
+            let size = metadata.len();
+            let mtime = metadata
+                .modified()?
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs());
+
+            let sha256 = match base.and_then(|base| base.entries.get(&path)) {
+                Some(previous) if previous.size == size && previous.mtime == mtime => {
+                    previous.sha256.clone()
+                }
+                _ => {
+                    let (digest, _) = compute_digest(entry.path())?;
+                    HEXLOWER.encode(digest.as_ref())
+                }
+            };
+
+            entries.insert(
+                path.clone(),
+                ManifestEntry {
+                    path,
+                    size,
+                    mtime,
+                    sha256,
+                },
+            );
+        }
+
+        Ok(Manifest {
+            entries,
+            ..Manifest::default()
+        })
+    }
+
+    /// Load a manifest from a JSON file
+    pub fn load<P: AsRef<Path>>(file: P) -> Result<Self, Error> {
+        let reader = BufReader::new(File::open(file)?);
+        serde_json::from_reader(reader).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    /// Save the manifest to a JSON file
+    pub fn save<P: AsRef<Path>>(&self, file: P) -> Result<(), Error> {
+        let writer = BufWriter::new(File::create(file)?);
+        serde_json::to_writer_pretty(writer, self).map_err(Error::other)
+    }
+
+    /// Compare the manifest with an older one of the same directory
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - The older manifest
+    ///
+    /// # Returns
+    ///
+    /// * A tuple with the paths added or modified since `base`, and the paths deleted since `base`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compression::{Manifest, ManifestEntry};
+    ///
+    /// let entry = |path: &str, sha256: &str| ManifestEntry {
+    ///     path: path.to_string(),
+    ///     size: 1,
+    ///     mtime: 0,
+    ///     sha256: sha256.to_string(),
+    /// };
+    /// let mut base = Manifest::default();
+    /// base.entries.insert("kept".to_string(), entry("kept", "aa"));
+    /// base.entries.insert("gone".to_string(), entry("gone", "bb"));
+    ///
+    /// let mut current = Manifest::default();
+    /// current.entries.insert("kept".to_string(), entry("kept", "cc"));
+    /// current.entries.insert("new".to_string(), entry("new", "dd"));
+    ///
+    /// let (changed, deleted) = current.changes_since(&base);
+    /// assert_eq!(changed, vec!["kept", "new"]);
+    /// assert_eq!(deleted, vec!["gone"]);
+    /// ```
+    pub fn changes_since(&self, base: &Manifest) -> (Vec<String>, Vec<String>) {
+        let changed = self
+            .entries
+            .values()
+            .filter(|entry| match base.entries.get(&entry.path) {
+                Some(previous) => previous.size != entry.size || previous.sha256 != entry.sha256,
+                None => true,
+            })
+            .map(|entry| entry.path.clone())
+            .collect();
+
+        let deleted = base
+            .entries
+            .keys()
+            .filter(|path| !self.entries.contains_key(*path))
+            .cloned()
+            .collect();
+
+        (changed, deleted)
+    }
+}
+
+/// The path of the manifest written alongside an archive
+///
+/// # Examples
+///
+/// ```
+/// use compression::manifest_file;
+/// use std::path::Path;
+///
+/// assert_eq!(
+///     manifest_file("backups/logs.tar.gz"),
+///     Path::new("backups/logs.tar.gz.manifest.json")
+/// );
+/// ```
+pub fn manifest_file<P: AsRef<Path>>(archive: P) -> PathBuf {
+    let mut file = archive.as_ref().as_os_str().to_owned();
+    file.push(".manifest.json");
+    PathBuf::from(file)
+}
+
+/// Manifest paths always use forward slashes.
+fn manifest_path_name(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Archive a whole directory and write its manifest alongside
+///
+/// Entries are stored relative to `src_path`.
+///
+/// # Arguments
+///
+/// * `file` - The path of the archive to create, the manifest is written to `manifest_file(file)`
+///
+/// * `src_path` - The directory to archive
+///
+/// * `builder` - The format and compression level of the archive
+///
+/// # Returns
+///
+/// * The manifest of the archive
+///
+/// # Examples
+///
+/// ```
+/// use compression::{compress_full, manifest_file, ArchiveBuilder, ArchiveFormat};
+/// use std::fs::remove_file;
+///
+/// let builder = ArchiveBuilder::new(ArchiveFormat::TarGz);
+/// let manifest = compress_full("test_full.tar.gz", "src", &builder).unwrap();
+/// assert_eq!(manifest.archived.len(), manifest.entries.len());
+///
+/// remove_file(manifest_file("test_full.tar.gz")).unwrap();
+/// remove_file("test_full.tar.gz").unwrap();
+/// ```
+pub fn compress_full<P: AsRef<Path>, Q: AsRef<Path>>(
+    file: P,
+    src_path: Q,
+    builder: &ArchiveBuilder,
+) -> Result<Manifest, Error> {
+    let manifest = Manifest::scan(&src_path, None)?;
+    write_archive(file.as_ref(), src_path.as_ref(), builder, manifest, None)
+}
+
+/// Archive only the files that changed since a previous manifest
+///
+/// Files are compared by size and SHA-256; deletions are recorded in the new
+/// manifest so that `restore_archives` can replay them.
+///
+/// # Arguments
+///
+/// * `file` - The path of the archive to create, the manifest is written to `manifest_file(file)`
+///
+/// * `src_path` - The directory to archive
+///
+/// * `base` - The path of the previous archive, whose manifest is read from `manifest_file(base)`
+///
+/// * `builder` - The format and compression level of the archive
+///
+/// # Returns
+///
+/// * The manifest of the incremental archive
+///
+/// # Examples
+///
+/// ```
+/// use compression::{compress_full, compress_incremental, manifest_file, ArchiveBuilder, ArchiveFormat};
+/// use std::fs::remove_file;
+///
+/// let builder = ArchiveBuilder::new(ArchiveFormat::TarZst);
+/// compress_full("test_base.tar.zst", "src", &builder).unwrap();
+/// let manifest = compress_incremental("test_incr.tar.zst", "src", "test_base.tar.zst", &builder).unwrap();
+/// assert!(manifest.archived.is_empty());
+/// assert_eq!(manifest.base.as_deref(), Some("test_base.tar.zst"));
+///
+/// for file in ["test_base.tar.zst", "test_incr.tar.zst"] {
+///     remove_file(manifest_file(file)).unwrap();
+///     remove_file(file).unwrap();
+/// }
+/// ```
+pub fn compress_incremental<P: AsRef<Path>, Q: AsRef<Path>, B: AsRef<Path>>(
+    file: P,
+    src_path: Q,
+    base: B,
+    builder: &ArchiveBuilder,
+) -> Result<Manifest, Error> {
+    let base_manifest = Manifest::load(manifest_file(&base))?;
+    let manifest = Manifest::scan(&src_path, Some(&base_manifest))?;
+    let base_name = base
+        .as_ref()
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned());
+
+    write_archive(
+        file.as_ref(),
+        src_path.as_ref(),
+        builder,
+        manifest,
+        Some((base_name, &base_manifest)),
+    )
+}
+
+fn write_archive(
+    file: &Path,
+    src_path: &Path,
+    builder: &ArchiveBuilder,
+    mut manifest: Manifest,
+    base: Option<(Option<String>, &Manifest)>,
+) -> Result<Manifest, Error> {
+    let (archived, deleted) = match base {
+        Some((base_name, base_manifest)) => {
+            manifest.base = base_name;
+            manifest.changes_since(base_manifest)
+        }
+        None => (manifest.entries.keys().cloned().collect(), Vec::new()),
+    };
+
+    let mut writer = builder.create(file)?;
+    for path in &archived {
+        writer.append_file(path, src_path.join(path))?;
+    }
+    writer.finish()?;
+
+    manifest.archived = archived;
+    manifest.deleted = deleted;
+    manifest.save(manifest_file(file))?;
+
+    Ok(manifest)
+}
+
+/// Restore a full archive followed by its incrementals
+///
+/// Each archive is extracted in order with `extract_safely` and the deletions recorded
+/// in its manifest are applied, leaving `output_dir` as the directory was when the last
+/// archive was made. Every manifest is checked before anything is written: each
+/// incremental must be based on the archive before it, and deleted paths must be
+/// relative without `..`.
+///
+/// # Arguments
+///
+/// * `archives` - The full archive first, then the incrementals in the order they were made
+///
+/// * `output_dir` - The directory to restore into
+///
+/// # Returns
+///
+/// * The manifest of the last archive
+///
+/// # Examples
+///
+/// ```
+/// use compression::{compress_full, manifest_file, restore_archives, ArchiveBuilder, ArchiveFormat};
+/// use std::fs::{remove_dir_all, remove_file};
+///
+/// let builder = ArchiveBuilder::new(ArchiveFormat::TarGz);
+/// compress_full("test_restore.tar.gz", "src", &builder).unwrap();
+/// let manifest = restore_archives(&["test_restore.tar.gz"], "output_dir_restore").unwrap();
+/// assert!(manifest.entries.contains_key("lib.rs"));
+///
+/// remove_dir_all("output_dir_restore").unwrap();
+/// remove_file(manifest_file("test_restore.tar.gz")).unwrap();
+/// remove_file("test_restore.tar.gz").unwrap();
+/// ```
+pub fn restore_archives<P: AsRef<Path>, Q: AsRef<Path>>(
+    archives: &[P],
+    output_dir: Q,
+) -> Result<Manifest, Error> {
+    restore_archives_with(archives, output_dir, &ExtractLimits::default())
+}
+
+/// Restore a full archive followed by its incrementals, within custom extraction limits
+///
+/// Works like `restore_archives`, the limits apply to each archive in turn.
+///
+/// # Arguments
+///
+/// * `archives` - The full archive first, then the incrementals in the order they were made
+///
+/// * `output_dir` - The directory to restore into
+///
+/// * `limits` - The size, entry count and compression ratio limits of each extraction
+///
+/// # Returns
+///
+/// * The manifest of the last archive
+///
+/// # Examples
+///
+/// ```
+/// use compression::{
+///     compress_full, manifest_file, restore_archives_with, ArchiveBuilder, ArchiveFormat,
+///     ExtractLimits,
+/// };
+/// use std::fs::{remove_dir_all, remove_file};
+///
+/// let builder = ArchiveBuilder::new(ArchiveFormat::TarGz);
+/// compress_full("test_restore_with.tar.gz", "src", &builder).unwrap();
+/// let limits = ExtractLimits {
+///     max_total_bytes: 64 * 1024 * 1024,
+///     ..ExtractLimits::default()
+/// };
+/// let manifest =
+///     restore_archives_with(&["test_restore_with.tar.gz"], "output_dir_restore_with", &limits)
+///         .unwrap();
+/// assert!(manifest.entries.contains_key("lib.rs"));
+///
+/// remove_dir_all("output_dir_restore_with").unwrap();
+/// remove_file(manifest_file("test_restore_with.tar.gz")).unwrap();
+/// remove_file("test_restore_with.tar.gz").unwrap();
+/// ```
+pub fn restore_archives_with<P: AsRef<Path>, Q: AsRef<Path>>(
+    archives: &[P],
+    output_dir: Q,
+    limits: &ExtractLimits,
+) -> Result<Manifest, Error> {
+    let output_dir = output_dir.as_ref();
+    let mut manifests = archives
+        .iter()
+        .enumerate()
+        .map(|(index, archive)| {
+            let previous = index.checked_sub(1).map(|previous| &archives[previous]);
+            load_restore_manifest(archive.as_ref(), previous.map(AsRef::as_ref))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (archive, manifest) in archives.iter().zip(&manifests) {
+        ArchiveReader::open(archive)?
+            .extract_safely(output_dir, limits)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+        let archived: BTreeSet<&String> = manifest.archived.iter().collect();
+        for path in manifest
+            .deleted
+            .iter()
+            .filter(|path| !archived.contains(path))
+        {
+            match fs::remove_file(output_dir.join(path)) {
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+    }
+
+    manifests
+        .pop()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "No archives to restore"))
+}
+
+/// Load the manifest of an archive to restore, checking that it follows `previous`
+/// and that its deleted paths stay inside the output directory.
+fn load_restore_manifest(archive: &Path, previous: Option<&Path>) -> Result<Manifest, Error> {
+    let manifest = Manifest::load(manifest_file(archive))?;
+
+    let expected_base = previous
+        .and_then(Path::file_name)
+        .map(|name| name.to_string_lossy().into_owned());
+    if manifest.base != expected_base {
+        let message = match expected_base {
+            None => format!(
+                "{} is incremental, restore must start from a full archive",
+                archive.display()
+            ),
+            Some(expected) => format!(
+                "{} is not incremental to the previous archive {}",
+                archive.display(),
+                expected
+            ),
+        };
+        return Err(Error::new(ErrorKind::InvalidInput, message));
+    }
+
+    let relative = |path: &String| {
+        Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+    };
+    if let Some(path) = manifest.deleted.iter().find(|path| !relative(path)) {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "{} deletes {} outside of the output directory",
+                archive.display(),
+                path
+            ),
+        ));
+    }
+
+    Ok(manifest)
+}
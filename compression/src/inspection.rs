@@ -0,0 +1,537 @@
+use crate::{archive::Decoder, ArchiveFormat, ArchiveReader};
+use glob::Pattern;
+use std::{
+    fs::{self, DirBuilder, File},
+    io::{self, Error, ErrorKind, Read, Seek, Write},
+    path::{Path, PathBuf},
+};
+use tar::{Archive, EntryType};
+use zip::{read::ZipFile, DateTime, ZipArchive};
+
+/// The type of an archive entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Directory,
+    Symlink,
+    HardLink,
+    Other,
+}
+
+/// Metadata of an archive entry, read without extracting it
+///
+/// # Examples
+///
+/// ```
+/// use compression::{ArchiveBuilder, ArchiveFormat, ArchiveReader, EntryKind};
+/// use std::io::Cursor;
+///
+/// let mut writer = ArchiveBuilder::new(ArchiveFormat::TarGz)
+///     .build(Cursor::new(Vec::new()))
+///     .unwrap();
+/// writer.append_file("Cargo.toml", "Cargo.toml").unwrap();
+/// let mut archive = writer.finish().unwrap();
+/// archive.set_position(0);
+///
+/// let entries = ArchiveReader::new(archive).unwrap().list().unwrap();
+/// assert_eq!(entries[0].path.to_str(), Some("Cargo.toml"));
+/// assert_eq!(entries[0].kind, EntryKind::File);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveEntry {
+    pub path: PathBuf,
+    pub kind: EntryKind,
+    /// Uncompressed size in bytes
+    pub size: u64,
+    /// Unix permission bits, when the archive records them
+    pub mode: Option<u32>,
+    /// Modification time in seconds since the Unix epoch, when the archive records it
+    pub mtime: Option<u64>,
+    /// Target of symbolic and hard links
+    pub link_target: Option<PathBuf>,
+}
+
+/// The kind of damage found by `ArchiveReader::verify`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProblemKind {
+    /// The compressed stream or an entry ends early
+    Truncated,
+    /// A tar header checksum or a zip CRC-32 does not match
+    BadChecksum,
+    /// Any other decoding error, including a mismatching checksum of the compressed
+    /// stream itself
+    Corrupt,
+}
+
+/// A problem found while verifying an archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityProblem {
+    /// The entry being read when the problem was found, if its header could be read
+    pub entry: Option<PathBuf>,
+    pub kind: ProblemKind,
+    pub message: String,
+}
+
+impl IntegrityProblem {
+    fn from_error(entry: Option<PathBuf>, error: &Error) -> Self {
+        let kind = match error.kind() {
+            ErrorKind::UnexpectedEof => ProblemKind::Truncated,
+            _ => ProblemKind::Corrupt,
+        };
+
+        IntegrityProblem {
+            entry,
+            kind,
+            message: error.to_string(),
+        }
+    }
+
+    fn bad_checksum(entry: Option<PathBuf>, error: &Error) -> Self {
+        IntegrityProblem {
+            kind: ProblemKind::BadChecksum,
+            ..IntegrityProblem::from_error(entry, error)
+        }
+    }
+
+    fn truncated(entry: Option<PathBuf>, error: &Error) -> Self {
+        IntegrityProblem {
+            kind: ProblemKind::Truncated,
+            ..IntegrityProblem::from_error(entry, error)
+        }
+    }
+}
+
+/// Result of reading a whole archive with `ArchiveReader::verify`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityReport {
+    pub format: ArchiveFormat,
+    /// Number of entries read successfully
+    pub entries: usize,
+    /// Number of uncompressed bytes read successfully
+    pub bytes: u64,
+    /// Problems found, tarballs stop at the first one since the stream cannot be resynchronized
+    pub problems: Vec<IntegrityProblem>,
+}
+
+impl IntegrityReport {
+    /// Whether the archive was read without problems
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+fn tar_entry_kind(entry_type: EntryType) -> EntryKind {
+    match entry_type {
+        EntryType::Regular | EntryType::Continuous => EntryKind::File,
+        EntryType::Directory => EntryKind::Directory,
+        EntryType::Symlink => EntryKind::Symlink,
+        EntryType::Link => EntryKind::HardLink,
+        _ => EntryKind::Other,
+    }
+}
+
+fn zip_entry_kind<R: Read>(file: &ZipFile<'_, R>) -> EntryKind {
+    if file.is_dir() {
+        EntryKind::Directory
+    } else if file.is_symlink() {
+        EntryKind::Symlink
+    } else {
+        EntryKind::File
+    }
+}
+
+/// Seconds since the Unix epoch of a zip timestamp, which carries no time zone.
+fn zip_mtime(date_time: DateTime) -> u64 {
+    // Days from civil, see http://howardhinnant.github.io/date_algorithms.html
+    let (month, day) = (date_time.month() as i64, date_time.day() as i64);
+    let year = date_time.year() as i64 - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let seconds = days * 86_400
+        + date_time.hour() as i64 * 3_600
+        + date_time.minute() as i64 * 60
+        + date_time.second() as i64;
+    seconds.max(0) as u64
+}
+
+impl<R: Read + Seek> ArchiveReader<R> {
+    /// List the entries of the archive without extracting them
+    ///
+    /// # Returns
+    ///
+    /// * The entries in archive order
+    pub fn list(self) -> Result<Vec<ArchiveEntry>, Error> {
+        let mut entries = Vec::new();
+
+        match self.format {
+            ArchiveFormat::Zip => {
+                let mut archive = ZipArchive::new(self.reader).map_err(Error::other)?;
+                for index in 0..archive.len() {
+                    let mut file = archive.by_index(index).map_err(Error::other)?;
+                    let kind = zip_entry_kind(&file);
+                    let link_target = if kind == EntryKind::Symlink {
+                        let mut target = String::new();
+                        file.by_ref().take(4096).read_to_string(&mut target)?;
+                        Some(PathBuf::from(target))
+                    } else {
+                        None
+                    };
+                    entries.push(ArchiveEntry {
+                        path: PathBuf::from(file.name()),
+                        kind,
+                        size: file.size(),
+                        mode: file.unix_mode(),
+                        mtime: file.last_modified().map(zip_mtime),
+                        link_target,
+                    });
+                }
+            }
+            format => {
+                let mut archive = Archive::new(Decoder::new(self.reader, format)?);
+                for entry in archive.entries()? {
+                    let entry = entry?;
+                    let header = entry.header();
+                    entries.push(ArchiveEntry {
+                        path: entry.path()?.into_owned(),
+                        kind: tar_entry_kind(header.entry_type()),
+                        size: entry.size(),
+                        mode: header.mode().ok(),
+                        mtime: header.mtime().ok(),
+                        link_target: entry.link_name()?.map(|target| target.into_owned()),
+                    });
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Write the contents of a single entry to a writer
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The path of the entry inside the archive
+    ///
+    /// * `writer` - The destination of the entry contents
+    ///
+    /// # Returns
+    ///
+    /// * The number of bytes written, or a `NotFound` error if no entry has that path
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compression::{compress_file, ArchiveReader};
+    /// use std::fs::{read, remove_file};
+    ///
+    /// compress_file("test_extract_entry.tar.gz", "backup", "src").unwrap();
+    ///
+    /// let mut contents = Vec::new();
+    /// ArchiveReader::open("test_extract_entry.tar.gz")
+    ///     .unwrap()
+    ///     .extract_entry("backup/lib.rs", &mut contents)
+    ///     .unwrap();
+    /// assert_eq!(contents, read("src/lib.rs").unwrap());
+    ///
+    /// remove_file("test_extract_entry.tar.gz").unwrap();
+    /// ```
+    pub fn extract_entry<P: AsRef<Path>, W: Write>(
+        self,
+        name: P,
+        writer: &mut W,
+    ) -> Result<u64, Error> {
+        let name = name.as_ref();
+
+        match self.format {
+            ArchiveFormat::Zip => {
+                let mut archive = ZipArchive::new(self.reader).map_err(Error::other)?;
+                for index in 0..archive.len() {
+                    let mut file = archive.by_index(index).map_err(Error::other)?;
+                    if Path::new(file.name()) == name && !file.is_dir() {
+                        return io::copy(&mut file, writer);
+                    }
+                }
+            }
+            format => {
+                let mut archive = Archive::new(Decoder::new(self.reader, format)?);
+                for entry in archive.entries()? {
+                    let mut entry = entry?;
+                    if entry.path()? == name && entry.header().entry_type().is_file() {
+                        return io::copy(&mut entry, writer);
+                    }
+                }
+            }
+        }
+
+        Err(Error::new(
+            ErrorKind::NotFound,
+            format!("No file entry {} in the archive", name.display()),
+        ))
+    }
+
+    /// Extract the entries whose path matches a glob pattern
+    ///
+    /// Entries keep their archive path below `output_dir`; entries that would be
+    /// written outside of it are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - A glob pattern such as `backup/**/*.log`
+    ///
+    /// * `output_dir` - The directory to extract the entries into
+    ///
+    /// # Returns
+    ///
+    /// * The paths of the extracted entries, relative to `output_dir`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compression::{compress_file, ArchiveReader};
+    /// use std::fs::{remove_dir_all, remove_file};
+    ///
+    /// compress_file("test_extract_matching.tar.gz", "backup", "src").unwrap();
+    ///
+    /// let extracted = ArchiveReader::open("test_extract_matching.tar.gz")
+    ///     .unwrap()
+    ///     .extract_matching("backup/l*.rs", "output_dir_extract_matching")
+    ///     .unwrap();
+    /// assert_eq!(extracted.len(), 1);
+    ///
+    /// remove_dir_all("output_dir_extract_matching").unwrap();
+    /// remove_file("test_extract_matching.tar.gz").unwrap();
+    /// ```
+    pub fn extract_matching<P: AsRef<Path>>(
+        self,
+        pattern: &str,
+        output_dir: P,
+    ) -> Result<Vec<PathBuf>, Error> {
+        let pattern = Pattern::new(pattern).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+        let output_dir = output_dir.as_ref();
+        DirBuilder::new().recursive(true).create(output_dir)?;
+        let mut extracted = Vec::new();
+
+        match self.format {
+            ArchiveFormat::Zip => {
+                let mut archive = ZipArchive::new(self.reader).map_err(Error::other)?;
+                for index in 0..archive.len() {
+                    let mut file = archive.by_index(index).map_err(Error::other)?;
+                    let path = match file.enclosed_name() {
+                        Some(path) if pattern.matches_path(&path) => path,
+                        _ => continue,
+                    };
+                    let out_path = output_dir.join(&path);
+                    if file.is_dir() {
+                        DirBuilder::new().recursive(true).create(&out_path)?;
+                    } else {
+                        if let Some(parent) = out_path.parent() {
+                            DirBuilder::new().recursive(true).create(parent)?;
+                        }
+                        io::copy(&mut file, &mut File::create(&out_path)?)?;
+                        #[cfg(unix)]
+                        if let Some(mode) = file.unix_mode() {
+                            use std::os::unix::fs::PermissionsExt;
+                            fs::set_permissions(&out_path, fs::Permissions::from_mode(mode))?;
+                        }
+                    }
+                    extracted.push(path);
+                }
+            }
+            format => {
+                let mut archive = Archive::new(Decoder::new(self.reader, format)?);
+                for entry in archive.entries()? {
+                    let mut entry = entry?;
+                    let path = entry.path()?.into_owned();
+                    if pattern.matches_path(&path) && entry.unpack_in(output_dir)? {
+                        extracted.push(path);
+                    }
+                }
+            }
+        }
+
+        Ok(extracted)
+    }
+
+    /// Read every entry to the end, checking the compressed stream and the checksums
+    ///
+    /// # Returns
+    ///
+    /// * An `IntegrityReport`, whose `problems` are empty for a sound archive
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compression::{compress_file, ArchiveReader, ProblemKind};
+    /// use std::fs::{read, remove_file, write};
+    ///
+    /// compress_file("test_verify.tar.gz", "backup", "src").unwrap();
+    /// let report = ArchiveReader::open("test_verify.tar.gz").unwrap().verify();
+    /// assert!(report.is_ok());
+    ///
+    /// let bytes = read("test_verify.tar.gz").unwrap();
+    /// write("test_verify.tar.gz", &bytes[..bytes.len() / 2]).unwrap();
+    /// let report = ArchiveReader::open("test_verify.tar.gz").unwrap().verify();
+    /// assert_eq!(report.problems[0].kind, ProblemKind::Truncated);
+    ///
+    /// remove_file("test_verify.tar.gz").unwrap();
+    /// ```
+    pub fn verify(self) -> IntegrityReport {
+        let mut report = IntegrityReport {
+            format: self.format,
+            entries: 0,
+            bytes: 0,
+            problems: Vec::new(),
+        };
+
+        match self.format {
+            ArchiveFormat::Zip => verify_zip(self.reader, &mut report),
+            format => verify_tar(self.reader, format, &mut report),
+        }
+
+        report
+    }
+}
+
+/// Keeps the last 512-byte block read by `tar`, to tell why a header was rejected.
+struct BlockTap<R> {
+    inner: R,
+    position: usize,
+    block: [u8; 512],
+}
+
+impl<R: Read> Read for BlockTap<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        for byte in &buf[..read] {
+            self.block[self.position % 512] = *byte;
+            self.position += 1;
+        }
+        Ok(read)
+    }
+}
+
+impl<R> BlockTap<R> {
+    /// Whether the last block is a header with a valid checksum, computed with the
+    /// checksum field itself counted as spaces
+    fn header_checksum_matches(&self) -> bool {
+        let header = tar::Header::from_byte_slice(&self.block);
+        let sum = self.block[..148]
+            .iter()
+            .chain(&self.block[156..])
+            .map(|byte| *byte as u32)
+            .sum::<u32>()
+            + 8 * b' ' as u32;
+        header.cksum().is_ok_and(|cksum| cksum == sum)
+    }
+}
+
+/// Where reading a tarball failed
+enum TarFailure {
+    Header(Error),
+    Entry(Option<PathBuf>, Error),
+}
+
+fn verify_tar<R: Read>(reader: R, format: ArchiveFormat, report: &mut IntegrityReport) {
+    let decoder = match Decoder::new(reader, format) {
+        Ok(decoder) => decoder,
+        Err(e) => return report.problems.push(IntegrityProblem::from_error(None, &e)),
+    };
+    let mut archive = Archive::new(BlockTap {
+        inner: decoder,
+        position: 0,
+        block: [0; 512],
+    });
+
+    match read_tar_entries(&mut archive, report) {
+        Ok(()) => {}
+        Err(TarFailure::Header(e)) => {
+            let tap = archive.into_inner();
+            // A stream cut mid-header leaves a partial block, which has no checksum to check.
+            let problem =
+                if e.kind() == ErrorKind::UnexpectedEof || !tap.position.is_multiple_of(512) {
+                    IntegrityProblem::truncated(None, &e)
+                } else if tap.header_checksum_matches() {
+                    IntegrityProblem::from_error(None, &e)
+                } else {
+                    IntegrityProblem::bad_checksum(None, &e)
+                };
+            return report.problems.push(problem);
+        }
+        Err(TarFailure::Entry(path, e)) => {
+            return report.problems.push(IntegrityProblem::from_error(path, &e))
+        }
+    }
+
+    // The entries stop at the end-of-archive block, read the compressed stream to its
+    // end so that a truncated or corrupt trailer is noticed.
+    if let Err(e) = io::copy(&mut archive.into_inner(), &mut io::sink()) {
+        report.problems.push(IntegrityProblem::from_error(None, &e));
+    }
+}
+
+fn read_tar_entries<R: Read>(
+    archive: &mut Archive<R>,
+    report: &mut IntegrityReport,
+) -> Result<(), TarFailure> {
+    for entry in archive.entries().map_err(TarFailure::Header)? {
+        let mut entry = entry.map_err(TarFailure::Header)?;
+        let path = entry.path().ok().map(|path| path.into_owned());
+        let bytes =
+            io::copy(&mut entry, &mut io::sink()).map_err(|e| TarFailure::Entry(path, e))?;
+        report.entries += 1;
+        report.bytes += bytes;
+    }
+    Ok(())
+}
+
+fn verify_zip<R: Read + Seek>(reader: R, report: &mut IntegrityReport) {
+    let mut archive = match ZipArchive::new(reader) {
+        Ok(archive) => archive,
+        Err(e) => {
+            let error = Error::other(e);
+            return report
+                .problems
+                .push(IntegrityProblem::from_error(None, &error));
+        }
+    };
+
+    for index in 0..archive.len() {
+        let mut file = match archive.by_index(index) {
+            Ok(file) => file,
+            Err(e) => {
+                let problem = IntegrityProblem::from_error(None, &Error::other(e));
+                report.problems.push(problem);
+                continue;
+            }
+        };
+        let path = PathBuf::from(file.name());
+        let size = file.size();
+        let mut bytes = 0;
+        let mut buffer = vec![0; 64 * 1024];
+        let result = loop {
+            match file.read(&mut buffer) {
+                Ok(0) => break Ok(()),
+                Ok(read) => bytes += read as u64,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+        match result {
+            Ok(()) => {
+                report.entries += 1;
+                report.bytes += bytes;
+            }
+            // The CRC-32 is only checked once every byte has been read.
+            Err(e) if e.kind() == ErrorKind::InvalidData && bytes == size => report
+                .problems
+                .push(IntegrityProblem::bad_checksum(Some(path), &e)),
+            Err(e) => report
+                .problems
+                .push(IntegrityProblem::from_error(Some(path), &e)),
+        }
+    }
+}
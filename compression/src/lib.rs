@@ -1,13 +1,24 @@
-use flate2::{read::GzDecoder, write::GzEncoder, Compression};
-use std::{
-    fs::{DirBuilder, File},
-    io::{Error, ErrorKind},
-    path::PathBuf,
-};
-use tar::{Archive, Builder};
+mod archive;
+pub use archive::*;
+
+mod safe_extraction;
+pub use safe_extraction::*;
+
+mod incremental;
+pub use incremental::*;
+
+mod inspection;
+pub use inspection::*;
+
+mod parallel;
+pub use parallel::*;
+
+use std::io::Error;
 
 /// Compress a list of directories into a tar.gz file
 ///
+/// Use `ArchiveBuilder` to pick another format or compression level.
+///
 /// # Arguments
 ///
 /// * `file` - A string slice that holds the path to the tar.gz file
@@ -26,19 +37,20 @@ use tar::{Archive, Builder};
 /// remove_file("test_compress.tar.gz").unwrap();
 /// ```
 pub fn compress_file(file: &str, path: &str, src_path: &str) -> Result<(), Error> {
-    let tar_gz = File::create(file)?;
-    let enc = GzEncoder::new(tar_gz, Compression::default());
-    let mut tar = Builder::new(enc);
-    tar.append_dir_all(path, src_path)?;
+    let mut archive = ArchiveBuilder::new(ArchiveFormat::TarGz).create(file)?;
+    archive.append_dir_all(path, src_path)?;
+    archive.finish()?;
 
     Ok(())
 }
 
-/// Decompress a tar.gz file into a directory
+/// Decompress an archive into a directory
+///
+/// The format is detected from the magic bytes, so any `ArchiveFormat` is accepted.
 ///
 /// # Arguments
 ///
-/// * `file` - A string slice that holds the path to the tar.gz file
+/// * `file` - A string slice that holds the path to the archive
 ///
 /// * `output_dir` - A string slice that holds the path to the output directory
 ///
@@ -54,20 +66,16 @@ pub fn compress_file(file: &str, path: &str, src_path: &str) -> Result<(), Error
 /// remove_file("test_decompress.tar.gz").unwrap();
 /// ```
 pub fn decompress_file(file: &str, output_dir: &str) -> Result<(), Error> {
-    let tar_gz = File::open(file)?;
-    let tar = GzDecoder::new(tar_gz);
-    let mut archive = Archive::new(tar);
-    DirBuilder::new().recursive(true).create(output_dir)?;
-    archive.unpack(output_dir)?;
-
-    Ok(())
+    ArchiveReader::open(file)?.extract(output_dir)
 }
 
-/// Decompress a tar.gz file into a directory, removing a prefix from the extracted files
+/// Decompress an archive into a directory, removing a prefix from the extracted files
+///
+/// The format is detected from the magic bytes, so any `ArchiveFormat` is accepted.
 ///
 /// # Arguments
 ///
-/// * `file` - A string slice that holds the path to the tar.gz file
+/// * `file` - A string slice that holds the path to the archive
 ///
 /// * `prefix` - A string slice that holds the prefix to be removed from the extracted files
 ///
@@ -85,27 +93,48 @@ pub fn decompress_file(file: &str, output_dir: &str) -> Result<(), Error> {
 /// remove_file("test_decompress_prefix.tar.gz").unwrap();
 /// ```
 pub fn decompress_removing_prefix(file: &str, prefix: &str, output_dir: &str) -> Result<(), Error> {
-    let tar_gz = File::open(file)?;
-    let tar = GzDecoder::new(tar_gz);
-    let mut archive = Archive::new(tar);
-    DirBuilder::new().recursive(true).create(output_dir)?;
+    let extracted = ArchiveReader::open(file)?.extract_removing_prefix(prefix, output_dir)?;
 
     println!("Extracted the following files:");
-    archive
-        .entries()?
-        .filter_map(|e| e.ok())
-        .map(|mut entry| -> Result<PathBuf, Error> {
-            let path = entry
-                .path()?
-                .strip_prefix(prefix)
-                .map_err(|e| Error::new(ErrorKind::Other, e))?
-                .to_owned();
-            let out_path = PathBuf::from(output_dir).join(&path);
-            entry.unpack(&out_path)?;
-            Ok(path)
-        })
-        .filter_map(|e| e.ok())
-        .for_each(|x| println!("> {}", x.display()));
+    extracted.iter().for_each(|x| println!("> {}", x.display()));
+
+    Ok(())
+}
+
+/// Decompress an archive into a directory, rejecting unsafe entries
+///
+/// Entries escaping `output_dir` (absolute paths, `..`, symlinks pointing outside)
+/// are refused, and extraction stops as soon as one of the `limits` is exceeded.
+///
+/// # Arguments
+///
+/// * `file` - A string slice that holds the path to the archive
+///
+/// * `output_dir` - A string slice that holds the path to the output directory
+///
+/// * `limits` - The limits on total uncompressed bytes, entry count and compression ratio
+///
+/// # Examples
+///
+/// ```
+/// use compression::{compress_file, decompress_file_safely, ExtractError, ExtractLimits};
+/// use std::fs::{remove_dir_all, remove_file};
+///
+/// compress_file("test_decompress_safely.tar.gz", "backup/src", "src").unwrap();
+///
+/// let limits = ExtractLimits { max_entries: 1, ..ExtractLimits::default() };
+/// let result = decompress_file_safely("test_decompress_safely.tar.gz", "output_dir_decompress_safely", &limits);
+/// assert!(matches!(result, Err(ExtractError::TooManyEntries { .. })));
+///
+/// remove_dir_all("output_dir_decompress_safely").unwrap();
+/// remove_file("test_decompress_safely.tar.gz").unwrap();
+/// ```
+pub fn decompress_file_safely(
+    file: &str,
+    output_dir: &str,
+    limits: &ExtractLimits,
+) -> Result<(), ExtractError> {
+    ArchiveReader::open(file)?.extract_safely(output_dir, limits)?;
 
     Ok(())
 }
@@ -0,0 +1,238 @@
+use crc32fast::Hasher;
+use flate2::{Compress, Compression, FlushCompress, Status};
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+use std::io::{self, Error, Write};
+
+/// Default size of the blocks compressed in parallel, the same as pigz.
+pub const DEFAULT_BLOCK_SIZE: usize = 128 * 1024;
+
+/// Blocks queued per worker before a batch is compressed.
+const BLOCKS_PER_THREAD: usize = 4;
+
+/// A pigz-style gzip writer that compresses blocks of input on a rayon pool
+///
+/// Blocks are deflated independently, ended with a sync flush and stitched
+/// together into a single gzip member, so the output is read by `gunzip` and
+/// `flate2::read::GzDecoder` like any other gzip file. Call `finish` to write
+/// the last block and the trailer.
+///
+/// # Examples
+///
+/// ```
+/// use compression::ParallelGzEncoder;
+/// use flate2::{read::GzDecoder, Compression};
+/// use std::io::{Read, Write};
+///
+/// let input = "INFO request served\n".repeat(100_000);
+///
+/// let mut encoder = ParallelGzEncoder::new(Vec::new(), Compression::default(), 4).unwrap();
+/// encoder.write_all(input.as_bytes()).unwrap();
+/// let compressed = encoder.finish().unwrap();
+///
+/// let mut output = String::new();
+/// GzDecoder::new(&compressed[..]).read_to_string(&mut output).unwrap();
+/// assert_eq!(output, input);
+/// ```
+pub struct ParallelGzEncoder<W: Write> {
+    writer: W,
+    level: Compression,
+    block_size: usize,
+    pool: ThreadPool,
+    header_written: bool,
+    buffer: Vec<u8>,
+    blocks: Vec<Vec<u8>>,
+    crc: Hasher,
+    size: u64,
+}
+
+impl<W: Write> ParallelGzEncoder<W> {
+    /// Create an encoder with its own pool of `threads` workers
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The destination of the gzip stream
+    ///
+    /// * `level` - The deflate compression level
+    ///
+    /// * `threads` - The number of workers, `0` uses one per logical CPU
+    pub fn new(writer: W, level: Compression, threads: usize) -> Result<Self, Error> {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|index| format!("gzip-worker-{}", index))
+            .build()
+            .map_err(Error::other)?;
+
+        Ok(ParallelGzEncoder {
+            writer,
+            level,
+            block_size: DEFAULT_BLOCK_SIZE,
+            pool,
+            header_written: false,
+            buffer: Vec::with_capacity(DEFAULT_BLOCK_SIZE),
+            blocks: Vec::new(),
+            crc: Hasher::new(),
+            size: 0,
+        })
+    }
+
+    /// Set the size of the blocks compressed in parallel
+    ///
+    /// Smaller blocks spread better across workers but compress slightly worse,
+    /// since every block starts without the history of the previous one.
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size.max(1);
+        self
+    }
+
+    /// The number of workers of the pool
+    pub fn threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    fn write_header(&mut self) -> Result<(), Error> {
+        if self.header_written {
+            return Ok(());
+        }
+        let extra_flags = match self.level.level() {
+            9 => 2,
+            1 => 4,
+            _ => 0,
+        };
+        // Magic, deflate method, no flags, no mtime, extra flags, unknown OS
+        self.writer
+            .write_all(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, extra_flags, 255])?;
+        self.header_written = true;
+        Ok(())
+    }
+
+    /// Compress the queued blocks in parallel and write them in order.
+    fn flush_blocks(&mut self, last: bool) -> Result<(), Error> {
+        if last {
+            let remaining = std::mem::take(&mut self.buffer);
+            self.blocks.push(remaining);
+        }
+        let blocks = std::mem::take(&mut self.blocks);
+        let count = blocks.len();
+        let level = self.level;
+
+        let compressed = self.pool.install(|| {
+            blocks
+                .par_iter()
+                .enumerate()
+                .map(|(index, block)| {
+                    let flush = if last && index + 1 == count {
+                        FlushCompress::Finish
+                    } else {
+                        FlushCompress::Sync
+                    };
+                    let mut crc = Hasher::new();
+                    crc.update(block);
+                    deflate_block(block, level, flush).map(|deflated| (deflated, crc))
+                })
+                .collect::<Result<Vec<_>, Error>>()
+        })?;
+
+        self.write_header()?;
+        for ((deflated, crc), block) in compressed.iter().zip(&blocks) {
+            self.writer.write_all(deflated)?;
+            self.crc.combine(crc);
+            self.size += block.len() as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Write the last block and the gzip trailer, returning the underlying writer
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.flush_blocks(true)?;
+        let crc = self.crc.clone().finalize();
+        self.writer.write_all(&crc.to_le_bytes())?;
+        self.writer.write_all(&(self.size as u32).to_le_bytes())?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for ParallelGzEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = buf.len().min(self.block_size - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..count]);
+
+        if self.buffer.len() == self.block_size {
+            let block = std::mem::replace(&mut self.buffer, Vec::with_capacity(self.block_size));
+            self.blocks.push(block);
+            if self.blocks.len() >= self.threads() * BLOCKS_PER_THREAD {
+                self.flush_blocks(false)?;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Compress every complete block queued so far and flush the writer
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_blocks(false)?;
+        self.writer.flush()
+    }
+}
+
+/// Deflate a block without zlib header, ending it on a byte boundary.
+fn deflate_block(block: &[u8], level: Compression, flush: FlushCompress) -> Result<Vec<u8>, Error> {
+    let mut compress = Compress::new(level, false);
+    let mut output = Vec::with_capacity(block.len() + block.len() / 100 + 64);
+
+    loop {
+        let consumed = compress.total_in() as usize;
+        let status = compress
+            .compress_vec(&block[consumed..], &mut output, flush)
+            .map_err(Error::other)?;
+        let done = match flush {
+            FlushCompress::Finish => status == Status::StreamEnd,
+            _ => compress.total_in() as usize == block.len() && output.len() < output.capacity(),
+        };
+        if done {
+            return Ok(output);
+        }
+        output.reserve(output.capacity().max(64));
+    }
+}
+
+/// Create a zstd encoder that compresses on `threads` background workers
+///
+/// The frames are identical in format to the single-threaded encoder, so any
+/// zstd decoder can read them.
+///
+/// # Arguments
+///
+/// * `writer` - The destination of the zstd stream
+///
+/// * `level` - The zstd compression level, from 1 to 22
+///
+/// * `threads` - The number of workers, `0` uses one per logical CPU
+///
+/// # Examples
+///
+/// ```
+/// use compression::parallel_zstd_encoder;
+///
+/// let mut encoder = parallel_zstd_encoder(Vec::new(), 3, 4).unwrap();
+/// std::io::copy(&mut "INFO request served\n".repeat(1000).as_bytes(), &mut encoder).unwrap();
+/// let compressed = encoder.finish().unwrap();
+///
+/// let output = zstd::decode_all(&compressed[..]).unwrap();
+/// assert_eq!(output.len(), 20_000);
+/// ```
+pub fn parallel_zstd_encoder<W: Write>(
+    writer: W,
+    level: i32,
+    threads: u32,
+) -> Result<zstd::Encoder<'static, W>, Error> {
+    // zstd itself treats 0 workers as compressing on the calling thread.
+    let threads = match threads {
+        0 => std::thread::available_parallelism().map_or(1, |count| count.get() as u32),
+        threads => threads,
+    };
+    let mut encoder = zstd::Encoder::new(writer, level)?;
+    encoder.multithread(threads)?;
+    Ok(encoder)
+}
@@ -0,0 +1,575 @@
+use crate::{archive::Decoder, ArchiveFormat, ArchiveReader};
+use std::{
+    cell::Cell,
+    error::Error,
+    fmt,
+    fs::{self, DirBuilder, File},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+};
+use tar::{Archive, EntryType};
+use zip::ZipArchive;
+
+/// Uncompressed bytes that may be written before the compression ratio is enforced,
+/// so that small and highly compressible files are not rejected.
+pub const RATIO_GRACE_BYTES: u64 = 1024 * 1024;
+
+/// Limits enforced by the hardened extraction
+///
+/// # Examples
+///
+/// ```
+/// use compression::ExtractLimits;
+///
+/// let limits = ExtractLimits {
+///     max_entries: 100,
+///     ..ExtractLimits::default()
+/// };
+/// assert_eq!(limits.max_entries, 100);
+/// assert_eq!(limits.max_ratio, 100.0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExtractLimits {
+    /// Maximum number of uncompressed bytes written to disk
+    pub max_total_bytes: u64,
+    /// Maximum number of entries read from the archive, including skipped ones
+    pub max_entries: usize,
+    /// Maximum ratio between uncompressed and compressed bytes
+    pub max_ratio: f64,
+}
+
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        ExtractLimits {
+            max_total_bytes: 1024 * 1024 * 1024,
+            max_entries: 10_000,
+            max_ratio: 100.0,
+        }
+    }
+}
+
+/// Errors returned by the hardened extraction, naming the offending entry
+#[derive(Debug)]
+pub enum ExtractError {
+    /// The entry is absolute or contains `..` components
+    PathTraversal { entry: PathBuf },
+    /// The entry is a symbolic or hard link pointing outside of the output directory
+    UnsafeLink { entry: PathBuf, target: PathBuf },
+    /// The entry is a device, fifo or another type that is never extracted
+    UnsupportedEntry { entry: PathBuf },
+    /// The archive has more entries than `ExtractLimits::max_entries`
+    TooManyEntries { entry: PathBuf, limit: usize },
+    /// The extracted data exceeds `ExtractLimits::max_total_bytes`
+    TotalSizeExceeded { entry: PathBuf, limit: u64 },
+    /// The extracted data exceeds `ExtractLimits::max_ratio`
+    CompressionRatioExceeded { entry: PathBuf, limit: f64 },
+    /// Reading the archive or writing an entry failed
+    Io {
+        entry: Option<PathBuf>,
+        source: io::Error,
+    },
+}
+
+impl ExtractError {
+    /// The archive entry that caused the error, if any
+    pub fn entry(&self) -> Option<&Path> {
+        match self {
+            ExtractError::PathTraversal { entry }
+            | ExtractError::UnsafeLink { entry, .. }
+            | ExtractError::UnsupportedEntry { entry }
+            | ExtractError::TooManyEntries { entry, .. }
+            | ExtractError::TotalSizeExceeded { entry, .. }
+            | ExtractError::CompressionRatioExceeded { entry, .. } => Some(entry),
+            ExtractError::Io { entry, .. } => entry.as_deref(),
+        }
+    }
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExtractError::PathTraversal { entry } => {
+                write!(f, "Entry {} escapes the output directory", entry.display())
+            }
+            ExtractError::UnsafeLink { entry, target } => write!(
+                f,
+                "Entry {} links to {} outside of the output directory",
+                entry.display(),
+                target.display()
+            ),
+            ExtractError::UnsupportedEntry { entry } => {
+                write!(f, "Entry {} has an unsupported type", entry.display())
+            }
+            ExtractError::TooManyEntries { entry, limit } => write!(
+                f,
+                "Entry {} exceeds the limit of {} entries",
+                entry.display(),
+                limit
+            ),
+            ExtractError::TotalSizeExceeded { entry, limit } => write!(
+                f,
+                "Entry {} exceeds the limit of {} uncompressed bytes",
+                entry.display(),
+                limit
+            ),
+            ExtractError::CompressionRatioExceeded { entry, limit } => write!(
+                f,
+                "Entry {} exceeds the compression ratio limit of {}",
+                entry.display(),
+                limit
+            ),
+            ExtractError::Io {
+                entry: Some(entry),
+                source,
+            } => write!(f, "Failed to extract {}: {}", entry.display(), source),
+            ExtractError::Io {
+                entry: None,
+                source,
+            } => write!(f, "Failed to read archive: {}", source),
+        }
+    }
+}
+
+impl Error for ExtractError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ExtractError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ExtractError {
+    fn from(source: io::Error) -> Self {
+        ExtractError::Io {
+            entry: None,
+            source,
+        }
+    }
+}
+
+impl From<zip::result::ZipError> for ExtractError {
+    fn from(error: zip::result::ZipError) -> Self {
+        ExtractError::from(io::Error::other(error))
+    }
+}
+
+fn io_error(entry: &Path) -> impl FnOnce(io::Error) -> ExtractError + '_ {
+    move |source| ExtractError::Io {
+        entry: Some(entry.to_path_buf()),
+        source,
+    }
+}
+
+/// Counts the compressed bytes consumed by a decoder.
+struct CountingReader<R> {
+    inner: R,
+    count: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count.set(self.count.get() + read as u64);
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for CountingReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// Keep only the normal components of an archive path, rejecting absolute paths and `..`.
+pub(crate) fn sanitize(path: &Path) -> Option<PathBuf> {
+    let mut sanitized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => sanitized.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(sanitized)
+}
+
+/// Lexically resolve a symlink target relative to the link location, `None` if it escapes.
+fn resolve_link(link: &Path, target: &Path) -> Option<PathBuf> {
+    let mut resolved: Vec<_> = link.parent()?.components().collect();
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => resolved.push(component),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop()?;
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(resolved.iter().collect())
+}
+
+struct Extractor<'a> {
+    output_dir: &'a Path,
+    prefix: Option<&'a Path>,
+    limits: &'a ExtractLimits,
+    entries: usize,
+    total_bytes: u64,
+    extracted: Vec<PathBuf>,
+}
+
+impl Extractor<'_> {
+    /// Validate an entry, returning its destination relative to the output directory,
+    /// or `None` if it is outside of the prefix.
+    fn begin_entry(&mut self, entry: &Path) -> Result<Option<PathBuf>, ExtractError> {
+        self.entries += 1;
+        if self.entries > self.limits.max_entries {
+            return Err(ExtractError::TooManyEntries {
+                entry: entry.to_path_buf(),
+                limit: self.limits.max_entries,
+            });
+        }
+
+        let sanitized = sanitize(entry).ok_or_else(|| ExtractError::PathTraversal {
+            entry: entry.to_path_buf(),
+        })?;
+
+        Ok(match self.prefix {
+            Some(prefix) => sanitized.strip_prefix(prefix).ok().map(Path::to_path_buf),
+            None => Some(sanitized),
+        })
+    }
+
+    fn check_limits(&self, entry: &Path, compressed_bytes: u64) -> Result<(), ExtractError> {
+        if self.total_bytes > self.limits.max_total_bytes {
+            return Err(ExtractError::TotalSizeExceeded {
+                entry: entry.to_path_buf(),
+                limit: self.limits.max_total_bytes,
+            });
+        }
+
+        let ratio = self.total_bytes as f64 / compressed_bytes.max(1) as f64;
+        if self.total_bytes > RATIO_GRACE_BYTES && ratio > self.limits.max_ratio {
+            return Err(ExtractError::CompressionRatioExceeded {
+                entry: entry.to_path_buf(),
+                limit: self.limits.max_ratio,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Refuse a destination going through a symlink already on disk, which a chain of
+    /// links would otherwise use to escape the output directory.
+    fn check_no_symlinks(&self, entry: &Path, relative: &Path) -> Result<(), ExtractError> {
+        let mut path = self.output_dir.to_path_buf();
+        for component in relative.components() {
+            path.push(component);
+            match fs::symlink_metadata(&path) {
+                Ok(metadata) if metadata.file_type().is_symlink() => {
+                    return Err(ExtractError::PathTraversal {
+                        entry: entry.to_path_buf(),
+                    })
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => break,
+                Err(e) => return Err(io_error(entry)(e)),
+            }
+        }
+        Ok(())
+    }
+
+    fn create_dir(&mut self, entry: &Path, relative: PathBuf) -> Result<(), ExtractError> {
+        self.check_no_symlinks(entry, &relative)?;
+        DirBuilder::new()
+            .recursive(true)
+            .create(self.output_dir.join(&relative))
+            .map_err(io_error(entry))?;
+        self.extracted.push(relative);
+        Ok(())
+    }
+
+    fn write_file<R: Read>(
+        &mut self,
+        entry: &Path,
+        relative: PathBuf,
+        reader: &mut R,
+        compressed_bytes: impl Fn() -> u64,
+        mode: Option<u32>,
+    ) -> Result<(), ExtractError> {
+        self.check_no_symlinks(entry, &relative)?;
+        let out_path = self.output_dir.join(&relative);
+        if let Some(parent) = out_path.parent() {
+            DirBuilder::new()
+                .recursive(true)
+                .create(parent)
+                .map_err(io_error(entry))?;
+        }
+
+        let mut file = File::create(&out_path).map_err(io_error(entry))?;
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let count = reader.read(&mut buffer).map_err(io_error(entry))?;
+            if count == 0 {
+                break;
+            }
+            self.total_bytes += count as u64;
+            self.check_limits(entry, compressed_bytes())?;
+            file.write_all(&buffer[..count]).map_err(io_error(entry))?;
+        }
+
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&out_path, fs::Permissions::from_mode(mode & 0o777))
+                .map_err(io_error(entry))?;
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+
+        self.extracted.push(relative);
+        Ok(())
+    }
+
+    fn create_symlink(
+        &mut self,
+        entry: &Path,
+        relative: PathBuf,
+        target: &Path,
+    ) -> Result<(), ExtractError> {
+        if resolve_link(&relative, target).is_none() {
+            return Err(ExtractError::UnsafeLink {
+                entry: entry.to_path_buf(),
+                target: target.to_path_buf(),
+            });
+        }
+        self.check_no_symlinks(entry, &relative)?;
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(target, self.output_dir.join(&relative))
+                .map_err(io_error(entry))?;
+            self.extracted.push(relative);
+            Ok(())
+        }
+        #[cfg(not(unix))]
+        Err(ExtractError::UnsupportedEntry {
+            entry: entry.to_path_buf(),
+        })
+    }
+
+    fn create_hard_link(
+        &mut self,
+        entry: &Path,
+        relative: PathBuf,
+        target: &Path,
+    ) -> Result<(), ExtractError> {
+        let unsafe_link = || ExtractError::UnsafeLink {
+            entry: entry.to_path_buf(),
+            target: target.to_path_buf(),
+        };
+        let target_relative = sanitize(target).ok_or_else(unsafe_link)?;
+        let target_relative = match self.prefix {
+            Some(prefix) => target_relative
+                .strip_prefix(prefix)
+                .map_err(|_| unsafe_link())?
+                .to_path_buf(),
+            None => target_relative,
+        };
+        self.check_no_symlinks(entry, &target_relative)?;
+        self.check_no_symlinks(entry, &relative)?;
+
+        fs::hard_link(
+            self.output_dir.join(target_relative),
+            self.output_dir.join(&relative),
+        )
+        .map_err(io_error(entry))?;
+        self.extracted.push(relative);
+        Ok(())
+    }
+
+    fn extract_tar<R: Read>(
+        &mut self,
+        reader: R,
+        format: ArchiveFormat,
+    ) -> Result<(), ExtractError> {
+        let compressed = Rc::new(Cell::new(0));
+        let counting = CountingReader {
+            inner: reader,
+            count: Rc::clone(&compressed),
+        };
+        let mut archive = Archive::new(Decoder::new(counting, format)?);
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let name = entry.path()?.into_owned();
+            let relative = match self.begin_entry(&name)? {
+                Some(relative) => relative,
+                None => continue,
+            };
+
+            match entry.header().entry_type() {
+                EntryType::Directory => self.create_dir(&name, relative)?,
+                EntryType::Regular | EntryType::Continuous => {
+                    if self.total_bytes + entry.size() > self.limits.max_total_bytes {
+                        return Err(ExtractError::TotalSizeExceeded {
+                            entry: name,
+                            limit: self.limits.max_total_bytes,
+                        });
+                    }
+                    let mode = entry.header().mode().ok();
+                    self.write_file(&name, relative, &mut entry, || compressed.get(), mode)?
+                }
+                EntryType::Symlink => {
+                    let target = entry
+                        .link_name()?
+                        .ok_or_else(|| ExtractError::UnsupportedEntry {
+                            entry: name.clone(),
+                        })?
+                        .into_owned();
+                    self.create_symlink(&name, relative, &target)?
+                }
+                EntryType::Link => {
+                    let target = entry
+                        .link_name()?
+                        .ok_or_else(|| ExtractError::UnsupportedEntry {
+                            entry: name.clone(),
+                        })?
+                        .into_owned();
+                    self.create_hard_link(&name, relative, &target)?
+                }
+                EntryType::XHeader | EntryType::XGlobalHeader => {}
+                _ => return Err(ExtractError::UnsupportedEntry { entry: name }),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn extract_zip<R: Read + Seek>(&mut self, reader: R) -> Result<(), ExtractError> {
+        // Count the bytes actually read rather than trusting the sizes the archive declares.
+        let compressed = Rc::new(Cell::new(0));
+        let counting = CountingReader {
+            inner: reader,
+            count: Rc::clone(&compressed),
+        };
+        let mut archive = ZipArchive::new(counting)?;
+        compressed.set(0);
+
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index)?;
+            let name = PathBuf::from(file.name());
+            let relative = match self.begin_entry(&name)? {
+                Some(relative) => relative,
+                None => continue,
+            };
+
+            if file.is_dir() {
+                self.create_dir(&name, relative)?;
+            } else if file.is_symlink() {
+                let mut target = String::new();
+                file.by_ref()
+                    .take(4096)
+                    .read_to_string(&mut target)
+                    .map_err(io_error(&name))?;
+                self.create_symlink(&name, relative, Path::new(&target))?;
+            } else {
+                let mode = file.unix_mode();
+                self.write_file(&name, relative, &mut file, || compressed.get(), mode)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> ArchiveReader<R> {
+    /// Extract every entry into a directory, rejecting unsafe entries
+    ///
+    /// Entries that are absolute, contain `..`, are links pointing outside of
+    /// `output_dir` or would be written through a symlink are refused, and the archive
+    /// is aborted as soon as one of the `limits` is exceeded. Entries extracted before
+    /// the error are left on disk.
+    ///
+    /// # Arguments
+    ///
+    /// * `output_dir` - The directory to extract the archive into
+    ///
+    /// * `limits` - The limits on size, entry count and compression ratio
+    ///
+    /// # Returns
+    ///
+    /// * The paths of the extracted entries, relative to `output_dir`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compression::{compress_file, ArchiveReader, ExtractLimits};
+    /// use std::fs::{remove_dir_all, remove_file};
+    ///
+    /// compress_file("test_extract_safely.tar.gz", "backup/src", "src").unwrap();
+    /// let extracted = ArchiveReader::open("test_extract_safely.tar.gz")
+    ///     .unwrap()
+    ///     .extract_safely("output_dir_extract_safely", &ExtractLimits::default())
+    ///     .unwrap();
+    /// assert!(!extracted.is_empty());
+    /// remove_dir_all("output_dir_extract_safely").unwrap();
+    /// remove_file("test_extract_safely.tar.gz").unwrap();
+    /// ```
+    pub fn extract_safely<P: AsRef<Path>>(
+        self,
+        output_dir: P,
+        limits: &ExtractLimits,
+    ) -> Result<Vec<PathBuf>, ExtractError> {
+        self.extract_safely_inner(None, output_dir.as_ref(), limits)
+    }
+
+    /// Extract the entries under `prefix`, removing the prefix and rejecting unsafe entries
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The prefix to be removed from the extracted entries
+    ///
+    /// * `output_dir` - The directory to extract the entries into
+    ///
+    /// * `limits` - The limits on size, entry count and compression ratio
+    ///
+    /// # Returns
+    ///
+    /// * The paths of the extracted entries, relative to `output_dir`
+    pub fn extract_safely_removing_prefix<P: AsRef<Path>, Q: AsRef<Path>>(
+        self,
+        prefix: P,
+        output_dir: Q,
+        limits: &ExtractLimits,
+    ) -> Result<Vec<PathBuf>, ExtractError> {
+        self.extract_safely_inner(Some(prefix.as_ref()), output_dir.as_ref(), limits)
+    }
+
+    fn extract_safely_inner(
+        self,
+        prefix: Option<&Path>,
+        output_dir: &Path,
+        limits: &ExtractLimits,
+    ) -> Result<Vec<PathBuf>, ExtractError> {
+        DirBuilder::new().recursive(true).create(output_dir)?;
+
+        let mut extractor = Extractor {
+            output_dir,
+            prefix,
+            limits,
+            entries: 0,
+            total_bytes: 0,
+            extracted: Vec::new(),
+        };
+
+        match self.format {
+            ArchiveFormat::Zip => extractor.extract_zip(self.reader)?,
+            format => extractor.extract_tar(self.reader, format)?,
+        }
+
+        Ok(extractor.extracted)
+    }
+}
@@ -0,0 +1,109 @@
+use compression::{decompress_file, ArchiveBuilder, ArchiveFormat, ArchiveReader};
+use std::{
+    fs::{read_to_string, remove_dir_all, remove_file},
+    io::Cursor,
+    path::Path,
+};
+
+#[cfg(test)]
+mod tests_archive {
+    use super::*;
+
+    fn fixture_archive(format: ArchiveFormat, test_id: usize) -> String {
+        let file = format!("test_archive_{}.{}", test_id, format.extension());
+        let mut writer = ArchiveBuilder::new(format).create(&file).unwrap();
+        writer.append_dir_all("backup/src", "src").unwrap();
+        writer
+            .append_file("backup/Cargo.toml", "Cargo.toml")
+            .unwrap();
+        writer.finish().unwrap();
+        assert!(Path::new(&file).exists());
+        file
+    }
+
+    #[test]
+    fn test_round_trip_every_format() {
+        for (test_id, format) in ArchiveFormat::ALL.into_iter().enumerate() {
+            let file = fixture_archive(format, test_id);
+            let output_dir = format!("output_dir_archive_{}", test_id);
+
+            let reader = ArchiveReader::open(&file).unwrap();
+            assert_eq!(reader.format(), format);
+            reader.extract(&output_dir).unwrap();
+
+            assert_eq!(
+                read_to_string(Path::new(&output_dir).join("backup/src/lib.rs")).unwrap(),
+                read_to_string("src/lib.rs").unwrap()
+            );
+            assert!(Path::new(&output_dir).join("backup/Cargo.toml").exists());
+
+            remove_dir_all(output_dir).unwrap();
+            remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_extract_removing_prefix_every_format() {
+        for (test_id, format) in ArchiveFormat::ALL.into_iter().enumerate() {
+            let file = fixture_archive(format, test_id + 10);
+            let output_dir = format!("output_dir_archive_prefix_{}", test_id);
+
+            let extracted = ArchiveReader::open(&file)
+                .unwrap()
+                .extract_removing_prefix("backup/src", &output_dir)
+                .unwrap();
+
+            assert!(extracted.contains(&Path::new("archive.rs").to_path_buf()));
+            assert!(!extracted.iter().any(|path| path.ends_with("Cargo.toml")));
+            assert!(Path::new(&output_dir).join("lib.rs").exists());
+            assert!(!Path::new(&output_dir).join("Cargo.toml").exists());
+
+            remove_dir_all(output_dir).unwrap();
+            remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_decompress_file_detects_format() {
+        let file = fixture_archive(ArchiveFormat::TarZst, 20);
+        let output_dir = "output_dir_archive_detect";
+        decompress_file(&file, output_dir).unwrap();
+        assert!(Path::new(output_dir).join("backup/src/lib.rs").exists());
+        remove_dir_all(output_dir).unwrap();
+        remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_detect_unknown_format() {
+        let mut reader = Cursor::new(b"not an archive".to_vec());
+        assert!(ArchiveFormat::detect(&mut reader).is_err());
+        assert!(ArchiveReader::new(reader).is_err());
+    }
+
+    #[test]
+    fn test_compression_level() {
+        for format in ArchiveFormat::ALL {
+            let range = format.level_range();
+            let mut low = ArchiveBuilder::new(format)
+                .level(*range.start())
+                .build(Cursor::new(Vec::new()))
+                .unwrap();
+            low.append_dir_all("src", "src").unwrap();
+            let low = low.finish().unwrap().into_inner();
+
+            let mut high = ArchiveBuilder::new(format)
+                .level(*range.end())
+                .build(Cursor::new(Vec::new()))
+                .unwrap();
+            high.append_dir_all("src", "src").unwrap();
+            let high = high.finish().unwrap().into_inner();
+
+            assert_eq!(ArchiveFormat::from_magic(&low), Some(format));
+            assert!(high.len() <= low.len());
+            assert!(ArchiveBuilder::new(format)
+                .level(range.end() + 1)
+                .build(Cursor::new(Vec::new()))
+                .is_err());
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use compression::{
+    compress_full, compress_incremental, manifest_file, restore_archives, restore_archives_with,
+    ArchiveBuilder, ArchiveFormat, ExtractLimits, Manifest,
+};
+use std::{
+    fs::{create_dir_all, read_to_string, remove_dir_all, remove_file, write},
+    path::Path,
+};
+
+#[cfg(test)]
+mod tests_incremental {
+    use super::*;
+
+    fn fixture_src_dir(src_dir: &str) {
+        let _ = remove_dir_all(src_dir);
+        create_dir_all(format!("{}/app", src_dir)).unwrap();
+        write(format!("{}/syslog", src_dir), "boot\n").unwrap();
+        write(format!("{}/app/app.log", src_dir), "started\n").unwrap();
+        write(format!("{}/app/old.log", src_dir), "old\n").unwrap();
+    }
+
+    fn remove_archive(file: &str) {
+        remove_file(manifest_file(file)).unwrap();
+        remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_full_then_incremental_then_restore() {
+        let src_dir = "src_dir_incremental";
+        let output_dir = "output_dir_incremental";
+        let (full, incremental) = ("test_full_1.tar.xz", "test_incremental_1.tar.xz");
+        let builder = ArchiveBuilder::new(ArchiveFormat::TarXz);
+        fixture_src_dir(src_dir);
+
+        let full_manifest = compress_full(full, src_dir, &builder).unwrap();
+        assert_eq!(
+            full_manifest.archived,
+            vec!["app/app.log", "app/old.log", "syslog"]
+        );
+        assert_eq!(Manifest::load(manifest_file(full)).unwrap(), full_manifest);
+
+        write(format!("{}/app/app.log", src_dir), "started\nstopped\n").unwrap();
+        write(format!("{}/app/new.log", src_dir), "new\n").unwrap();
+        remove_file(format!("{}/app/old.log", src_dir)).unwrap();
+
+        let manifest = compress_incremental(incremental, src_dir, full, &builder).unwrap();
+        assert_eq!(manifest.base.as_deref(), Some(full));
+        assert_eq!(manifest.archived, vec!["app/app.log", "app/new.log"]);
+        assert_eq!(manifest.deleted, vec!["app/old.log"]);
+        assert_eq!(manifest.entries["syslog"], full_manifest.entries["syslog"]);
+
+        let restored = restore_archives(&[full, incremental], output_dir).unwrap();
+        assert_eq!(restored, manifest);
+        assert_eq!(
+            read_to_string(Path::new(output_dir).join("app/app.log")).unwrap(),
+            "started\nstopped\n"
+        );
+        assert!(Path::new(output_dir).join("app/new.log").exists());
+        assert!(Path::new(output_dir).join("syslog").exists());
+        assert!(!Path::new(output_dir).join("app/old.log").exists());
+
+        let rescanned = Manifest::scan(output_dir, None).unwrap();
+        assert_eq!(rescanned.entries, manifest.entries);
+
+        remove_dir_all(output_dir).unwrap();
+        remove_dir_all(src_dir).unwrap();
+        remove_archive(full);
+        remove_archive(incremental);
+    }
+
+    #[test]
+    fn test_restore_requires_full_archive_first() {
+        let src_dir = "src_dir_incremental_order";
+        let (full, incremental) = ("test_full_2.zip", "test_incremental_2.zip");
+        let builder = ArchiveBuilder::new(ArchiveFormat::Zip);
+        fixture_src_dir(src_dir);
+
+        compress_full(full, src_dir, &builder).unwrap();
+        compress_incremental(incremental, src_dir, full, &builder).unwrap();
+
+        let result = restore_archives(&[incremental], "output_dir_incremental_order");
+        assert!(result.is_err());
+        assert!(!Path::new("output_dir_incremental_order").exists());
+
+        remove_dir_all(src_dir).unwrap();
+        remove_archive(full);
+        remove_archive(incremental);
+    }
+
+    #[test]
+    fn test_restore_checks_incremental_chain() {
+        let src_dir = "src_dir_incremental_chain";
+        let output_dir = "output_dir_incremental_chain";
+        let (full, first, second) = (
+            "test_full_3.tar.gz",
+            "test_incremental_3a.tar.gz",
+            "test_incremental_3b.tar.gz",
+        );
+        let builder = ArchiveBuilder::new(ArchiveFormat::TarGz);
+        fixture_src_dir(src_dir);
+
+        compress_full(full, src_dir, &builder).unwrap();
+        compress_incremental(first, src_dir, full, &builder).unwrap();
+        compress_incremental(second, src_dir, first, &builder).unwrap();
+
+        let error = restore_archives(&[full, second], output_dir).unwrap_err();
+        assert!(error.to_string().contains("not incremental"));
+        assert!(!Path::new(output_dir).exists());
+        restore_archives(&[full, first, second], output_dir).unwrap();
+
+        remove_dir_all(output_dir).unwrap();
+        remove_dir_all(src_dir).unwrap();
+        remove_archive(full);
+        remove_archive(first);
+        remove_archive(second);
+    }
+
+    #[test]
+    fn test_restore_applies_extract_limits() {
+        let src_dir = "src_dir_incremental_limits";
+        let output_dir = "output_dir_incremental_limits";
+        let (full, incremental) = ("test_full_5.tar.gz", "test_incremental_5.tar.gz");
+        let builder = ArchiveBuilder::new(ArchiveFormat::TarGz);
+        fixture_src_dir(src_dir);
+
+        compress_full(full, src_dir, &builder).unwrap();
+        write(format!("{}/app/new.log", src_dir), "new\n").unwrap();
+        compress_incremental(incremental, src_dir, full, &builder).unwrap();
+
+        let limits = ExtractLimits {
+            max_entries: 2,
+            ..ExtractLimits::default()
+        };
+        let result = restore_archives_with(&[full, incremental], output_dir, &limits);
+        assert!(result.is_err());
+
+        let limits = ExtractLimits {
+            max_entries: 10,
+            ..ExtractLimits::default()
+        };
+        let restored = restore_archives_with(&[full, incremental], output_dir, &limits).unwrap();
+        assert_eq!(restored.archived, vec!["app/new.log"]);
+        assert!(Path::new(output_dir).join("app/new.log").exists());
+
+        remove_dir_all(output_dir).unwrap();
+        remove_dir_all(src_dir).unwrap();
+        remove_archive(full);
+        remove_archive(incremental);
+    }
+
+    #[test]
+    fn test_restore_rejects_deletions_outside_output_dir() {
+        let src_dir = "src_dir_incremental_deleted";
+        let output_dir = "output_dir_incremental_deleted";
+        let victim = "victim_incremental_deleted.txt";
+        let (full, incremental) = ("test_full_4.tar.gz", "test_incremental_4.tar.gz");
+        let builder = ArchiveBuilder::new(ArchiveFormat::TarGz);
+        fixture_src_dir(src_dir);
+        write(victim, "keep me\n").unwrap();
+
+        compress_full(full, src_dir, &builder).unwrap();
+        let mut manifest = compress_incremental(incremental, src_dir, full, &builder).unwrap();
+        manifest.deleted.push(format!("../{}", victim));
+        manifest.save(manifest_file(incremental)).unwrap();
+
+        let result = restore_archives(&[full, incremental], output_dir);
+        assert!(result.is_err());
+        assert!(Path::new(victim).exists());
+
+        let _ = remove_dir_all(output_dir);
+        remove_dir_all(src_dir).unwrap();
+        remove_file(victim).unwrap();
+        remove_archive(full);
+        remove_archive(incremental);
+    }
+}
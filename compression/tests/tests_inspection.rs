@@ -0,0 +1,219 @@
+use compression::{ArchiveBuilder, ArchiveFormat, ArchiveReader, EntryKind, ProblemKind};
+use flate2::{write::GzEncoder, Compression};
+use std::{
+    fs::{read, read_to_string, remove_dir_all},
+    io::{Cursor, ErrorKind, Write},
+    path::Path,
+};
+use tar::Builder;
+
+#[cfg(test)]
+mod tests_inspection {
+    use super::*;
+
+    fn fixture_archive(format: ArchiveFormat) -> Cursor<Vec<u8>> {
+        let mut writer = ArchiveBuilder::new(format)
+            .build(Cursor::new(Vec::new()))
+            .unwrap();
+        writer.append_dir_all("backup/src", "src").unwrap();
+        writer
+            .append_file("backup/Cargo.toml", "Cargo.toml")
+            .unwrap();
+        let mut archive = writer.finish().unwrap();
+        archive.set_position(0);
+        archive
+    }
+
+    #[test]
+    fn test_list_every_format() {
+        for format in ArchiveFormat::ALL {
+            let entries = ArchiveReader::new(fixture_archive(format))
+                .unwrap()
+                .list()
+                .unwrap();
+
+            let lib = entries
+                .iter()
+                .find(|entry| entry.path == Path::new("backup/src/lib.rs"))
+                .unwrap();
+            assert_eq!(lib.kind, EntryKind::File);
+            assert_eq!(lib.size, read("src/lib.rs").unwrap().len() as u64);
+            assert!(lib.mode.is_some());
+            assert!(lib.mtime.is_some());
+
+            assert!(entries
+                .iter()
+                .any(|entry| entry.kind == EntryKind::Directory));
+            assert!(!Path::new("backup/src").exists());
+        }
+    }
+
+    #[test]
+    fn test_extract_entry_every_format() {
+        for format in ArchiveFormat::ALL {
+            let mut contents = Vec::new();
+            let written = ArchiveReader::new(fixture_archive(format))
+                .unwrap()
+                .extract_entry("backup/Cargo.toml", &mut contents)
+                .unwrap();
+            assert_eq!(written, contents.len() as u64);
+            assert_eq!(contents, read("Cargo.toml").unwrap());
+
+            let missing = ArchiveReader::new(fixture_archive(format))
+                .unwrap()
+                .extract_entry("backup/missing.toml", &mut Vec::new());
+            assert_eq!(missing.unwrap_err().kind(), ErrorKind::NotFound);
+        }
+    }
+
+    #[test]
+    fn test_extract_matching_every_format() {
+        for format in ArchiveFormat::ALL {
+            let output_dir = format!("output_dir_matching_{}", format.extension());
+            let extracted = ArchiveReader::new(fixture_archive(format))
+                .unwrap()
+                .extract_matching("backup/src/*.rs", &output_dir)
+                .unwrap();
+
+            assert!(extracted.contains(&Path::new("backup/src/inspection.rs").to_path_buf()));
+            assert!(!extracted.iter().any(|path| path.ends_with("Cargo.toml")));
+            assert_eq!(
+                read_to_string(Path::new(&output_dir).join("backup/src/lib.rs")).unwrap(),
+                read_to_string("src/lib.rs").unwrap()
+            );
+            assert!(!Path::new(&output_dir).join("backup/Cargo.toml").exists());
+            remove_dir_all(output_dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_verify_sound_archives() {
+        for format in ArchiveFormat::ALL {
+            let report = ArchiveReader::new(fixture_archive(format))
+                .unwrap()
+                .verify();
+            assert!(report.is_ok(), "{:?}", report);
+            assert_eq!(report.format, format);
+            assert!(report.entries > 1);
+            assert!(report.bytes > 0);
+        }
+    }
+
+    #[test]
+    fn test_verify_truncated_gzip() {
+        let mut bytes = fixture_archive(ArchiveFormat::TarGz).into_inner();
+        bytes.truncate(bytes.len() / 2);
+        let report = ArchiveReader::new(Cursor::new(bytes)).unwrap().verify();
+        assert!(!report.is_ok());
+        assert_eq!(report.problems.len(), 1);
+        assert_eq!(report.problems[0].kind, ProblemKind::Truncated);
+    }
+
+    #[test]
+    fn test_verify_truncated_gzip_trailer() {
+        let mut bytes = fixture_archive(ArchiveFormat::TarGz).into_inner();
+        bytes.truncate(bytes.len() - 6);
+        let report = ArchiveReader::new(Cursor::new(bytes)).unwrap().verify();
+        assert!(report.entries > 1);
+        assert_eq!(report.problems.len(), 1);
+        assert_eq!(report.problems[0].kind, ProblemKind::Truncated);
+    }
+
+    #[test]
+    fn test_verify_bad_gzip_crc() {
+        let mut bytes = fixture_archive(ArchiveFormat::TarGz).into_inner();
+        let crc = bytes.len() - 8;
+        bytes[crc] ^= 0xff;
+        let report = ArchiveReader::new(Cursor::new(bytes)).unwrap().verify();
+        assert!(report.entries > 1);
+        assert_eq!(report.problems.len(), 1);
+        assert_eq!(report.problems[0].kind, ProblemKind::Corrupt);
+        assert_eq!(report.problems[0].entry, None);
+    }
+
+    #[test]
+    fn test_verify_bad_tar_checksum() {
+        let mut builder = Builder::new(Vec::new());
+        builder
+            .append_path_with_name("Cargo.toml", "Cargo.toml")
+            .unwrap();
+        let mut tar = builder.into_inner().unwrap();
+        tar[0] = b'X';
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&tar).unwrap();
+        let bytes = encoder.finish().unwrap();
+
+        let report = ArchiveReader::new(Cursor::new(bytes)).unwrap().verify();
+        assert_eq!(report.entries, 0);
+        assert_eq!(report.problems[0].kind, ProblemKind::BadChecksum);
+    }
+
+    fn fixture_two_entry_tar() -> (Vec<u8>, usize) {
+        let mut builder = Builder::new(Vec::new());
+        builder
+            .append_path_with_name("Cargo.toml", "Cargo.toml")
+            .unwrap();
+        let second_header = builder.get_ref().len();
+        builder
+            .append_path_with_name("Cargo.toml", "Cargo.copy.toml")
+            .unwrap();
+        (builder.into_inner().unwrap(), second_header)
+    }
+
+    #[test]
+    fn test_verify_tar_truncated_mid_header() {
+        let (mut tar, second_header) = fixture_two_entry_tar();
+        tar.truncate(second_header + 100);
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&tar).unwrap();
+        let bytes = encoder.finish().unwrap();
+
+        let report = ArchiveReader::new(Cursor::new(bytes)).unwrap().verify();
+        assert_eq!(report.entries, 1);
+        assert_eq!(report.problems.len(), 1);
+        assert_eq!(report.problems[0].kind, ProblemKind::Truncated);
+    }
+
+    #[test]
+    fn test_verify_tar_gz_truncated_mid_header() {
+        let (tar, second_header) = fixture_two_entry_tar();
+        // Stored blocks keep the tar bytes at a fixed offset after the gzip and block headers.
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::none());
+        encoder.write_all(&tar).unwrap();
+        let mut bytes = encoder.finish().unwrap();
+        bytes.truncate(10 + 5 + second_header + 100);
+
+        let report = ArchiveReader::new(Cursor::new(bytes)).unwrap().verify();
+        assert_eq!(report.entries, 1);
+        assert_eq!(report.problems.len(), 1);
+        assert_eq!(report.problems[0].kind, ProblemKind::Truncated);
+    }
+
+    #[test]
+    fn test_verify_bad_zip_crc() {
+        let mut writer = ArchiveBuilder::new(ArchiveFormat::Zip)
+            .level(0)
+            .build(Cursor::new(Vec::new()))
+            .unwrap();
+        writer.append_file("a.txt", "Cargo.toml").unwrap();
+        writer.append_file("b.txt", "Cargo.toml").unwrap();
+        let mut bytes = writer.finish().unwrap().into_inner();
+
+        let needle = b"[package]";
+        let offset = bytes
+            .windows(needle.len())
+            .position(|window| window == needle)
+            .unwrap();
+        bytes[offset] = b'{';
+
+        let report = ArchiveReader::new(Cursor::new(bytes)).unwrap().verify();
+        assert_eq!(report.entries, 1);
+        assert_eq!(report.problems.len(), 1);
+        assert_eq!(report.problems[0].kind, ProblemKind::BadChecksum);
+        assert_eq!(
+            report.problems[0].entry.as_deref(),
+            Some(Path::new("a.txt"))
+        );
+    }
+}
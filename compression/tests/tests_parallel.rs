@@ -0,0 +1,122 @@
+use compression::{
+    parallel_zstd_encoder, ArchiveBuilder, ArchiveFormat, ArchiveReader, ParallelGzEncoder,
+};
+use flate2::{read::GzDecoder, Compression};
+use std::{
+    fs::{read_to_string, remove_dir_all, remove_file},
+    io::{Read, Write},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+#[cfg(test)]
+mod tests_parallel {
+    use super::*;
+
+    fn fixture_input(lines: usize) -> Vec<u8> {
+        (0..lines)
+            .map(|line| format!("{} INFO worker-{} served request\n", line, line % 7))
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    fn gunzip(compressed: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        GzDecoder::new(compressed).read_to_end(&mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_parallel_gzip_round_trip() {
+        let input = fixture_input(200_000);
+        for threads in [1, 3, 8] {
+            let mut encoder = ParallelGzEncoder::new(Vec::new(), Compression::default(), threads)
+                .unwrap()
+                .block_size(64 * 1024);
+            assert_eq!(encoder.threads(), threads);
+            for chunk in input.chunks(10_000) {
+                encoder.write_all(chunk).unwrap();
+            }
+            let compressed = encoder.finish().unwrap();
+
+            assert!(compressed.len() < input.len() / 5);
+            assert_eq!(gunzip(&compressed), input);
+        }
+    }
+
+    #[test]
+    fn test_parallel_gzip_empty_and_flushed_input() {
+        let encoder = ParallelGzEncoder::new(Vec::new(), Compression::best(), 2).unwrap();
+        assert!(gunzip(&encoder.finish().unwrap()).is_empty());
+
+        let mut encoder = ParallelGzEncoder::new(Vec::new(), Compression::fast(), 2)
+            .unwrap()
+            .block_size(10);
+        encoder.write_all(b"first line\n").unwrap();
+        encoder.flush().unwrap();
+        encoder.write_all(b"second line\n").unwrap();
+        assert_eq!(
+            gunzip(&encoder.finish().unwrap()),
+            b"first line\nsecond line\n"
+        );
+    }
+
+    #[test]
+    fn test_parallel_gzip_readable_by_gzip_tool() {
+        let input = fixture_input(50_000);
+        let mut encoder = ParallelGzEncoder::new(Vec::new(), Compression::default(), 4).unwrap();
+        encoder.write_all(&input).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let child = Command::new("gzip")
+            .arg("-dc")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(_) => return println!("gzip is not installed, skipping"),
+        };
+        child.stdin.take().unwrap().write_all(&compressed).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, input);
+    }
+
+    #[test]
+    fn test_parallel_zstd_round_trip() {
+        let input = fixture_input(100_000);
+        for threads in [0, 4] {
+            let mut encoder = parallel_zstd_encoder(Vec::new(), 3, threads).unwrap();
+            encoder.write_all(&input).unwrap();
+            let compressed = encoder.finish().unwrap();
+            assert_eq!(zstd::decode_all(&compressed[..]).unwrap(), input);
+        }
+    }
+
+    #[test]
+    fn test_archive_builder_threads() {
+        let formats = [ArchiveFormat::TarGz, ArchiveFormat::TarZst];
+        for (format, threads) in formats.into_iter().flat_map(|f| [(f, 0), (f, 4)]) {
+            let file = format!("test_parallel_{}.{}", threads, format.extension());
+            let output_dir = format!("output_dir_parallel_{}_{}", threads, format.extension());
+            let mut writer = ArchiveBuilder::new(format)
+                .threads(threads)
+                .create(&file)
+                .unwrap();
+            writer.append_dir_all("backup/src", "src").unwrap();
+            writer.finish().unwrap();
+
+            let reader = ArchiveReader::open(&file).unwrap();
+            assert_eq!(reader.format(), format);
+            reader.extract(&output_dir).unwrap();
+            assert_eq!(
+                read_to_string(Path::new(&output_dir).join("backup/src/parallel.rs")).unwrap(),
+                read_to_string("src/parallel.rs").unwrap()
+            );
+
+            remove_dir_all(output_dir).unwrap();
+            remove_file(file).unwrap();
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use compression::{
+    compress_file, decompress_file_safely, ArchiveReader, ExtractError, ExtractLimits,
+};
+use flate2::{write::GzEncoder, Compression};
+use std::{
+    fs::{remove_dir_all, remove_file, File},
+    io::{Cursor, Write},
+    path::Path,
+};
+use tar::{Builder, EntryType, Header};
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+#[cfg(test)]
+mod tests_safe_extraction {
+    use super::*;
+
+    /// Build a tar.gz in memory, writing the entry names verbatim so that
+    /// `..` and absolute paths end up in the headers.
+    fn fixture_tar_gz(entries: &[(&str, EntryType, &[u8], Option<&str>)]) -> Cursor<Vec<u8>> {
+        let mut builder = Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (name, entry_type, data, link) in entries {
+            let mut header = Header::new_old();
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            if let Some(link) = link {
+                header.as_old_mut().linkname[..link.len()].copy_from_slice(link.as_bytes());
+            }
+            header.set_entry_type(*entry_type);
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, *data).unwrap();
+        }
+        Cursor::new(builder.into_inner().unwrap().finish().unwrap())
+    }
+
+    fn extract(archive: Cursor<Vec<u8>>, output_dir: &str, limits: &ExtractLimits) -> ExtractError {
+        let result = ArchiveReader::new(archive)
+            .unwrap()
+            .extract_safely(output_dir, limits);
+        let _ = remove_dir_all(output_dir);
+        result.unwrap_err()
+    }
+
+    #[test]
+    fn test_reject_parent_dir_entry() {
+        let archive = fixture_tar_gz(&[("../evil.txt", EntryType::Regular, b"evil", None)]);
+        let error = extract(archive, "output_dir_safe_parent", &ExtractLimits::default());
+        assert!(matches!(error, ExtractError::PathTraversal { .. }));
+        assert_eq!(error.entry(), Some(Path::new("../evil.txt")));
+        assert!(!Path::new("evil.txt").exists());
+    }
+
+    #[test]
+    fn test_reject_absolute_entry() {
+        let archive = fixture_tar_gz(&[("/tmp/evil.txt", EntryType::Regular, b"evil", None)]);
+        let error = extract(
+            archive,
+            "output_dir_safe_absolute",
+            &ExtractLimits::default(),
+        );
+        assert!(matches!(error, ExtractError::PathTraversal { .. }));
+        assert_eq!(error.entry(), Some(Path::new("/tmp/evil.txt")));
+    }
+
+    #[test]
+    fn test_reject_symlink_outside() {
+        let archive = fixture_tar_gz(&[
+            (
+                "inside",
+                EntryType::Symlink,
+                b"",
+                Some("nested/../file.txt"),
+            ),
+            ("link", EntryType::Symlink, b"", Some("../../etc")),
+        ]);
+        let error = extract(
+            archive,
+            "output_dir_safe_symlink",
+            &ExtractLimits::default(),
+        );
+        match error {
+            ExtractError::UnsafeLink { entry, target } => {
+                assert_eq!(entry, Path::new("link"));
+                assert_eq!(target, Path::new("../../etc"));
+            }
+            error => panic!("unexpected error: {}", error),
+        }
+    }
+
+    #[test]
+    fn test_reject_chained_symlinks() {
+        let archive = fixture_tar_gz(&[
+            ("a/", EntryType::Directory, b"", None),
+            ("a/l", EntryType::Symlink, b"", Some("..")),
+            ("a/l/m", EntryType::Symlink, b"", Some("..")),
+            ("a/l/m/escaped.txt", EntryType::Regular, b"evil", None),
+        ]);
+        let sandbox = Path::new("output_dir_safe_chain");
+        let output_dir = sandbox.join("output");
+        let result = ArchiveReader::new(archive)
+            .unwrap()
+            .extract_safely(&output_dir, &ExtractLimits::default());
+        let escaped = sandbox.join("escaped.txt").exists();
+        remove_dir_all(sandbox).unwrap();
+
+        let error = result.unwrap_err();
+        assert!(matches!(error, ExtractError::PathTraversal { .. }));
+        assert_eq!(error.entry(), Some(Path::new("a/l/m")));
+        assert!(!escaped);
+    }
+
+    #[test]
+    fn test_reject_too_many_entries() {
+        let archive = fixture_tar_gz(&[
+            ("a.txt", EntryType::Regular, b"a", None),
+            ("b.txt", EntryType::Regular, b"b", None),
+            ("c.txt", EntryType::Regular, b"c", None),
+        ]);
+        let limits = ExtractLimits {
+            max_entries: 2,
+            ..ExtractLimits::default()
+        };
+        let error = extract(archive, "output_dir_safe_entries", &limits);
+        assert!(matches!(
+            error,
+            ExtractError::TooManyEntries { limit: 2, .. }
+        ));
+        assert_eq!(error.entry(), Some(Path::new("c.txt")));
+    }
+
+    #[test]
+    fn test_reject_total_size() {
+        let data = vec![b'x'; 1024];
+        let archive = fixture_tar_gz(&[("big.txt", EntryType::Regular, &data, None)]);
+        let limits = ExtractLimits {
+            max_total_bytes: 512,
+            ..ExtractLimits::default()
+        };
+        let error = extract(archive, "output_dir_safe_size", &limits);
+        assert!(matches!(
+            error,
+            ExtractError::TotalSizeExceeded { limit: 512, .. }
+        ));
+        assert_eq!(error.entry(), Some(Path::new("big.txt")));
+    }
+
+    #[test]
+    fn test_reject_compression_ratio() {
+        let zeros = vec![0; 8 * 1024 * 1024];
+        let archive = fixture_tar_gz(&[("bomb.bin", EntryType::Regular, &zeros, None)]);
+        let error = extract(archive, "output_dir_safe_ratio", &ExtractLimits::default());
+        assert!(matches!(
+            error,
+            ExtractError::CompressionRatioExceeded { .. }
+        ));
+        assert_eq!(error.entry(), Some(Path::new("bomb.bin")));
+    }
+
+    #[test]
+    fn test_reject_zip_parent_dir_entry() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("../evil.txt", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"evil").unwrap();
+        let mut archive = writer.finish().unwrap();
+        archive.set_position(0);
+
+        let error = extract(archive, "output_dir_safe_zip", &ExtractLimits::default());
+        assert!(matches!(error, ExtractError::PathTraversal { .. }));
+        assert_eq!(error.entry(), Some(Path::new("../evil.txt")));
+    }
+
+    #[test]
+    fn test_reject_zip_compression_ratio() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("bomb.bin", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(&vec![0; 8 * 1024 * 1024]).unwrap();
+        let mut archive = writer.finish().unwrap();
+        archive.set_position(0);
+
+        let error = extract(
+            archive,
+            "output_dir_safe_zip_ratio",
+            &ExtractLimits::default(),
+        );
+        assert!(matches!(
+            error,
+            ExtractError::CompressionRatioExceeded { .. }
+        ));
+        assert_eq!(error.entry(), Some(Path::new("bomb.bin")));
+    }
+
+    #[test]
+    fn test_extract_safe_archive() {
+        let archive = fixture_tar_gz(&[
+            ("logs/", EntryType::Directory, b"", None),
+            ("logs/app.log", EntryType::Regular, b"started", None),
+            ("logs/current", EntryType::Symlink, b"", Some("app.log")),
+        ]);
+        let output_dir = "output_dir_safe_ok";
+        let extracted = ArchiveReader::new(archive)
+            .unwrap()
+            .extract_safely_removing_prefix("logs", output_dir, &ExtractLimits::default())
+            .unwrap();
+        assert_eq!(extracted.len(), 3);
+        assert!(Path::new(output_dir).join("app.log").exists());
+        assert!(Path::new(output_dir)
+            .join("current")
+            .symlink_metadata()
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        remove_dir_all(output_dir).unwrap();
+    }
+
+    #[test]
+    fn test_extract_removing_prefix_stays_inside() {
+        let output_dir = "output_dir_prefix_traversal/out";
+        let archive = fixture_tar_gz(&[
+            ("logs/../evil.txt", EntryType::Regular, b"evil", None),
+            ("logs/app.log", EntryType::Regular, b"started", None),
+        ]);
+        let extracted = ArchiveReader::new(archive)
+            .unwrap()
+            .extract_removing_prefix("logs", output_dir)
+            .unwrap();
+        assert_eq!(extracted, vec![Path::new("app.log")]);
+        assert!(!Path::new("output_dir_prefix_traversal/evil.txt").exists());
+
+        let archive = fixture_tar_gz(&[
+            ("logs/up", EntryType::Symlink, b"", Some("..")),
+            ("logs/up/evil.txt", EntryType::Regular, b"evil", None),
+        ]);
+        let result = ArchiveReader::new(archive)
+            .unwrap()
+            .extract_removing_prefix("logs", output_dir);
+        assert!(result.is_err());
+        assert!(!Path::new("output_dir_prefix_traversal/evil.txt").exists());
+        remove_dir_all("output_dir_prefix_traversal").unwrap();
+    }
+
+    #[test]
+    fn test_decompress_file_safely() {
+        let tar_file = "test_safe_extraction.tar.gz";
+        let output_dir = "output_dir_safe_file";
+        compress_file(tar_file, "backup/src", "src").unwrap();
+        decompress_file_safely(tar_file, output_dir, &ExtractLimits::default()).unwrap();
+        assert!(Path::new(output_dir).join("backup/src/lib.rs").exists());
+        remove_dir_all(output_dir).unwrap();
+        remove_file(tar_file).unwrap();
+
+        let missing =
+            decompress_file_safely("missing.tar.gz", output_dir, &ExtractLimits::default());
+        assert!(matches!(missing, Err(ExtractError::Io { entry: None, .. })));
+        assert!(File::open(output_dir).is_err());
+    }
+}
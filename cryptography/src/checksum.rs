@@ -0,0 +1,396 @@
+use crate::{hash_file, HashAlgorithm, Hasher};
+use data_encoding::HEXLOWER;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::{canonicalize, read_to_string, write},
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+/// Prefix of the hash of a file leaf of the Merkle tree.
+const LEAF_PREFIX: u8 = 0x00;
+
+/// Prefix of the hash of an inner node of the Merkle tree.
+const NODE_PREFIX: u8 = 0x01;
+
+/// List the regular files under `dir` as sorted `/`-separated relative paths.
+fn relative_files(dir: &Path, skip: Option<&Path>) -> Result<Vec<(String, PathBuf)>, Error> {
+    let skip = skip.and_then(|path| canonicalize(path).ok());
+    let mut files = Vec::new();
+
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        if skip.is_some() && canonicalize(entry.path()).ok() == skip {
+            continue;
+        }
+
+        let relative = entry.path().strip_prefix(dir).map_err(Error::other)?;
+        let name = relative
+            .components()
+            .map(|component| component.as_os_str().to_str())
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Path is not valid UTF-8: {}", relative.display()),
+                )
+            })?
+            .join("/");
+        files.push((name, entry.into_path()));
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Computes a deterministic Merkle root of the files under a directory.
+///
+/// Every regular file is a leaf hashing its relative `/`-separated path and
+/// the digest of its contents; leaves are sorted by path and paired into
+/// inner nodes, an odd node being carried up as is. Leaves and inner nodes
+/// are prefixed with different bytes so one cannot pass for the other. The
+/// root only depends on paths and contents, not on timestamps, permissions or
+/// empty directories.
+///
+/// # Arguments
+///
+/// * `algorithm` - The hash algorithm of files and nodes.
+///
+/// * `dir` - The directory to hash.
+///
+/// # Returns
+///
+/// The root digest; for a directory without files, the digest of no data.
+///
+/// # Examples
+///
+/// ```
+/// use cryptography::{merkle_root, HashAlgorithm};
+/// use std::fs::{create_dir_all, remove_dir_all, write};
+///
+/// create_dir_all("merkle_dir/logs").unwrap();
+/// write("merkle_dir/logs/app.log", "started").unwrap();
+/// let root = merkle_root(HashAlgorithm::Blake3, "merkle_dir").unwrap();
+///
+/// write("merkle_dir/logs/app.log", "stopped").unwrap();
+/// assert_ne!(merkle_root(HashAlgorithm::Blake3, "merkle_dir").unwrap(), root);
+///
+/// remove_dir_all("merkle_dir").unwrap();
+/// ```
+pub fn merkle_root<P: AsRef<Path>>(algorithm: HashAlgorithm, dir: P) -> Result<Vec<u8>, Error> {
+    let mut level = relative_files(dir.as_ref(), None)?
+        .into_iter()
+        .map(|(name, path)| {
+            let mut hasher = Hasher::new(algorithm);
+            hasher.update(&[LEAF_PREFIX]);
+            hasher.update(&(name.len() as u64).to_be_bytes());
+            hasher.update(name.as_bytes());
+            hasher.update(&hash_file(algorithm, path)?);
+            Ok(hasher.finalize())
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    if level.is_empty() {
+        return Ok(Hasher::new(algorithm).finalize());
+    }
+
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => {
+                    let mut hasher = Hasher::new(algorithm);
+                    hasher.update(&[NODE_PREFIX]);
+                    hasher.update(left);
+                    hasher.update(right);
+                    hasher.finalize()
+                }
+                [single] => single.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+
+    Ok(level.remove(0))
+}
+
+/// The digest of one file of a checksum file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumEntry {
+    pub digest: Vec<u8>,
+    /// Relative `/`-separated path of the file.
+    pub path: String,
+}
+
+/// A list of file digests in the format of `sha256sum` and its siblings
+///
+/// Each line is the hex digest, two spaces (or a space and `*` for binary
+/// mode) and the path. Paths with a backslash or newline are escaped the way
+/// GNU coreutils does, with the line starting with a backslash.
+///
+/// # Examples
+///
+/// ```
+/// use cryptography::{ChecksumFile, HashAlgorithm};
+///
+/// let text = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  abc.txt\n";
+/// let checksums = ChecksumFile::parse(HashAlgorithm::Sha256, text).unwrap();
+///
+/// assert_eq!(checksums.entries[0].path, "abc.txt");
+/// assert_eq!(checksums.to_string(), text);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumFile {
+    pub algorithm: HashAlgorithm,
+    pub entries: Vec<ChecksumEntry>,
+}
+
+/// The outcome of checking a directory against a `ChecksumFile`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Files whose digest matches.
+    pub verified: Vec<String>,
+    /// Listed files that do not exist.
+    pub missing: Vec<String>,
+    /// Listed files whose digest differs.
+    pub modified: Vec<String>,
+    /// Files that exist but are not listed.
+    pub extra: Vec<String>,
+}
+
+impl VerifyReport {
+    /// Whether every listed file matches and no file is missing or extra
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.modified.is_empty() && self.extra.is_empty()
+    }
+}
+
+impl ChecksumFile {
+    /// Hash every regular file under a directory
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - The hash algorithm.
+    ///
+    /// * `dir` - The directory the paths are relative to.
+    pub fn from_dir<P: AsRef<Path>>(algorithm: HashAlgorithm, dir: P) -> Result<Self, Error> {
+        ChecksumFile::hash_dir(algorithm, dir.as_ref(), None)
+    }
+
+    fn hash_dir(algorithm: HashAlgorithm, dir: &Path, skip: Option<&Path>) -> Result<Self, Error> {
+        let entries = relative_files(dir, skip)?
+            .into_iter()
+            .map(|(name, path)| {
+                Ok(ChecksumEntry {
+                    digest: hash_file(algorithm, path)?,
+                    path: name,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(ChecksumFile { algorithm, entries })
+    }
+
+    /// Parse the contents of a checksum file
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - The algorithm of the digests, which the format does not record.
+    ///
+    /// * `text` - The contents of the checksum file.
+    pub fn parse(algorithm: HashAlgorithm, text: &str) -> Result<Self, Error> {
+        let entries = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                parse_line(algorithm, line).ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Improperly formatted checksum line {}", index + 1),
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(ChecksumFile { algorithm, entries })
+    }
+
+    /// Read a checksum file
+    pub fn load<P: AsRef<Path>>(algorithm: HashAlgorithm, path: P) -> Result<Self, Error> {
+        ChecksumFile::parse(algorithm, &read_to_string(path)?)
+    }
+
+    /// Write the checksum file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        write(path, self.to_string())
+    }
+
+    /// Check the files under `dir` against the listed digests
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory the listed paths are relative to.
+    ///
+    /// # Returns
+    ///
+    /// The verified, missing, modified and extra files, each sorted by path.
+    pub fn verify<P: AsRef<Path>>(&self, dir: P) -> Result<VerifyReport, Error> {
+        self.verify_dir(dir.as_ref(), None)
+    }
+
+    fn verify_dir(&self, dir: &Path, skip: Option<&Path>) -> Result<VerifyReport, Error> {
+        let mut present = relative_files(dir, skip)?
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        let mut report = VerifyReport::default();
+
+        for entry in &self.entries {
+            match present.remove(&entry.path) {
+                None => report.missing.push(entry.path.clone()),
+                Some(path) if hash_file(self.algorithm, &path)? == entry.digest => {
+                    report.verified.push(entry.path.clone())
+                }
+                Some(_) => report.modified.push(entry.path.clone()),
+            }
+        }
+        report.extra = present.into_keys().collect();
+
+        report.verified.sort();
+        report.missing.sort();
+        report.modified.sort();
+        Ok(report)
+    }
+}
+
+fn parse_line(algorithm: HashAlgorithm, line: &str) -> Option<ChecksumEntry> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+    let hex_len = algorithm.output_len() * 2;
+    let digest = HEXLOWER
+        .decode(line.get(..hex_len)?.to_ascii_lowercase().as_bytes())
+        .ok()?;
+    let rest = line.get(hex_len..)?;
+    let path = rest
+        .strip_prefix("  ")
+        .or_else(|| rest.strip_prefix(" *"))?;
+    if path.is_empty() {
+        return None;
+    }
+    let path = if escaped {
+        unescape(path)?
+    } else {
+        path.to_string()
+    };
+
+    Some(ChecksumEntry { digest, path })
+}
+
+fn unescape(path: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(path.len());
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                '\\' => unescaped.push('\\'),
+                'n' => unescaped.push('\n'),
+                'r' => unescaped.push('\r'),
+                _ => return None,
+            },
+            c => unescaped.push(c),
+        }
+    }
+    Some(unescaped)
+}
+
+impl fmt::Display for ChecksumFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            let digest = HEXLOWER.encode(&entry.digest);
+            if entry.path.contains(['\\', '\n', '\r']) {
+                let path = entry
+                    .path
+                    .replace('\\', "\\\\")
+                    .replace('\n', "\\n")
+                    .replace('\r', "\\r");
+                writeln!(f, "\\{}  {}", digest, path)?;
+            } else {
+                writeln!(f, "{}  {}", digest, entry.path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Hashes a directory into a checksum file.
+///
+/// The checksum file itself is left out when it is inside the directory, so
+/// a `SHA256SUMS` next to the files can be regenerated in place.
+///
+/// # Arguments
+///
+/// * `algorithm` - The hash algorithm.
+///
+/// * `dir` - The directory to hash.
+///
+/// * `checksum_file` - The checksum file to write.
+///
+/// # Example
+///
+/// ```
+/// use cryptography::{verify_checksum_file, write_checksum_file, HashAlgorithm};
+/// use std::fs::{create_dir_all, remove_dir_all, remove_file, write};
+///
+/// create_dir_all("release_dir").unwrap();
+/// write("release_dir/app.tar.gz", "app").unwrap();
+/// write("release_dir/README", "readme").unwrap();
+/// write_checksum_file(HashAlgorithm::Sha256, "release_dir", "release_dir/SHA256SUMS").unwrap();
+///
+/// write("release_dir/app.tar.gz", "tampered").unwrap();
+/// remove_file("release_dir/README").unwrap();
+/// write("release_dir/NOTES", "notes").unwrap();
+///
+/// let report =
+///     verify_checksum_file(HashAlgorithm::Sha256, "release_dir/SHA256SUMS", "release_dir").unwrap();
+/// assert_eq!(report.modified, vec!["app.tar.gz"]);
+/// assert_eq!(report.missing, vec!["README"]);
+/// assert_eq!(report.extra, vec!["NOTES"]);
+///
+/// remove_dir_all("release_dir").unwrap();
+/// ```
+pub fn write_checksum_file<P: AsRef<Path>, Q: AsRef<Path>>(
+    algorithm: HashAlgorithm,
+    dir: P,
+    checksum_file: Q,
+) -> Result<ChecksumFile, Error> {
+    let checksums = ChecksumFile::hash_dir(algorithm, dir.as_ref(), Some(checksum_file.as_ref()))?;
+    checksums.save(checksum_file)?;
+    Ok(checksums)
+}
+
+/// Verifies a directory against a checksum file.
+///
+/// # Arguments
+///
+/// * `algorithm` - The algorithm of the digests.
+///
+/// * `checksum_file` - The checksum file to read, ignored as an extra file.
+///
+/// * `dir` - The directory the listed paths are relative to.
+///
+/// # Returns
+///
+/// The verified, missing, modified and extra files.
+pub fn verify_checksum_file<P: AsRef<Path>, Q: AsRef<Path>>(
+    algorithm: HashAlgorithm,
+    checksum_file: P,
+    dir: Q,
+) -> Result<VerifyReport, Error> {
+    let checksums = ChecksumFile::load(algorithm, &checksum_file)?;
+    checksums.verify_dir(dir.as_ref(), Some(checksum_file.as_ref()))
+}
@@ -1,12 +1,43 @@
 use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, CHACHA20_POLY1305, MAX_TAG_LEN},
     digest::SHA1_OUTPUT_LEN,
     pbkdf2::{derive, PBKDF2_HMAC_SHA512},
     rand::{SecureRandom, SystemRandom},
 };
-use std::{error::Error, num::NonZeroU32};
+use std::{
+    error::Error,
+    fs::{remove_file, File},
+    io::{self, BufReader, BufWriter, ErrorKind, Read, Write},
+    num::NonZeroU32,
+    path::Path,
+};
 
 const CREDENTIAL_LEN: usize = SHA1_OUTPUT_LEN;
 
+/// Length of the keys used by every cipher.
+pub const KEY_LEN: usize = 32;
+
+/// Default plaintext size of each encrypted chunk.
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Largest chunk size accepted in a header, to bound memory when decrypting.
+pub const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// Default PBKDF2 iterations of the password mode.
+pub const DEFAULT_PBKDF2_ITERATIONS: u32 = 210_000;
+
+/// Largest PBKDF2 iteration count accepted in a header.
+const MAX_PBKDF2_ITERATIONS: u32 = 10_000_000;
+
+/// Version of the stream format written by `Encryptor`.
+pub const FORMAT_VERSION: u8 = 1;
+
+const MAGIC: &[u8; 4] = b"RPAE";
+const SALT_LEN: usize = 16;
+const NONCE_PREFIX_LEN: usize = 7;
+const KDF_NONE: u8 = 0;
+const KDF_PBKDF2_SHA512: u8 = 1;
+
 /// Salts and hashes a password using PBKDF2 with HMAC-SHA512.
 ///
 /// # Arguments
@@ -41,13 +72,582 @@ pub fn salt_and_hash_password_with_pbkdf2(
         .map_err(|_| Box::<dyn Error>::from("Failed to generate random salt"))?;
 
     let mut pbkdf2_hash = [0u8; CREDENTIAL_LEN];
+    derive_key_with_pbkdf2(password, &salt, iterations, &mut pbkdf2_hash);
+
+    Ok((salt, pbkdf2_hash))
+}
+
+/// Derives a key from a password using PBKDF2 with HMAC-SHA512.
+///
+/// # Arguments
+///
+/// * `password` - The password to derive the key from.
+///
+/// * `salt` - The salt of the derivation.
+///
+/// * `iterations` - The number of PBKDF2 iterations.
+///
+/// * `key` - The buffer filled with the derived key.
+///
+/// # Example
+///
+/// ```
+/// use cryptography::{derive_key_with_pbkdf2, KEY_LEN};
+/// use std::num::NonZeroU32;
+///
+/// let mut key = [0u8; KEY_LEN];
+/// derive_key_with_pbkdf2("ABCabc123?", b"salt", NonZeroU32::new(1_000).unwrap(), &mut key);
+///
+/// assert_ne!(key, [0u8; KEY_LEN]);
+/// ```
+pub fn derive_key_with_pbkdf2(password: &str, salt: &[u8], iterations: NonZeroU32, key: &mut [u8]) {
     derive(
         PBKDF2_HMAC_SHA512,
         iterations,
-        &salt,
+        salt,
         password.as_bytes(),
-        &mut pbkdf2_hash,
+        key,
     );
+}
 
-    Ok((salt, pbkdf2_hash))
+/// Generates a random key for `Encryptor` and `DecryptReader`.
+///
+/// # Returns
+///
+/// A key of `KEY_LEN` random bytes.
+pub fn generate_key() -> Result<[u8; KEY_LEN], io::Error> {
+    let mut key = [0u8; KEY_LEN];
+    fill_random(&mut key)?;
+    Ok(key)
+}
+
+fn fill_random(buffer: &mut [u8]) -> Result<(), io::Error> {
+    SystemRandom::new()
+        .fill(buffer)
+        .map_err(|_| io::Error::other("Failed to generate random bytes"))
+}
+
+/// An authenticated cipher of the stream format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cipher {
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+impl Cipher {
+    fn id(&self) -> u8 {
+        match self {
+            Cipher::Aes256Gcm => 1,
+            Cipher::ChaCha20Poly1305 => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Result<Self, io::Error> {
+        match id {
+            1 => Ok(Cipher::Aes256Gcm),
+            2 => Ok(Cipher::ChaCha20Poly1305),
+            _ => Err(invalid_data(format!("Unknown cipher {}", id))),
+        }
+    }
+
+    fn key(&self, key: &[u8]) -> Result<LessSafeKey, io::Error> {
+        let algorithm = match self {
+            Cipher::Aes256Gcm => &AES_256_GCM,
+            Cipher::ChaCha20Poly1305 => &CHACHA20_POLY1305,
+        };
+        UnboundKey::new(algorithm, key)
+            .map(LessSafeKey::new)
+            .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "Invalid key length"))
+    }
+}
+
+fn invalid_data<E: Into<Box<dyn Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, error)
+}
+
+/// The versioned header that starts every encrypted stream
+///
+/// Layout: magic `RPAE`, version, cipher, key derivation, chunk size (u32 BE),
+/// nonce prefix, and for the password mode the PBKDF2 iterations (u32 BE) and
+/// salt. The whole header is authenticated as associated data of every chunk.
+struct Header {
+    cipher: Cipher,
+    chunk_size: usize,
+    nonce_prefix: [u8; NONCE_PREFIX_LEN],
+    pbkdf2: Option<(NonZeroU32, [u8; SALT_LEN])>,
+}
+
+impl Header {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(FORMAT_VERSION);
+        bytes.push(self.cipher.id());
+        bytes.push(match self.pbkdf2 {
+            Some(_) => KDF_PBKDF2_SHA512,
+            None => KDF_NONE,
+        });
+        bytes.extend_from_slice(&(self.chunk_size as u32).to_be_bytes());
+        bytes.extend_from_slice(&self.nonce_prefix);
+        if let Some((iterations, salt)) = &self.pbkdf2 {
+            bytes.extend_from_slice(&iterations.get().to_be_bytes());
+            bytes.extend_from_slice(salt);
+        }
+        bytes
+    }
+
+    fn read<R: Read>(reader: &mut R) -> Result<(Self, Vec<u8>), io::Error> {
+        let mut bytes = vec![0u8; MAGIC.len() + 7 + NONCE_PREFIX_LEN];
+        reader.read_exact(&mut bytes)?;
+        if &bytes[..4] != MAGIC {
+            return Err(invalid_data("Not an encrypted stream"));
+        }
+        if bytes[4] != FORMAT_VERSION {
+            return Err(invalid_data(format!(
+                "Unsupported format version {}",
+                bytes[4]
+            )));
+        }
+        let cipher = Cipher::from_id(bytes[5])?;
+        let chunk_size = u32::from_be_bytes(bytes[7..11].try_into().unwrap()) as usize;
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(invalid_data(format!("Invalid chunk size {}", chunk_size)));
+        }
+        let nonce_prefix = bytes[11..].try_into().unwrap();
+
+        let pbkdf2 = match bytes[6] {
+            KDF_NONE => None,
+            KDF_PBKDF2_SHA512 => {
+                let mut params = [0u8; 4 + SALT_LEN];
+                reader.read_exact(&mut params)?;
+                bytes.extend_from_slice(&params);
+                let iterations = u32::from_be_bytes(params[..4].try_into().unwrap());
+                let iterations = NonZeroU32::new(iterations)
+                    .filter(|iterations| iterations.get() <= MAX_PBKDF2_ITERATIONS)
+                    .ok_or_else(|| invalid_data(format!("Invalid iterations {}", iterations)))?;
+                Some((iterations, params[4..].try_into().unwrap()))
+            }
+            kdf => return Err(invalid_data(format!("Unknown key derivation {}", kdf))),
+        };
+
+        let header = Header {
+            cipher,
+            chunk_size,
+            nonce_prefix,
+            pbkdf2,
+        };
+        Ok((header, bytes))
+    }
+}
+
+/// Shared nonce sequence of both directions: prefix, chunk counter, last flag.
+struct ChunkNonces {
+    prefix: [u8; NONCE_PREFIX_LEN],
+    counter: u32,
+    exhausted: bool,
+}
+
+impl ChunkNonces {
+    fn next(&mut self, last: bool) -> Result<Nonce, io::Error> {
+        if self.exhausted {
+            return Err(io::Error::other("Too many chunks in one stream"));
+        }
+        let mut nonce = [0u8; 12];
+        nonce[..NONCE_PREFIX_LEN].copy_from_slice(&self.prefix);
+        nonce[NONCE_PREFIX_LEN..11].copy_from_slice(&self.counter.to_be_bytes());
+        nonce[11] = last as u8;
+
+        match self.counter.checked_add(1) {
+            Some(counter) => self.counter = counter,
+            None => self.exhausted = true,
+        }
+        Ok(Nonce::assume_unique_for_key(nonce))
+    }
+}
+
+/// Settings of new encrypted streams
+///
+/// Data is split into chunks sealed separately, so streams of any size are
+/// encrypted and decrypted in constant memory. Every chunk has its own nonce
+/// and the last one is flagged, so reordered, dropped or truncated chunks fail
+/// to decrypt.
+///
+/// # Examples
+///
+/// ```
+/// use cryptography::{generate_key, Cipher, DecryptReader, Encryptor};
+/// use std::io::{Read, Write};
+///
+/// let key = generate_key().unwrap();
+///
+/// let mut writer = Encryptor::new(Cipher::ChaCha20Poly1305).encrypt(Vec::new(), &key).unwrap();
+/// writer.write_all(b"Hello, world!").unwrap();
+/// let encrypted = writer.finish().unwrap();
+///
+/// let mut decrypted = String::new();
+/// DecryptReader::new(&encrypted[..], &key)
+///     .unwrap()
+///     .read_to_string(&mut decrypted)
+///     .unwrap();
+/// assert_eq!(decrypted, "Hello, world!");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Encryptor {
+    cipher: Cipher,
+    chunk_size: usize,
+    iterations: NonZeroU32,
+}
+
+impl Encryptor {
+    /// Create settings for the given cipher with the default chunk size and iterations
+    pub fn new(cipher: Cipher) -> Self {
+        Encryptor {
+            cipher,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            iterations: NonZeroU32::new(DEFAULT_PBKDF2_ITERATIONS).unwrap(),
+        }
+    }
+
+    /// Set the plaintext size of each chunk, from 1 byte to `MAX_CHUNK_SIZE`
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.clamp(1, MAX_CHUNK_SIZE);
+        self
+    }
+
+    /// Set the PBKDF2 iterations of the password mode
+    pub fn iterations(mut self, iterations: NonZeroU32) -> Self {
+        self.iterations = iterations.min(NonZeroU32::new(MAX_PBKDF2_ITERATIONS).unwrap());
+        self
+    }
+
+    /// Start an encrypted stream with a raw key
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The destination of the encrypted stream
+    ///
+    /// * `key` - A random key of `KEY_LEN` bytes
+    pub fn encrypt<W: Write>(
+        &self,
+        writer: W,
+        key: &[u8; KEY_LEN],
+    ) -> Result<EncryptWriter<W>, io::Error> {
+        EncryptWriter::new(writer, self.header(None)?, key)
+    }
+
+    /// Start an encrypted stream keyed by a password
+    ///
+    /// The key is derived with PBKDF2-HMAC-SHA512 from a random salt, both
+    /// stored in the header.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The destination of the encrypted stream
+    ///
+    /// * `password` - The password to derive the key from
+    pub fn encrypt_with_password<W: Write>(
+        &self,
+        writer: W,
+        password: &str,
+    ) -> Result<EncryptWriter<W>, io::Error> {
+        let mut salt = [0u8; SALT_LEN];
+        fill_random(&mut salt)?;
+        let mut key = [0u8; KEY_LEN];
+        derive_key_with_pbkdf2(password, &salt, self.iterations, &mut key);
+        EncryptWriter::new(writer, self.header(Some((self.iterations, salt)))?, &key)
+    }
+
+    fn header(&self, pbkdf2: Option<(NonZeroU32, [u8; SALT_LEN])>) -> Result<Header, io::Error> {
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_LEN];
+        fill_random(&mut nonce_prefix)?;
+        Ok(Header {
+            cipher: self.cipher,
+            chunk_size: self.chunk_size,
+            nonce_prefix,
+            pbkdf2,
+        })
+    }
+}
+
+/// A writer that encrypts everything written to it
+///
+/// Call `finish` to seal the last chunk; a stream dropped before that is
+/// rejected as truncated when decrypted.
+pub struct EncryptWriter<W: Write> {
+    writer: W,
+    key: LessSafeKey,
+    header: Vec<u8>,
+    nonces: ChunkNonces,
+    chunk_size: usize,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> EncryptWriter<W> {
+    fn new(mut writer: W, header: Header, key: &[u8]) -> Result<Self, io::Error> {
+        let bytes = header.to_bytes();
+        writer.write_all(&bytes)?;
+        Ok(EncryptWriter {
+            writer,
+            key: header.cipher.key(key)?,
+            header: bytes,
+            nonces: ChunkNonces {
+                prefix: header.nonce_prefix,
+                counter: 0,
+                exhausted: false,
+            },
+            chunk_size: header.chunk_size,
+            buffer: Vec::with_capacity(header.chunk_size + MAX_TAG_LEN),
+        })
+    }
+
+    fn seal_chunk(&mut self, last: bool) -> Result<(), io::Error> {
+        let nonce = self.nonces.next(last)?;
+        self.key
+            .seal_in_place_append_tag(nonce, Aad::from(&self.header), &mut self.buffer)
+            .map_err(|_| io::Error::other("Failed to encrypt chunk"))?;
+        self.writer.write_all(&self.buffer)?;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Seal the last chunk and return the underlying writer
+    pub fn finish(mut self) -> Result<W, io::Error> {
+        self.seal_chunk(true)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for EncryptWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // A full chunk is only sealed once more data arrives, so the last one
+        // is always sealed by `finish` with the final flag.
+        if self.buffer.len() == self.chunk_size && !buf.is_empty() {
+            self.seal_chunk(false)?;
+        }
+        let count = buf.len().min(self.chunk_size - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..count]);
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// A reader that decrypts and authenticates a stream written by `EncryptWriter`
+///
+/// Chunks are returned only after their tag is verified. A modified, reordered
+/// or truncated stream fails with an `InvalidData` error.
+pub struct DecryptReader<R: Read> {
+    reader: R,
+    key: LessSafeKey,
+    header: Vec<u8>,
+    nonces: ChunkNonces,
+    chunk_size: usize,
+    cipher: Cipher,
+    buffer: Vec<u8>,
+    plaintext: Vec<u8>,
+    position: usize,
+    finished: bool,
+}
+
+impl<R: Read> DecryptReader<R> {
+    /// Read the header of a stream encrypted with a raw key
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The encrypted stream
+    ///
+    /// * `key` - The key the stream was encrypted with
+    pub fn new(mut reader: R, key: &[u8; KEY_LEN]) -> Result<Self, io::Error> {
+        let (header, bytes) = Header::read(&mut reader)?;
+        if header.pbkdf2.is_some() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "Stream was encrypted with a password",
+            ));
+        }
+        DecryptReader::with_header(reader, header, bytes, key)
+    }
+
+    /// Read the header of a stream encrypted with a password
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The encrypted stream
+    ///
+    /// * `password` - The password the stream was encrypted with
+    pub fn with_password(mut reader: R, password: &str) -> Result<Self, io::Error> {
+        let (header, bytes) = Header::read(&mut reader)?;
+        let (iterations, salt) = header.pbkdf2.ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                "Stream was encrypted with a raw key",
+            )
+        })?;
+        let mut key = [0u8; KEY_LEN];
+        derive_key_with_pbkdf2(password, &salt, iterations, &mut key);
+        DecryptReader::with_header(reader, header, bytes, &key)
+    }
+
+    fn with_header(
+        reader: R,
+        header: Header,
+        bytes: Vec<u8>,
+        key: &[u8],
+    ) -> Result<Self, io::Error> {
+        Ok(DecryptReader {
+            reader,
+            key: header.cipher.key(key)?,
+            header: bytes,
+            nonces: ChunkNonces {
+                prefix: header.nonce_prefix,
+                counter: 0,
+                exhausted: false,
+            },
+            chunk_size: header.chunk_size,
+            cipher: header.cipher,
+            buffer: Vec::new(),
+            plaintext: Vec::new(),
+            position: 0,
+            finished: false,
+        })
+    }
+
+    /// The cipher named in the header
+    pub fn cipher(&self) -> Cipher {
+        self.cipher
+    }
+
+    /// Read and open the next chunk, looking one byte ahead to tell the last one.
+    fn next_chunk(&mut self) -> Result<(), io::Error> {
+        let sealed_len = self.chunk_size + MAX_TAG_LEN;
+        let mut eof = false;
+        while self.buffer.len() <= sealed_len {
+            let filled = self.buffer.len();
+            self.buffer.resize(sealed_len + 1, 0);
+            match self.reader.read(&mut self.buffer[filled..]) {
+                Ok(0) => {
+                    self.buffer.truncate(filled);
+                    eof = true;
+                    break;
+                }
+                Ok(count) => self.buffer.truncate(filled + count),
+                Err(e) if e.kind() == ErrorKind::Interrupted => self.buffer.truncate(filled),
+                Err(e) => {
+                    self.buffer.truncate(filled);
+                    return Err(e);
+                }
+            }
+        }
+
+        let last = eof;
+        let rest = if last {
+            Vec::new()
+        } else {
+            self.buffer.split_off(sealed_len)
+        };
+        let mut sealed = std::mem::replace(&mut self.buffer, rest);
+        if sealed.len() < MAX_TAG_LEN {
+            return Err(invalid_data("Encrypted stream is truncated"));
+        }
+
+        let nonce = self.nonces.next(last)?;
+        let len = self
+            .key
+            .open_in_place(nonce, Aad::from(&self.header), &mut sealed)
+            .map_err(|_| invalid_data("Decryption failed, the data was modified or truncated"))?
+            .len();
+        sealed.truncate(len);
+
+        self.plaintext = sealed;
+        self.position = 0;
+        self.finished = last;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.plaintext.len() {
+            if self.finished {
+                return Ok(0);
+            }
+            self.next_chunk()?;
+        }
+        let count = buf.len().min(self.plaintext.len() - self.position);
+        buf[..count].copy_from_slice(&self.plaintext[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+/// Encrypts a file with a password.
+///
+/// # Arguments
+///
+/// * `input` - The file to encrypt.
+///
+/// * `output` - The encrypted file to create.
+///
+/// * `password` - The password to derive the key from.
+///
+/// * `cipher` - The cipher to encrypt with.
+///
+/// # Example
+///
+/// ```
+/// use cryptography::{decrypt_file, encrypt_file, Cipher};
+/// use std::fs::{read_to_string, remove_file, write};
+///
+/// write("secret.txt", "Hello, world!").unwrap();
+/// encrypt_file("secret.txt", "secret.txt.enc", "ABCabc123?", Cipher::Aes256Gcm).unwrap();
+/// decrypt_file("secret.txt.enc", "secret.out.txt", "ABCabc123?").unwrap();
+///
+/// assert_eq!(read_to_string("secret.out.txt").unwrap(), "Hello, world!");
+/// assert!(decrypt_file("secret.txt.enc", "secret.out.txt", "wrong").is_err());
+/// assert!(!std::path::Path::new("secret.out.txt").exists());
+///
+/// for file in ["secret.txt", "secret.txt.enc"] {
+///     remove_file(file).unwrap();
+/// }
+/// ```
+pub fn encrypt_file<P: AsRef<Path>, Q: AsRef<Path>>(
+    input: P,
+    output: Q,
+    password: &str,
+    cipher: Cipher,
+) -> Result<(), io::Error> {
+    let mut reader = BufReader::new(File::open(input)?);
+    let writer = BufWriter::new(File::create(output)?);
+    let mut writer = Encryptor::new(cipher).encrypt_with_password(writer, password)?;
+    io::copy(&mut reader, &mut writer)?;
+    writer.finish()?.flush()
+}
+
+/// Decrypts a file encrypted with `encrypt_file`.
+///
+/// The output file is removed if the input fails to authenticate, so no
+/// unverified plaintext is left behind.
+///
+/// # Arguments
+///
+/// * `input` - The encrypted file.
+///
+/// * `output` - The decrypted file to create.
+///
+/// * `password` - The password the file was encrypted with.
+pub fn decrypt_file<P: AsRef<Path>, Q: AsRef<Path>>(
+    input: P,
+    output: Q,
+    password: &str,
+) -> Result<(), io::Error> {
+    let mut reader = DecryptReader::with_password(BufReader::new(File::open(input)?), password)?;
+    let mut writer = BufWriter::new(File::create(&output)?);
+    let result = io::copy(&mut reader, &mut writer).and_then(|_| writer.flush());
+    if result.is_err() {
+        drop(writer);
+        let _ = remove_file(output);
+    }
+    result
 }
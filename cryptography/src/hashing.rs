@@ -1,10 +1,19 @@
+use data_encoding::HEXLOWER;
 use ring::{
-    digest::{Context, Digest, SHA256},
+    digest::{Context, Digest, SHA1_FOR_LEGACY_USE_ONLY, SHA256, SHA384, SHA512},
     error::Unspecified,
     hmac::{self, Key, Tag},
     rand::{self, SecureRandom},
 };
-use std::io::{Error, Read};
+use std::{
+    fs::File,
+    io::{self, Error, ErrorKind, Read, Write},
+    path::Path,
+    str::FromStr,
+};
+
+/// Size of the buffer used to stream data into a hasher.
+pub const HASH_BUFFER_SIZE: usize = 64 * 1024;
 
 /// Computes the SHA-256 digest of the data read from the given reader.
 ///
@@ -40,7 +49,7 @@ use std::io::{Error, Read};
 /// ```
 pub fn sha256_digest<R: Read>(mut reader: R) -> Result<Digest, Error> {
     let mut context = Context::new(&SHA256);
-    let mut buffer = [0; 1024];
+    let mut buffer = vec![0; HASH_BUFFER_SIZE];
 
     loop {
         let count = reader.read(&mut buffer)?;
@@ -53,6 +62,200 @@ pub fn sha256_digest<R: Read>(mut reader: R) -> Result<Digest, Error> {
     Ok(context.finish())
 }
 
+/// A hash function supported by `Hasher`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    /// SHA-1, only for compatibility with existing checksums.
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+    Blake3,
+}
+
+impl HashAlgorithm {
+    pub const ALL: [HashAlgorithm; 5] = [
+        HashAlgorithm::Sha1,
+        HashAlgorithm::Sha256,
+        HashAlgorithm::Sha384,
+        HashAlgorithm::Sha512,
+        HashAlgorithm::Blake3,
+    ];
+
+    /// The lowercase name of the algorithm, e.g. `sha256`
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha1 => "sha1",
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha384 => "sha384",
+            HashAlgorithm::Sha512 => "sha512",
+            HashAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    /// The length of the digest in bytes
+    pub fn output_len(&self) -> usize {
+        match self {
+            HashAlgorithm::Sha1 => 20,
+            HashAlgorithm::Sha256 | HashAlgorithm::Blake3 => 32,
+            HashAlgorithm::Sha384 => 48,
+            HashAlgorithm::Sha512 => 64,
+        }
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        HashAlgorithm::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Unknown hash algorithm: {}", name),
+                )
+            })
+    }
+}
+
+enum HasherInner {
+    Ring(Box<Context>),
+    Blake3(Box<blake3::Hasher>),
+}
+
+/// An incremental hasher over any `HashAlgorithm`
+///
+/// `Hasher` implements `Write`, so readers can be hashed with `io::copy`.
+///
+/// # Examples
+///
+/// ```
+/// use cryptography::{HashAlgorithm, Hasher};
+///
+/// let mut hasher = Hasher::new(HashAlgorithm::Blake3);
+/// hasher.update(b"Hello, ");
+/// hasher.update(b"world!");
+///
+/// assert_eq!(
+///     hasher.finalize_hex(),
+///     "ede5c0b10f2ec4979c69b52f61e42ff5b413519ce09be0f14d098dcfe5f6f98d"
+/// );
+/// ```
+pub struct Hasher {
+    algorithm: HashAlgorithm,
+    inner: HasherInner,
+}
+
+impl Hasher {
+    /// Create a hasher for the given algorithm
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        let inner = match algorithm {
+            HashAlgorithm::Sha1 => {
+                HasherInner::Ring(Box::new(Context::new(&SHA1_FOR_LEGACY_USE_ONLY)))
+            }
+            HashAlgorithm::Sha256 => HasherInner::Ring(Box::new(Context::new(&SHA256))),
+            HashAlgorithm::Sha384 => HasherInner::Ring(Box::new(Context::new(&SHA384))),
+            HashAlgorithm::Sha512 => HasherInner::Ring(Box::new(Context::new(&SHA512))),
+            HashAlgorithm::Blake3 => HasherInner::Blake3(Box::default()),
+        };
+        Hasher { algorithm, inner }
+    }
+
+    /// The algorithm of the hasher
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    /// Feed data to the hasher
+    pub fn update(&mut self, data: &[u8]) {
+        match &mut self.inner {
+            HasherInner::Ring(context) => context.update(data),
+            HasherInner::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    /// Consume the hasher, returning the digest
+    pub fn finalize(self) -> Vec<u8> {
+        match self.inner {
+            HasherInner::Ring(context) => context.finish().as_ref().to_vec(),
+            HasherInner::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+        }
+    }
+
+    /// Consume the hasher, returning the digest in lowercase hex
+    pub fn finalize_hex(self) -> String {
+        HEXLOWER.encode(&self.finalize())
+    }
+}
+
+impl Write for Hasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Computes the digest of the data read from the given reader.
+///
+/// # Arguments
+///
+/// * `algorithm` - The hash algorithm.
+///
+/// * `reader` - The reader to read the data from.
+///
+/// # Returns
+///
+/// The digest of the data read from the given reader.
+///
+/// # Examples
+///
+/// ```
+/// use cryptography::{hash_reader, HashAlgorithm};
+/// use data_encoding::HEXLOWER;
+///
+/// let digest = hash_reader(HashAlgorithm::Sha1, &b"abc"[..]).unwrap();
+/// assert_eq!(HEXLOWER.encode(&digest), "a9993e364706816aba3e25717850c26c9cd0d89d");
+/// ```
+pub fn hash_reader<R: Read>(algorithm: HashAlgorithm, mut reader: R) -> Result<Vec<u8>, Error> {
+    let mut hasher = Hasher::new(algorithm);
+    let mut buffer = vec![0; HASH_BUFFER_SIZE];
+
+    loop {
+        let count = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(count) => count,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.update(&buffer[..count]);
+    }
+
+    Ok(hasher.finalize())
+}
+
+/// Computes the digest of a file.
+///
+/// # Arguments
+///
+/// * `algorithm` - The hash algorithm.
+///
+/// * `path` - The file to hash.
+///
+/// # Returns
+///
+/// The digest of the file.
+pub fn hash_file<P: AsRef<Path>>(algorithm: HashAlgorithm, path: P) -> Result<Vec<u8>, Error> {
+    hash_reader(algorithm, File::open(path)?)
+}
+
 /// Signs the HMAC of the given message.
 ///
 /// # Arguments
@@ -0,0 +1,167 @@
+use crate::KEY_LEN;
+use aes_kw::KekAes256;
+use ring::hkdf::{KeyType, Salt, HKDF_SHA256};
+use std::io::{Error, ErrorKind};
+
+/// Longest output of HKDF-SHA256, 255 blocks of 32 bytes.
+pub const HKDF_MAX_LEN: usize = 255 * 32;
+
+/// Output length requested from `ring::hkdf`.
+struct OutputLen(usize);
+
+impl KeyType for OutputLen {
+    fn len(&self) -> usize {
+        self.0
+    }
+}
+
+/// Derives keying material with HKDF-SHA256 (RFC 5869).
+///
+/// # Arguments
+///
+/// * `salt` - A non-secret random value, may be empty.
+///
+/// * `input_key` - The input keying material.
+///
+/// * `info` - The context that binds the output to a purpose.
+///
+/// * `output` - The buffer to fill, up to `HKDF_MAX_LEN` bytes.
+///
+/// # Example
+///
+/// ```
+/// use cryptography::hkdf_sha256;
+/// use data_encoding::HEXLOWER;
+///
+/// // Test case 1 of RFC 5869
+/// let input_key = [0x0b; 22];
+/// let salt = HEXLOWER.decode(b"000102030405060708090a0b0c").unwrap();
+/// let info = HEXLOWER.decode(b"f0f1f2f3f4f5f6f7f8f9").unwrap();
+///
+/// let mut output = [0u8; 42];
+/// hkdf_sha256(&salt, &input_key, &info, &mut output).unwrap();
+///
+/// assert_eq!(
+///     HEXLOWER.encode(&output),
+///     "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865"
+/// );
+/// ```
+pub fn hkdf_sha256(
+    salt: &[u8],
+    input_key: &[u8],
+    info: &[u8],
+    output: &mut [u8],
+) -> Result<(), Error> {
+    let invalid_len = || {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("HKDF output must be 1 to {} bytes", HKDF_MAX_LEN),
+        )
+    };
+    if output.is_empty() {
+        return Err(invalid_len());
+    }
+
+    let info = [info];
+    Salt::new(HKDF_SHA256, salt)
+        .extract(input_key)
+        .expand(&info, OutputLen(output.len()))
+        .and_then(|okm| okm.fill(output))
+        .map_err(|_| invalid_len())
+}
+
+/// Derives a subkey for one purpose from a master key.
+///
+/// Subkeys of different purposes are independent, so leaking one does not
+/// expose the master key or its other subkeys.
+///
+/// # Arguments
+///
+/// * `master_key` - The master key.
+///
+/// * `purpose` - A label naming what the subkey is used for.
+///
+/// # Returns
+///
+/// A key of `KEY_LEN` bytes.
+///
+/// # Example
+///
+/// ```
+/// use cryptography::{derive_subkey, generate_key};
+///
+/// let master_key = generate_key().unwrap();
+/// let sessions = derive_subkey(&master_key, "sessions").unwrap();
+/// let backups = derive_subkey(&master_key, "backups").unwrap();
+///
+/// assert_ne!(sessions, backups);
+/// assert_eq!(sessions, derive_subkey(&master_key, "sessions").unwrap());
+/// ```
+pub fn derive_subkey(master_key: &[u8; KEY_LEN], purpose: &str) -> Result<[u8; KEY_LEN], Error> {
+    let mut subkey = [0u8; KEY_LEN];
+    hkdf_sha256(&[], master_key, purpose.as_bytes(), &mut subkey)?;
+    Ok(subkey)
+}
+
+/// Wraps a key with AES-256 key wrap (RFC 3394).
+///
+/// # Arguments
+///
+/// * `kek` - The key-encryption key.
+///
+/// * `key` - The key to wrap, a multiple of 8 bytes and at least 16 bytes.
+///
+/// # Returns
+///
+/// The wrapped key, 8 bytes longer than the key.
+///
+/// # Example
+///
+/// ```
+/// use cryptography::{generate_key, unwrap_key, wrap_key};
+///
+/// let kek = generate_key().unwrap();
+/// let key = generate_key().unwrap();
+///
+/// let wrapped = wrap_key(&kek, &key).unwrap();
+/// assert_eq!(wrapped.len(), 40);
+/// assert_eq!(unwrap_key(&kek, &wrapped).unwrap(), key);
+/// ```
+pub fn wrap_key(kek: &[u8; KEY_LEN], key: &[u8]) -> Result<Vec<u8>, Error> {
+    if key.len() < 16 || !key.len().is_multiple_of(8) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Wrapped keys must be a multiple of 8 bytes and at least 16 bytes",
+        ));
+    }
+    KekAes256::from(*kek)
+        .wrap_vec(key)
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))
+}
+
+/// Unwraps a key wrapped by `wrap_key`.
+///
+/// # Arguments
+///
+/// * `kek` - The key-encryption key.
+///
+/// * `wrapped` - The wrapped key.
+///
+/// # Returns
+///
+/// The key, or an `InvalidData` error if the key-encryption key is wrong or
+/// the wrapped key was modified.
+pub fn unwrap_key(kek: &[u8; KEY_LEN], wrapped: &[u8]) -> Result<Vec<u8>, Error> {
+    if wrapped.len() < 24 || !wrapped.len().is_multiple_of(8) {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Wrapped key has an invalid length",
+        ));
+    }
+    KekAes256::from(*kek).unwrap_vec(wrapped).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidData,
+            "Key unwrap failed, wrong key or modified data",
+        )
+    })
+}
@@ -3,3 +3,18 @@ pub use hashing::*;
 
 mod encryption;
 pub use encryption::*;
+
+mod password;
+pub use password::*;
+
+mod signing;
+pub use signing::*;
+
+mod checksum;
+pub use checksum::*;
+
+mod keys;
+pub use keys::*;
+
+mod secrets;
+pub use secrets::*;
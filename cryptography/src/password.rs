@@ -0,0 +1,460 @@
+use argon2::{Argon2, Params as Argon2Params, Version};
+use data_encoding::BASE64_NOPAD;
+use ring::{
+    pbkdf2::{derive, PBKDF2_HMAC_SHA256, PBKDF2_HMAC_SHA512},
+    rand::{SecureRandom, SystemRandom},
+};
+use scrypt::{scrypt, Params as ScryptParams};
+use std::{error::Error, fmt, num::NonZeroU32, str::FromStr};
+use subtle::ConstantTimeEq;
+
+/// Length of the random salt of new hashes.
+pub const DEFAULT_SALT_LEN: usize = 16;
+
+/// Length of the derived key of new hashes.
+pub const DEFAULT_HASH_LEN: usize = 32;
+
+/// Longest derived key accepted when parsing a PHC string.
+const MAX_HASH_LEN: usize = 128;
+
+/// A password hashing algorithm and its cost parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordAlgorithm {
+    Pbkdf2Sha256 {
+        iterations: u32,
+    },
+    Pbkdf2Sha512 {
+        iterations: u32,
+    },
+    Argon2id {
+        memory_kib: u32,
+        iterations: u32,
+        parallelism: u32,
+    },
+    Scrypt {
+        log_n: u8,
+        r: u32,
+        p: u32,
+    },
+}
+
+impl PasswordAlgorithm {
+    /// The PHC identifier of the algorithm, e.g. `pbkdf2-sha512`
+    pub fn id(&self) -> &'static str {
+        match self {
+            PasswordAlgorithm::Pbkdf2Sha256 { .. } => "pbkdf2-sha256",
+            PasswordAlgorithm::Pbkdf2Sha512 { .. } => "pbkdf2-sha512",
+            PasswordAlgorithm::Argon2id { .. } => "argon2id",
+            PasswordAlgorithm::Scrypt { .. } => "scrypt",
+        }
+    }
+
+    /// Derive `output.len()` bytes from the password and salt
+    fn derive(&self, password: &[u8], salt: &[u8], output: &mut [u8]) -> Result<(), PasswordError> {
+        match *self {
+            PasswordAlgorithm::Pbkdf2Sha256 { iterations } => derive(
+                PBKDF2_HMAC_SHA256,
+                non_zero(iterations)?,
+                salt,
+                password,
+                output,
+            ),
+            PasswordAlgorithm::Pbkdf2Sha512 { iterations } => derive(
+                PBKDF2_HMAC_SHA512,
+                non_zero(iterations)?,
+                salt,
+                password,
+                output,
+            ),
+            PasswordAlgorithm::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => {
+                let params =
+                    Argon2Params::new(memory_kib, iterations, parallelism, Some(output.len()))
+                        .map_err(|e| PasswordError::InvalidParams(e.to_string()))?;
+                Argon2::new(argon2::Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password_into(password, salt, output)
+                    .map_err(|e| PasswordError::InvalidParams(e.to_string()))?;
+            }
+            PasswordAlgorithm::Scrypt { log_n, r, p } => {
+                let params = ScryptParams::new(log_n, r, p, output.len())
+                    .map_err(|e| PasswordError::InvalidParams(e.to_string()))?;
+                scrypt(password, salt, &params, output)
+                    .map_err(|e| PasswordError::InvalidParams(e.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn params(&self) -> String {
+        match self {
+            PasswordAlgorithm::Pbkdf2Sha256 { iterations }
+            | PasswordAlgorithm::Pbkdf2Sha512 { iterations } => format!("i={}", iterations),
+            PasswordAlgorithm::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => format!("v=19$m={},t={},p={}", memory_kib, iterations, parallelism),
+            PasswordAlgorithm::Scrypt { log_n, r, p } => format!("ln={},r={},p={}", log_n, r, p),
+        }
+    }
+}
+
+fn non_zero(iterations: u32) -> Result<NonZeroU32, PasswordError> {
+    NonZeroU32::new(iterations)
+        .ok_or_else(|| PasswordError::InvalidParams("iterations must be at least 1".to_string()))
+}
+
+/// Errors produced while hashing, parsing or verifying passwords
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PasswordError {
+    /// The string is not a well-formed PHC string.
+    Malformed(String),
+    /// The PHC string names an algorithm this module does not implement.
+    UnsupportedAlgorithm(String),
+    /// The cost parameters are out of range for the algorithm.
+    InvalidParams(String),
+    /// The system random number generator failed.
+    Random,
+}
+
+impl fmt::Display for PasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasswordError::Malformed(reason) => write!(f, "malformed PHC string: {}", reason),
+            PasswordError::UnsupportedAlgorithm(id) => {
+                write!(f, "unsupported password hashing algorithm: {}", id)
+            }
+            PasswordError::InvalidParams(reason) => write!(f, "invalid parameters: {}", reason),
+            PasswordError::Random => write!(f, "failed to generate random salt"),
+        }
+    }
+}
+
+impl Error for PasswordError {}
+
+/// A parsed PHC string: `$<id>$<params>$<salt>$<hash>`
+///
+/// Salt and hash are encoded in unpadded standard base64, as the PHC string
+/// format specifies.
+///
+/// # Examples
+///
+/// ```
+/// use cryptography::{PasswordAlgorithm, PasswordHash};
+///
+/// let phc = "$pbkdf2-sha512$i=1000$c2FsdHNhbHQ$ZGVyaXZlZGtleQ";
+/// let hash: PasswordHash = phc.parse().unwrap();
+///
+/// assert_eq!(hash.algorithm, PasswordAlgorithm::Pbkdf2Sha512 { iterations: 1000 });
+/// assert_eq!(hash.salt, b"saltsalt");
+/// assert_eq!(hash.to_string(), phc);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswordHash {
+    pub algorithm: PasswordAlgorithm,
+    pub salt: Vec<u8>,
+    pub hash: Vec<u8>,
+}
+
+impl PasswordHash {
+    /// Check a password against the hash in constant time
+    pub fn verify(&self, password: &str) -> Result<bool, PasswordError> {
+        let mut derived = vec![0u8; self.hash.len()];
+        self.algorithm
+            .derive(password.as_bytes(), &self.salt, &mut derived)?;
+        Ok(derived.ct_eq(&self.hash).into())
+    }
+}
+
+impl fmt::Display for PasswordHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "${}${}${}${}",
+            self.algorithm.id(),
+            self.algorithm.params(),
+            BASE64_NOPAD.encode(&self.salt),
+            BASE64_NOPAD.encode(&self.hash)
+        )
+    }
+}
+
+impl FromStr for PasswordHash {
+    type Err = PasswordError;
+
+    fn from_str(phc: &str) -> Result<Self, Self::Err> {
+        let malformed = |reason: &str| PasswordError::Malformed(reason.to_string());
+        let fields = phc
+            .strip_prefix('$')
+            .ok_or_else(|| malformed("missing leading '$'"))?
+            .split('$')
+            .collect::<Vec<_>>();
+
+        let (id, version, params, salt, hash) = match fields[..] {
+            [id, params, salt, hash] => (id, None, params, salt, hash),
+            [id, version, params, salt, hash] => (id, Some(version), params, salt, hash),
+            _ => return Err(malformed("expected '$id$params$salt$hash'")),
+        };
+
+        let params = params
+            .split(',')
+            .map(|pair| {
+                let (key, value) = pair
+                    .split_once('=')
+                    .ok_or_else(|| malformed("parameter without '='"))?;
+                let value = value
+                    .parse::<u32>()
+                    .map_err(|_| malformed("parameter is not a number"))?;
+                Ok((key, value))
+            })
+            .collect::<Result<Vec<_>, PasswordError>>()?;
+        let param = |key: &str| {
+            params
+                .iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| *value)
+                .ok_or_else(|| PasswordError::Malformed(format!("missing parameter '{}'", key)))
+        };
+
+        let algorithm = match id {
+            "pbkdf2-sha256" | "pbkdf2-sha512" if version.is_none() => {
+                let iterations = param("i")?;
+                if id == "pbkdf2-sha256" {
+                    PasswordAlgorithm::Pbkdf2Sha256 { iterations }
+                } else {
+                    PasswordAlgorithm::Pbkdf2Sha512 { iterations }
+                }
+            }
+            "argon2id" => {
+                if version != Some("v=19") {
+                    return Err(malformed("argon2id requires version 'v=19'"));
+                }
+                PasswordAlgorithm::Argon2id {
+                    memory_kib: param("m")?,
+                    iterations: param("t")?,
+                    parallelism: param("p")?,
+                }
+            }
+            "scrypt" if version.is_none() => PasswordAlgorithm::Scrypt {
+                log_n: u8::try_from(param("ln")?).map_err(|_| malformed("'ln' is too large"))?,
+                r: param("r")?,
+                p: param("p")?,
+            },
+            "pbkdf2-sha256" | "pbkdf2-sha512" | "scrypt" => {
+                return Err(malformed("unexpected version field"))
+            }
+            _ => return Err(PasswordError::UnsupportedAlgorithm(id.to_string())),
+        };
+
+        let salt = BASE64_NOPAD
+            .decode(salt.as_bytes())
+            .map_err(|_| malformed("salt is not base64"))?;
+        let hash = BASE64_NOPAD
+            .decode(hash.as_bytes())
+            .map_err(|_| malformed("hash is not base64"))?;
+        if salt.is_empty() || hash.is_empty() || hash.len() > MAX_HASH_LEN {
+            return Err(malformed("salt or hash has an invalid length"));
+        }
+
+        Ok(PasswordHash {
+            algorithm,
+            salt,
+            hash,
+        })
+    }
+}
+
+/// Hashes passwords into PHC strings with a chosen algorithm and cost
+///
+/// Hashes created with older or weaker settings still verify, and
+/// `needs_rehash` tells when they should be replaced after a successful login.
+///
+/// # Examples
+///
+/// ```
+/// use cryptography::{PasswordAlgorithm, PasswordHasher};
+///
+/// let hasher = PasswordHasher::new(PasswordAlgorithm::Pbkdf2Sha512 { iterations: 1_000 });
+/// let phc = hasher.hash("ABCabc123?").unwrap();
+///
+/// assert!(phc.starts_with("$pbkdf2-sha512$i=1000$"));
+/// assert!(hasher.verify("ABCabc123?", &phc).unwrap());
+/// assert!(!hasher.verify("DEFdef456&", &phc).unwrap());
+///
+/// let upgraded = PasswordHasher::new(PasswordAlgorithm::Pbkdf2Sha512 { iterations: 2_000 });
+/// assert!(upgraded.needs_rehash(&phc).unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswordHasher {
+    algorithm: PasswordAlgorithm,
+    salt_len: usize,
+    hash_len: usize,
+}
+
+impl Default for PasswordHasher {
+    fn default() -> Self {
+        PasswordHasher::argon2id()
+    }
+}
+
+impl PasswordHasher {
+    /// Create a hasher with the given algorithm and default salt and hash lengths
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - The algorithm and cost parameters of new hashes
+    pub fn new(algorithm: PasswordAlgorithm) -> Self {
+        PasswordHasher {
+            algorithm,
+            salt_len: DEFAULT_SALT_LEN,
+            hash_len: DEFAULT_HASH_LEN,
+        }
+    }
+
+    /// PBKDF2-HMAC-SHA256 with 600,000 iterations
+    pub fn pbkdf2_sha256() -> Self {
+        PasswordHasher::new(PasswordAlgorithm::Pbkdf2Sha256 {
+            iterations: 600_000,
+        })
+    }
+
+    /// PBKDF2-HMAC-SHA512 with 210,000 iterations
+    pub fn pbkdf2_sha512() -> Self {
+        PasswordHasher::new(PasswordAlgorithm::Pbkdf2Sha512 {
+            iterations: 210_000,
+        })
+    }
+
+    /// Argon2id with 19 MiB of memory, 2 passes and a single lane
+    pub fn argon2id() -> Self {
+        PasswordHasher::new(PasswordAlgorithm::Argon2id {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        })
+    }
+
+    /// scrypt with N = 2^17, r = 8 and p = 1
+    pub fn scrypt() -> Self {
+        PasswordHasher::new(PasswordAlgorithm::Scrypt {
+            log_n: 17,
+            r: 8,
+            p: 1,
+        })
+    }
+
+    /// Set the length of the random salt in bytes
+    pub fn salt_len(mut self, salt_len: usize) -> Self {
+        self.salt_len = salt_len;
+        self
+    }
+
+    /// Set the length of the derived key in bytes
+    pub fn hash_len(mut self, hash_len: usize) -> Self {
+        self.hash_len = hash_len;
+        self
+    }
+
+    /// The algorithm and cost parameters of new hashes
+    pub fn algorithm(&self) -> PasswordAlgorithm {
+        self.algorithm
+    }
+
+    /// Hash a password with a fresh random salt
+    ///
+    /// # Arguments
+    ///
+    /// * `password` - The password to hash
+    ///
+    /// # Returns
+    ///
+    /// The PHC string to store.
+    pub fn hash(&self, password: &str) -> Result<String, PasswordError> {
+        if self.salt_len == 0 || self.hash_len == 0 || self.hash_len > MAX_HASH_LEN {
+            return Err(PasswordError::InvalidParams(
+                "salt or hash has an invalid length".to_string(),
+            ));
+        }
+
+        let mut salt = vec![0u8; self.salt_len];
+        SystemRandom::new()
+            .fill(&mut salt)
+            .map_err(|_| PasswordError::Random)?;
+
+        let mut hash = vec![0u8; self.hash_len];
+        self.algorithm
+            .derive(password.as_bytes(), &salt, &mut hash)?;
+
+        Ok(PasswordHash {
+            algorithm: self.algorithm,
+            salt,
+            hash,
+        }
+        .to_string())
+    }
+
+    /// Check a password against a stored PHC string in constant time
+    ///
+    /// The stored string is verified with its own algorithm and parameters,
+    /// whatever this hasher is configured with.
+    ///
+    /// # Arguments
+    ///
+    /// * `password` - The password to check
+    ///
+    /// * `phc` - The stored PHC string
+    ///
+    /// # Returns
+    ///
+    /// `true` if the password matches.
+    pub fn verify(&self, password: &str, phc: &str) -> Result<bool, PasswordError> {
+        verify_password(password, phc)
+    }
+
+    /// Whether a stored hash was made with other parameters than this hasher's
+    ///
+    /// # Arguments
+    ///
+    /// * `phc` - The stored PHC string
+    ///
+    /// # Returns
+    ///
+    /// `true` if the algorithm, a cost parameter or the hash length differ
+    /// from the current settings, or the salt is shorter.
+    pub fn needs_rehash(&self, phc: &str) -> Result<bool, PasswordError> {
+        let stored = phc.parse::<PasswordHash>()?;
+        Ok(stored.algorithm != self.algorithm
+            || stored.salt.len() < self.salt_len
+            || stored.hash.len() != self.hash_len)
+    }
+}
+
+/// Check a password against a PHC string in constant time
+///
+/// # Arguments
+///
+/// * `password` - The password to check
+///
+/// * `phc` - The stored PHC string
+///
+/// # Returns
+///
+/// `true` if the password matches, an error if the string cannot be parsed.
+///
+/// # Examples
+///
+/// ```
+/// use cryptography::{verify_password, PasswordAlgorithm, PasswordHasher};
+///
+/// let hasher = PasswordHasher::new(PasswordAlgorithm::Scrypt { log_n: 10, r: 8, p: 1 });
+/// let phc = hasher.hash("ABCabc123?").unwrap();
+///
+/// assert!(verify_password("ABCabc123?", &phc).unwrap());
+/// assert!(!verify_password("abcABC123?", &phc).unwrap());
+/// ```
+pub fn verify_password(password: &str, phc: &str) -> Result<bool, PasswordError> {
+    phc.parse::<PasswordHash>()?.verify(password)
+}
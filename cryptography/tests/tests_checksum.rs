@@ -0,0 +1,138 @@
+use cryptography::{
+    merkle_root, verify_checksum_file, write_checksum_file, ChecksumFile, HashAlgorithm,
+};
+use std::{
+    fs::{create_dir_all, read_to_string, remove_dir_all, remove_file, write},
+    path::Path,
+    process::Command,
+};
+
+#[cfg(test)]
+mod tests_checksum {
+
+    use super::*;
+
+    fn fixture_dir(dir: &str) {
+        let _ = remove_dir_all(dir);
+        create_dir_all(format!("{}/logs/app", dir)).unwrap();
+        create_dir_all(format!("{}/empty", dir)).unwrap();
+        write(format!("{}/README", dir), "readme").unwrap();
+        write(format!("{}/logs/syslog", dir), "boot").unwrap();
+        write(format!("{}/logs/app/app.log", dir), "started").unwrap();
+    }
+
+    #[test]
+    fn test_merkle_root_is_deterministic() {
+        let (first, second) = ("merkle_dir_first", "merkle_dir_second");
+        fixture_dir(first);
+        fixture_dir(second);
+        remove_dir_all(format!("{}/empty", second)).unwrap();
+
+        for algorithm in HashAlgorithm::ALL {
+            let root = merkle_root(algorithm, first).unwrap();
+            assert_eq!(root.len(), algorithm.output_len());
+            assert_eq!(merkle_root(algorithm, second).unwrap(), root);
+        }
+
+        let root = merkle_root(HashAlgorithm::Sha256, first).unwrap();
+        std::fs::rename(
+            format!("{}/logs/syslog", second),
+            format!("{}/logs/messages", second),
+        )
+        .unwrap();
+        assert_ne!(merkle_root(HashAlgorithm::Sha256, second).unwrap(), root);
+
+        remove_dir_all(first).unwrap();
+        remove_dir_all(second).unwrap();
+    }
+
+    #[test]
+    fn test_checksum_file_round_trip() {
+        let dir = "checksum_dir_round_trip";
+        fixture_dir(dir);
+        write(format!("{}/odd\\name\nfile", dir), "odd").unwrap();
+
+        let checksums = ChecksumFile::from_dir(HashAlgorithm::Sha512, dir).unwrap();
+        assert_eq!(checksums.entries.len(), 4);
+        assert_eq!(checksums.entries[1].path, "logs/app/app.log");
+
+        let text = checksums.to_string();
+        let escaped = text.lines().last().unwrap();
+        assert!(escaped.starts_with('\\'));
+        assert!(escaped.ends_with("  odd\\\\name\\nfile"));
+        assert_eq!(
+            ChecksumFile::parse(HashAlgorithm::Sha512, &text).unwrap(),
+            checksums
+        );
+        assert!(checksums.verify(dir).unwrap().is_ok());
+
+        let error = ChecksumFile::parse(HashAlgorithm::Sha256, &text).unwrap_err();
+        assert_eq!(error.to_string(), "Improperly formatted checksum line 1");
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_verify_reports_changes() {
+        let dir = "checksum_dir_verify";
+        let sums = format!("{}/SHA256SUMS", dir);
+        fixture_dir(dir);
+
+        write_checksum_file(HashAlgorithm::Sha256, dir, &sums).unwrap();
+        let checksums = write_checksum_file(HashAlgorithm::Sha256, dir, &sums).unwrap();
+        assert!(!checksums
+            .entries
+            .iter()
+            .any(|entry| entry.path == "SHA256SUMS"));
+
+        let report = verify_checksum_file(HashAlgorithm::Sha256, &sums, dir).unwrap();
+        assert!(report.is_ok(), "{:?}", report);
+        assert_eq!(report.verified.len(), 3);
+
+        write(format!("{}/logs/syslog", dir), "reboot").unwrap();
+        remove_file(format!("{}/README", dir)).unwrap();
+        write(format!("{}/logs/new.log", dir), "new").unwrap();
+
+        let report = verify_checksum_file(HashAlgorithm::Sha256, &sums, dir).unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(report.verified, vec!["logs/app/app.log"]);
+        assert_eq!(report.modified, vec!["logs/syslog"]);
+        assert_eq!(report.missing, vec!["README"]);
+        assert_eq!(report.extra, vec!["logs/new.log"]);
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_compatible_with_sha256sum() {
+        let dir = "checksum_dir_sha256sum";
+        fixture_dir(dir);
+        write_checksum_file(HashAlgorithm::Sha256, dir, format!("{}/SHA256SUMS", dir)).unwrap();
+
+        let output = Command::new("sha256sum")
+            .args(["--check", "--strict", "SHA256SUMS"])
+            .current_dir(dir)
+            .output();
+        match output {
+            Ok(output) => assert!(output.status.success(), "{:?}", output),
+            Err(_) => println!("sha256sum is not installed, skipping"),
+        }
+
+        let output = Command::new("sha256sum")
+            .args(["README", "logs/syslog"])
+            .current_dir(dir)
+            .output();
+        if let Ok(output) = output {
+            let text = String::from_utf8(output.stdout).unwrap();
+            let checksums = ChecksumFile::parse(HashAlgorithm::Sha256, &text).unwrap();
+            let report = checksums.verify(Path::new(dir)).unwrap();
+            assert_eq!(report.verified, vec!["README", "logs/syslog"]);
+            assert_eq!(report.extra, vec!["SHA256SUMS", "logs/app/app.log"]);
+        }
+
+        assert!(read_to_string(format!("{}/SHA256SUMS", dir))
+            .unwrap()
+            .ends_with("  logs/syslog\n"));
+        remove_dir_all(dir).unwrap();
+    }
+}
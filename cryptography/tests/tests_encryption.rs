@@ -1,12 +1,36 @@
-use cryptography::salt_and_hash_password_with_pbkdf2;
+use cryptography::{
+    generate_key, salt_and_hash_password_with_pbkdf2, Cipher, DecryptReader, Encryptor,
+    DEFAULT_CHUNK_SIZE, KEY_LEN,
+};
 use ring::pbkdf2::{verify, PBKDF2_HMAC_SHA512};
-use std::num::NonZeroU32;
+use std::{
+    io::{ErrorKind, Read, Write},
+    num::NonZeroU32,
+};
 
 #[cfg(test)]
 mod tests_encryption {
 
     use super::*;
 
+    const HEADER_LEN: usize = 18;
+    const TAG_LEN: usize = 16;
+
+    fn encrypt(cipher: Cipher, chunk_size: usize, key: &[u8; KEY_LEN], data: &[u8]) -> Vec<u8> {
+        let mut writer = Encryptor::new(cipher)
+            .chunk_size(chunk_size)
+            .encrypt(Vec::new(), key)
+            .unwrap();
+        writer.write_all(data).unwrap();
+        writer.finish().unwrap()
+    }
+
+    fn decrypt(key: &[u8; KEY_LEN], encrypted: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+        let mut decrypted = Vec::new();
+        DecryptReader::new(encrypted, key)?.read_to_end(&mut decrypted)?;
+        Ok(decrypted)
+    }
+
     #[test]
     fn test_salt_and_hash_password_with_pbkdf2() {
         let password = "ABCabc123?";
@@ -35,4 +59,88 @@ mod tests_encryption {
 
         assert!(!should_fail.is_ok());
     }
+
+    #[test]
+    fn test_encrypt_and_decrypt_every_cipher() {
+        let key = generate_key().unwrap();
+        let data = (0..10_000u32)
+            .flat_map(|i| i.to_le_bytes())
+            .collect::<Vec<_>>();
+
+        for cipher in [Cipher::Aes256Gcm, Cipher::ChaCha20Poly1305] {
+            for (chunk_size, len) in [(1000, 0), (1000, 999), (1000, 4000), (1000, 40_000)] {
+                let encrypted = encrypt(cipher, chunk_size, &key, &data[..len]);
+                let chunks = len.div_ceil(chunk_size).max(1);
+                assert_eq!(encrypted.len(), HEADER_LEN + len + chunks * TAG_LEN);
+
+                let reader = DecryptReader::new(&encrypted[..], &key).unwrap();
+                assert_eq!(reader.cipher(), cipher);
+                assert_eq!(decrypt(&key, &encrypted).unwrap(), &data[..len]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_encrypt_with_password() {
+        let iterations = NonZeroU32::new(1_000).unwrap();
+        let mut writer = Encryptor::new(Cipher::Aes256Gcm)
+            .iterations(iterations)
+            .encrypt_with_password(Vec::new(), "ABCabc123?")
+            .unwrap();
+        writer.write_all(b"Hello, world!").unwrap();
+        let encrypted = writer.finish().unwrap();
+
+        let mut decrypted = String::new();
+        DecryptReader::with_password(&encrypted[..], "ABCabc123?")
+            .unwrap()
+            .read_to_string(&mut decrypted)
+            .unwrap();
+        assert_eq!(decrypted, "Hello, world!");
+
+        let mut reader = DecryptReader::with_password(&encrypted[..], "DEFdef456&").unwrap();
+        let error = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+        let key = [0u8; KEY_LEN];
+        let error = DecryptReader::new(&encrypted[..], &key).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_detect_tampering_and_truncation() {
+        let key = generate_key().unwrap();
+        let data = vec![7u8; 3 * DEFAULT_CHUNK_SIZE / 2];
+        let encrypted = encrypt(Cipher::ChaCha20Poly1305, 100, &key, &data);
+        let sealed = 100 + TAG_LEN;
+
+        let mut flipped = encrypted.clone();
+        flipped[HEADER_LEN + 5 * sealed + 3] ^= 1;
+        let mut resized = encrypted.clone();
+        resized[10] = 99;
+        let mut reordered = encrypted.clone();
+        reordered[HEADER_LEN..HEADER_LEN + 2 * sealed].rotate_left(sealed);
+        let mut extended = encrypted.clone();
+        extended.extend_from_slice(&[0u8; TAG_LEN]);
+
+        for tampered in [
+            flipped,
+            resized,
+            reordered,
+            extended,
+            encrypted[..HEADER_LEN + 3 * sealed].to_vec(),
+            encrypted[..encrypted.len() - 1].to_vec(),
+            encrypted[..HEADER_LEN].to_vec(),
+        ] {
+            let error = decrypt(&key, &tampered).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+        }
+
+        let mut versioned = encrypted.clone();
+        versioned[4] = 2;
+        let error = decrypt(&key, &versioned).unwrap_err();
+        assert_eq!(error.to_string(), "Unsupported format version 2");
+
+        assert!(decrypt(&generate_key().unwrap(), &encrypted).is_err());
+        assert_eq!(decrypt(&key, &encrypted).unwrap(), data);
+    }
 }
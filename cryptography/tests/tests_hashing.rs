@@ -1,9 +1,11 @@
-use cryptography::{sha256_digest, sign_and_verify_hmac};
-use data_encoding::HEXUPPER;
+use cryptography::{
+    hash_file, hash_reader, sha256_digest, sign_and_verify_hmac, HashAlgorithm, Hasher,
+};
+use data_encoding::{HEXLOWER, HEXUPPER};
 use ring::hmac;
 use std::{
-    fs::{remove_file, File},
-    io::{BufReader, Write},
+    fs::{remove_file, write, File},
+    io::{copy, BufReader, Write},
     path::Path,
 };
 
@@ -40,4 +42,65 @@ mod tests_hashing {
         let verification = hmac::verify(&key, message.as_bytes(), signature.as_ref()).unwrap();
         assert_eq!(verification, ());
     }
+
+    #[test]
+    fn test_hasher_known_digests() {
+        let expected = [
+            (HashAlgorithm::Sha1, "a9993e364706816aba3e25717850c26c9cd0d89d"),
+            (
+                HashAlgorithm::Sha256,
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+            (
+                HashAlgorithm::Sha384,
+                "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7",
+            ),
+            (
+                HashAlgorithm::Sha512,
+                "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+            ),
+            (
+                HashAlgorithm::Blake3,
+                "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+            ),
+        ];
+
+        for (algorithm, digest) in expected {
+            let mut hasher = Hasher::new(algorithm);
+            hasher.update(b"a");
+            hasher.update(b"bc");
+            assert_eq!(hasher.finalize_hex(), digest);
+
+            let streamed = hash_reader(algorithm, &b"abc"[..]).unwrap();
+            assert_eq!(streamed.len(), algorithm.output_len());
+            assert_eq!(HEXLOWER.encode(&streamed), digest);
+            assert_eq!(
+                algorithm.name().parse::<HashAlgorithm>().unwrap(),
+                algorithm
+            );
+        }
+        assert!("md5".parse::<HashAlgorithm>().is_err());
+    }
+
+    #[test]
+    fn test_hash_large_file() {
+        let file = "test_hash_large_file.bin";
+        let data = (0..1_000_000u32)
+            .flat_map(|i| i.to_le_bytes())
+            .collect::<Vec<_>>();
+        write(file, &data).unwrap();
+
+        for algorithm in HashAlgorithm::ALL {
+            let mut hasher = Hasher::new(algorithm);
+            copy(&mut &data[..], &mut hasher).unwrap();
+            assert_eq!(hash_file(algorithm, file).unwrap(), hasher.finalize());
+        }
+
+        let digest = sha256_digest(File::open(file).unwrap()).unwrap();
+        assert_eq!(
+            digest.as_ref(),
+            hash_file(HashAlgorithm::Sha256, file).unwrap()
+        );
+        remove_file(file).unwrap();
+    }
 }
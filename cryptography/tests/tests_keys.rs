@@ -0,0 +1,67 @@
+use cryptography::{derive_subkey, hkdf_sha256, unwrap_key, wrap_key, HKDF_MAX_LEN};
+use data_encoding::HEXUPPER;
+use std::io::ErrorKind;
+
+#[cfg(test)]
+mod tests_keys {
+
+    use super::*;
+
+    #[test]
+    fn test_hkdf_sha256() {
+        // Test case 3 of RFC 5869: no salt and no info
+        let mut output = [0u8; 42];
+        hkdf_sha256(&[], &[0x0b; 22], &[], &mut output).unwrap();
+        assert_eq!(
+            HEXUPPER.encode(&output),
+            "8DA4E775A563C18F715F802A063C5A31B8A11F5C5EE1879EC3454E5F3C738D2D9D201395FAA4B61A96C8"
+        );
+
+        let mut longest = vec![0u8; HKDF_MAX_LEN];
+        assert!(hkdf_sha256(b"salt", b"key", b"info", &mut longest).is_ok());
+        let mut too_long = vec![0u8; HKDF_MAX_LEN + 1];
+        let error = hkdf_sha256(b"salt", b"key", b"info", &mut too_long).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert!(hkdf_sha256(b"salt", b"key", b"info", &mut []).is_err());
+    }
+
+    #[test]
+    fn test_derive_subkey() {
+        let master_key = [7u8; 32];
+        let mut expected = [0u8; 32];
+        hkdf_sha256(&[], &master_key, b"sessions", &mut expected).unwrap();
+
+        assert_eq!(derive_subkey(&master_key, "sessions").unwrap(), expected);
+        assert_ne!(derive_subkey(&[8u8; 32], "sessions").unwrap(), expected);
+    }
+
+    #[test]
+    fn test_wrap_and_unwrap_key() {
+        // Section 4.6 of RFC 3394: 256-bit key data with a 256-bit KEK
+        let kek: [u8; 32] = HEXUPPER
+            .decode(b"000102030405060708090A0B0C0D0E0F101112131415161718191A1B1C1D1E1F")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let key = HEXUPPER
+            .decode(b"00112233445566778899AABBCCDDEEFF000102030405060708090A0B0C0D0E0F")
+            .unwrap();
+
+        let wrapped = wrap_key(&kek, &key).unwrap();
+        assert_eq!(
+            HEXUPPER.encode(&wrapped),
+            "28C9F404C4B810F4CBCCB35CFB87F8263F5786E2D80ED326CBC7F0E71A99F43BFB988B9B7A02DD21"
+        );
+        assert_eq!(unwrap_key(&kek, &wrapped).unwrap(), key);
+
+        let mut modified = wrapped.clone();
+        modified[10] ^= 1;
+        let error = unwrap_key(&kek, &modified).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(unwrap_key(&[0u8; 32], &wrapped).is_err());
+        assert!(unwrap_key(&kek, &wrapped[..20]).is_err());
+
+        let error = wrap_key(&kek, &key[..20]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,135 @@
+use cryptography::{
+    verify_password, PasswordAlgorithm, PasswordError, PasswordHash, PasswordHasher,
+};
+use data_encoding::HEXLOWER;
+
+#[cfg(test)]
+mod tests_password {
+
+    use super::*;
+
+    fn fast_hashers() -> Vec<PasswordHasher> {
+        vec![
+            PasswordHasher::new(PasswordAlgorithm::Pbkdf2Sha256 { iterations: 1_000 }),
+            PasswordHasher::new(PasswordAlgorithm::Pbkdf2Sha512 { iterations: 1_000 }),
+            PasswordHasher::new(PasswordAlgorithm::Argon2id {
+                memory_kib: 1024,
+                iterations: 1,
+                parallelism: 1,
+            }),
+            PasswordHasher::new(PasswordAlgorithm::Scrypt {
+                log_n: 8,
+                r: 8,
+                p: 1,
+            }),
+        ]
+    }
+
+    #[test]
+    fn test_hash_and_verify_every_algorithm() {
+        for hasher in fast_hashers() {
+            let phc = hasher.hash("ABCabc123?").unwrap();
+            assert!(phc.starts_with(&format!("${}$", hasher.algorithm().id())));
+
+            assert!(hasher.verify("ABCabc123?", &phc).unwrap());
+            assert!(!hasher.verify("DEFdef456&", &phc).unwrap());
+            assert!(!hasher.needs_rehash(&phc).unwrap());
+
+            let parsed: PasswordHash = phc.parse().unwrap();
+            assert_eq!(parsed.algorithm, hasher.algorithm());
+            assert_eq!(parsed.salt.len(), 16);
+            assert_eq!(parsed.hash.len(), 32);
+            assert_eq!(parsed.to_string(), phc);
+        }
+    }
+
+    #[test]
+    fn test_salts_are_random() {
+        let hasher = &fast_hashers()[0];
+        assert_ne!(
+            hasher.hash("secret").unwrap(),
+            hasher.hash("secret").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_known_vectors() {
+        // PBKDF2-HMAC-SHA256 and scrypt vectors from RFC 7914
+        let vectors = [
+            (
+                PasswordAlgorithm::Pbkdf2Sha256 { iterations: 1 },
+                "passwd",
+                "salt",
+                "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783",
+            ),
+            (
+                PasswordAlgorithm::Scrypt {
+                    log_n: 10,
+                    r: 8,
+                    p: 16,
+                },
+                "password",
+                "NaCl",
+                "fdbabe1c9d3472007856e7190d01e9fe7c6ad7cbc8237830e77376634b3731622eaf30d92e22a3886ff109279d9830dac727afb94a83ee6d8360cbdfa2cc0640",
+            ),
+        ];
+
+        for (algorithm, password, salt, hash) in vectors {
+            let phc = PasswordHash {
+                algorithm,
+                salt: salt.as_bytes().to_vec(),
+                hash: HEXLOWER.decode(hash.as_bytes()).unwrap(),
+            }
+            .to_string();
+            assert!(verify_password(password, &phc).unwrap(), "{}", phc);
+            assert!(!verify_password("Password", &phc).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_needs_rehash() {
+        let old = PasswordHasher::new(PasswordAlgorithm::Pbkdf2Sha512 { iterations: 1_000 });
+        let phc = old.hash("ABCabc123?").unwrap();
+
+        let stronger = PasswordHasher::new(PasswordAlgorithm::Pbkdf2Sha512 { iterations: 2_000 });
+        assert!(stronger.needs_rehash(&phc).unwrap());
+        assert!(stronger.verify("ABCabc123?", &phc).unwrap());
+
+        assert!(PasswordHasher::default().needs_rehash(&phc).unwrap());
+        assert!(old.clone().hash_len(64).needs_rehash(&phc).unwrap());
+        assert!(old.clone().salt_len(32).needs_rehash(&phc).unwrap());
+        assert!(!old.salt_len(8).needs_rehash(&phc).unwrap());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for phc in [
+            "pbkdf2-sha512$i=1000$c2FsdA$aGFzaA",
+            "$pbkdf2-sha512$i=1000$c2FsdA",
+            "$pbkdf2-sha512$iterations$c2FsdA$aGFzaA",
+            "$pbkdf2-sha512$i=many$c2FsdA$aGFzaA",
+            "$pbkdf2-sha512$r=1000$c2FsdA$aGFzaA",
+            "$pbkdf2-sha512$i=1000$c2Fsd!$aGFzaA",
+            "$argon2id$v=16$m=1024,t=1,p=1$c2FsdA$aGFzaA",
+            "$scrypt$ln=300,r=8,p=1$c2FsdA$aGFzaA",
+        ] {
+            assert!(
+                matches!(
+                    phc.parse::<PasswordHash>(),
+                    Err(PasswordError::Malformed(_))
+                ),
+                "{}",
+                phc
+            );
+        }
+
+        assert_eq!(
+            verify_password("secret", "$bcrypt$i=10$c2FsdA$aGFzaA"),
+            Err(PasswordError::UnsupportedAlgorithm("bcrypt".to_string()))
+        );
+        assert!(matches!(
+            verify_password("secret", "$pbkdf2-sha256$i=0$c2FsdA$aGFzaA"),
+            Err(PasswordError::InvalidParams(_))
+        ));
+    }
+}
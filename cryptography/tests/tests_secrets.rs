@@ -0,0 +1,102 @@
+use cryptography::SecretsStore;
+use serde_json::Value;
+use std::{
+    fs::{read_to_string, remove_file, write},
+    io::ErrorKind,
+    num::NonZeroU32,
+};
+
+#[cfg(test)]
+mod tests_secrets {
+
+    use super::*;
+
+    fn fixture_store(path: &str) -> SecretsStore {
+        let _ = remove_file(path);
+        let iterations = NonZeroU32::new(1_000).unwrap();
+        let mut store =
+            SecretsStore::create_with_iterations(path, "passphrase", iterations).unwrap();
+        store.set("api_token", b"token-123").unwrap();
+        store
+            .set("database_url", b"postgres://localhost/app")
+            .unwrap();
+        store.save().unwrap();
+        store
+    }
+
+    #[test]
+    fn test_set_get_remove_and_reopen() {
+        let path = "test_secrets_reopen.json";
+        let mut store = fixture_store(path);
+
+        assert_eq!(
+            store.names().collect::<Vec<_>>(),
+            vec!["api_token", "database_url"]
+        );
+        assert_eq!(store.get("api_token").unwrap().unwrap(), b"token-123");
+        assert_eq!(store.get("missing").unwrap(), None);
+
+        store.set("api_token", b"token-456").unwrap();
+        assert!(store.remove("database_url"));
+        assert!(!store.remove("database_url"));
+        store.save().unwrap();
+
+        let store = SecretsStore::open(path, "passphrase").unwrap();
+        assert_eq!(store.names().collect::<Vec<_>>(), vec!["api_token"]);
+        assert_eq!(store.get("api_token").unwrap().unwrap(), b"token-456");
+
+        let text = read_to_string(path).unwrap();
+        assert!(!text.contains("token-456"));
+        let error = SecretsStore::create(path, "passphrase").err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_rotate_master_key() {
+        let path = "test_secrets_rotate.json";
+        let mut store = fixture_store(path);
+        let before: Value = serde_json::from_str(&read_to_string(path).unwrap()).unwrap();
+
+        store.rotate_master_key("new passphrase").unwrap();
+        store.save().unwrap();
+        let after: Value = serde_json::from_str(&read_to_string(path).unwrap()).unwrap();
+
+        assert_eq!(before["secrets"], after["secrets"]);
+        assert_ne!(before["wrapped_key"], after["wrapped_key"]);
+        assert_ne!(before["salt"], after["salt"]);
+
+        let error = SecretsStore::open(path, "passphrase").err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        let store = SecretsStore::open(path, "new passphrase").unwrap();
+        assert_eq!(
+            store.get("database_url").unwrap().unwrap(),
+            b"postgres://localhost/app"
+        );
+
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_detect_tampering() {
+        let path = "test_secrets_tamper.json";
+        fixture_store(path);
+
+        let mut file: Value = serde_json::from_str(&read_to_string(path).unwrap()).unwrap();
+        let token = file["secrets"]["api_token"].clone();
+        file["secrets"]["database_url"] = token;
+        write(path, file.to_string()).unwrap();
+
+        let store = SecretsStore::open(path, "passphrase").unwrap();
+        assert_eq!(store.get("api_token").unwrap().unwrap(), b"token-123");
+        let error = store.get("database_url").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+        file["version"] = Value::from(2);
+        write(path, file.to_string()).unwrap();
+        assert!(SecretsStore::open(path, "passphrase").is_err());
+
+        remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,581 @@
+use crate::{quote_identifier, Database, DatabaseError};
+use chrono::NaiveDateTime;
+use rusqlite::{
+    params_from_iter,
+    types::{Type, Value},
+    Connection, Error, Result, Row,
+};
+use serde_json::{Map, Value as Json};
+
+/// The default audit table name.
+pub const DEFAULT_AUDIT_TABLE: &str = "audit_log";
+
+/// The format of audit timestamps, in UTC.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+/// The kind of change recorded by an `AuditEntry`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditOperation {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl AuditOperation {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AuditOperation::Insert => "INSERT",
+            AuditOperation::Update => "UPDATE",
+            AuditOperation::Delete => "DELETE",
+        }
+    }
+
+    fn parse(operation: &str) -> Option<Self> {
+        match operation {
+            "INSERT" => Some(AuditOperation::Insert),
+            "UPDATE" => Some(AuditOperation::Update),
+            "DELETE" => Some(AuditOperation::Delete),
+            _ => None,
+        }
+    }
+}
+
+/// A change to one row of an audited table
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    /// The position of the change in the log.
+    pub id: i64,
+    /// The changed table.
+    pub table: String,
+    /// The rowid of the changed row.
+    pub row_id: i64,
+    pub operation: AuditOperation,
+    /// The row before the change, `None` for inserts.
+    pub old_row: Option<Map<String, Json>>,
+    /// The row after the change, `None` for deletes.
+    pub new_row: Option<Map<String, Json>>,
+    /// When the change was made, in UTC.
+    pub changed_at: NaiveDateTime,
+    /// Who made the change, see `AuditLog::set_actor`.
+    pub actor: Option<String>,
+}
+
+/// A change-data audit log of SQLite tables
+///
+/// Triggers on every tracked table record inserts, updates and deletes in
+/// the audit table, with the old and new row as JSON objects, the time and
+/// the current actor. Blobs are stored as `{"$blob": "<hex>"}`.
+///
+/// Rows are identified by their rowid, so `WITHOUT ROWID` tables can't be
+/// tracked. Track a table again after changing its columns.
+///
+/// # Examples
+///
+/// ```
+/// use database::{AuditLog, AuditOperation, Database};
+///
+/// let db = Database::open_in_memory().unwrap();
+/// db.connection()
+///     .execute_batch("CREATE TABLE cats (id INTEGER PRIMARY KEY, name TEXT)")
+///     .unwrap();
+///
+/// let audit = AuditLog::new(&db);
+/// audit.track("cats").unwrap();
+/// audit.set_actor(Some("ana")).unwrap();
+///
+/// db.connection().execute("INSERT INTO cats (name) VALUES ('Tom')", []).unwrap();
+/// db.connection().execute("UPDATE cats SET name = 'Tommy'", []).unwrap();
+///
+/// let history = audit.history("cats", 1).unwrap();
+/// assert_eq!(history.len(), 2);
+/// assert_eq!(history[1].operation, AuditOperation::Update);
+/// assert_eq!(history[1].old_row.as_ref().unwrap()["name"], "Tom");
+/// assert_eq!(history[1].actor.as_deref(), Some("ana"));
+/// ```
+#[derive(Clone)]
+pub struct AuditLog {
+    db: Database,
+    table: String,
+}
+
+impl AuditLog {
+    /// An audit log in the `audit_log` table
+    pub fn new(db: &Database) -> Self {
+        AuditLog::with_table(db, DEFAULT_AUDIT_TABLE)
+    }
+
+    /// An audit log in a custom table
+    pub fn with_table(db: &Database, table: &str) -> Self {
+        AuditLog {
+            db: db.clone(),
+            table: table.to_string(),
+        }
+    }
+
+    /// Create the audit and actor tables if they don't exist
+    pub fn install(&self) -> Result<()> {
+        self.db.connection().execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {log} (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                table_name TEXT NOT NULL,
+                row_id INTEGER NOT NULL,
+                operation TEXT NOT NULL CHECK (operation IN ('INSERT', 'UPDATE', 'DELETE')),
+                old_row TEXT,
+                new_row TEXT,
+                changed_at TEXT NOT NULL,
+                actor TEXT
+            );
+            CREATE INDEX IF NOT EXISTS {index} ON {log} (table_name, row_id);
+            CREATE TABLE IF NOT EXISTS {actor} (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                actor TEXT
+            );",
+            log = quote_identifier(&self.table),
+            index = quote_identifier(&format!("{}_row", self.table)),
+            actor = self.actor_table(),
+        ))
+    }
+
+    /// Record every change to a table from now on
+    ///
+    /// Installs the audit table if needed and replaces the triggers of a
+    /// table that was already tracked, picking up new columns. A missing
+    /// table is `DatabaseError::NotFound` and a table without a rowid is
+    /// `DatabaseError::Unsupported`.
+    pub fn track(&self, table: &str) -> std::result::Result<(), DatabaseError> {
+        self.install()?;
+        let connection = self.db.connection();
+        // A missing table fails here with the driver's own NotFound error.
+        connection.prepare(&format!(
+            "SELECT * FROM {} LIMIT 0",
+            quote_identifier(table)
+        ))?;
+        let columns = table_columns(&connection, table)?;
+        let has_rowid = connection
+            .prepare(&format!(
+                "SELECT rowid FROM {} LIMIT 0",
+                quote_identifier(table)
+            ))
+            .is_ok();
+        if columns.is_empty() || !has_rowid {
+            return Err(DatabaseError::Unsupported(format!(
+                "{} has no rowid, e.g. it is a WITHOUT ROWID table",
+                table
+            )));
+        }
+
+        let row_json = |prefix: &str| {
+            let fields = columns
+                .iter()
+                .map(|column| {
+                    let value = format!("{}.{}", prefix, quote_identifier(column));
+                    format!(
+                        "{}, CASE typeof({value}) WHEN 'blob' THEN json_object('$blob', hex({value})) ELSE {value} END",
+                        quote_string(column)
+                    )
+                })
+                .collect::<Vec<_>>();
+            format!("json_object({})", fields.join(", "))
+        };
+        let (old_row, new_row) = (row_json("OLD"), row_json("NEW"));
+        let record = |operation: AuditOperation, row_id: &str, old_row: &str, new_row: &str| {
+            format!(
+                "INSERT INTO {log} (table_name, row_id, operation, old_row, new_row, changed_at, actor)
+                 VALUES ({name}, {row_id}, '{operation}', {old_row}, {new_row},
+                         strftime('%Y-%m-%d %H:%M:%f', 'now'),
+                         (SELECT actor FROM {actor} WHERE id = 1));",
+                log = quote_identifier(&self.table),
+                name = quote_string(table),
+                operation = operation.as_str(),
+                actor = self.actor_table(),
+            )
+        };
+        let quoted = quote_identifier(table);
+
+        let transaction = connection.unchecked_transaction()?;
+        transaction.execute_batch(&format!(
+            "{drop}
+             CREATE TRIGGER {insert_trigger} AFTER INSERT ON {quoted} BEGIN
+                 {insert}
+             END;
+             CREATE TRIGGER {update_trigger} AFTER UPDATE ON {quoted}
+             WHEN {old_row} IS NOT {new_row} BEGIN
+                 {update}
+             END;
+             CREATE TRIGGER {delete_trigger} AFTER DELETE ON {quoted} BEGIN
+                 {delete}
+             END;",
+            drop = self.drop_triggers_sql(table),
+            insert_trigger = self.trigger(table, AuditOperation::Insert),
+            update_trigger = self.trigger(table, AuditOperation::Update),
+            delete_trigger = self.trigger(table, AuditOperation::Delete),
+            insert = record(AuditOperation::Insert, "NEW.rowid", "NULL", &new_row),
+            update = record(AuditOperation::Update, "NEW.rowid", &old_row, &new_row),
+            delete = record(AuditOperation::Delete, "OLD.rowid", &old_row, "NULL"),
+        ))?;
+        Ok(transaction.commit()?)
+    }
+
+    /// Stop recording changes to a table, keeping its history
+    pub fn untrack(&self, table: &str) -> Result<()> {
+        self.db
+            .connection()
+            .execute_batch(&self.drop_triggers_sql(table))
+    }
+
+    /// Set who the following changes are recorded for, on every connection
+    pub fn set_actor(&self, actor: Option<&str>) -> Result<()> {
+        self.install()?;
+        self.db.connection().execute(
+            &format!(
+                "INSERT OR REPLACE INTO {} (id, actor) VALUES (1, ?1)",
+                self.actor_table()
+            ),
+            [actor],
+        )?;
+        Ok(())
+    }
+
+    /// The changes to one row, oldest first
+    pub fn history(&self, table: &str, row_id: i64) -> Result<Vec<AuditEntry>> {
+        self.entries(
+            "table_name = ?1 AND row_id = ?2 ORDER BY id",
+            (table, row_id),
+        )
+    }
+
+    /// The changes to a table made after `since`, oldest first
+    ///
+    /// Timestamps have millisecond precision, so changes made in the same
+    /// millisecond as `since` are left out. Use `changes_after` to split
+    /// changes made in the same millisecond.
+    pub fn changes_since(&self, table: &str, since: NaiveDateTime) -> Result<Vec<AuditEntry>> {
+        self.entries(
+            "table_name = ?1 AND changed_at > ?2 ORDER BY id",
+            (table, timestamp(since)),
+        )
+    }
+
+    /// The changes to a table recorded after the entry `id`, oldest first
+    pub fn changes_after(&self, table: &str, id: i64) -> Result<Vec<AuditEntry>> {
+        self.entries("table_name = ?1 AND id > ?2 ORDER BY id", (table, id))
+    }
+
+    /// Undo the changes to a table made after `point`, newest first
+    ///
+    /// The undoing changes are recorded in the log like any other change.
+    /// Changes made in the same millisecond as `point` are kept, see
+    /// `revert_to_entry` to undo some of them.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - The tracked table.
+    /// * `point` - The time, in UTC, to bring the table back to.
+    ///
+    /// # Returns
+    ///
+    /// The number of undone changes.
+    pub fn revert_to(&self, table: &str, point: NaiveDateTime) -> Result<usize> {
+        self.revert(table, self.changes_since(table, point)?)
+    }
+
+    /// Undo the changes to a table recorded after the entry `id`, newest first
+    ///
+    /// The undoing changes are recorded in the log like any other change.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - The tracked table.
+    /// * `id` - The id of the last entry to keep.
+    ///
+    /// # Returns
+    ///
+    /// The number of undone changes.
+    pub fn revert_to_entry(&self, table: &str, id: i64) -> Result<usize> {
+        self.revert(table, self.changes_after(table, id)?)
+    }
+
+    /// Rebuild the state of a table at `until` by applying its history to
+    /// another table
+    ///
+    /// The target is created with the columns of `table` if it doesn't exist.
+    /// The result matches `table` as of `until` when the table was empty when
+    /// it started being tracked. Changes made in the same millisecond as
+    /// `until` are applied, see `replay_to_entry` to stop between them.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - The tracked table.
+    /// * `target` - The table to apply the changes to.
+    /// * `until` - The time, in UTC, of the last change to apply.
+    ///
+    /// # Returns
+    ///
+    /// The number of applied changes.
+    pub fn replay(&self, table: &str, target: &str, until: NaiveDateTime) -> Result<usize> {
+        let changes = self.entries(
+            "table_name = ?1 AND changed_at <= ?2 ORDER BY id",
+            (table, timestamp(until)),
+        )?;
+        self.apply(table, target, changes)
+    }
+
+    /// Rebuild the state of a table at the entry `id` by applying its history
+    /// to another table, like `replay`
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - The tracked table.
+    /// * `target` - The table to apply the changes to.
+    /// * `id` - The id of the last entry to apply.
+    ///
+    /// # Returns
+    ///
+    /// The number of applied changes.
+    pub fn replay_to_entry(&self, table: &str, target: &str, id: i64) -> Result<usize> {
+        let changes = self.entries("table_name = ?1 AND id <= ?2 ORDER BY id", (table, id))?;
+        self.apply(table, target, changes)
+    }
+
+    fn revert(&self, table: &str, changes: Vec<AuditEntry>) -> Result<usize> {
+        let connection = self.db.connection();
+        let transaction = connection.unchecked_transaction()?;
+        for change in changes.iter().rev() {
+            match change.operation {
+                AuditOperation::Insert => delete_row(&transaction, table, change.row_id)?,
+                AuditOperation::Update => update_row(
+                    &transaction,
+                    table,
+                    change.row_id,
+                    row(change.old_row.as_ref())?,
+                )?,
+                AuditOperation::Delete => insert_row(
+                    &transaction,
+                    table,
+                    change.row_id,
+                    row(change.old_row.as_ref())?,
+                )?,
+            }
+        }
+        transaction.commit()?;
+        Ok(changes.len())
+    }
+
+    fn apply(&self, table: &str, target: &str, changes: Vec<AuditEntry>) -> Result<usize> {
+        let connection = self.db.connection();
+        let transaction = connection.unchecked_transaction()?;
+        transaction.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {} AS SELECT * FROM {} WHERE 0",
+            quote_identifier(target),
+            quote_identifier(table)
+        ))?;
+        for change in &changes {
+            match change.operation {
+                AuditOperation::Insert => insert_row(
+                    &transaction,
+                    target,
+                    change.row_id,
+                    row(change.new_row.as_ref())?,
+                )?,
+                AuditOperation::Update => update_row(
+                    &transaction,
+                    target,
+                    change.row_id,
+                    row(change.new_row.as_ref())?,
+                )?,
+                AuditOperation::Delete => delete_row(&transaction, target, change.row_id)?,
+            }
+        }
+        transaction.commit()?;
+        Ok(changes.len())
+    }
+
+    fn entries<P: rusqlite::Params>(&self, condition: &str, params: P) -> Result<Vec<AuditEntry>> {
+        let connection = self.db.connection();
+        let mut statement = connection.prepare(&format!(
+            "SELECT id, table_name, row_id, operation, old_row, new_row, changed_at, actor
+             FROM {} WHERE {}",
+            quote_identifier(&self.table),
+            condition
+        ))?;
+        let rows = statement.query_map(params, entry_of)?;
+        rows.collect()
+    }
+
+    fn actor_table(&self) -> String {
+        quote_identifier(&format!("{}_actor", self.table))
+    }
+
+    fn trigger(&self, table: &str, operation: AuditOperation) -> String {
+        quote_identifier(&format!(
+            "{}_{}_{}",
+            table,
+            self.table,
+            operation.as_str().to_lowercase()
+        ))
+    }
+
+    fn drop_triggers_sql(&self, table: &str) -> String {
+        [
+            AuditOperation::Insert,
+            AuditOperation::Update,
+            AuditOperation::Delete,
+        ]
+        .iter()
+        .map(|operation| {
+            format!(
+                "DROP TRIGGER IF EXISTS {};",
+                self.trigger(table, *operation)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+    }
+}
+
+fn table_columns(connection: &Connection, table: &str) -> Result<Vec<String>> {
+    let mut statement =
+        connection.prepare("SELECT name FROM pragma_table_info(?1) ORDER BY cid")?;
+    let rows = statement.query_map([table], |row| row.get(0))?;
+    rows.collect()
+}
+
+fn entry_of(row: &Row) -> Result<AuditEntry> {
+    let json = |index: usize| -> Result<Option<Map<String, Json>>> {
+        row.get::<_, Option<String>>(index)?
+            .map(|text| {
+                serde_json::from_str(&text)
+                    .map_err(|e| Error::FromSqlConversionFailure(index, Type::Text, Box::new(e)))
+            })
+            .transpose()
+    };
+    let operation: String = row.get(3)?;
+    let changed_at: String = row.get(6)?;
+    Ok(AuditEntry {
+        id: row.get(0)?,
+        table: row.get(1)?,
+        row_id: row.get(2)?,
+        operation: AuditOperation::parse(&operation).ok_or_else(|| {
+            Error::FromSqlConversionFailure(
+                3,
+                Type::Text,
+                format!("unknown operation {}", operation).into(),
+            )
+        })?,
+        old_row: json(4)?,
+        new_row: json(5)?,
+        changed_at: NaiveDateTime::parse_from_str(&changed_at, TIMESTAMP_FORMAT)
+            .map_err(|e| Error::FromSqlConversionFailure(6, Type::Text, Box::new(e)))?,
+        actor: row.get(7)?,
+    })
+}
+
+fn timestamp(time: NaiveDateTime) -> String {
+    time.format(TIMESTAMP_FORMAT).to_string()
+}
+
+fn quote_string(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// The row of an entry that must have one, as column names and values.
+fn row(row: Option<&Map<String, Json>>) -> Result<Vec<(String, Value)>> {
+    let row = row.ok_or_else(|| {
+        Error::FromSqlConversionFailure(0, Type::Null, "audit entry without a row".into())
+    })?;
+    row.iter()
+        .map(|(column, value)| Ok((column.clone(), sql_value(value)?)))
+        .collect()
+}
+
+fn sql_value(value: &Json) -> Result<Value> {
+    Ok(match value {
+        Json::Null => Value::Null,
+        Json::Bool(value) => Value::Integer(*value as i64),
+        Json::Number(number) => match number.as_i64() {
+            Some(integer) => Value::Integer(integer),
+            None => Value::Real(number.as_f64().unwrap_or_default()),
+        },
+        Json::String(text) => Value::Text(text.clone()),
+        Json::Object(object) => match object.get("$blob") {
+            Some(Json::String(hex)) => Value::Blob(unhex(hex)?),
+            _ => Value::Text(value.to_string()),
+        },
+        Json::Array(_) => Value::Text(value.to_string()),
+    })
+}
+
+fn unhex(hex: &str) -> Result<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| {
+            hex.get(index..index + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| {
+                    Error::FromSqlConversionFailure(
+                        0,
+                        Type::Blob,
+                        format!("invalid hex {}", hex).into(),
+                    )
+                })
+        })
+        .collect()
+}
+
+fn insert_row(
+    connection: &Connection,
+    table: &str,
+    row_id: i64,
+    row: Vec<(String, Value)>,
+) -> Result<()> {
+    let columns = row
+        .iter()
+        .map(|(column, _)| quote_identifier(column))
+        .collect::<Vec<_>>();
+    let placeholders = vec!["?"; row.len() + 1].join(", ");
+    let values =
+        std::iter::once(Value::Integer(row_id)).chain(row.into_iter().map(|(_, value)| value));
+    connection.execute(
+        &format!(
+            "INSERT INTO {} (rowid, {}) VALUES ({})",
+            quote_identifier(table),
+            columns.join(", "),
+            placeholders
+        ),
+        params_from_iter(values),
+    )?;
+    Ok(())
+}
+
+fn update_row(
+    connection: &Connection,
+    table: &str,
+    row_id: i64,
+    row: Vec<(String, Value)>,
+) -> Result<()> {
+    let assignments = row
+        .iter()
+        .map(|(column, _)| format!("{} = ?", quote_identifier(column)))
+        .collect::<Vec<_>>();
+    let values = row
+        .into_iter()
+        .map(|(_, value)| value)
+        .chain(std::iter::once(Value::Integer(row_id)));
+    connection.execute(
+        &format!(
+            "UPDATE {} SET {} WHERE rowid = ?",
+            quote_identifier(table),
+            assignments.join(", ")
+        ),
+        params_from_iter(values),
+    )?;
+    Ok(())
+}
+
+fn delete_row(connection: &Connection, table: &str, row_id: i64) -> Result<()> {
+    connection.execute(
+        &format!("DELETE FROM {} WHERE rowid = ?1", quote_identifier(table)),
+        [row_id],
+    )?;
+    Ok(())
+}
@@ -0,0 +1,444 @@
+use serde::Deserialize;
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    fs::read_to_string,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+/// An error raised while loading a configuration or building a pool
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Toml(toml::de::Error),
+    /// A value is missing or cannot be parsed.
+    Invalid(String),
+    Tls(native_tls::Error),
+    Postgres(postgres::Error),
+    Pool(r2d2::Error),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "I/O error: {}", e),
+            ConfigError::Toml(e) => write!(f, "Invalid TOML: {}", e),
+            ConfigError::Invalid(message) => write!(f, "Invalid configuration: {}", message),
+            ConfigError::Tls(e) => write!(f, "TLS error: {}", e),
+            ConfigError::Postgres(e) => write!(f, "PostgreSQL error: {}", e),
+            ConfigError::Pool(e) => write!(f, "Pool error: {}", e),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Io(e) => Some(e),
+            ConfigError::Toml(e) => Some(e),
+            ConfigError::Tls(e) => Some(e),
+            ConfigError::Postgres(e) => Some(e),
+            ConfigError::Pool(e) => Some(e),
+            ConfigError::Invalid(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(e: std::io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(e: toml::de::Error) -> Self {
+        ConfigError::Toml(e)
+    }
+}
+
+impl From<native_tls::Error> for ConfigError {
+    fn from(e: native_tls::Error) -> Self {
+        ConfigError::Tls(e)
+    }
+}
+
+impl From<r2d2::Error> for ConfigError {
+    fn from(e: r2d2::Error) -> Self {
+        ConfigError::Pool(e)
+    }
+}
+
+/// Whether and how PostgreSQL connections use TLS, as in libpq's `sslmode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TlsMode {
+    /// Never use TLS.
+    Disable,
+    /// Use TLS if the server supports it, without verifying the certificate.
+    #[default]
+    Prefer,
+    /// Always use TLS, without verifying the certificate.
+    Require,
+    /// Always use TLS and verify the certificate and host name.
+    VerifyFull,
+}
+
+impl FromStr for TlsMode {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('_', "-").as_str() {
+            "disable" => Ok(TlsMode::Disable),
+            "prefer" => Ok(TlsMode::Prefer),
+            "require" => Ok(TlsMode::Require),
+            "verify-full" => Ok(TlsMode::VerifyFull),
+            _ => Err(ConfigError::Invalid(format!("Unknown TLS mode {}", s))),
+        }
+    }
+}
+
+/// The SQLite `journal_mode` pragma.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalMode {
+    Delete,
+    Truncate,
+    Persist,
+    Memory,
+    Wal,
+    Off,
+}
+
+impl FromStr for JournalMode {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "delete" => Ok(JournalMode::Delete),
+            "truncate" => Ok(JournalMode::Truncate),
+            "persist" => Ok(JournalMode::Persist),
+            "memory" => Ok(JournalMode::Memory),
+            "wal" => Ok(JournalMode::Wal),
+            "off" => Ok(JournalMode::Off),
+            _ => Err(ConfigError::Invalid(format!("Unknown journal mode {}", s))),
+        }
+    }
+}
+
+impl JournalMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JournalMode::Delete => "DELETE",
+            JournalMode::Truncate => "TRUNCATE",
+            JournalMode::Persist => "PERSIST",
+            JournalMode::Memory => "MEMORY",
+            JournalMode::Wal => "WAL",
+            JournalMode::Off => "OFF",
+        }
+    }
+}
+
+/// The SQLite `synchronous` pragma.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Synchronous {
+    Off,
+    Normal,
+    Full,
+    Extra,
+}
+
+impl FromStr for Synchronous {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(Synchronous::Off),
+            "normal" => Ok(Synchronous::Normal),
+            "full" => Ok(Synchronous::Full),
+            "extra" => Ok(Synchronous::Extra),
+            _ => Err(ConfigError::Invalid(format!(
+                "Unknown synchronous mode {}",
+                s
+            ))),
+        }
+    }
+}
+
+impl Synchronous {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Synchronous::Off => "OFF",
+            Synchronous::Normal => "NORMAL",
+            Synchronous::Full => "FULL",
+            Synchronous::Extra => "EXTRA",
+        }
+    }
+}
+
+/// How to reach a PostgreSQL server.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PostgresConfig {
+    pub host: String,
+    pub port: u16,
+    pub dbname: String,
+    pub user: String,
+    pub password: Option<String>,
+    pub tls: TlsMode,
+    pub connect_timeout_secs: Option<u64>,
+    pub application_name: Option<String>,
+}
+
+impl Default for PostgresConfig {
+    fn default() -> Self {
+        PostgresConfig {
+            host: "localhost".to_string(),
+            port: 5432,
+            dbname: "postgres".to_string(),
+            user: "postgres".to_string(),
+            password: None,
+            tls: TlsMode::default(),
+            connect_timeout_secs: None,
+            application_name: None,
+        }
+    }
+}
+
+/// A SQLite database file and the pragmas set on every connection.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SqliteConfig {
+    /// The database file, `:memory:` for a private in-memory database per connection.
+    pub path: PathBuf,
+    pub journal_mode: Option<JournalMode>,
+    pub synchronous: Option<Synchronous>,
+    pub foreign_keys: bool,
+    pub busy_timeout_ms: u64,
+}
+
+impl Default for SqliteConfig {
+    fn default() -> Self {
+        SqliteConfig {
+            path: PathBuf::from(":memory:"),
+            journal_mode: None,
+            synchronous: None,
+            foreign_keys: true,
+            busy_timeout_ms: 5000,
+        }
+    }
+}
+
+/// Sizing and health checks of a connection pool.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PoolConfig {
+    pub max_size: u32,
+    /// Idle connections kept open, `max_size` when unset.
+    pub min_idle: Option<u32>,
+    /// Close connections idle for longer, never when unset.
+    pub idle_timeout_secs: Option<u64>,
+    /// Close connections older than this, never when unset.
+    pub max_lifetime_secs: Option<u64>,
+    /// How long to wait for a free connection.
+    pub connection_timeout_secs: u64,
+    /// Check that a connection works before handing it out.
+    pub test_on_check_out: bool,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            max_size: 10,
+            min_idle: None,
+            idle_timeout_secs: Some(600),
+            max_lifetime_secs: Some(1800),
+            connection_timeout_secs: 30,
+            test_on_check_out: true,
+        }
+    }
+}
+
+impl PoolConfig {
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout_secs.map(Duration::from_secs)
+    }
+
+    pub fn max_lifetime(&self) -> Option<Duration> {
+        self.max_lifetime_secs.map(Duration::from_secs)
+    }
+
+    pub fn connection_timeout(&self) -> Duration {
+        Duration::from_secs(self.connection_timeout_secs)
+    }
+}
+
+/// Settings of the database crate, loaded from TOML or environment variables
+///
+/// # Examples
+///
+/// ```
+/// use database::{DatabaseConfig, JournalMode, TlsMode};
+///
+/// let config = DatabaseConfig::from_toml(
+///     r#"
+///     [postgres]
+///     host = "db.internal"
+///     dbname = "cats"
+///     tls = "verify-full"
+///
+///     [sqlite]
+///     path = "cats.db"
+///     journal_mode = "wal"
+///
+///     [pool]
+///     max_size = 4
+///     "#,
+/// )
+/// .unwrap();
+///
+/// let postgres = config.postgres.unwrap();
+/// assert_eq!((postgres.host.as_str(), postgres.port), ("db.internal", 5432));
+/// assert_eq!(postgres.tls, TlsMode::VerifyFull);
+/// assert_eq!(config.sqlite.unwrap().journal_mode, Some(JournalMode::Wal));
+/// assert_eq!(config.pool.max_size, 4);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub postgres: Option<PostgresConfig>,
+    pub sqlite: Option<SqliteConfig>,
+    pub pool: PoolConfig,
+}
+
+impl DatabaseConfig {
+    /// Parse a TOML document
+    pub fn from_toml(toml: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    /// Read a TOML file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        DatabaseConfig::from_toml(&read_to_string(path)?)
+    }
+
+    /// Read the process environment, see `from_vars`
+    pub fn from_env(prefix: &str) -> Result<Self, ConfigError> {
+        DatabaseConfig::from_vars(prefix, std::env::vars())
+    }
+
+    /// Build the configuration from `<PREFIX>_<SECTION>_<KEY>` variables
+    ///
+    /// Sections are `PG`, `SQLITE` and `POOL` and keys are the upper-case
+    /// field names, e.g. `APP_PG_HOST`, `APP_SQLITE_JOURNAL_MODE` or
+    /// `APP_POOL_MAX_SIZE`. A backend is configured when at least one of its
+    /// variables is set; other fields keep their defaults.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use database::{DatabaseConfig, TlsMode};
+    ///
+    /// let vars = [
+    ///     ("APP_PG_HOST", "db.internal"),
+    ///     ("APP_PG_PORT", "6432"),
+    ///     ("APP_PG_TLS", "require"),
+    ///     ("APP_POOL_IDLE_TIMEOUT_SECS", "60"),
+    ///     ("HOME", "/root"),
+    /// ];
+    /// let config = DatabaseConfig::from_vars(
+    ///     "APP",
+    ///     vars.iter().map(|(k, v)| (k.to_string(), v.to_string())),
+    /// )
+    /// .unwrap();
+    ///
+    /// let postgres = config.postgres.unwrap();
+    /// assert_eq!((postgres.port, postgres.tls), (6432, TlsMode::Require));
+    /// assert!(config.sqlite.is_none());
+    /// assert_eq!(config.pool.idle_timeout_secs, Some(60));
+    /// ```
+    pub fn from_vars<I>(prefix: &str, vars: I) -> Result<Self, ConfigError>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut config = DatabaseConfig::default();
+        let prefix = format!("{}_", prefix.to_ascii_uppercase());
+
+        for (name, value) in vars {
+            let key = match name.strip_prefix(&prefix) {
+                Some(key) => key,
+                None => continue,
+            };
+            let invalid =
+                |e: &dyn Display| ConfigError::Invalid(format!("{}={}: {}", name, value, e));
+
+            if let Some(field) = key.strip_prefix("PG_") {
+                let postgres = config.postgres.get_or_insert_with(PostgresConfig::default);
+                match field {
+                    "HOST" => postgres.host = value.clone(),
+                    "PORT" => postgres.port = value.parse().map_err(|e| invalid(&e))?,
+                    "DBNAME" => postgres.dbname = value.clone(),
+                    "USER" => postgres.user = value.clone(),
+                    "PASSWORD" => postgres.password = Some(value.clone()),
+                    "TLS" => postgres.tls = value.parse().map_err(|e| invalid(&e))?,
+                    "CONNECT_TIMEOUT_SECS" => {
+                        postgres.connect_timeout_secs =
+                            Some(value.parse().map_err(|e| invalid(&e))?)
+                    }
+                    "APPLICATION_NAME" => postgres.application_name = Some(value.clone()),
+                    _ => return Err(invalid(&"unknown variable")),
+                }
+            } else if let Some(field) = key.strip_prefix("SQLITE_") {
+                let sqlite = config.sqlite.get_or_insert_with(SqliteConfig::default);
+                match field {
+                    "PATH" => sqlite.path = PathBuf::from(&value),
+                    "JOURNAL_MODE" => {
+                        sqlite.journal_mode = Some(value.parse().map_err(|e| invalid(&e))?)
+                    }
+                    "SYNCHRONOUS" => {
+                        sqlite.synchronous = Some(value.parse().map_err(|e| invalid(&e))?)
+                    }
+                    "FOREIGN_KEYS" => {
+                        sqlite.foreign_keys =
+                            parse_flag(&value).ok_or_else(|| invalid(&"expected a boolean"))?
+                    }
+                    "BUSY_TIMEOUT_MS" => {
+                        sqlite.busy_timeout_ms = value.parse().map_err(|e| invalid(&e))?
+                    }
+                    _ => return Err(invalid(&"unknown variable")),
+                }
+            } else if let Some(field) = key.strip_prefix("POOL_") {
+                let pool = &mut config.pool;
+                match field {
+                    "MAX_SIZE" => pool.max_size = value.parse().map_err(|e| invalid(&e))?,
+                    "MIN_IDLE" => pool.min_idle = Some(value.parse().map_err(|e| invalid(&e))?),
+                    "IDLE_TIMEOUT_SECS" => {
+                        pool.idle_timeout_secs = Some(value.parse().map_err(|e| invalid(&e))?)
+                    }
+                    "MAX_LIFETIME_SECS" => {
+                        pool.max_lifetime_secs = Some(value.parse().map_err(|e| invalid(&e))?)
+                    }
+                    "CONNECTION_TIMEOUT_SECS" => {
+                        pool.connection_timeout_secs = value.parse().map_err(|e| invalid(&e))?
+                    }
+                    "TEST_ON_CHECK_OUT" => {
+                        pool.test_on_check_out =
+                            parse_flag(&value).ok_or_else(|| invalid(&"expected a boolean"))?
+                    }
+                    _ => return Err(invalid(&"unknown variable")),
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+fn parse_flag(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
@@ -0,0 +1,649 @@
+use crate::DatabaseError;
+use bytes::Bytes;
+use chrono::{NaiveDate, NaiveDateTime};
+use csv::{ReaderBuilder, StringRecord, Writer, WriterBuilder};
+use futures_util::{pin_mut, SinkExt};
+use postgres::Client;
+use rusqlite::{types::Value, Connection};
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// Number of rows sampled to infer column types by default.
+pub const DEFAULT_SAMPLE_ROWS: usize = 1000;
+
+const TIMESTAMP_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M:%S%.f",
+];
+
+/// The type of a CSV column, from the most to the least specific.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Integer,
+    Numeric,
+    Boolean,
+    Date,
+    Timestamp,
+    Text,
+}
+
+impl ColumnType {
+    /// The most specific type of a single non-empty value.
+    fn of(value: &str) -> ColumnType {
+        if value.parse::<i64>().is_ok() {
+            ColumnType::Integer
+        } else if value.parse::<f64>().is_ok_and(f64::is_finite) {
+            ColumnType::Numeric
+        } else if parse_bool(value).is_some() {
+            ColumnType::Boolean
+        } else if NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok() {
+            ColumnType::Date
+        } else if parse_timestamp(value).is_some() {
+            ColumnType::Timestamp
+        } else {
+            ColumnType::Text
+        }
+    }
+
+    /// The narrowest type holding values of both types.
+    fn widen(self, other: ColumnType) -> ColumnType {
+        use ColumnType::*;
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Integer, Numeric) | (Numeric, Integer) => Numeric,
+            (Date, Timestamp) | (Timestamp, Date) => Timestamp,
+            _ => Text,
+        }
+    }
+
+    /// Check whether a non-empty value can be stored in a column of this type.
+    fn accepts(&self, value: &str) -> bool {
+        match self {
+            ColumnType::Integer => value.parse::<i64>().is_ok(),
+            ColumnType::Numeric => value.parse::<f64>().is_ok_and(f64::is_finite),
+            ColumnType::Boolean => parse_bool(value).is_some(),
+            ColumnType::Date => NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok(),
+            ColumnType::Timestamp => {
+                NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok()
+                    || parse_timestamp(value).is_some()
+            }
+            ColumnType::Text => true,
+        }
+    }
+
+    /// The PostgreSQL column type.
+    pub fn postgres_type(&self) -> &'static str {
+        match self {
+            ColumnType::Integer => "BIGINT",
+            ColumnType::Numeric => "NUMERIC",
+            ColumnType::Boolean => "BOOLEAN",
+            ColumnType::Date => "DATE",
+            ColumnType::Timestamp => "TIMESTAMP",
+            ColumnType::Text => "TEXT",
+        }
+    }
+
+    /// The SQLite column type, booleans stored as 0 and 1 and dates as ISO 8601 text.
+    pub fn sqlite_type(&self) -> &'static str {
+        match self {
+            ColumnType::Integer | ColumnType::Boolean => "INTEGER",
+            ColumnType::Numeric => "REAL",
+            ColumnType::Date | ColumnType::Timestamp | ColumnType::Text => "TEXT",
+        }
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "t" | "yes" | "y" => Some(true),
+        "false" | "f" | "no" | "n" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_timestamp(value: &str) -> Option<NaiveDateTime> {
+    TIMESTAMP_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+}
+
+/// A column of the imported table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnSchema {
+    pub name: String,
+    pub column_type: ColumnType,
+    /// Whether an empty value was seen in the sample.
+    pub nullable: bool,
+}
+
+/// Quote an identifier for PostgreSQL or SQLite.
+///
+/// # Arguments
+///
+/// * `identifier` - A table or column name.
+///
+/// # Returns
+///
+/// The identifier in double quotes, with inner double quotes doubled.
+///
+/// # Example
+///
+/// ```
+/// use database::quote_identifier;
+///
+/// assert_eq!(quote_identifier("artist"), "\"artist\"");
+/// assert_eq!(quote_identifier("a\"b"), "\"a\"\"b\"");
+/// ```
+pub fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// Turn CSV headers into unique lowercase column names.
+fn column_names(headers: &StringRecord) -> Vec<String> {
+    let mut seen = HashSet::new();
+    headers
+        .iter()
+        .enumerate()
+        .map(|(index, header)| {
+            let name = header
+                .trim_start_matches('\u{feff}')
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join("_")
+                .to_lowercase();
+            let name = if name.is_empty() {
+                format!("column_{}", index + 1)
+            } else {
+                name
+            };
+
+            let mut unique = name.clone();
+            let mut suffix = 2;
+            while !seen.insert(unique.clone()) {
+                unique = format!("{}_{}", name, suffix);
+                suffix += 1;
+            }
+            unique
+        })
+        .collect()
+}
+
+/// Counts of an import.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportReport {
+    pub columns: Vec<ColumnSchema>,
+    pub imported: u64,
+    pub rejected: u64,
+}
+
+/// Why a row was not imported.
+struct Rejection(String);
+
+/// Writes rejected rows with their line number and reason, if a rejects file is set.
+struct Rejects {
+    writer: Option<Writer<File>>,
+    count: u64,
+}
+
+impl Rejects {
+    fn new(path: Option<&Path>, headers: &StringRecord) -> Result<Self, DatabaseError> {
+        let writer = match path {
+            Some(path) => {
+                let mut writer = WriterBuilder::new().flexible(true).from_path(path)?;
+                let mut header = vec!["line", "error"];
+                header.extend(headers.iter());
+                writer.write_record(&header)?;
+                Some(writer)
+            }
+            None => None,
+        };
+        Ok(Rejects { writer, count: 0 })
+    }
+
+    fn reject(&mut self, record: &StringRecord, reason: &str) -> Result<(), DatabaseError> {
+        self.count += 1;
+        if let Some(writer) = &mut self.writer {
+            let line = record
+                .position()
+                .map_or(String::new(), |position| position.line().to_string());
+            let mut row = vec![line.as_str(), reason];
+            row.extend(record.iter());
+            writer.write_record(&row)?;
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<u64, DatabaseError> {
+        if let Some(mut writer) = self.writer {
+            writer.flush()?;
+        }
+        Ok(self.count)
+    }
+}
+
+/// Streams a CSV file into a PostgreSQL or SQLite table
+///
+/// Column types are inferred from the first rows of the file: integer,
+/// numeric, boolean, date, timestamp, or text when values disagree. Every
+/// row is then checked against the inferred types; rows that do not fit are
+/// skipped and, when a rejects file is set, written to it with their line
+/// number and the reason. Empty values are stored as NULL.
+///
+/// Headers become lowercase column names with whitespace replaced by `_`,
+/// and all identifiers are quoted.
+///
+/// # Examples
+///
+/// ```
+/// use database::{ColumnType, CsvImporter};
+/// use rusqlite::Connection;
+/// use std::fs::{remove_file, write};
+///
+/// write(
+///     "import_example.csv",
+///     "Name,Age,Indoor,Adopted\nTom,7,yes,2019-04-01\nFelix,three,no,2020-01-15\n",
+/// )
+/// .unwrap();
+///
+/// let importer = CsvImporter::new("import_example.csv").sample_rows(1);
+/// let columns = importer.infer_schema().unwrap();
+/// assert_eq!(columns[1].column_type, ColumnType::Integer);
+/// assert_eq!(columns[3].column_type, ColumnType::Date);
+///
+/// let connection = Connection::open_in_memory().unwrap();
+/// let report = importer.import_sqlite(&connection, "cats").unwrap();
+/// assert_eq!((report.imported, report.rejected), (1, 1));
+///
+/// remove_file("import_example.csv").unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct CsvImporter {
+    path: PathBuf,
+    delimiter: u8,
+    sample_rows: usize,
+    rejects: Option<PathBuf>,
+}
+
+impl CsvImporter {
+    /// Create an importer for a CSV file with a header row
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        CsvImporter {
+            path: path.as_ref().to_path_buf(),
+            delimiter: b',',
+            sample_rows: DEFAULT_SAMPLE_ROWS,
+            rejects: None,
+        }
+    }
+
+    /// Set the field delimiter
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Set how many rows are read to infer column types
+    pub fn sample_rows(mut self, sample_rows: usize) -> Self {
+        self.sample_rows = sample_rows.max(1);
+        self
+    }
+
+    /// Write rejected rows to a CSV file
+    pub fn rejects_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.rejects = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    fn reader(&self) -> Result<csv::Reader<File>, DatabaseError> {
+        Ok(ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .flexible(true)
+            .from_path(&self.path)?)
+    }
+
+    /// Infer the columns from the header and the sampled rows
+    ///
+    /// Columns without any value in the sample are text.
+    pub fn infer_schema(&self) -> Result<Vec<ColumnSchema>, DatabaseError> {
+        let mut reader = self.reader()?;
+        let names = column_names(reader.headers()?);
+        let mut types: Vec<Option<ColumnType>> = vec![None; names.len()];
+        let mut nullable = vec![false; names.len()];
+
+        for record in reader.records().take(self.sample_rows) {
+            let record = record?;
+            if record.len() != names.len() {
+                continue;
+            }
+            for (index, value) in record.iter().enumerate() {
+                if value.is_empty() {
+                    nullable[index] = true;
+                    continue;
+                }
+                let value_type = ColumnType::of(value);
+                types[index] = Some(types[index].map_or(value_type, |t| t.widen(value_type)));
+            }
+        }
+
+        Ok(names
+            .into_iter()
+            .zip(types)
+            .zip(nullable)
+            .map(|((name, column_type), nullable)| ColumnSchema {
+                name,
+                column_type: column_type.unwrap_or(ColumnType::Text),
+                nullable,
+            })
+            .collect())
+    }
+
+    /// Run `load` on every row that matches the schema, rejecting the others.
+    fn each_row<F>(
+        &self,
+        columns: &[ColumnSchema],
+        mut load: F,
+    ) -> Result<(u64, u64), DatabaseError>
+    where
+        F: FnMut(&StringRecord) -> Result<Result<(), Rejection>, DatabaseError>,
+    {
+        let mut reader = self.reader()?;
+        let mut rejects = Rejects::new(self.rejects.as_deref(), reader.headers()?)?;
+        let mut imported = 0;
+
+        for record in reader.records() {
+            let record = record?;
+            let outcome = match check_row(columns, &record) {
+                Ok(()) => load(&record)?,
+                Err(rejection) => Err(rejection),
+            };
+            match outcome {
+                Ok(()) => imported += 1,
+                Err(rejection) => rejects.reject(&record, &rejection.0)?,
+            }
+        }
+        Ok((imported, rejects.finish()?))
+    }
+
+    /// Import into a PostgreSQL table, created if it does not exist
+    ///
+    /// Rows are checked first and the valid ones are streamed with a single
+    /// `COPY FROM STDIN`, in one transaction with the table creation.
+    pub fn import_postgres(
+        &self,
+        client: &mut Client,
+        table: &str,
+    ) -> Result<ImportReport, DatabaseError> {
+        let columns = self.infer_schema()?;
+        let definitions = columns
+            .iter()
+            .map(|column| {
+                format!(
+                    "{} {}",
+                    quote_identifier(&column.name),
+                    column.column_type.postgres_type()
+                )
+            })
+            .collect::<Vec<_>>();
+        let names = column_list(&columns);
+
+        let mut transaction = client.transaction()?;
+        transaction.batch_execute(&format!(
+            "CREATE TABLE IF NOT EXISTS {} ({})",
+            quote_identifier(table),
+            definitions.join(", ")
+        ))?;
+
+        let copy = transaction.copy_in(&format!(
+            "COPY {} ({}) FROM STDIN WITH (FORMAT csv)",
+            quote_identifier(table),
+            names
+        ))?;
+        let mut writer = WriterBuilder::new().has_headers(false).from_writer(copy);
+        let (imported, rejected) = self.each_row(&columns, |record| {
+            let row = columns
+                .iter()
+                .zip(record.iter())
+                .map(|(column, value)| postgres_value(column.column_type, value))
+                .collect::<Vec<_>>();
+            writer.write_record(&row)?;
+            Ok(Ok(()))
+        })?;
+        let mut copy = writer.into_inner().map_err(|e| e.into_error())?;
+        copy.flush()?;
+        copy.finish()?;
+        transaction.commit()?;
+
+        Ok(ImportReport {
+            columns,
+            imported,
+            rejected,
+        })
+    }
+
+    /// Import into a PostgreSQL table with an async client
+    ///
+    /// Works like `import_postgres`. The file is read on a blocking thread
+    /// and streamed to `COPY FROM STDIN` in chunks as it is parsed.
+    pub async fn import_postgres_async(
+        &self,
+        client: &mut tokio_postgres::Client,
+        table: &str,
+    ) -> Result<ImportReport, DatabaseError> {
+        let importer = self.clone();
+        let columns = tokio::task::spawn_blocking(move || importer.infer_schema())
+            .await
+            .map_err(join_error)??;
+        let definitions = columns
+            .iter()
+            .map(|column| {
+                format!(
+                    "{} {}",
+                    quote_identifier(&column.name),
+                    column.column_type.postgres_type()
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let transaction = client.transaction().await?;
+        transaction
+            .batch_execute(&format!(
+                "CREATE TABLE IF NOT EXISTS {} ({})",
+                quote_identifier(table),
+                definitions.join(", ")
+            ))
+            .await?;
+        let sink = transaction
+            .copy_in::<_, Bytes>(&format!(
+                "COPY {} ({}) FROM STDIN WITH (FORMAT csv)",
+                quote_identifier(table),
+                column_list(&columns)
+            ))
+            .await?;
+        pin_mut!(sink);
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Bytes>(COPY_CHANNEL_CHUNKS);
+        let importer = self.clone();
+        let schema = columns.clone();
+        let reading = tokio::task::spawn_blocking(move || {
+            let mut writer = chunk_writer();
+            importer
+                .each_row(&schema, |record| {
+                    let row = schema
+                        .iter()
+                        .zip(record.iter())
+                        .map(|(column, value)| postgres_value(column.column_type, value))
+                        .collect::<Vec<_>>();
+                    writer.write_record(&row)?;
+                    writer.flush()?;
+                    if writer.get_ref().len() >= COPY_CHUNK_BYTES {
+                        let full = std::mem::replace(&mut writer, chunk_writer());
+                        let chunk = full.into_inner().map_err(|e| e.into_error())?;
+                        sender
+                            .blocking_send(Bytes::from(chunk))
+                            .map_err(|_| closed_copy())?;
+                    }
+                    Ok(Ok(()))
+                })
+                .and_then(|counts| {
+                    let rest = writer.into_inner().map_err(|e| e.into_error())?;
+                    if !rest.is_empty() {
+                        sender
+                            .blocking_send(Bytes::from(rest))
+                            .map_err(|_| closed_copy())?;
+                    }
+                    Ok(counts)
+                })
+        });
+
+        while let Some(chunk) = receiver.recv().await {
+            sink.send(chunk).await?;
+        }
+        let (imported, rejected) = reading.await.map_err(join_error)??;
+        sink.as_mut().finish().await?;
+        transaction.commit().await?;
+
+        Ok(ImportReport {
+            columns,
+            imported,
+            rejected,
+        })
+    }
+
+    /// Import into a SQLite table, created if it does not exist
+    ///
+    /// Rows are inserted with one prepared statement in a single
+    /// transaction; rows SQLite refuses, e.g. for a constraint of an
+    /// existing table, are rejected like rows that do not match the types.
+    pub fn import_sqlite(
+        &self,
+        connection: &Connection,
+        table: &str,
+    ) -> Result<ImportReport, DatabaseError> {
+        let columns = self.infer_schema()?;
+        let definitions = columns
+            .iter()
+            .map(|column| {
+                format!(
+                    "{} {}",
+                    quote_identifier(&column.name),
+                    column.column_type.sqlite_type()
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let transaction = connection.unchecked_transaction()?;
+        transaction.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {} ({})",
+            quote_identifier(table),
+            definitions.join(", ")
+        ))?;
+
+        let (imported, rejected) = {
+            let mut insert = transaction.prepare(&format!(
+                "INSERT INTO {} ({}) VALUES ({})",
+                quote_identifier(table),
+                column_list(&columns),
+                vec!["?"; columns.len()].join(", ")
+            ))?;
+            self.each_row(&columns, |record| {
+                let row = columns
+                    .iter()
+                    .zip(record.iter())
+                    .map(|(column, value)| sqlite_value(column.column_type, value));
+                match insert.execute(rusqlite::params_from_iter(row)) {
+                    Ok(_) => Ok(Ok(())),
+                    Err(e @ rusqlite::Error::SqliteFailure(..)) => {
+                        Ok(Err(Rejection(e.to_string())))
+                    }
+                    Err(e) => Err(e.into()),
+                }
+            })?
+        };
+        transaction.commit()?;
+
+        Ok(ImportReport {
+            columns,
+            imported,
+            rejected,
+        })
+    }
+}
+
+/// Bytes of CSV sent to `COPY` at a time by `import_postgres_async`.
+const COPY_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Chunks parsed ahead of the `COPY` stream by `import_postgres_async`.
+const COPY_CHANNEL_CHUNKS: usize = 16;
+
+/// A blocking task of `import_postgres_async` panicked or was cancelled.
+fn join_error(e: tokio::task::JoinError) -> DatabaseError {
+    DatabaseError::Io(io::Error::other(e))
+}
+
+/// The `COPY` stream of `import_postgres_async` stopped taking chunks.
+fn closed_copy() -> DatabaseError {
+    DatabaseError::Io(io::ErrorKind::BrokenPipe.into())
+}
+
+fn chunk_writer() -> Writer<Vec<u8>> {
+    WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new())
+}
+
+fn column_list(columns: &[ColumnSchema]) -> String {
+    columns
+        .iter()
+        .map(|column| quote_identifier(&column.name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn check_row(columns: &[ColumnSchema], record: &StringRecord) -> Result<(), Rejection> {
+    if record.len() != columns.len() {
+        return Err(Rejection(format!(
+            "expected {} fields, found {}",
+            columns.len(),
+            record.len()
+        )));
+    }
+    for (column, value) in columns.iter().zip(record.iter()) {
+        if !value.is_empty() && !column.column_type.accepts(value) {
+            return Err(Rejection(format!(
+                "{}: {:?} is not a valid {:?}",
+                column.name, value, column.column_type
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The CSV field for `COPY`, where an unquoted empty field is NULL.
+fn postgres_value(column_type: ColumnType, value: &str) -> String {
+    match parse_bool(value) {
+        Some(value) if column_type == ColumnType::Boolean => value.to_string(),
+        _ => value.to_string(),
+    }
+}
+
+fn sqlite_value(column_type: ColumnType, value: &str) -> Value {
+    if value.is_empty() {
+        return Value::Null;
+    }
+    match column_type {
+        ColumnType::Integer => value.parse().map_or(Value::Null, Value::Integer),
+        ColumnType::Numeric => value.parse().map_or(Value::Null, Value::Real),
+        ColumnType::Boolean => Value::Integer(parse_bool(value).unwrap_or_default() as i64),
+        ColumnType::Timestamp => {
+            Value::Text(parse_timestamp(value).map_or(value.to_string(), |t| {
+                t.format("%Y-%m-%d %H:%M:%S%.f").to_string()
+            }))
+        }
+        ColumnType::Date | ColumnType::Text => Value::Text(value.to_string()),
+    }
+}
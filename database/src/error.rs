@@ -0,0 +1,295 @@
+use crate::{ConfigError, MigrationError};
+use rusqlite::{ffi, ErrorCode};
+use std::{error::Error, fmt, io};
+
+/// The error of a SQLite or PostgreSQL call, as returned by the driver
+#[derive(Debug)]
+pub enum BackendError {
+    Sqlite(rusqlite::Error),
+    Postgres(postgres::Error),
+}
+
+impl BackendError {
+    /// The SQLSTATE of a PostgreSQL error or the extended result code of a
+    /// SQLite error
+    pub fn code(&self) -> Option<String> {
+        match self {
+            BackendError::Sqlite(rusqlite::Error::SqliteFailure(e, _)) => {
+                Some(e.extended_code.to_string())
+            }
+            BackendError::Sqlite(_) => None,
+            BackendError::Postgres(e) => e.code().map(|code| code.code().to_string()),
+        }
+    }
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BackendError::Sqlite(e) => write!(f, "{}", e),
+            BackendError::Postgres(e) => match e.as_db_error() {
+                Some(db_error) => write!(f, "{}", db_error),
+                None => write!(f, "{}", e),
+            },
+        }
+    }
+}
+
+impl Error for BackendError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BackendError::Sqlite(e) => Some(e),
+            BackendError::Postgres(e) => Some(e),
+        }
+    }
+}
+
+/// An error of the `database` crate, classified by cause
+///
+/// Built from `rusqlite` and `postgres` errors with `From`, using SQLite
+/// extended result codes and PostgreSQL SQLSTATE codes, so callers can react
+/// to a cause without parsing messages.
+///
+/// # Examples
+///
+/// ```
+/// use database::DatabaseError;
+/// use rusqlite::Connection;
+///
+/// let connection = Connection::open_in_memory().unwrap();
+/// connection
+///     .execute_batch("CREATE TABLE cats (name TEXT UNIQUE); INSERT INTO cats VALUES ('Tom');")
+///     .unwrap();
+///
+/// let error: DatabaseError = connection
+///     .execute("INSERT INTO cats VALUES ('Tom')", [])
+///     .unwrap_err()
+///     .into();
+/// assert!(matches!(error, DatabaseError::UniqueViolation(_)));
+/// assert_eq!(error.code().as_deref(), Some("2067"));
+/// ```
+#[derive(Debug)]
+pub enum DatabaseError {
+    /// The database could not be opened or the server could not be reached.
+    Connection(BackendError),
+    /// A unique or primary key constraint failed.
+    UniqueViolation(BackendError),
+    /// A foreign key constraint failed.
+    ForeignKeyViolation(BackendError),
+    /// A not null, check or other constraint failed.
+    ConstraintViolation(BackendError),
+    /// A database, table, column or row does not exist.
+    NotFound(BackendError),
+    /// A database, table or other object already exists.
+    AlreadyExists(BackendError),
+    /// The transaction conflicted with another one and can be retried.
+    SerializationFailure(BackendError),
+    /// Reading or writing a file failed.
+    Io(io::Error),
+    /// The input, e.g. a CSV file or an export format, can't be used.
+    Invalid(String),
+    /// The operation can't be used on this object, e.g. a WITHOUT ROWID table.
+    Unsupported(String),
+    /// A migration plan is invalid or doesn't match the database.
+    Migration(MigrationError),
+    /// A configuration can't be loaded, or its TLS or pool setup failed.
+    Config(ConfigError),
+    /// Any other error.
+    Other(BackendError),
+}
+
+impl DatabaseError {
+    /// The driver error, `None` for errors not raised by a driver
+    pub fn backend(&self) -> Option<&BackendError> {
+        match self {
+            DatabaseError::Connection(e)
+            | DatabaseError::UniqueViolation(e)
+            | DatabaseError::ForeignKeyViolation(e)
+            | DatabaseError::ConstraintViolation(e)
+            | DatabaseError::NotFound(e)
+            | DatabaseError::AlreadyExists(e)
+            | DatabaseError::SerializationFailure(e)
+            | DatabaseError::Other(e) => Some(e),
+            // SQLite I/O failures keep their driver error inside.
+            DatabaseError::Io(e) => e.get_ref().and_then(|e| e.downcast_ref()),
+            DatabaseError::Invalid(_)
+            | DatabaseError::Unsupported(_)
+            | DatabaseError::Migration(_)
+            | DatabaseError::Config(_) => None,
+        }
+    }
+
+    /// The SQLSTATE or SQLite extended result code, if any
+    pub fn code(&self) -> Option<String> {
+        self.backend().and_then(BackendError::code)
+    }
+
+    /// Whether running the same work again may succeed
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, DatabaseError::SerializationFailure(_))
+    }
+}
+
+impl fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DatabaseError::Connection(e) => write!(f, "Connection failed: {}", e),
+            DatabaseError::UniqueViolation(e) => write!(f, "Unique constraint violated: {}", e),
+            DatabaseError::ForeignKeyViolation(e) => {
+                write!(f, "Foreign key constraint violated: {}", e)
+            }
+            DatabaseError::ConstraintViolation(e) => write!(f, "Constraint violated: {}", e),
+            DatabaseError::NotFound(e) => write!(f, "Not found: {}", e),
+            DatabaseError::AlreadyExists(e) => write!(f, "Already exists: {}", e),
+            DatabaseError::SerializationFailure(e) => write!(f, "Serialization failure: {}", e),
+            DatabaseError::Io(e) => write!(f, "I/O error: {}", e),
+            DatabaseError::Invalid(message) => write!(f, "Invalid input: {}", message),
+            DatabaseError::Unsupported(message) => write!(f, "Unsupported: {}", message),
+            DatabaseError::Migration(e) => write!(f, "{}", e),
+            DatabaseError::Config(e) => write!(f, "{}", e),
+            DatabaseError::Other(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl Error for DatabaseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DatabaseError::Io(e) => Some(e),
+            DatabaseError::Invalid(_) | DatabaseError::Unsupported(_) => None,
+            DatabaseError::Migration(e) => Some(e),
+            DatabaseError::Config(e) => Some(e),
+            _ => self.backend().map(|e| e as &(dyn Error + 'static)),
+        }
+    }
+}
+
+impl From<io::Error> for DatabaseError {
+    fn from(e: io::Error) -> Self {
+        DatabaseError::Io(e)
+    }
+}
+
+impl From<csv::Error> for DatabaseError {
+    fn from(e: csv::Error) -> Self {
+        if !e.is_io_error() {
+            return DatabaseError::Invalid(e.to_string());
+        }
+        match e.into_kind() {
+            csv::ErrorKind::Io(e) => DatabaseError::Io(e),
+            kind => DatabaseError::Invalid(format!("{:?}", kind)),
+        }
+    }
+}
+
+impl From<serde_json::Error> for DatabaseError {
+    fn from(e: serde_json::Error) -> Self {
+        match e.is_io() {
+            true => DatabaseError::Io(e.into()),
+            false => DatabaseError::Invalid(e.to_string()),
+        }
+    }
+}
+
+impl From<MigrationError> for DatabaseError {
+    fn from(e: MigrationError) -> Self {
+        match e {
+            MigrationError::Sqlite(e) => e.into(),
+            MigrationError::Postgres(e) => e.into(),
+            MigrationError::Io(e) => DatabaseError::Io(e),
+            e => DatabaseError::Migration(e),
+        }
+    }
+}
+
+impl From<ConfigError> for DatabaseError {
+    fn from(e: ConfigError) -> Self {
+        match e {
+            ConfigError::Io(e) => DatabaseError::Io(e),
+            ConfigError::Postgres(e) => e.into(),
+            e => DatabaseError::Config(e),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for DatabaseError {
+    fn from(e: rusqlite::Error) -> Self {
+        let (code, message) = match &e {
+            rusqlite::Error::QueryReturnedNoRows => {
+                return DatabaseError::NotFound(BackendError::Sqlite(e))
+            }
+            rusqlite::Error::SqliteFailure(code, message) => (*code, message.clone()),
+            _ => return DatabaseError::Other(BackendError::Sqlite(e)),
+        };
+        let message = message.unwrap_or_default();
+        let backend = BackendError::Sqlite(e);
+
+        match code.code {
+            ErrorCode::ConstraintViolation => match code.extended_code {
+                ffi::SQLITE_CONSTRAINT_UNIQUE | ffi::SQLITE_CONSTRAINT_PRIMARYKEY => {
+                    DatabaseError::UniqueViolation(backend)
+                }
+                ffi::SQLITE_CONSTRAINT_FOREIGNKEY => DatabaseError::ForeignKeyViolation(backend),
+                _ => DatabaseError::ConstraintViolation(backend),
+            },
+            ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked => {
+                DatabaseError::SerializationFailure(backend)
+            }
+            ErrorCode::CannotOpen | ErrorCode::NotADatabase | ErrorCode::PermissionDenied => {
+                DatabaseError::Connection(backend)
+            }
+            ErrorCode::SystemIoFailure => DatabaseError::Io(io::Error::other(backend)),
+            // Missing and duplicate objects are plain SQLITE_ERROR, told
+            // apart only by the message.
+            ErrorCode::Unknown if message.starts_with("no such ") => {
+                DatabaseError::NotFound(backend)
+            }
+            ErrorCode::Unknown
+                if message.ends_with(" already exists")
+                    || message.starts_with("duplicate column name") =>
+            {
+                DatabaseError::AlreadyExists(backend)
+            }
+            _ => DatabaseError::Other(backend),
+        }
+    }
+}
+
+impl From<postgres::Error> for DatabaseError {
+    fn from(e: postgres::Error) -> Self {
+        let state = match e.code() {
+            Some(state) => state.code().to_string(),
+            None if e.is_closed() || e.source().is_some_and(|source| source.is::<io::Error>()) => {
+                return DatabaseError::Connection(BackendError::Postgres(e))
+            }
+            None => return DatabaseError::Other(BackendError::Postgres(e)),
+        };
+        let backend = BackendError::Postgres(e);
+
+        match state.as_str() {
+            // unique_violation
+            "23505" => DatabaseError::UniqueViolation(backend),
+            // foreign_key_violation
+            "23503" => DatabaseError::ForeignKeyViolation(backend),
+            // undefined table, column, object, function, schema and database
+            "42P01" | "42703" | "42704" | "42883" | "3F000" | "3D000" => {
+                DatabaseError::NotFound(backend)
+            }
+            // duplicate database, table, column, object, schema and function
+            "42P04" | "42P07" | "42701" | "42710" | "42P06" | "42723" => {
+                DatabaseError::AlreadyExists(backend)
+            }
+            // serialization_failure and deadlock_detected
+            "40001" | "40P01" => DatabaseError::SerializationFailure(backend),
+            // too_many_connections and server shutdowns
+            "53300" | "57P01" | "57P02" | "57P03" => DatabaseError::Connection(backend),
+            // Classes 23 (integrity constraint), 08 (connection exception)
+            // and 28 (invalid authorization)
+            state => match &state[..2] {
+                "23" => DatabaseError::ConstraintViolation(backend),
+                "08" | "28" => DatabaseError::Connection(backend),
+                _ => DatabaseError::Other(backend),
+            },
+        }
+    }
+}
@@ -0,0 +1,419 @@
+use crate::{quote_identifier, DatabaseError};
+use postgres::{Client, NoTls};
+use rusqlite::{
+    backup::{Backup, Progress, StepResult},
+    ffi,
+    types::ValueRef,
+    Connection, OpenFlags, Params,
+};
+use std::{
+    fs::File,
+    io::{copy, BufWriter, Write},
+    path::Path,
+    thread::sleep,
+    time::Duration,
+};
+
+/// Pages copied per backup step by default.
+pub const DEFAULT_BACKUP_PAGES_PER_STEP: i32 = 256;
+
+/// Busy or locked backup steps in a row before giving up, 100 ms apart.
+const BACKUP_BUSY_RETRIES: u32 = 20;
+
+/// A plain text export format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Comma-separated values with a header row.
+    Csv,
+    /// Tab-separated values with a header row.
+    Tsv,
+    /// One JSON object per row, keys in column order.
+    JsonLines,
+}
+
+impl ExportFormat {
+    /// Pick the format from a file extension: `csv`, `tsv`, `jsonl` or `ndjson`
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        match path
+            .as_ref()
+            .extension()?
+            .to_str()?
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "csv" => Some(ExportFormat::Csv),
+            "tsv" => Some(ExportFormat::Tsv),
+            "jsonl" | "ndjson" => Some(ExportFormat::JsonLines),
+            _ => None,
+        }
+    }
+}
+
+/// Export the rows of a SQLite query.
+///
+/// NULL is an empty field in CSV and TSV and `null` in JSON; blobs are
+/// written as lowercase hex prefixed with `\x`.
+///
+/// # Arguments
+///
+/// * `connection` - The SQLite connection.
+/// * `query` - The query.
+/// * `params` - The query parameters.
+/// * `format` - The output format.
+/// * `writer` - Where to write the rows.
+///
+/// # Returns
+///
+/// The number of rows written.
+///
+/// # Example
+///
+/// ```
+/// use database::{export_sqlite_query, ExportFormat};
+/// use rusqlite::Connection;
+///
+/// let connection = Connection::open_in_memory().unwrap();
+/// connection
+///     .execute_batch(
+///         "CREATE TABLE cats (name TEXT, age INTEGER);
+///          INSERT INTO cats VALUES ('Tom', 7), ('Felix', NULL);",
+///     )
+///     .unwrap();
+///
+/// let mut output = Vec::new();
+/// let rows = export_sqlite_query(
+///     &connection,
+///     "SELECT name, age FROM cats ORDER BY name",
+///     [],
+///     ExportFormat::JsonLines,
+///     &mut output,
+/// )
+/// .unwrap();
+///
+/// assert_eq!(rows, 2);
+/// assert_eq!(
+///     String::from_utf8(output).unwrap(),
+///     "{\"name\":\"Felix\",\"age\":null}\n{\"name\":\"Tom\",\"age\":7}\n"
+/// );
+/// ```
+pub fn export_sqlite_query<P: Params, W: Write>(
+    connection: &Connection,
+    query: &str,
+    params: P,
+    format: ExportFormat,
+    writer: W,
+) -> Result<u64, DatabaseError> {
+    let mut statement = connection.prepare(query)?;
+    let columns: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    let mut rows = statement.query(params)?;
+    let mut count = 0;
+
+    match format {
+        ExportFormat::Csv | ExportFormat::Tsv => {
+            let delimiter = if format == ExportFormat::Csv {
+                b','
+            } else {
+                b'\t'
+            };
+            let mut csv = csv::WriterBuilder::new()
+                .delimiter(delimiter)
+                .from_writer(writer);
+            csv.write_record(&columns)?;
+            while let Some(row) = rows.next()? {
+                let fields = (0..columns.len())
+                    .map(|index| Ok(text_field(row.get_ref(index)?)))
+                    .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+                csv.write_record(&fields)?;
+                count += 1;
+            }
+            csv.flush()?;
+        }
+        ExportFormat::JsonLines => {
+            let mut writer = BufWriter::new(writer);
+            let keys = columns
+                .iter()
+                .map(serde_json::to_string)
+                .collect::<Result<Vec<_>, _>>()?;
+            while let Some(row) = rows.next()? {
+                let fields = keys
+                    .iter()
+                    .enumerate()
+                    .map(|(index, key)| Ok(format!("{}:{}", key, json_value(row.get_ref(index)?)?)))
+                    .collect::<Result<Vec<_>, DatabaseError>>()?;
+                writeln!(writer, "{{{}}}", fields.join(","))?;
+                count += 1;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(count)
+}
+
+/// Export every row of a SQLite table.
+///
+/// # Arguments
+///
+/// * `connection` - The SQLite connection.
+/// * `table` - The table name.
+/// * `format` - The output format.
+/// * `writer` - Where to write the rows.
+///
+/// # Returns
+///
+/// The number of rows written.
+pub fn export_sqlite_table<W: Write>(
+    connection: &Connection,
+    table: &str,
+    format: ExportFormat,
+    writer: W,
+) -> Result<u64, DatabaseError> {
+    export_sqlite_query(
+        connection,
+        &format!("SELECT * FROM {}", quote_identifier(table)),
+        [],
+        format,
+        writer,
+    )
+}
+
+/// Export a SQLite table to a file, in the format of its extension.
+///
+/// # Arguments
+///
+/// * `connection` - The SQLite connection.
+/// * `table` - The table name.
+/// * `file_path` - A `.csv`, `.tsv`, `.jsonl` or `.ndjson` file.
+///
+/// # Returns
+///
+/// The number of rows written.
+pub fn export_sqlite_table_to_file<P: AsRef<Path>>(
+    connection: &Connection,
+    table: &str,
+    file_path: P,
+) -> Result<u64, DatabaseError> {
+    let format = ExportFormat::from_path(&file_path).ok_or_else(|| {
+        DatabaseError::Invalid(format!(
+            "Unknown export format for {}",
+            file_path.as_ref().display()
+        ))
+    })?;
+    export_sqlite_table(connection, table, format, File::create(file_path)?)
+}
+
+fn text_field(value: ValueRef) -> String {
+    match value {
+        ValueRef::Null => String::new(),
+        ValueRef::Integer(value) => value.to_string(),
+        ValueRef::Real(value) => value.to_string(),
+        ValueRef::Text(value) => String::from_utf8_lossy(value).into_owned(),
+        ValueRef::Blob(value) => hex_blob(value),
+    }
+}
+
+fn json_value(value: ValueRef) -> Result<String, serde_json::Error> {
+    match value {
+        ValueRef::Null => Ok("null".to_string()),
+        ValueRef::Integer(value) => Ok(value.to_string()),
+        ValueRef::Real(value) if value.is_finite() => serde_json::to_string(&value),
+        ValueRef::Real(_) => Ok("null".to_string()),
+        ValueRef::Text(value) => serde_json::to_string(&String::from_utf8_lossy(value)),
+        ValueRef::Blob(value) => serde_json::to_string(&hex_blob(value)),
+    }
+}
+
+fn hex_blob(value: &[u8]) -> String {
+    let hex: String = value.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("\\x{}", hex)
+}
+
+/// Copy a live SQLite database to a file with the online backup API.
+///
+/// The source stays usable during the backup: the copy proceeds in steps
+/// of `pages_per_step` pages and waits briefly when the source is busy,
+/// giving up after 20 busy or locked steps in a row.
+///
+/// # Arguments
+///
+/// * `connection` - The source database.
+/// * `destination` - The backup file, overwritten if it exists.
+/// * `pages_per_step` - Pages copied per step, -1 for all at once.
+/// * `progress` - Called after every step with the remaining and total page counts.
+///
+/// # Example
+///
+/// ```
+/// use database::{backup_sqlite, DEFAULT_BACKUP_PAGES_PER_STEP};
+/// use rusqlite::Connection;
+/// use std::fs::remove_file;
+///
+/// let connection = Connection::open_in_memory().unwrap();
+/// connection.execute_batch("CREATE TABLE cats (name TEXT)").unwrap();
+///
+/// let mut steps = 0;
+/// backup_sqlite(&connection, "backup_example.db", DEFAULT_BACKUP_PAGES_PER_STEP, |progress| {
+///     steps += 1;
+///     println!("{} of {} pages left", progress.remaining, progress.pagecount);
+/// })
+/// .unwrap();
+/// assert!(steps > 0);
+///
+/// remove_file("backup_example.db").unwrap();
+/// ```
+pub fn backup_sqlite<P, F>(
+    connection: &Connection,
+    destination: P,
+    pages_per_step: i32,
+    progress: F,
+) -> Result<(), DatabaseError>
+where
+    P: AsRef<Path>,
+    F: FnMut(Progress),
+{
+    let mut target = Connection::open(destination)?;
+    copy_pages(connection, &mut target, pages_per_step, progress)
+}
+
+/// Replace the contents of a SQLite database with a backup file.
+///
+/// The backup file is opened read-only, so a missing file is an error
+/// rather than an empty database restored over `connection`.
+///
+/// # Arguments
+///
+/// * `connection` - The database to overwrite.
+/// * `source` - The backup file.
+/// * `pages_per_step` - Pages copied per step, -1 for all at once.
+/// * `progress` - Called after every step with the remaining and total page counts.
+pub fn restore_sqlite<P, F>(
+    connection: &mut Connection,
+    source: P,
+    pages_per_step: i32,
+    progress: F,
+) -> Result<(), DatabaseError>
+where
+    P: AsRef<Path>,
+    F: FnMut(Progress),
+{
+    let backup = Connection::open_with_flags(
+        source,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    copy_pages(&backup, connection, pages_per_step, progress)
+}
+
+fn copy_pages<F: FnMut(Progress)>(
+    from: &Connection,
+    to: &mut Connection,
+    pages_per_step: i32,
+    mut progress: F,
+) -> Result<(), DatabaseError> {
+    let backup = Backup::new(from, to)?;
+    let mut retries = 0;
+    loop {
+        let result = backup.step(pages_per_step)?;
+        progress(backup.progress());
+        match result {
+            StepResult::Done => return Ok(()),
+            StepResult::More => retries = 0,
+            _ if retries == BACKUP_BUSY_RETRIES => {
+                let busy = ffi::Error::new(ffi::SQLITE_BUSY);
+                let message = format!("Backup gave up after {} busy steps", retries);
+                return Err(rusqlite::Error::SqliteFailure(busy, Some(message)).into());
+            }
+            _ => {
+                retries += 1;
+                sleep(Duration::from_millis(100));
+            }
+        }
+    }
+}
+
+/// Copy a PostgreSQL table to CSV with `COPY TO STDOUT`.
+///
+/// # Arguments
+///
+/// * `client` - The PostgreSQL client.
+/// * `table` - The table name.
+/// * `writer` - Where to write the CSV, with a header row.
+///
+/// # Returns
+///
+/// The number of bytes written.
+pub fn export_postgres_table_csv<W: Write>(
+    client: &mut Client,
+    table: &str,
+    writer: W,
+) -> Result<u64, DatabaseError> {
+    copy_out_csv(client, &quote_identifier(table), writer)
+}
+
+/// Copy the result of a PostgreSQL query to CSV with `COPY TO STDOUT`.
+///
+/// # Arguments
+///
+/// * `client` - The PostgreSQL client.
+/// * `query` - A `SELECT`, `VALUES` or similar query without parameters.
+/// * `writer` - Where to write the CSV, with a header row.
+///
+/// # Returns
+///
+/// The number of bytes written.
+pub fn export_postgres_query_csv<W: Write>(
+    client: &mut Client,
+    query: &str,
+    writer: W,
+) -> Result<u64, DatabaseError> {
+    copy_out_csv(client, &format!("({})", query), writer)
+}
+
+fn copy_out_csv<W: Write>(
+    client: &mut Client,
+    source: &str,
+    mut writer: W,
+) -> Result<u64, DatabaseError> {
+    let mut reader = client.copy_out(&format!(
+        "COPY {} TO STDOUT WITH (FORMAT csv, HEADER true)",
+        source
+    ))?;
+    let bytes = copy(&mut reader, &mut writer)?;
+    writer.flush()?;
+    Ok(bytes)
+}
+
+/// Export a table of a database to a CSV file, the reverse of `create_db_from_csv`.
+///
+/// # Arguments
+///
+/// * `connection_string` - A connection string to the PostgreSQL server.
+/// * `db` - The name of the database.
+/// * `file_path` - The path to the CSV file to write.
+/// * `table_name` - The name of the table to export.
+///
+/// # Returns
+///
+/// A `Result` with the number of bytes written.
+///
+/// # Example
+///
+/// ```ignore
+/// use database::export_db_to_csv;
+///
+/// let connection_string = "postgresql://postgres:@localhost";
+/// let result = export_db_to_csv(connection_string, "moma", "artist_export.csv", "artist");
+/// assert!(result.is_ok());
+/// ```
+pub fn export_db_to_csv(
+    connection_string: &str,
+    db: &str,
+    file_path: &str,
+    table_name: &str,
+) -> Result<u64, DatabaseError> {
+    let connection_string_full = format!("{}/{}", connection_string, db);
+    let mut client = Client::connect(&connection_string_full, NoTls)?;
+    export_postgres_table_csv(&mut client, table_name, File::create(file_path)?)
+}
@@ -0,0 +1,269 @@
+use crate::{quote_identifier, Database};
+use rusqlite::{types::FromSql, OptionalExtension, Result, ToSql};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The default key-value table name.
+pub const DEFAULT_KV_TABLE: &str = "key_values";
+
+/// A key-value store with namespaces and expiring entries in a SQLite table
+///
+/// Values are any type rusqlite can bind and read back. An expired entry is
+/// invisible as soon as its time to live runs out and is deleted by the next
+/// write to its key or by `purge_expired`.
+///
+/// # Examples
+///
+/// ```
+/// use database::{Database, KeyValueStore};
+/// use std::time::Duration;
+///
+/// let store = KeyValueStore::open(&Database::open_in_memory().unwrap()).unwrap();
+/// let sessions = store.namespace("sessions");
+///
+/// sessions.set_with_ttl("abc", "user 1", Duration::from_secs(3600)).unwrap();
+/// sessions.set("counter", 42).unwrap();
+///
+/// assert_eq!(sessions.get::<String>("abc").unwrap(), Some("user 1".to_string()));
+/// assert_eq!(sessions.get::<i64>("counter").unwrap(), Some(42));
+/// assert_eq!(store.namespace("other").get::<i64>("counter").unwrap(), None);
+/// ```
+#[derive(Clone)]
+pub struct KeyValueStore {
+    db: Database,
+    table: String,
+}
+
+impl KeyValueStore {
+    /// Open the store in the `key_values` table, creating it if needed
+    pub fn open(db: &Database) -> Result<Self> {
+        KeyValueStore::open_table(db, DEFAULT_KV_TABLE)
+    }
+
+    /// Open the store in a custom table, creating it if needed
+    pub fn open_table(db: &Database, table: &str) -> Result<Self> {
+        let store = KeyValueStore {
+            db: db.clone(),
+            table: quote_identifier(table),
+        };
+        db.connection().execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                namespace TEXT NOT NULL,
+                key TEXT NOT NULL,
+                value BLOB,
+                expires_at INTEGER,
+                PRIMARY KEY (namespace, key)
+            ) WITHOUT ROWID",
+            store.table
+        ))?;
+        Ok(store)
+    }
+
+    /// A handle on the keys of one namespace
+    pub fn namespace(&self, namespace: &str) -> KeyValueNamespace {
+        KeyValueNamespace {
+            store: self.clone(),
+            namespace: namespace.to_string(),
+        }
+    }
+
+    /// The namespaces holding at least one live key, sorted
+    pub fn namespaces(&self) -> Result<Vec<String>> {
+        let connection = self.db.connection();
+        let mut statement = connection.prepare(&format!(
+            "SELECT DISTINCT namespace FROM {} WHERE {} ORDER BY namespace",
+            self.table, LIVE
+        ))?;
+        let rows = statement.query_map([now_millis()], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    /// Delete every expired entry of every namespace
+    ///
+    /// # Returns
+    ///
+    /// The number of deleted entries.
+    pub fn purge_expired(&self) -> Result<usize> {
+        self.db.connection().execute(
+            &format!(
+                "DELETE FROM {} WHERE expires_at IS NOT NULL AND expires_at <= ?1",
+                self.table
+            ),
+            [now_millis()],
+        )
+    }
+}
+
+/// The condition on `expires_at` for entries still alive at time `?1`.
+const LIVE: &str = "(expires_at IS NULL OR expires_at > ?1)";
+
+/// The keys of one namespace of a `KeyValueStore`
+#[derive(Clone)]
+pub struct KeyValueNamespace {
+    store: KeyValueStore,
+    namespace: String,
+}
+
+impl KeyValueNamespace {
+    /// The namespace name
+    pub fn name(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Store a value that never expires, replacing any previous one
+    pub fn set<V: ToSql>(&self, key: &str, value: V) -> Result<()> {
+        self.put(key, &value, None)
+    }
+
+    /// Store a value that expires after `ttl`, replacing any previous one
+    pub fn set_with_ttl<V: ToSql>(&self, key: &str, value: V, ttl: Duration) -> Result<()> {
+        self.put(key, &value, Some(expiry(ttl)))
+    }
+
+    fn put(&self, key: &str, value: &dyn ToSql, expires_at: Option<i64>) -> Result<()> {
+        self.store.db.connection().execute(
+            &format!(
+                "INSERT OR REPLACE INTO {} (namespace, key, value, expires_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                self.store.table
+            ),
+            (&self.namespace, key, value, expires_at),
+        )?;
+        Ok(())
+    }
+
+    /// Read a live value, `None` if the key is missing or expired
+    pub fn get<T: FromSql>(&self, key: &str) -> Result<Option<T>> {
+        self.store
+            .db
+            .connection()
+            .query_row(
+                &format!(
+                    "SELECT value FROM {} WHERE namespace = ?2 AND key = ?3 AND {}",
+                    self.store.table, LIVE
+                ),
+                (now_millis(), &self.namespace, key),
+                |row| row.get(0),
+            )
+            .optional()
+    }
+
+    /// Whether the key holds a live value
+    pub fn contains(&self, key: &str) -> Result<bool> {
+        Ok(self.ttl(key)?.is_some())
+    }
+
+    /// The time left before a key expires
+    ///
+    /// # Returns
+    ///
+    /// `None` for a missing or expired key, `Some(None)` for a key that never
+    /// expires and `Some(Some(remaining))` otherwise.
+    pub fn ttl(&self, key: &str) -> Result<Option<Option<Duration>>> {
+        let now = now_millis();
+        let expires_at: Option<Option<i64>> = self
+            .store
+            .db
+            .connection()
+            .query_row(
+                &format!(
+                    "SELECT expires_at FROM {} WHERE namespace = ?2 AND key = ?3 AND {}",
+                    self.store.table, LIVE
+                ),
+                (now, &self.namespace, key),
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(expires_at.map(|expires_at| {
+            expires_at.map(|expires_at| Duration::from_millis((expires_at - now) as u64))
+        }))
+    }
+
+    /// Set or remove the expiry of a live key without changing its value
+    ///
+    /// # Returns
+    ///
+    /// Whether the key was found.
+    pub fn expire(&self, key: &str, ttl: Option<Duration>) -> Result<bool> {
+        let updated = self.store.db.connection().execute(
+            &format!(
+                "UPDATE {} SET expires_at = ?4 WHERE namespace = ?2 AND key = ?3 AND {}",
+                self.store.table, LIVE
+            ),
+            (now_millis(), &self.namespace, key, ttl.map(expiry)),
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Delete a key
+    ///
+    /// # Returns
+    ///
+    /// Whether a live value was deleted.
+    pub fn delete(&self, key: &str) -> Result<bool> {
+        let now = now_millis();
+        let expires_at: Option<Option<i64>> = self
+            .store
+            .db
+            .connection()
+            .query_row(
+                &format!(
+                    "DELETE FROM {} WHERE namespace = ?1 AND key = ?2 RETURNING expires_at",
+                    self.store.table
+                ),
+                (&self.namespace, key),
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(matches!(expires_at, Some(expires_at) if expires_at.is_none_or(|at| at > now)))
+    }
+
+    /// The live keys of the namespace, sorted
+    pub fn keys(&self) -> Result<Vec<String>> {
+        let connection = self.store.db.connection();
+        let mut statement = connection.prepare(&format!(
+            "SELECT key FROM {} WHERE namespace = ?2 AND {} ORDER BY key",
+            self.store.table, LIVE
+        ))?;
+        let rows = statement.query_map((now_millis(), &self.namespace), |row| row.get(0))?;
+        rows.collect()
+    }
+
+    /// The number of live keys in the namespace
+    pub fn len(&self) -> Result<u64> {
+        self.store.db.connection().query_row(
+            &format!(
+                "SELECT COUNT(*) FROM {} WHERE namespace = ?2 AND {}",
+                self.store.table, LIVE
+            ),
+            (now_millis(), &self.namespace),
+            |row| row.get(0),
+        )
+    }
+
+    /// Whether the namespace has no live key
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Delete every key of the namespace
+    ///
+    /// # Returns
+    ///
+    /// The number of deleted entries, expired ones included.
+    pub fn clear(&self) -> Result<usize> {
+        self.store.db.connection().execute(
+            &format!("DELETE FROM {} WHERE namespace = ?1", self.store.table),
+            [&self.namespace],
+        )
+    }
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as i64)
+}
+
+fn expiry(ttl: Duration) -> i64 {
+    now_millis().saturating_add(i64::try_from(ttl.as_millis()).unwrap_or(i64::MAX))
+}
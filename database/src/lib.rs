@@ -1,5 +1,41 @@
+mod error;
+pub use error::*;
+
 mod sqlite;
 pub use sqlite::*;
 
 mod postgres;
 pub use postgres::*;
+
+mod postgres_async;
+pub use postgres_async::*;
+
+mod repository;
+pub use repository::*;
+
+mod migrations;
+pub use migrations::*;
+
+mod csv_import;
+pub use csv_import::*;
+
+mod config;
+pub use config::*;
+
+mod pool;
+pub use pool::*;
+
+mod export;
+pub use export::*;
+
+mod search;
+pub use search::*;
+
+mod kv_store;
+pub use kv_store::*;
+
+mod audit;
+pub use audit::*;
+
+pub use rusqlite;
+pub use tokio_postgres;
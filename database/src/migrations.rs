@@ -0,0 +1,584 @@
+use crate::quote_identifier;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::{self, Display, Formatter, Write},
+    fs::{read_dir, read_to_string},
+    path::Path,
+};
+
+/// Default name of the table recording applied migrations.
+pub const MIGRATIONS_TABLE: &str = "schema_migrations";
+
+/// An error raised while planning or applying migrations
+#[derive(Debug)]
+pub enum MigrationError {
+    Sqlite(rusqlite::Error),
+    Postgres(postgres::Error),
+    Io(std::io::Error),
+    /// Two migrations share a version, or a migration file name is invalid.
+    Invalid(String),
+    /// An applied migration's script changed since it was applied.
+    ChecksumMismatch {
+        version: i64,
+        name: String,
+    },
+    /// The database records a migration that is not known anymore.
+    UnknownApplied {
+        version: i64,
+        name: String,
+    },
+    /// A migration has to be reverted but has no down script.
+    Irreversible {
+        version: i64,
+        name: String,
+    },
+}
+
+impl Display for MigrationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::Sqlite(e) => write!(f, "SQLite error: {}", e),
+            MigrationError::Postgres(e) => write!(f, "PostgreSQL error: {}", e),
+            MigrationError::Io(e) => write!(f, "I/O error: {}", e),
+            MigrationError::Invalid(message) => write!(f, "Invalid migrations: {}", message),
+            MigrationError::ChecksumMismatch { version, name } => write!(
+                f,
+                "Migration {} {} was modified after it was applied",
+                version, name
+            ),
+            MigrationError::UnknownApplied { version, name } => write!(
+                f,
+                "Applied migration {} {} is missing from the migrations",
+                version, name
+            ),
+            MigrationError::Irreversible { version, name } => {
+                write!(f, "Migration {} {} has no down script", version, name)
+            }
+        }
+    }
+}
+
+impl Error for MigrationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MigrationError::Sqlite(e) => Some(e),
+            MigrationError::Postgres(e) => Some(e),
+            MigrationError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for MigrationError {
+    fn from(e: rusqlite::Error) -> Self {
+        MigrationError::Sqlite(e)
+    }
+}
+
+impl From<postgres::Error> for MigrationError {
+    fn from(e: postgres::Error) -> Self {
+        MigrationError::Postgres(e)
+    }
+}
+
+impl From<std::io::Error> for MigrationError {
+    fn from(e: std::io::Error) -> Self {
+        MigrationError::Io(e)
+    }
+}
+
+/// A versioned schema change with an optional down script to revert it
+///
+/// # Examples
+///
+/// ```
+/// use database::Migration;
+///
+/// let migration = Migration::new(1, "create cats", "CREATE TABLE cats (name TEXT)")
+///     .with_down("DROP TABLE cats");
+/// assert_eq!(migration.checksum().len(), 64);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Migration {
+    pub version: i64,
+    pub name: String,
+    pub up: String,
+    pub down: Option<String>,
+}
+
+impl Migration {
+    /// Create an irreversible migration
+    pub fn new(version: i64, name: &str, up: &str) -> Self {
+        Migration {
+            version,
+            name: name.to_string(),
+            up: up.to_string(),
+            down: None,
+        }
+    }
+
+    /// Set the script that reverts the migration
+    pub fn with_down(mut self, down: &str) -> Self {
+        self.down = Some(down.to_string());
+        self
+    }
+
+    /// SHA-256 of the up script in hex, recorded when the migration is applied
+    pub fn checksum(&self) -> String {
+        Sha256::digest(self.up.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+/// A migration recorded in the migrations table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub name: String,
+    pub checksum: String,
+}
+
+/// Whether a step applies or reverts a migration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationDirection {
+    Up,
+    Down,
+}
+
+/// One step of a migration plan
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStep {
+    pub version: i64,
+    pub name: String,
+    pub direction: MigrationDirection,
+    pub sql: String,
+}
+
+/// A database the migrator can record and run migrations on
+///
+/// Implemented for `rusqlite::Connection` and `postgres::Client`.
+pub trait MigrationBackend {
+    /// Create the migrations table if it does not exist
+    fn ensure_migrations_table(&mut self, table: &str) -> Result<(), MigrationError>;
+
+    /// The applied migrations by ascending version, none if the table does not exist
+    fn applied_migrations(&mut self, table: &str) -> Result<Vec<AppliedMigration>, MigrationError>;
+
+    /// Run a step and record it in the migrations table, in one transaction
+    fn run_migration_step(
+        &mut self,
+        table: &str,
+        step: &MigrationStep,
+        checksum: &str,
+    ) -> Result<(), MigrationError>;
+}
+
+impl MigrationBackend for rusqlite::Connection {
+    fn ensure_migrations_table(&mut self, table: &str) -> Result<(), MigrationError> {
+        self.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                checksum TEXT NOT NULL,
+                applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            )",
+            quote_identifier(table)
+        ))?;
+        Ok(())
+    }
+
+    fn applied_migrations(&mut self, table: &str) -> Result<Vec<AppliedMigration>, MigrationError> {
+        let exists: bool = self.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+            [table],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            return Ok(Vec::new());
+        }
+
+        let mut statement = self.prepare(&format!(
+            "SELECT version, name, checksum FROM {} ORDER BY version",
+            quote_identifier(table)
+        ))?;
+        let applied = statement
+            .query_map([], |row| {
+                Ok(AppliedMigration {
+                    version: row.get(0)?,
+                    name: row.get(1)?,
+                    checksum: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(applied)
+    }
+
+    fn run_migration_step(
+        &mut self,
+        table: &str,
+        step: &MigrationStep,
+        checksum: &str,
+    ) -> Result<(), MigrationError> {
+        let transaction = self.transaction()?;
+        transaction.execute_batch(&step.sql)?;
+        match step.direction {
+            MigrationDirection::Up => transaction.execute(
+                &format!(
+                    "INSERT INTO {} (version, name, checksum) VALUES (?1, ?2, ?3)",
+                    quote_identifier(table)
+                ),
+                rusqlite::params![step.version, step.name, checksum],
+            )?,
+            MigrationDirection::Down => transaction.execute(
+                &format!("DELETE FROM {} WHERE version = ?1", quote_identifier(table)),
+                [step.version],
+            )?,
+        };
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+impl MigrationBackend for postgres::Client {
+    fn ensure_migrations_table(&mut self, table: &str) -> Result<(), MigrationError> {
+        self.batch_execute(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                version BIGINT PRIMARY KEY,
+                name TEXT NOT NULL,
+                checksum TEXT NOT NULL,
+                applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+            )",
+            quote_identifier(table)
+        ))?;
+        Ok(())
+    }
+
+    fn applied_migrations(&mut self, table: &str) -> Result<Vec<AppliedMigration>, MigrationError> {
+        let exists: bool = self
+            .query_one(
+                "SELECT to_regclass($1) IS NOT NULL",
+                &[&quote_identifier(table)],
+            )?
+            .get(0);
+        if !exists {
+            return Ok(Vec::new());
+        }
+
+        let rows = self.query(
+            &format!(
+                "SELECT version, name, checksum FROM {} ORDER BY version",
+                quote_identifier(table)
+            ),
+            &[],
+        )?;
+        Ok(rows
+            .iter()
+            .map(|row| AppliedMigration {
+                version: row.get(0),
+                name: row.get(1),
+                checksum: row.get(2),
+            })
+            .collect())
+    }
+
+    fn run_migration_step(
+        &mut self,
+        table: &str,
+        step: &MigrationStep,
+        checksum: &str,
+    ) -> Result<(), MigrationError> {
+        let mut transaction = self.transaction()?;
+        transaction.batch_execute(&step.sql)?;
+        match step.direction {
+            MigrationDirection::Up => transaction.execute(
+                &format!(
+                    "INSERT INTO {} (version, name, checksum) VALUES ($1, $2, $3)",
+                    quote_identifier(table)
+                ),
+                &[&step.version, &step.name, &checksum],
+            )?,
+            MigrationDirection::Down => transaction.execute(
+                &format!("DELETE FROM {} WHERE version = $1", quote_identifier(table)),
+                &[&step.version],
+            )?,
+        };
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+/// Plans and applies versioned migrations on SQLite or PostgreSQL
+///
+/// Each migration runs in its own transaction together with the update of
+/// the migrations table, so a failing script leaves the schema at the
+/// previous version. Before planning, the checksums of applied migrations
+/// are compared with the known scripts.
+///
+/// # Examples
+///
+/// ```
+/// use database::{Migration, Migrator};
+/// use rusqlite::Connection;
+///
+/// let migrator = Migrator::new(vec![
+///     Migration::new(1, "create cats", "CREATE TABLE cats (name TEXT NOT NULL)")
+///         .with_down("DROP TABLE cats"),
+///     Migration::new(2, "add color", "ALTER TABLE cats ADD COLUMN color TEXT")
+///         .with_down("ALTER TABLE cats DROP COLUMN color"),
+/// ])
+/// .unwrap();
+///
+/// let mut connection = Connection::open_in_memory().unwrap();
+/// assert_eq!(migrator.migrate(&mut connection).unwrap(), vec![1, 2]);
+/// assert_eq!(migrator.current_version(&mut connection).unwrap(), Some(2));
+///
+/// assert_eq!(migrator.migrate_to(&mut connection, 1).unwrap(), vec![2]);
+/// assert_eq!(migrator.current_version(&mut connection).unwrap(), Some(1));
+/// ```
+pub struct Migrator {
+    migrations: BTreeMap<i64, Migration>,
+    table: String,
+}
+
+impl Migrator {
+    /// Create a migrator, rejecting duplicate versions
+    pub fn new(migrations: Vec<Migration>) -> Result<Self, MigrationError> {
+        let mut by_version = BTreeMap::new();
+        for migration in migrations {
+            if let Some(previous) = by_version.insert(migration.version, migration) {
+                return Err(MigrationError::Invalid(format!(
+                    "Version {} is used more than once",
+                    previous.version
+                )));
+            }
+        }
+        Ok(Migrator {
+            migrations: by_version,
+            table: MIGRATIONS_TABLE.to_string(),
+        })
+    }
+
+    /// Load migrations from a directory
+    ///
+    /// Files are named `<version>_<name>.up.sql`, with an optional matching
+    /// `<version>_<name>.down.sql`, e.g. `0001_create_cats.up.sql`.
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self, MigrationError> {
+        let mut ups = BTreeMap::new();
+        let mut downs = BTreeMap::new();
+        for entry in read_dir(dir)? {
+            let path = entry?.path();
+            let file_name = match path.file_name().and_then(|name| name.to_str()) {
+                Some(file_name) => file_name.to_string(),
+                None => continue,
+            };
+            let (stem, scripts) = if let Some(stem) = file_name.strip_suffix(".up.sql") {
+                (stem, &mut ups)
+            } else if let Some(stem) = file_name.strip_suffix(".down.sql") {
+                (stem, &mut downs)
+            } else {
+                continue;
+            };
+
+            let (version, name) = stem
+                .split_once('_')
+                .and_then(|(version, name)| Some((version.parse::<i64>().ok()?, name)))
+                .ok_or_else(|| {
+                    MigrationError::Invalid(format!("Unexpected migration file {}", file_name))
+                })?;
+            if scripts
+                .insert(version, (name.to_string(), read_to_string(&path)?))
+                .is_some()
+            {
+                return Err(MigrationError::Invalid(format!(
+                    "Version {} is used more than once",
+                    version
+                )));
+            }
+        }
+
+        if let Some((version, (name, _))) =
+            downs.iter().find(|(version, _)| !ups.contains_key(version))
+        {
+            return Err(MigrationError::Invalid(format!(
+                "Down script {} {} has no up script",
+                version, name
+            )));
+        }
+
+        let migrations = ups
+            .into_iter()
+            .map(|(version, (name, up))| Migration {
+                version,
+                name: name.replace('_', " "),
+                up,
+                down: downs.remove(&version).map(|(_, down)| down),
+            })
+            .collect();
+        Migrator::new(migrations)
+    }
+
+    /// Use another table to record applied migrations
+    pub fn table(mut self, table: &str) -> Self {
+        self.table = table.to_string();
+        self
+    }
+
+    /// The known migrations, by ascending version
+    pub fn migrations(&self) -> impl Iterator<Item = &Migration> {
+        self.migrations.values()
+    }
+
+    /// The applied migrations after checking them against the known scripts
+    pub fn applied<B: MigrationBackend>(
+        &self,
+        backend: &mut B,
+    ) -> Result<Vec<AppliedMigration>, MigrationError> {
+        let applied = backend.applied_migrations(&self.table)?;
+        for record in &applied {
+            match self.migrations.get(&record.version) {
+                None => {
+                    return Err(MigrationError::UnknownApplied {
+                        version: record.version,
+                        name: record.name.clone(),
+                    })
+                }
+                Some(migration) if migration.checksum() != record.checksum => {
+                    return Err(MigrationError::ChecksumMismatch {
+                        version: record.version,
+                        name: record.name.clone(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(applied)
+    }
+
+    /// The highest applied version, `None` on a fresh database
+    pub fn current_version<B: MigrationBackend>(
+        &self,
+        backend: &mut B,
+    ) -> Result<Option<i64>, MigrationError> {
+        Ok(self.applied(backend)?.last().map(|record| record.version))
+    }
+
+    /// The steps that bring the database to `target`
+    ///
+    /// Pending migrations up to `target` are applied in ascending order and
+    /// applied migrations above it are reverted in descending order. A
+    /// target of 0 reverts every migration.
+    pub fn plan<B: MigrationBackend>(
+        &self,
+        backend: &mut B,
+        target: i64,
+    ) -> Result<Vec<MigrationStep>, MigrationError> {
+        let applied = self
+            .applied(backend)?
+            .into_iter()
+            .map(|record| record.version)
+            .collect::<Vec<_>>();
+
+        let mut steps = Vec::new();
+        for version in applied.iter().rev().filter(|version| **version > target) {
+            let migration = &self.migrations[version];
+            let down = migration
+                .down
+                .as_ref()
+                .ok_or_else(|| MigrationError::Irreversible {
+                    version: migration.version,
+                    name: migration.name.clone(),
+                })?;
+            steps.push(MigrationStep {
+                version: migration.version,
+                name: migration.name.clone(),
+                direction: MigrationDirection::Down,
+                sql: down.clone(),
+            });
+        }
+        steps.extend(
+            self.migrations
+                .range(..=target)
+                .filter(|(version, _)| !applied.contains(version))
+                .map(|(_, migration)| MigrationStep {
+                    version: migration.version,
+                    name: migration.name.clone(),
+                    direction: MigrationDirection::Up,
+                    sql: migration.up.clone(),
+                }),
+        );
+        Ok(steps)
+    }
+
+    /// Apply every pending migration
+    ///
+    /// # Returns
+    ///
+    /// The versions applied, in order.
+    pub fn migrate<B: MigrationBackend>(
+        &self,
+        backend: &mut B,
+    ) -> Result<Vec<i64>, MigrationError> {
+        self.migrate_to(backend, i64::MAX)
+    }
+
+    /// Apply or revert migrations until `target` is the current version
+    ///
+    /// # Returns
+    ///
+    /// The versions applied or reverted, in order.
+    pub fn migrate_to<B: MigrationBackend>(
+        &self,
+        backend: &mut B,
+        target: i64,
+    ) -> Result<Vec<i64>, MigrationError> {
+        let steps = self.plan(backend, target)?;
+        backend.ensure_migrations_table(&self.table)?;
+        for step in &steps {
+            let checksum = self.migrations[&step.version].checksum();
+            backend.run_migration_step(&self.table, step, &checksum)?;
+        }
+        Ok(steps.iter().map(|step| step.version).collect())
+    }
+
+    /// Revert the last `count` applied migrations
+    pub fn rollback<B: MigrationBackend>(
+        &self,
+        backend: &mut B,
+        count: usize,
+    ) -> Result<Vec<i64>, MigrationError> {
+        let applied = self.applied(backend)?;
+        let target = applied
+            .len()
+            .checked_sub(count + 1)
+            .map_or(0, |index| applied[index].version);
+        self.migrate_to(backend, target)
+    }
+
+    /// Describe the SQL `migrate_to` would run, without changing the database
+    pub fn dry_run<B: MigrationBackend>(
+        &self,
+        backend: &mut B,
+        target: i64,
+    ) -> Result<String, MigrationError> {
+        let mut output = String::new();
+        for step in self.plan(backend, target)? {
+            let direction = match step.direction {
+                MigrationDirection::Up => "up",
+                MigrationDirection::Down => "down",
+            };
+            let _ = writeln!(
+                output,
+                "-- {} {} ({})\n{}\n",
+                step.version,
+                step.name,
+                direction,
+                step.sql.trim_end()
+            );
+        }
+        Ok(output)
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{ConfigError, PoolConfig, PostgresConfig, SqliteConfig, TlsMode};
+use native_tls::TlsConnector;
+use postgres::{config::SslMode, Client};
+use postgres_native_tls::MakeTlsConnector;
+use r2d2::{ManageConnection, Pool, PooledConnection};
+use r2d2_postgres::PostgresConnectionManager;
+use rusqlite::Connection;
+use std::time::Duration;
+
+/// A pool of PostgreSQL connections.
+pub type PostgresPool = Pool<PostgresConnectionManager<MakeTlsConnector>>;
+
+/// A PostgreSQL connection checked out of a `PostgresPool`.
+pub type PooledPostgres = PooledConnection<PostgresConnectionManager<MakeTlsConnector>>;
+
+/// A pool of SQLite connections.
+pub type SqlitePool = Pool<SqliteConnectionManager>;
+
+/// A SQLite connection checked out of a `SqlitePool`.
+pub type PooledSqlite = PooledConnection<SqliteConnectionManager>;
+
+impl PostgresConfig {
+    /// The `postgres` connection settings
+    pub fn to_postgres_config(&self) -> postgres::Config {
+        let mut config = postgres::Config::new();
+        config
+            .host(&self.host)
+            .port(self.port)
+            .dbname(&self.dbname)
+            .user(&self.user)
+            .ssl_mode(match self.tls {
+                TlsMode::Disable => SslMode::Disable,
+                TlsMode::Prefer => SslMode::Prefer,
+                TlsMode::Require | TlsMode::VerifyFull => SslMode::Require,
+            });
+        if let Some(password) = &self.password {
+            config.password(password);
+        }
+        if let Some(seconds) = self.connect_timeout_secs {
+            config.connect_timeout(Duration::from_secs(seconds));
+        }
+        if let Some(application_name) = &self.application_name {
+            config.application_name(application_name);
+        }
+        config
+    }
+
+    /// The TLS connector matching the TLS mode
+    ///
+    /// Only `verify-full` checks the server certificate, like libpq.
+    pub fn tls_connector(&self) -> Result<MakeTlsConnector, ConfigError> {
+        let verify = self.tls == TlsMode::VerifyFull;
+        let connector = TlsConnector::builder()
+            .danger_accept_invalid_certs(!verify)
+            .danger_accept_invalid_hostnames(!verify)
+            .build()?;
+        Ok(MakeTlsConnector::new(connector))
+    }
+
+    /// Open a single connection
+    pub fn connect(&self) -> Result<Client, ConfigError> {
+        self.to_postgres_config()
+            .connect(self.tls_connector()?)
+            .map_err(ConfigError::Postgres)
+    }
+
+    /// Build a pool of connections
+    ///
+    /// The pool opens its idle connections right away, so an unreachable
+    /// server is reported here.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use database::{PoolConfig, PostgresConfig};
+    ///
+    /// let pool = PostgresConfig::default().pool(&PoolConfig::default()).unwrap();
+    /// let mut client = pool.get().unwrap();
+    /// let row = client.query_one("SELECT 1::INT", &[]).unwrap();
+    /// assert_eq!(row.get::<_, i32>(0), 1);
+    /// ```
+    pub fn pool(&self, pool: &PoolConfig) -> Result<PostgresPool, ConfigError> {
+        let manager =
+            PostgresConnectionManager::new(self.to_postgres_config(), self.tls_connector()?);
+        Ok(builder(pool).build(manager)?)
+    }
+}
+
+impl SqliteConfig {
+    /// Open a single connection with the configured pragmas
+    pub fn open(&self) -> Result<Connection, rusqlite::Error> {
+        let connection = Connection::open(&self.path)?;
+        self.apply_pragmas(&connection)?;
+        Ok(connection)
+    }
+
+    fn apply_pragmas(&self, connection: &Connection) -> Result<(), rusqlite::Error> {
+        connection.busy_timeout(Duration::from_millis(self.busy_timeout_ms))?;
+        if let Some(journal_mode) = self.journal_mode {
+            connection.pragma_update_and_check(
+                None,
+                "journal_mode",
+                journal_mode.as_str(),
+                |_| Ok(()),
+            )?;
+        }
+        if let Some(synchronous) = self.synchronous {
+            connection.pragma_update(None, "synchronous", synchronous.as_str())?;
+        }
+        connection.pragma_update(None, "foreign_keys", self.foreign_keys)
+    }
+
+    /// Build a pool of connections
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use database::{JournalMode, PoolConfig, SqliteConfig};
+    /// use std::fs::remove_file;
+    ///
+    /// let config = SqliteConfig {
+    ///     path: "pool_example.db".into(),
+    ///     journal_mode: Some(JournalMode::Wal),
+    ///     ..SqliteConfig::default()
+    /// };
+    /// let pool = config.pool(&PoolConfig { max_size: 2, ..PoolConfig::default() }).unwrap();
+    ///
+    /// let connection = pool.get().unwrap();
+    /// let mode: String = connection
+    ///     .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+    ///     .unwrap();
+    /// assert_eq!(mode, "wal");
+    ///
+    /// drop(connection);
+    /// drop(pool);
+    /// for suffix in ["", "-wal", "-shm"] {
+    ///     let _ = remove_file(format!("pool_example.db{}", suffix));
+    /// }
+    /// ```
+    pub fn pool(&self, pool: &PoolConfig) -> Result<SqlitePool, ConfigError> {
+        Ok(builder(pool).build(SqliteConnectionManager {
+            config: self.clone(),
+        })?)
+    }
+}
+
+fn builder<M: ManageConnection>(pool: &PoolConfig) -> r2d2::Builder<M> {
+    Pool::builder()
+        .max_size(pool.max_size)
+        .min_idle(pool.min_idle)
+        .idle_timeout(pool.idle_timeout())
+        .max_lifetime(pool.max_lifetime())
+        .connection_timeout(pool.connection_timeout())
+        .test_on_check_out(pool.test_on_check_out)
+}
+
+/// Opens SQLite connections for an r2d2 pool with the configured pragmas.
+#[derive(Debug)]
+pub struct SqliteConnectionManager {
+    config: SqliteConfig,
+}
+
+impl SqliteConnectionManager {
+    pub fn new(config: SqliteConfig) -> Self {
+        SqliteConnectionManager { config }
+    }
+}
+
+impl ManageConnection for SqliteConnectionManager {
+    type Connection = Connection;
+    type Error = rusqlite::Error;
+
+    fn connect(&self) -> Result<Connection, rusqlite::Error> {
+        self.config.open()
+    }
+
+    fn is_valid(&self, connection: &mut Connection) -> Result<(), rusqlite::Error> {
+        connection.execute_batch("SELECT 1")
+    }
+
+    /// A connection handed back inside a transaction is rolled back, and
+    /// dropped if that fails.
+    fn has_broken(&self, connection: &mut Connection) -> bool {
+        !connection.is_autocommit() && connection.execute_batch("ROLLBACK").is_err()
+    }
+}
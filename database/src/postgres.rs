@@ -1,6 +1,5 @@
-use csv::ReaderBuilder;
-use postgres::{Client, Error, NoTls};
-use std::fs::File;
+use crate::{CsvImporter, DatabaseError, ImportReport};
+use postgres::{Client, NoTls};
 
 /// Create a new database.
 ///
@@ -11,7 +10,8 @@ use std::fs::File;
 ///
 /// # Returns
 ///
-/// A `Result` indicating whether the operation was successful.
+/// A `Result` indicating whether the operation was successful, with
+/// `DatabaseError::AlreadyExists` if the database exists.
 ///
 /// # Example
 ///
@@ -24,22 +24,9 @@ use std::fs::File;
 /// let result = create_db(connection_string, db_name);
 /// assert!(result.is_ok());
 /// ```
-pub fn create_db(connection_string: &str, db_name: &str) -> Result<(), Error> {
+pub fn create_db(connection_string: &str, db_name: &str) -> Result<(), DatabaseError> {
     let mut client = Client::connect(connection_string, NoTls)?;
-
-    match client.batch_execute(&format!("CREATE DATABASE {}", db_name)) {
-        Ok(_) => println!("Database created successfully."),
-        Err(e) => {
-            if e.as_db_error()
-                .map_or(false, |e| e.code().code() == "42P04")
-            {
-                println!("Database already exists.");
-            } else {
-                return Err(e);
-            }
-        }
-    }
-
+    client.batch_execute(&format!("CREATE DATABASE {}", db_name))?;
     Ok(())
 }
 
@@ -52,7 +39,8 @@ pub fn create_db(connection_string: &str, db_name: &str) -> Result<(), Error> {
 ///
 /// # Returns
 ///
-/// A `Result` indicating whether the operation was successful.
+/// A `Result` indicating whether the operation was successful, with
+/// `DatabaseError::NotFound` if the database does not exist.
 ///
 /// # Example
 ///
@@ -65,22 +53,9 @@ pub fn create_db(connection_string: &str, db_name: &str) -> Result<(), Error> {
 /// let result = delete_db(connection_string, db_name);
 /// assert!(result.is_ok());
 /// ```
-pub fn delete_db(connection_string: &str, db_name: &str) -> Result<(), Error> {
+pub fn delete_db(connection_string: &str, db_name: &str) -> Result<(), DatabaseError> {
     let mut client = Client::connect(connection_string, NoTls)?;
-
-    match client.batch_execute(&format!("DROP DATABASE {}", db_name)) {
-        Ok(_) => println!("Database deleted successfully."),
-        Err(e) => {
-            if e.as_db_error()
-                .map_or(false, |e| e.code().code() == "3D000")
-            {
-                println!("Database does not exist.");
-            } else {
-                return Err(e);
-            }
-        }
-    }
-
+    client.batch_execute(&format!("DROP DATABASE {}", db_name))?;
     Ok(())
 }
 
@@ -118,7 +93,7 @@ pub fn delete_db(connection_string: &str, db_name: &str) -> Result<(), Error> {
 /// let result = add_tables(connection_string, tables);
 /// assert!(result.is_ok());
 /// ```
-pub fn add_tables(connection_string: &str, tables: Vec<&str>) -> Result<(), Error> {
+pub fn add_tables(connection_string: &str, tables: Vec<&str>) -> Result<(), DatabaseError> {
     let mut client = Client::connect(connection_string, NoTls)?;
 
     for table in tables {
@@ -173,7 +148,7 @@ pub fn add_tables(connection_string: &str, tables: Vec<&str>) -> Result<(), Erro
 /// let result = insert_data(&connection_string, data);
 /// assert!(result.is_ok());
 /// ```
-pub fn insert_data(connection_string: &str, data: Vec<&str>) -> Result<(), Error> {
+pub fn insert_data(connection_string: &str, data: Vec<&str>) -> Result<(), DatabaseError> {
     let mut client = Client::connect(connection_string, NoTls)?;
 
     for row in data {
@@ -185,15 +160,21 @@ pub fn insert_data(connection_string: &str, data: Vec<&str>) -> Result<(), Error
 
 /// Create a new database from a CSV file.
 ///
+/// An existing database is reused.
+///
+/// The table columns are named after the CSV headers and typed from the
+/// values, see `CsvImporter`, and the rows are loaded with `COPY`.
+///
 /// # Arguments
 ///
 /// * `connection_string` - A connection string to the PostgreSQL server.
 /// * `db` - The name of the database to create.
 /// * `file_path` - The path to the CSV file.
+/// * `table_name` - The name of the table to create.
 ///
 /// # Returns
 ///
-/// A `Result` indicating whether the operation was successful.
+/// A `Result` with the import report.
 ///
 /// # Example
 ///
@@ -213,45 +194,13 @@ pub fn create_db_from_csv(
     db: &str,
     file_path: &str,
     table_name: &str,
-) -> Result<(), Error> {
-    create_db(connection_string, db)?;
-
-    let file = File::open(file_path).unwrap();
-    let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(file);
-
-    let headers = rdr.headers().unwrap().clone();
-
-    let columns: Vec<String> = headers
-        .iter()
-        .map(|h| format!("{} VARCHAR NULL", h.replace(" ", "_")))
-        .collect();
-
-    let columns_clean: Vec<String> = headers.iter().map(|h| h.replace(" ", "_")).collect();
-
-    let create_table_query = format!(
-        "CREATE TABLE IF NOT EXISTS {} (id SERIAL PRIMARY KEY,{})",
-        table_name,
-        columns.join(", ")
-    );
+) -> Result<ImportReport, DatabaseError> {
+    match create_db(connection_string, db) {
+        Ok(()) | Err(DatabaseError::AlreadyExists(_)) => {}
+        Err(e) => return Err(e),
+    }
 
     let connection_string_full = format!("{}/{}", connection_string, db);
     let mut client = Client::connect(&connection_string_full, NoTls)?;
-    client.batch_execute(&create_table_query)?;
-
-    for result in rdr.records() {
-        let record = result.unwrap();
-        let values: Vec<String> = record
-            .iter()
-            .map(|v| format!("'{}'", v.replace("'", "''")))
-            .collect();
-        let insert_query = format!(
-            "INSERT INTO {} ({}) VALUES ({})",
-            table_name,
-            columns_clean.join(", "),
-            values.join(", ")
-        );
-        client.batch_execute(&insert_query)?;
-    }
-
-    Ok(())
+    CsvImporter::new(file_path).import_postgres(&mut client, table_name)
 }
@@ -0,0 +1,351 @@
+use crate::{quote_identifier, CsvImporter, DatabaseError, ImportReport};
+use futures_util::{
+    future::try_join_all,
+    stream::{poll_fn, Stream, StreamExt},
+};
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio_postgres::{types::ToSql, AsyncMessage, Client, NoTls, Notification, Row};
+
+/// Connect to PostgreSQL without TLS and drive the connection on a tokio task.
+///
+/// Must be called from a tokio runtime. The connection closes when the
+/// client is dropped.
+///
+/// # Arguments
+///
+/// * `connection_string` - A connection string to the PostgreSQL server.
+///
+/// # Returns
+///
+/// A `Result` with the connected client.
+///
+/// # Example
+///
+/// ```ignore
+/// use database::connect_async;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let client = connect_async("postgresql://postgres:@localhost").await.unwrap();
+///     let row = client.query_one("SELECT 1::INT", &[]).await.unwrap();
+///     assert_eq!(row.get::<_, i32>(0), 1);
+/// }
+/// ```
+pub async fn connect_async(connection_string: &str) -> Result<Client, DatabaseError> {
+    let (client, connection) = tokio_postgres::connect(connection_string, NoTls).await?;
+    tokio::spawn(async move {
+        // Errors of a broken connection surface on the client's next call.
+        let _ = connection.await;
+    });
+    Ok(client)
+}
+
+/// Create a new database, like `create_db`.
+///
+/// # Arguments
+///
+/// * `connection_string` - A connection string to the PostgreSQL server.
+/// * `db_name` - The name of the database to create.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful, with
+/// `DatabaseError::AlreadyExists` if the database exists.
+pub async fn create_db_async(connection_string: &str, db_name: &str) -> Result<(), DatabaseError> {
+    let client = connect_async(connection_string).await?;
+    client
+        .batch_execute(&format!("CREATE DATABASE {}", db_name))
+        .await?;
+    Ok(())
+}
+
+/// Delete an existing database, like `delete_db`.
+///
+/// # Arguments
+///
+/// * `connection_string` - A connection string to the PostgreSQL server.
+/// * `db_name` - The name of the database to delete.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful, with
+/// `DatabaseError::NotFound` if the database does not exist.
+pub async fn delete_db_async(connection_string: &str, db_name: &str) -> Result<(), DatabaseError> {
+    let client = connect_async(connection_string).await?;
+    client
+        .batch_execute(&format!("DROP DATABASE {}", db_name))
+        .await?;
+    Ok(())
+}
+
+/// Add tables to a database, like `add_tables`.
+///
+/// Tables are created one after the other, so later tables can reference
+/// earlier ones.
+///
+/// # Arguments
+///
+/// * `connection_string` - A connection string to the PostgreSQL server.
+/// * `tables` - A vector of strings representing the tables to create.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+pub async fn add_tables_async(
+    connection_string: &str,
+    tables: Vec<&str>,
+) -> Result<(), DatabaseError> {
+    let client = connect_async(connection_string).await?;
+
+    for table in tables {
+        client
+            .batch_execute(&format!("CREATE TABLE IF NOT EXISTS {}", table))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Insert data into a database, like `insert_data`.
+///
+/// The inserts are pipelined: they are all sent before waiting for the
+/// first answer, in one transaction, so either every row is inserted or
+/// none is.
+///
+/// # Arguments
+///
+/// * `connection_string` - A connection string to the PostgreSQL server.
+/// * `data` - A vector of strings representing the data to insert.
+///
+/// # Returns
+///
+/// A `Result` indicating whether the operation was successful.
+///
+/// # Example
+///
+/// ```ignore
+/// use database::insert_data_async;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let data = vec![
+///         "author (name, country) VALUES ('Octavio Paz', 'México')",
+///         "author (name, country) VALUES ('Jorge Luis Borges', 'Argentina')",
+///     ];
+///     let result = insert_data_async("postgresql://postgres:@localhost/test_db", data).await;
+///     assert!(result.is_ok());
+/// }
+/// ```
+pub async fn insert_data_async(
+    connection_string: &str,
+    data: Vec<&str>,
+) -> Result<(), DatabaseError> {
+    let mut client = connect_async(connection_string).await?;
+    let transaction = client.transaction().await?;
+
+    let statements: Vec<String> = data
+        .iter()
+        .map(|row| format!("INSERT INTO {}", row))
+        .collect();
+    try_join_all(
+        statements
+            .iter()
+            .map(|statement| transaction.batch_execute(statement)),
+    )
+    .await?;
+
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Create a new database from a CSV file, like `create_db_from_csv`.
+///
+/// An existing database is reused, as `create_db_from_csv` does, so the table
+/// can be added to a database created beforehand.
+///
+/// # Arguments
+///
+/// * `connection_string` - A connection string to the PostgreSQL server.
+/// * `db` - The name of the database to create.
+/// * `file_path` - The path to the CSV file.
+/// * `table_name` - The name of the table to create.
+///
+/// # Returns
+///
+/// A `Result` with the import report.
+pub async fn create_db_from_csv_async(
+    connection_string: &str,
+    db: &str,
+    file_path: &str,
+    table_name: &str,
+) -> Result<ImportReport, DatabaseError> {
+    match create_db_async(connection_string, db).await {
+        Ok(()) | Err(DatabaseError::AlreadyExists(_)) => {}
+        Err(e) => return Err(e),
+    }
+
+    let connection_string_full = format!("{}/{}", connection_string, db);
+    let mut client = connect_async(&connection_string_full).await?;
+    CsvImporter::new(file_path)
+        .import_postgres_async(&mut client, table_name)
+        .await
+}
+
+/// Run several queries in one round trip.
+///
+/// Every query is sent before the first answer is awaited, which saves a
+/// network round trip per query. The queries run in order but independently,
+/// so a failing query does not stop the later ones unless the client is in
+/// a transaction.
+///
+/// # Arguments
+///
+/// * `client` - The async client.
+/// * `queries` - The queries with their parameters.
+///
+/// # Returns
+///
+/// The rows of every query, in order.
+///
+/// # Example
+///
+/// ```ignore
+/// use database::{connect_async, query_pipelined};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let client = connect_async("postgresql://postgres:@localhost").await.unwrap();
+///     let results = query_pipelined(
+///         &client,
+///         &[("SELECT $1::INT", &[&1]), ("SELECT $1::TEXT", &[&"two"])],
+///     )
+///     .await
+///     .unwrap();
+///     assert_eq!(results[1][0].get::<_, &str>(0), "two");
+/// }
+/// ```
+pub async fn query_pipelined(
+    client: &Client,
+    queries: &[(&str, &[&(dyn ToSql + Sync)])],
+) -> Result<Vec<Vec<Row>>, DatabaseError> {
+    Ok(try_join_all(
+        queries
+            .iter()
+            .map(|(query, params)| client.query(*query, params)),
+    )
+    .await?)
+}
+
+/// Send a notification on a channel with `pg_notify`.
+///
+/// # Arguments
+///
+/// * `client` - The async client.
+/// * `channel` - The channel name.
+/// * `payload` - The notification payload.
+pub async fn notify_async(
+    client: &Client,
+    channel: &str,
+    payload: &str,
+) -> Result<(), DatabaseError> {
+    client
+        .execute("SELECT pg_notify($1, $2)", &[&channel, &payload])
+        .await?;
+    Ok(())
+}
+
+/// Open a dedicated connection listening to channels.
+///
+/// # Arguments
+///
+/// * `connection_string` - A connection string to the PostgreSQL server.
+/// * `channels` - The channels to `LISTEN` to.
+///
+/// # Returns
+///
+/// A `NotificationStream` of the notifications sent on the channels.
+///
+/// # Example
+///
+/// ```ignore
+/// use database::{connect_async, listen_async, notify_async};
+/// use futures_util::StreamExt;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let connection_string = "postgresql://postgres:@localhost";
+///     let mut notifications = listen_async(connection_string, &["cats"]).await.unwrap();
+///
+///     let client = connect_async(connection_string).await.unwrap();
+///     notify_async(&client, "cats", "Tom was fed").await.unwrap();
+///
+///     let notification = notifications.next().await.unwrap().unwrap();
+///     assert_eq!(notification.payload(), "Tom was fed");
+/// }
+/// ```
+pub async fn listen_async(
+    connection_string: &str,
+    channels: &[&str],
+) -> Result<NotificationStream, DatabaseError> {
+    let (client, mut connection) = tokio_postgres::connect(connection_string, NoTls).await?;
+    let (sender, receiver) = unbounded_channel();
+    tokio::spawn(async move {
+        let mut messages = poll_fn(move |cx| connection.poll_message(cx));
+        while let Some(message) = messages.next().await {
+            let notification = match message {
+                Ok(AsyncMessage::Notification(notification)) => Ok(notification),
+                Ok(_) => continue,
+                Err(e) => Err(DatabaseError::from(e)),
+            };
+            let failed = notification.is_err();
+            if sender.send(notification).is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    let stream = NotificationStream { client, receiver };
+    for channel in channels {
+        stream.listen(channel).await?;
+    }
+    Ok(stream)
+}
+
+/// Notifications received on a dedicated connection, as a `Stream`
+///
+/// The stream ends when the connection closes and yields an error first if
+/// it broke.
+pub struct NotificationStream {
+    client: Client,
+    receiver: UnboundedReceiver<Result<Notification, DatabaseError>>,
+}
+
+impl NotificationStream {
+    /// Start listening to a channel
+    pub async fn listen(&self, channel: &str) -> Result<(), DatabaseError> {
+        self.client
+            .batch_execute(&format!("LISTEN {}", quote_identifier(channel)))
+            .await?;
+        Ok(())
+    }
+
+    /// Stop listening to a channel
+    pub async fn unlisten(&self, channel: &str) -> Result<(), DatabaseError> {
+        self.client
+            .batch_execute(&format!("UNLISTEN {}", quote_identifier(channel)))
+            .await?;
+        Ok(())
+    }
+}
+
+impl Stream for NotificationStream {
+    type Item = Result<Notification, DatabaseError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().receiver.poll_recv(cx)
+    }
+}
@@ -0,0 +1,569 @@
+use crate::quote_identifier;
+use rusqlite::{
+    params_from_iter,
+    types::{ToSqlOutput, Value},
+    Connection, Error, Result, Row, ToSql,
+};
+use std::{
+    marker::PhantomData,
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// A SQLite connection shared by every repository of an application
+///
+/// Cloning the handle is cheap and every clone uses the same connection, so
+/// repositories of different entities see each other's writes.
+///
+/// # Examples
+///
+/// ```
+/// use database::Database;
+///
+/// let db = Database::open_in_memory().unwrap();
+/// db.connection().execute_batch("CREATE TABLE notes (body TEXT)").unwrap();
+///
+/// let other = db.clone();
+/// other.connection().execute("INSERT INTO notes VALUES ('hello')", []).unwrap();
+///
+/// let count: i64 = db
+///     .connection()
+///     .query_row("SELECT COUNT(*) FROM notes", [], |row| row.get(0))
+///     .unwrap();
+/// assert_eq!(count, 1);
+/// ```
+#[derive(Clone)]
+pub struct Database {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl Database {
+    /// Open or create a database file
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Database::from_connection(Connection::open(path)?))
+    }
+
+    /// Open a private in-memory database
+    pub fn open_in_memory() -> Result<Self> {
+        Ok(Database::from_connection(Connection::open_in_memory()?))
+    }
+
+    /// Share an already configured connection
+    pub fn from_connection(connection: Connection) -> Self {
+        Database {
+            connection: Arc::new(Mutex::new(connection)),
+        }
+    }
+
+    /// Lock the connection for the duration of the returned guard
+    pub fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A Rust struct stored as a row of a SQLite table
+///
+/// The table has an `INTEGER PRIMARY KEY` column named by `PRIMARY_KEY` and
+/// one column per entry of `COLUMNS`. Implement it by hand or with the
+/// `sqlite_entity!` macro.
+pub trait Entity: Sized {
+    /// The table name.
+    const TABLE: &'static str;
+    /// The `INTEGER PRIMARY KEY` column.
+    const PRIMARY_KEY: &'static str = "id";
+    /// The other columns as `(name, SQL type and constraints)`, in `values` order.
+    const COLUMNS: &'static [(&'static str, &'static str)];
+
+    /// The primary key, `None` before the entity is inserted
+    fn id(&self) -> Option<i64>;
+
+    /// Store the primary key assigned on insert
+    fn set_id(&mut self, id: i64);
+
+    /// The values of `COLUMNS`, in the same order
+    fn values(&self) -> Vec<&dyn ToSql>;
+
+    /// Build the entity from a row holding the primary key and every column
+    fn from_row(row: &Row) -> Result<Self>;
+}
+
+/// Implements `Entity` for a struct with an `Option<i64>` primary key field.
+///
+/// # Examples
+///
+/// ```
+/// use database::{sqlite_entity, Database, Repository};
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Cat {
+///     id: Option<i64>,
+///     name: String,
+///     color: String,
+/// }
+///
+/// sqlite_entity!(Cat, "cats", id, {
+///     name: "TEXT NOT NULL",
+///     color: "TEXT NOT NULL",
+/// });
+///
+/// let cats = Repository::<Cat>::new(&Database::open_in_memory().unwrap());
+/// cats.create_table().unwrap();
+///
+/// let mut cat = Cat { id: None, name: "Fluffy".to_string(), color: "White".to_string() };
+/// cats.insert(&mut cat).unwrap();
+/// assert_eq!(cats.get(cat.id.unwrap()).unwrap(), Some(cat));
+/// ```
+#[macro_export]
+macro_rules! sqlite_entity {
+    ($type:ty, $table:literal, $id:ident, { $($field:ident : $sql:literal),+ $(,)? }) => {
+        impl $crate::Entity for $type {
+            const TABLE: &'static str = $table;
+            const PRIMARY_KEY: &'static str = stringify!($id);
+            const COLUMNS: &'static [(&'static str, &'static str)] =
+                &[$((stringify!($field), $sql)),+];
+
+            fn id(&self) -> Option<i64> {
+                self.$id
+            }
+
+            fn set_id(&mut self, id: i64) {
+                self.$id = Some(id);
+            }
+
+            fn values(&self) -> Vec<&dyn $crate::rusqlite::ToSql> {
+                vec![$(&self.$field as &dyn $crate::rusqlite::ToSql),+]
+            }
+
+            fn from_row(row: &$crate::rusqlite::Row) -> $crate::rusqlite::Result<Self> {
+                Ok(Self {
+                    $id: row.get(stringify!($id))?,
+                    $($field: row.get(stringify!($field))?),+
+                })
+            }
+        }
+    };
+}
+
+/// A comparison of a `Query` filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Like,
+}
+
+impl Comparison {
+    fn sql(&self) -> &'static str {
+        match self {
+            Comparison::Eq => "=",
+            Comparison::Ne => "<>",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+            Comparison::Like => "LIKE",
+        }
+    }
+}
+
+/// A sort direction of a `Query`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Compare(String, Comparison, Value),
+    IsNull(String, bool),
+    In(String, Vec<Value>),
+}
+
+/// Filters, sorting and pagination for `Repository` lookups
+///
+/// Column names are checked against the entity's columns when the query
+/// runs and values are always bound as parameters, so a query never splices
+/// user input into SQL.
+///
+/// # Examples
+///
+/// ```
+/// use database::{Comparison, SortOrder, Query};
+///
+/// let query = Query::new()
+///     .filter("color", Comparison::Eq, "Black")
+///     .filter("age", Comparison::Ge, 2)
+///     .order_by("name", SortOrder::Asc)
+///     .page(2, 20);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    filters: Vec<Filter>,
+    order: Vec<(String, SortOrder)>,
+    limit: Option<u64>,
+    offset: Option<u64>,
+    error: Option<String>,
+}
+
+impl Query {
+    /// Create a query matching every row
+    pub fn new() -> Self {
+        Query::default()
+    }
+
+    /// Keep rows where `column op value`
+    pub fn filter<V: ToSql>(mut self, column: &str, op: Comparison, value: V) -> Self {
+        let value = self.value_of(&value);
+        self.filters
+            .push(Filter::Compare(column.to_string(), op, value));
+        self
+    }
+
+    /// Keep rows where the column is NULL, or is not NULL when `null` is false
+    pub fn filter_null(mut self, column: &str, null: bool) -> Self {
+        self.filters.push(Filter::IsNull(column.to_string(), null));
+        self
+    }
+
+    /// Keep rows where the column equals one of `values`
+    pub fn filter_in<V: ToSql, I: IntoIterator<Item = V>>(
+        mut self,
+        column: &str,
+        values: I,
+    ) -> Self {
+        let values = values
+            .into_iter()
+            .map(|value| self.value_of(&value))
+            .collect();
+        self.filters.push(Filter::In(column.to_string(), values));
+        self
+    }
+
+    /// Sort by a column, after any previous sort column
+    pub fn order_by(mut self, column: &str, order: SortOrder) -> Self {
+        self.order.push((column.to_string(), order));
+        self
+    }
+
+    /// Return at most `limit` rows
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Skip the first `offset` rows
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Return page `page` (starting at 1) of `per_page` rows
+    pub fn page(self, page: u64, per_page: u64) -> Self {
+        self.limit(per_page)
+            .offset(page.saturating_sub(1) * per_page)
+    }
+
+    /// Copy a parameter into an owned value, remembering the first failure.
+    fn value_of(&mut self, value: &dyn ToSql) -> Value {
+        match value.to_sql() {
+            Ok(ToSqlOutput::Borrowed(value)) => value.into(),
+            Ok(ToSqlOutput::Owned(value)) => value,
+            Ok(_) => Value::Null,
+            Err(e) => {
+                self.error.get_or_insert(e.to_string());
+                Value::Null
+            }
+        }
+    }
+
+    /// Build the `WHERE`, `ORDER BY` and `LIMIT` clauses for entity `T`.
+    fn clauses<T: Entity>(&self, paginate: bool) -> Result<(String, Vec<Value>)> {
+        if let Some(error) = &self.error {
+            return Err(Error::ToSqlConversionFailure(error.clone().into()));
+        }
+        let mut sql = String::new();
+        let mut params = Vec::new();
+
+        let conditions = self
+            .filters
+            .iter()
+            .map(|filter| match filter {
+                Filter::Compare(column, op, value) => {
+                    params.push(value.clone());
+                    Ok(format!("{} {} ?", column_of::<T>(column)?, op.sql()))
+                }
+                Filter::IsNull(column, null) => Ok(format!(
+                    "{} IS {}NULL",
+                    column_of::<T>(column)?,
+                    if *null { "" } else { "NOT " }
+                )),
+                Filter::In(column, values) if values.is_empty() => {
+                    column_of::<T>(column)?;
+                    Ok("0".to_string())
+                }
+                Filter::In(column, values) => {
+                    params.extend(values.iter().cloned());
+                    let placeholders = vec!["?"; values.len()].join(", ");
+                    Ok(format!("{} IN ({})", column_of::<T>(column)?, placeholders))
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+
+        if !paginate {
+            return Ok((sql, params));
+        }
+
+        let order = self
+            .order
+            .iter()
+            .map(|(column, order)| {
+                let direction = match order {
+                    SortOrder::Asc => "ASC",
+                    SortOrder::Desc => "DESC",
+                };
+                Ok(format!("{} {}", column_of::<T>(column)?, direction))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if !order.is_empty() {
+            sql.push_str(" ORDER BY ");
+            sql.push_str(&order.join(", "));
+        }
+
+        if self.limit.is_some() || self.offset.is_some() {
+            sql.push_str(" LIMIT ? OFFSET ?");
+            params.push(Value::Integer(
+                self.limit
+                    .map_or(-1, |limit| limit.min(i64::MAX as u64) as i64),
+            ));
+            params.push(Value::Integer(
+                self.offset.unwrap_or(0).min(i64::MAX as u64) as i64,
+            ));
+        }
+
+        Ok((sql, params))
+    }
+}
+
+/// Return the quoted column if it belongs to `T`, an error otherwise.
+fn column_of<T: Entity>(column: &str) -> Result<String> {
+    if column == T::PRIMARY_KEY || T::COLUMNS.iter().any(|(name, _)| *name == column) {
+        Ok(quote_identifier(column))
+    } else {
+        Err(Error::InvalidColumnName(column.to_string()))
+    }
+}
+
+fn select_columns<T: Entity>() -> String {
+    std::iter::once(T::PRIMARY_KEY)
+        .chain(T::COLUMNS.iter().map(|(name, _)| *name))
+        .map(quote_identifier)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// CRUD and queries for the table of entity `T`
+///
+/// # Examples
+///
+/// ```
+/// use database::{sqlite_entity, Database, Comparison, SortOrder, Query, Repository};
+///
+/// struct Book {
+///     id: Option<i64>,
+///     title: String,
+///     year: i64,
+/// }
+///
+/// sqlite_entity!(Book, "books", id, { title: "TEXT NOT NULL", year: "INTEGER NOT NULL" });
+///
+/// let books = Repository::<Book>::new(&Database::open_in_memory().unwrap());
+/// books.create_table().unwrap();
+/// for (title, year) in [("Dune", 1965), ("Neuromancer", 1984), ("Hyperion", 1989)] {
+///     let mut book = Book { id: None, title: title.to_string(), year };
+///     books.insert(&mut book).unwrap();
+/// }
+///
+/// let query = Query::new()
+///     .filter("year", Comparison::Gt, 1970)
+///     .order_by("year", SortOrder::Desc);
+/// let titles: Vec<String> = books.find(&query).unwrap().into_iter().map(|b| b.title).collect();
+/// assert_eq!(titles, vec!["Hyperion", "Neuromancer"]);
+/// ```
+pub struct Repository<T: Entity> {
+    db: Database,
+    entity: PhantomData<T>,
+}
+
+impl<T: Entity> Repository<T> {
+    /// Create a repository on a shared database
+    pub fn new(db: &Database) -> Self {
+        Repository {
+            db: db.clone(),
+            entity: PhantomData,
+        }
+    }
+
+    /// The shared database of the repository
+    pub fn database(&self) -> &Database {
+        &self.db
+    }
+
+    /// Create the table of the entity if it does not exist
+    pub fn create_table(&self) -> Result<()> {
+        let columns = T::COLUMNS
+            .iter()
+            .map(|(name, sql)| format!("{} {}", quote_identifier(name), sql))
+            .collect::<Vec<_>>();
+        self.db.connection().execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {} ({} INTEGER PRIMARY KEY, {})",
+            quote_identifier(T::TABLE),
+            quote_identifier(T::PRIMARY_KEY),
+            columns.join(", ")
+        ))
+    }
+
+    /// Insert an entity and store its new primary key in it
+    ///
+    /// An entity that already has a primary key is inserted with it.
+    pub fn insert(&self, entity: &mut T) -> Result<i64> {
+        let connection = self.db.connection();
+        let id = insert_with(&connection, entity)?;
+        entity.set_id(id);
+        Ok(id)
+    }
+
+    /// Insert many entities in a single transaction
+    ///
+    /// The new primary keys are stored in the entities only once the transaction
+    /// is committed, so a failed insert leaves every entity unchanged.
+    pub fn insert_all(&self, entities: &mut [T]) -> Result<Vec<i64>> {
+        let mut connection = self.db.connection();
+        let transaction = connection.transaction()?;
+        let ids = entities
+            .iter()
+            .map(|entity| insert_with(&transaction, entity))
+            .collect::<Result<Vec<_>>>()?;
+        transaction.commit()?;
+
+        for (entity, id) in entities.iter_mut().zip(&ids) {
+            entity.set_id(*id);
+        }
+        Ok(ids)
+    }
+
+    /// Fetch the entity with the given primary key
+    pub fn get(&self, id: i64) -> Result<Option<T>> {
+        self.find_one(&Query::new().filter(T::PRIMARY_KEY, Comparison::Eq, id))
+    }
+
+    /// Write every column of an inserted entity
+    ///
+    /// # Returns
+    ///
+    /// `false` if no row has the entity's primary key.
+    pub fn update(&self, entity: &T) -> Result<bool> {
+        let id = entity.id().ok_or(Error::QueryReturnedNoRows)?;
+        let assignments = T::COLUMNS
+            .iter()
+            .map(|(name, _)| format!("{} = ?", quote_identifier(name)))
+            .collect::<Vec<_>>();
+        let sql = format!(
+            "UPDATE {} SET {} WHERE {} = ?",
+            quote_identifier(T::TABLE),
+            assignments.join(", "),
+            quote_identifier(T::PRIMARY_KEY)
+        );
+
+        let mut params = entity.values();
+        params.push(&id);
+        let changed = self
+            .db
+            .connection()
+            .execute(&sql, params_from_iter(params))?;
+        Ok(changed > 0)
+    }
+
+    /// Delete the entity with the given primary key, returning whether it existed
+    pub fn delete(&self, id: i64) -> Result<bool> {
+        Ok(self.delete_where(&Query::new().filter(T::PRIMARY_KEY, Comparison::Eq, id))? > 0)
+    }
+
+    /// Delete every row matching the query's filters, returning how many were deleted
+    pub fn delete_where(&self, query: &Query) -> Result<usize> {
+        let (clauses, params) = query.clauses::<T>(false)?;
+        let sql = format!("DELETE FROM {}{}", quote_identifier(T::TABLE), clauses);
+        self.db.connection().execute(&sql, params_from_iter(params))
+    }
+
+    /// Fetch every entity
+    pub fn all(&self) -> Result<Vec<T>> {
+        self.find(&Query::new().order_by(T::PRIMARY_KEY, SortOrder::Asc))
+    }
+
+    /// Fetch the entities matching a query
+    pub fn find(&self, query: &Query) -> Result<Vec<T>> {
+        let (clauses, params) = query.clauses::<T>(true)?;
+        let sql = format!(
+            "SELECT {} FROM {}{}",
+            select_columns::<T>(),
+            quote_identifier(T::TABLE),
+            clauses
+        );
+
+        let connection = self.db.connection();
+        let mut statement = connection.prepare(&sql)?;
+        let rows = statement.query_map(params_from_iter(params), |row| T::from_row(row))?;
+        rows.collect()
+    }
+
+    /// Fetch the first entity matching a query
+    pub fn find_one(&self, query: &Query) -> Result<Option<T>> {
+        Ok(self.find(&query.clone().limit(1))?.pop())
+    }
+
+    /// Count the rows matching the query's filters
+    pub fn count(&self, query: &Query) -> Result<u64> {
+        let (clauses, params) = query.clauses::<T>(false)?;
+        let sql = format!(
+            "SELECT COUNT(*) FROM {}{}",
+            quote_identifier(T::TABLE),
+            clauses
+        );
+        self.db
+            .connection()
+            .query_row(&sql, params_from_iter(params), |row| row.get(0))
+    }
+}
+
+fn insert_with<T: Entity>(connection: &Connection, entity: &T) -> Result<i64> {
+    let mut columns = T::COLUMNS
+        .iter()
+        .map(|(name, _)| quote_identifier(name))
+        .collect::<Vec<_>>();
+    let mut params = entity.values();
+    let id = entity.id();
+    if let Some(id) = &id {
+        columns.push(quote_identifier(T::PRIMARY_KEY));
+        params.push(id);
+    }
+
+    let sql = format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        quote_identifier(T::TABLE),
+        columns.join(", "),
+        vec!["?"; columns.len()].join(", ")
+    );
+    connection.execute(&sql, params_from_iter(params))?;
+    Ok(connection.last_insert_rowid())
+}
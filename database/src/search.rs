@@ -0,0 +1,368 @@
+use crate::{quote_identifier, Database};
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+/// A FTS5 full-text index over columns of a SQLite table
+///
+/// The index is an external content FTS5 table: it stores only the search
+/// terms and reads the text back from the indexed table. Triggers on the
+/// indexed table keep it up to date on every insert, update and delete.
+///
+/// # Examples
+///
+/// ```
+/// use database::{Database, SearchIndex, SearchQuery};
+///
+/// let db = Database::open_in_memory().unwrap();
+/// db.connection()
+///     .execute_batch(
+///         "CREATE TABLE notes (id INTEGER PRIMARY KEY, title TEXT, body TEXT);
+///          INSERT INTO notes (title, body) VALUES ('Groceries', 'Milk, eggs and cat food');",
+///     )
+///     .unwrap();
+///
+/// let index = SearchIndex::new(&db, "notes_search", "notes", &["title", "body"]);
+/// index.create().unwrap();
+///
+/// db.connection()
+///     .execute("INSERT INTO notes (title, body) VALUES ('Vet', 'Take the cat in')", [])
+///     .unwrap();
+///
+/// let hits = index.search(&SearchQuery::new("cat")).unwrap();
+/// assert_eq!(hits.len(), 2);
+/// assert_eq!(hits[0].highlights[1], "Take the [cat] in");
+/// ```
+#[derive(Clone)]
+pub struct SearchIndex {
+    db: Database,
+    name: String,
+    table: String,
+    columns: Vec<String>,
+    content_rowid: String,
+    tokenizer: Option<String>,
+}
+
+impl SearchIndex {
+    /// Describe an index named `name` over `columns` of `table`
+    ///
+    /// # Arguments
+    ///
+    /// * `db` - The database holding the table.
+    /// * `name` - The name of the FTS5 table.
+    /// * `table` - The indexed table.
+    /// * `columns` - The indexed text columns.
+    pub fn new(db: &Database, name: &str, table: &str, columns: &[&str]) -> Self {
+        SearchIndex {
+            db: db.clone(),
+            name: name.to_string(),
+            table: table.to_string(),
+            columns: columns.iter().map(|column| column.to_string()).collect(),
+            content_rowid: "rowid".to_string(),
+            tokenizer: None,
+        }
+    }
+
+    /// Use an `INTEGER PRIMARY KEY` column as the row id, `rowid` by default
+    pub fn content_rowid(mut self, column: &str) -> Self {
+        self.content_rowid = column.to_string();
+        self
+    }
+
+    /// Set the FTS5 tokenizer, e.g. `porter unicode61`
+    pub fn tokenizer(mut self, tokenizer: &str) -> Self {
+        self.tokenizer = Some(tokenizer.to_string());
+        self
+    }
+
+    /// The name of the FTS5 table
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Create the index and its triggers if they don't exist
+    ///
+    /// A new index is filled with the rows already in the table.
+    pub fn create(&self) -> Result<()> {
+        let connection = self.db.connection();
+        let transaction = connection.unchecked_transaction()?;
+        let exists = transaction
+            .query_row(
+                "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+                [&self.name],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+
+        let mut options = vec![
+            format!("content={}", quote_string(&self.table)),
+            format!("content_rowid={}", quote_string(&self.content_rowid)),
+        ];
+        if let Some(tokenizer) = &self.tokenizer {
+            options.push(format!("tokenize={}", quote_string(tokenizer)));
+        }
+        let name = quote_identifier(&self.name);
+        let table = quote_identifier(&self.table);
+        let rowid = quote_identifier(&self.content_rowid);
+        let columns = self.quoted_columns("");
+        let new_values = self.quoted_columns("new.");
+        let old_values = self.quoted_columns("old.");
+        let insert_new =
+            format!("INSERT INTO {name} (rowid, {columns}) VALUES (new.{rowid}, {new_values});");
+        let delete_old = format!(
+            "INSERT INTO {name} ({name}, rowid, {columns}) VALUES ('delete', old.{rowid}, {old_values});"
+        );
+
+        transaction.execute_batch(&format!(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS {name} USING fts5({columns}, {options});
+             CREATE TRIGGER IF NOT EXISTS {insert_trigger} AFTER INSERT ON {table} BEGIN
+                 {insert_new}
+             END;
+             CREATE TRIGGER IF NOT EXISTS {delete_trigger} AFTER DELETE ON {table} BEGIN
+                 {delete_old}
+             END;
+             CREATE TRIGGER IF NOT EXISTS {update_trigger} AFTER UPDATE ON {table} BEGIN
+                 {delete_old}
+                 {insert_new}
+             END;",
+            options = options.join(", "),
+            insert_trigger = self.trigger("ai"),
+            delete_trigger = self.trigger("ad"),
+            update_trigger = self.trigger("au"),
+        ))?;
+        if !exists {
+            rebuild(&transaction, &self.name)?;
+        }
+        transaction.commit()
+    }
+
+    /// Drop the index and its triggers, leaving the table untouched
+    pub fn drop_index(&self) -> Result<()> {
+        self.db.connection().execute_batch(&format!(
+            "DROP TRIGGER IF EXISTS {};
+             DROP TRIGGER IF EXISTS {};
+             DROP TRIGGER IF EXISTS {};
+             DROP TABLE IF EXISTS {};",
+            self.trigger("ai"),
+            self.trigger("ad"),
+            self.trigger("au"),
+            quote_identifier(&self.name),
+        ))
+    }
+
+    /// Re-index every row, e.g. after the table was changed with triggers disabled
+    pub fn rebuild(&self) -> Result<()> {
+        rebuild(&self.db.connection(), &self.name)
+    }
+
+    /// Merge the index segments to speed up later searches
+    pub fn optimize(&self) -> Result<()> {
+        let name = quote_identifier(&self.name);
+        self.db
+            .connection()
+            .execute(
+                &format!("INSERT INTO {name} ({name}) VALUES ('optimize')"),
+                [],
+            )
+            .map(|_| ())
+    }
+
+    /// Find the rows matching a query, best match first
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The FTS5 query with its snippet and paging options.
+    ///
+    /// # Returns
+    ///
+    /// A hit per matching row with its rank, snippet and highlighted columns.
+    pub fn search(&self, query: &SearchQuery) -> Result<Vec<SearchHit>> {
+        let name = quote_identifier(&self.name);
+        let highlights: Vec<String> = (0..self.columns.len())
+            .map(|index| format!("highlight({name}, {index}, ?2, ?3)"))
+            .collect();
+        let weights: String = query
+            .weights
+            .iter()
+            .map(|weight| format!(", {}", weight))
+            .collect();
+        let sql = format!(
+            "SELECT rowid, bm25({name}{weights}) AS score,
+                    snippet({name}, -1, ?2, ?3, ?4, ?5), {highlights}
+             FROM {name} WHERE {name} MATCH ?1
+             ORDER BY score LIMIT ?6 OFFSET ?7",
+            highlights = highlights.join(", "),
+        );
+
+        let connection = self.db.connection();
+        let mut statement = connection.prepare(&sql)?;
+        let rows = statement.query_map(
+            params![
+                query.terms,
+                query.open,
+                query.close,
+                query.ellipsis,
+                query.snippet_tokens,
+                query.limit.map_or(-1, |limit| limit as i64),
+                query.offset as i64,
+            ],
+            |row| {
+                let highlights = (0..self.columns.len())
+                    .map(|index| Ok(row.get::<_, Option<String>>(3 + index)?.unwrap_or_default()))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(SearchHit {
+                    rowid: row.get(0)?,
+                    score: row.get(1)?,
+                    snippet: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                    highlights,
+                })
+            },
+        )?;
+        rows.collect()
+    }
+
+    /// Count the rows matching an FTS5 query
+    pub fn count(&self, terms: &str) -> Result<u64> {
+        let name = quote_identifier(&self.name);
+        self.db.connection().query_row(
+            &format!("SELECT COUNT(*) FROM {name} WHERE {name} MATCH ?1"),
+            [terms],
+            |row| row.get(0),
+        )
+    }
+
+    fn trigger(&self, suffix: &str) -> String {
+        quote_identifier(&format!("{}_{}", self.name, suffix))
+    }
+
+    fn quoted_columns(&self, prefix: &str) -> String {
+        self.columns
+            .iter()
+            .map(|column| format!("{}{}", prefix, quote_identifier(column)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn rebuild(connection: &Connection, name: &str) -> Result<()> {
+    let name = quote_identifier(name);
+    connection
+        .execute(
+            &format!("INSERT INTO {name} ({name}) VALUES ('rebuild')"),
+            [],
+        )
+        .map(|_| ())
+}
+
+fn quote_string(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Quote text as a single FTS5 phrase, so user input can't use query syntax
+///
+/// # Example
+///
+/// ```
+/// use database::fts5_phrase;
+///
+/// assert_eq!(fts5_phrase("say \"hi\" OR"), "\"say \"\"hi\"\" OR\"");
+/// ```
+pub fn fts5_phrase(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+/// An FTS5 query with snippet, ranking and paging options
+///
+/// # Examples
+///
+/// ```
+/// use database::SearchQuery;
+///
+/// let query = SearchQuery::new("cat* NOT dog")
+///     .markers("<b>", "</b>")
+///     .snippet_tokens(8)
+///     .weights(&[10.0, 1.0])
+///     .page(1, 20);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchQuery {
+    terms: String,
+    open: String,
+    close: String,
+    ellipsis: String,
+    snippet_tokens: i64,
+    weights: Vec<f64>,
+    limit: Option<u64>,
+    offset: u64,
+}
+
+impl SearchQuery {
+    /// Create a query from FTS5 query syntax
+    pub fn new(terms: &str) -> Self {
+        SearchQuery {
+            terms: terms.to_string(),
+            open: "[".to_string(),
+            close: "]".to_string(),
+            ellipsis: "...".to_string(),
+            snippet_tokens: 16,
+            weights: Vec::new(),
+            limit: None,
+            offset: 0,
+        }
+    }
+
+    /// Surround matched terms with `open` and `close`, `[` and `]` by default
+    pub fn markers(mut self, open: &str, close: &str) -> Self {
+        self.open = open.to_string();
+        self.close = close.to_string();
+        self
+    }
+
+    /// Mark text left out of a snippet with `ellipsis`, `...` by default
+    pub fn ellipsis(mut self, ellipsis: &str) -> Self {
+        self.ellipsis = ellipsis.to_string();
+        self
+    }
+
+    /// Return snippets of at most `tokens` tokens (1 to 64), 16 by default
+    pub fn snippet_tokens(mut self, tokens: u8) -> Self {
+        self.snippet_tokens = i64::from(tokens.clamp(1, 64));
+        self
+    }
+
+    /// Weigh matches in each column, in index column order, for ranking
+    pub fn weights(mut self, weights: &[f64]) -> Self {
+        self.weights = weights.to_vec();
+        self
+    }
+
+    /// Return at most `limit` hits
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Skip the first `offset` hits
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Return page `page` (starting at 1) of `per_page` hits
+    pub fn page(self, page: u64, per_page: u64) -> Self {
+        self.limit(per_page)
+            .offset(page.saturating_sub(1) * per_page)
+    }
+}
+
+/// A row matching a `SearchQuery`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    /// The row id of the matching row in the indexed table.
+    pub rowid: i64,
+    /// The BM25 score, lower is better.
+    pub score: f64,
+    /// A short extract of the best matching column.
+    pub snippet: String,
+    /// Every indexed column with the matched terms marked, in index order.
+    pub highlights: Vec<String>,
+}
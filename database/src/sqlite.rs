@@ -1,6 +1,7 @@
-use rusqlite::{params, Connection, Params, Result};
+use crate::{BackendError, DatabaseError};
+use rusqlite::{params, Connection, ErrorCode, Params, Result};
 use shared::Cat;
-use std::{collections::HashMap, error::Error};
+use std::{collections::HashMap, error::Error, ops::Deref, thread::sleep, time::Duration};
 
 /// Create a SQLite database with two tables: cat_colors and cats.
 ///
@@ -17,7 +18,7 @@ use std::{collections::HashMap, error::Error};
 /// let result = create_sqlite_database(database);
 /// assert!(result.is_ok());
 /// ```
-pub fn create_sqlite_cats_database(database: &str) -> Result<(), Box<dyn Error>> {
+pub fn create_sqlite_cats_database(database: &str) -> Result<(), DatabaseError> {
     let conn = Connection::open(database)?;
 
     conn.execute(
@@ -55,7 +56,7 @@ pub fn create_sqlite_cats_database(database: &str) -> Result<(), Box<dyn Error>>
 /// let result = delete_cats_database(database);
 /// assert!(result.is_ok());
 /// ```
-pub fn delete_cats_database(database: &str) -> Result<(), Box<dyn Error>> {
+pub fn delete_cats_database(database: &str) -> Result<(), DatabaseError> {
     let conn = Connection::open(database)?;
 
     conn.execute("DROP TABLE IF EXISTS cats;", params![])?;
@@ -64,7 +65,7 @@ pub fn delete_cats_database(database: &str) -> Result<(), Box<dyn Error>> {
     create_sqlite_cats_database(database)
 }
 
-pub fn insert_select_cats(database: &str, cats: &Vec<Cat>) -> Result<Vec<Cat>, Box<dyn Error>> {
+pub fn insert_select_cats(database: &str, cats: &Vec<Cat>) -> Result<Vec<Cat>, DatabaseError> {
     let conn = Connection::open(database)?;
 
     let mut cat_colors = HashMap::new();
@@ -105,7 +106,7 @@ pub fn insert_select_cats(database: &str, cats: &Vec<Cat>) -> Result<Vec<Cat>, B
         })?
         .collect();
 
-    cats_result.map_err(|e| Box::new(e) as Box<dyn Error>)
+    cats_result.map_err(DatabaseError::from)
 }
 
 /// SQLite transaction types.
@@ -161,8 +162,8 @@ pub fn submit_db_transaction<P: Params>(
     query: &str,
     params: P,
     tx_type: TransactionType,
-) -> Result<(), Box<dyn Error>> {
-    let mut conn = Connection::open(database).unwrap();
+) -> Result<(), DatabaseError> {
+    let mut conn = Connection::open(database)?;
     let tx = conn.transaction()?;
 
     tx.execute(query, params)?;
@@ -172,5 +173,271 @@ pub fn submit_db_transaction<P: Params>(
         TransactionType::Rollback => tx.rollback(),
     };
 
-    result.map_err(|e| Box::new(e) as Box<dyn Error>)
+    result.map_err(DatabaseError::from)
+}
+
+/// How a transaction acquires its locks.
+///
+/// # Deferred
+///
+/// Take locks on the first read or write.
+///
+/// # Immediate
+///
+/// Take the write lock when the transaction begins.
+///
+/// # Exclusive
+///
+/// Take the write lock and, outside WAL mode, keep readers out too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TransactionMode {
+    #[default]
+    Deferred,
+    Immediate,
+    Exclusive,
+}
+
+impl TransactionMode {
+    fn begin_sql(&self) -> &'static str {
+        match self {
+            TransactionMode::Deferred => "BEGIN DEFERRED",
+            TransactionMode::Immediate => "BEGIN IMMEDIATE",
+            TransactionMode::Exclusive => "BEGIN EXCLUSIVE",
+        }
+    }
+}
+
+/// How often and how long to wait before running a transaction again when
+/// the database is busy.
+///
+/// The wait doubles after every attempt, starting at `initial_backoff` and
+/// capped at `max_backoff`.
+///
+/// # Examples
+///
+/// ```
+/// use database::RetryPolicy;
+/// use std::time::Duration;
+///
+/// let retry = RetryPolicy {
+///     max_retries: 10,
+///     initial_backoff: Duration::from_millis(5),
+///     max_backoff: Duration::from_millis(200),
+/// };
+/// assert_eq!(retry.backoff(0), Duration::from_millis(5));
+/// assert_eq!(retry.backoff(3), Duration::from_millis(40));
+/// assert_eq!(retry.backoff(8), Duration::from_millis(200));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_retries: 0,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// The wait before retry number `attempt`, counting from 0
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff)
+    }
+}
+
+/// Check whether an error is SQLite reporting a busy or locked database.
+///
+/// # Arguments
+///
+/// * `error` - The error to check.
+///
+/// # Returns
+///
+/// `true` for `SQLITE_BUSY` and `SQLITE_LOCKED`, which are worth retrying,
+/// whether raw or wrapped in a `DatabaseError`.
+pub fn is_busy_error(error: &(dyn Error + 'static)) -> bool {
+    if let Some(DatabaseError::SerializationFailure(BackendError::Sqlite(_))) =
+        error.downcast_ref::<DatabaseError>()
+    {
+        return true;
+    }
+    matches!(
+        error
+            .downcast_ref::<rusqlite::Error>()
+            .and_then(|e| e.sqlite_error_code()),
+        Some(ErrorCode::DatabaseBusy) | Some(ErrorCode::DatabaseLocked)
+    )
+}
+
+/// Runs `sql` when dropped unless disarmed, so that errors and panics both
+/// undo the open transaction or savepoint.
+struct RollbackGuard<'c> {
+    connection: &'c Connection,
+    sql: Option<String>,
+}
+
+impl RollbackGuard<'_> {
+    fn disarm(&mut self) {
+        self.sql = None;
+    }
+}
+
+impl Drop for RollbackGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(sql) = self.sql.take() {
+            let _ = self.connection.execute_batch(&sql);
+        }
+    }
+}
+
+/// The connection inside a transaction or savepoint started by `with_transaction`.
+///
+/// Dereferences to the `Connection`, so statements run as usual.
+pub struct TransactionScope<'c> {
+    connection: &'c Connection,
+    depth: usize,
+}
+
+impl Deref for TransactionScope<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.connection
+    }
+}
+
+impl TransactionScope<'_> {
+    /// The number of savepoints enclosing this scope, 0 for the transaction itself
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Run `work` in a nested savepoint.
+    ///
+    /// The savepoint is released when `work` returns `Ok` and rolled back
+    /// when it returns `Err` or panics, leaving the enclosing transaction
+    /// open either way.
+    pub fn savepoint<T, F>(&self, work: F) -> Result<T, DatabaseError>
+    where
+        F: FnOnce(&TransactionScope) -> Result<T, DatabaseError>,
+    {
+        let depth = self.depth + 1;
+        let name = format!("sp_{}", depth);
+        self.connection
+            .execute_batch(&format!("SAVEPOINT {}", name))?;
+        let mut guard = RollbackGuard {
+            connection: self.connection,
+            sql: Some(format!("ROLLBACK TO {0}; RELEASE {0}", name)),
+        };
+
+        let value = work(&TransactionScope {
+            connection: self.connection,
+            depth,
+        })?;
+        self.connection
+            .execute_batch(&format!("RELEASE {}", name))?;
+        guard.disarm();
+        Ok(value)
+    }
+}
+
+/// Run `work` in a transaction.
+///
+/// The transaction commits when `work` returns `Ok` and rolls back when it
+/// returns `Err` or panics. When SQLite reports the database as busy, while
+/// beginning, working or committing, the whole transaction is rolled back and
+/// `work` runs again after the policy's backoff.
+///
+/// # Arguments
+///
+/// * `connection` - The SQLite connection.
+/// * `mode` - When the transaction takes its locks.
+/// * `retry` - How to retry when the database is busy.
+/// * `work` - The statements to run, possibly more than once.
+///
+/// # Examples
+///
+/// ```
+/// use database::*;
+/// use rusqlite::Connection;
+///
+/// let connection = Connection::open_in_memory().unwrap();
+/// connection.execute_batch("CREATE TABLE cats (name TEXT)").unwrap();
+///
+/// let count = with_transaction(
+///     &connection,
+///     TransactionMode::Immediate,
+///     &RetryPolicy::default(),
+///     |tx| {
+///         tx.execute("INSERT INTO cats VALUES ('Tom')", [])?;
+///         let nested: Result<(), _> = tx.savepoint(|sp| {
+///             sp.execute("INSERT INTO cats VALUES ('Ghost')", [])?;
+///             Err(DatabaseError::Invalid("changed my mind".to_string()))
+///         });
+///         assert!(nested.is_err());
+///         Ok(tx.query_row("SELECT COUNT(*) FROM cats", [], |row| row.get::<_, i64>(0))?)
+///     },
+/// )
+/// .unwrap();
+/// assert_eq!(count, 1);
+/// ```
+pub fn with_transaction<T, F>(
+    connection: &Connection,
+    mode: TransactionMode,
+    retry: &RetryPolicy,
+    mut work: F,
+) -> Result<T, DatabaseError>
+where
+    F: FnMut(&TransactionScope) -> Result<T, DatabaseError>,
+{
+    let mut attempt = 0;
+    loop {
+        match run_transaction(connection, mode, &mut work) {
+            Err(e) if attempt < retry.max_retries && e.is_retryable() => {
+                sleep(retry.backoff(attempt));
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn run_transaction<T, F>(
+    connection: &Connection,
+    mode: TransactionMode,
+    work: &mut F,
+) -> Result<T, DatabaseError>
+where
+    F: FnMut(&TransactionScope) -> Result<T, DatabaseError>,
+{
+    connection.execute_batch(mode.begin_sql())?;
+    let mut guard = RollbackGuard {
+        connection,
+        sql: Some("ROLLBACK".to_string()),
+    };
+
+    let value = work(&TransactionScope {
+        connection,
+        depth: 0,
+    })?;
+    connection.execute_batch("COMMIT")?;
+    guard.disarm();
+    Ok(value)
 }
@@ -0,0 +1,35 @@
+// Each test binary uses only some of the fixtures.
+#![allow(dead_code)]
+
+use rusqlite::Connection;
+
+/// An in-memory database with foreign keys on, the color Black and no cats yet
+pub fn fixture_cats_connection() -> Connection {
+    let connection = Connection::open_in_memory().unwrap();
+    connection
+        .execute_batch(
+            "PRAGMA foreign_keys = ON;
+             CREATE TABLE colors (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE);
+             CREATE TABLE cats (
+                 name TEXT NOT NULL CHECK (length(name) > 0),
+                 age INTEGER,
+                 weight REAL,
+                 photo BLOB,
+                 color_id INTEGER REFERENCES colors(id)
+             );
+             INSERT INTO colors (id, name) VALUES (1, 'Black');",
+        )
+        .unwrap();
+    connection
+}
+
+/// Add Tom, Felix and Luna, with a mix of nulls, quotes and a blob
+pub fn fixture_insert_cats(connection: &Connection) {
+    connection
+        .execute_batch(
+            "INSERT INTO cats VALUES ('Tom', 7, 4.5, x'cafe', 1);
+             INSERT INTO cats VALUES ('Felix, the \"cat\"', NULL, 3.0, NULL, NULL);
+             INSERT INTO cats VALUES ('Luna', 4, NULL, NULL, NULL);",
+        )
+        .unwrap();
+}
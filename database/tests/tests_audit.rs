@@ -0,0 +1,205 @@
+use database::*;
+use std::{thread::sleep, time::Duration};
+
+fn cats_db() -> (Database, AuditLog) {
+    let db = Database::open_in_memory().unwrap();
+    db.connection()
+        .execute_batch(
+            "CREATE TABLE cats (id INTEGER PRIMARY KEY, name TEXT NOT NULL, age INTEGER, photo BLOB)",
+        )
+        .unwrap();
+    let audit = AuditLog::new(&db);
+    audit.track("cats").unwrap();
+    (db, audit)
+}
+
+type CatRow = (i64, String, Option<i64>, Option<Vec<u8>>);
+
+fn cats(db: &Database, table: &str) -> Vec<CatRow> {
+    let connection = db.connection();
+    let mut statement = connection
+        .prepare(&format!(
+            "SELECT id, name, age, photo FROM {} ORDER BY id",
+            table
+        ))
+        .unwrap();
+    let rows = statement
+        .query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })
+        .unwrap();
+    rows.collect::<Result<_, _>>().unwrap()
+}
+
+/// Run some SQL and wait so the next change gets a later timestamp.
+fn change(db: &Database, sql: &str) {
+    db.connection().execute_batch(sql).unwrap();
+    sleep(Duration::from_millis(5));
+}
+
+#[cfg(test)]
+mod tests_audit {
+    use super::*;
+
+    #[test]
+    fn test_records_changes_with_actor() {
+        let (db, audit) = cats_db();
+        change(
+            &db,
+            "INSERT INTO cats (name, age, photo) VALUES ('Tom', 7, x'CAFE')",
+        );
+        audit.set_actor(Some("ana")).unwrap();
+        change(&db, "UPDATE cats SET age = 8 WHERE id = 1");
+        change(&db, "UPDATE cats SET age = 8 WHERE id = 1");
+        audit.set_actor(None).unwrap();
+        change(&db, "DELETE FROM cats WHERE id = 1");
+
+        let history = audit.history("cats", 1).unwrap();
+        let operations: Vec<AuditOperation> = history.iter().map(|entry| entry.operation).collect();
+        assert_eq!(
+            operations,
+            vec![
+                AuditOperation::Insert,
+                AuditOperation::Update,
+                AuditOperation::Delete
+            ]
+        );
+
+        let insert = &history[0];
+        assert_eq!(insert.old_row, None);
+        let new_row = insert.new_row.as_ref().unwrap();
+        assert_eq!(new_row["name"], "Tom");
+        assert_eq!(new_row["photo"]["$blob"], "CAFE");
+        assert_eq!(insert.actor, None);
+
+        let update = &history[1];
+        assert_eq!(update.old_row.as_ref().unwrap()["age"], 7);
+        assert_eq!(update.new_row.as_ref().unwrap()["age"], 8);
+        assert_eq!(update.actor.as_deref(), Some("ana"));
+        assert!(update.changed_at > insert.changed_at);
+
+        assert_eq!(history[2].new_row, None);
+        assert_eq!(history[2].actor, None);
+    }
+
+    #[test]
+    fn test_revert_to_point_in_time() {
+        let (db, audit) = cats_db();
+        change(
+            &db,
+            "INSERT INTO cats (name, age, photo) VALUES ('Tom', 7, x'CAFE');
+             INSERT INTO cats (name, age) VALUES ('Felix', 3);",
+        );
+        let point = audit.history("cats", 2).unwrap()[0].changed_at;
+        let before = cats(&db, "cats");
+
+        change(
+            &db,
+            "UPDATE cats SET name = 'Tommy', photo = NULL WHERE id = 1",
+        );
+        change(&db, "DELETE FROM cats WHERE id = 2");
+        change(&db, "INSERT INTO cats (name) VALUES ('Garfield')");
+        assert_eq!(audit.changes_since("cats", point).unwrap().len(), 3);
+
+        assert_eq!(audit.revert_to("cats", point).unwrap(), 3);
+        assert_eq!(cats(&db, "cats"), before);
+        assert_eq!(audit.history("cats", 2).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_replay_into_another_table() {
+        let (db, audit) = cats_db();
+        change(&db, "INSERT INTO cats (name, age) VALUES ('Tom', 7)");
+        change(
+            &db,
+            "INSERT INTO cats (name, age, photo) VALUES ('Felix', 3, x'00FF')",
+        );
+        change(&db, "UPDATE cats SET age = 4 WHERE id = 2");
+        let point = audit.history("cats", 2).unwrap()[1].changed_at;
+        let snapshot = cats(&db, "cats");
+        change(&db, "DELETE FROM cats WHERE id = 1");
+
+        assert_eq!(audit.replay("cats", "cats_snapshot", point).unwrap(), 3);
+        assert_eq!(cats(&db, "cats_snapshot"), snapshot);
+        assert_eq!(snapshot[1].3, Some(vec![0x00, 0xFF]));
+    }
+
+    #[test]
+    fn test_revert_and_replay_to_entry() {
+        let (db, audit) = cats_db();
+        db.connection()
+            .execute_batch(
+                "INSERT INTO cats (name, age) VALUES ('Tom', 7);
+                 INSERT INTO cats (name, age) VALUES ('Felix', 3);",
+            )
+            .unwrap();
+        let entry = audit.history("cats", 2).unwrap()[0].id;
+        let snapshot = cats(&db, "cats");
+        db.connection()
+            .execute_batch(
+                "UPDATE cats SET age = 4 WHERE id = 2;
+                 DELETE FROM cats WHERE id = 1;",
+            )
+            .unwrap();
+
+        assert_eq!(
+            audit
+                .replay_to_entry("cats", "cats_snapshot", entry)
+                .unwrap(),
+            2
+        );
+        assert_eq!(cats(&db, "cats_snapshot"), snapshot);
+
+        assert_eq!(audit.changes_after("cats", entry).unwrap().len(), 2);
+        assert_eq!(audit.revert_to_entry("cats", entry).unwrap(), 2);
+        assert_eq!(cats(&db, "cats"), snapshot);
+    }
+
+    #[test]
+    fn test_same_millisecond_changes() {
+        let (db, audit) = cats_db();
+        db.connection()
+            .execute_batch(
+                "INSERT INTO cats (name, age) VALUES ('Tom', 7);
+                 INSERT INTO cats (name, age) VALUES ('Felix', 3);
+                 UPDATE audit_log SET changed_at = '2024-01-01 12:00:00.000';",
+            )
+            .unwrap();
+        let point = audit.history("cats", 1).unwrap()[0].changed_at;
+
+        assert!(audit.changes_since("cats", point).unwrap().is_empty());
+        assert_eq!(audit.revert_to("cats", point).unwrap(), 0);
+        assert_eq!(cats(&db, "cats").len(), 2);
+        assert_eq!(audit.replay("cats", "cats_snapshot", point).unwrap(), 2);
+        assert_eq!(cats(&db, "cats_snapshot").len(), 2);
+    }
+
+    #[test]
+    fn test_track_again_and_untrack() {
+        let (db, audit) = cats_db();
+        change(&db, "ALTER TABLE cats ADD COLUMN color TEXT");
+        audit.track("cats").unwrap();
+        change(
+            &db,
+            "INSERT INTO cats (name, color) VALUES ('Salem', 'Black')",
+        );
+        let history = audit.history("cats", 1).unwrap();
+        assert_eq!(history[0].new_row.as_ref().unwrap()["color"], "Black");
+
+        audit.untrack("cats").unwrap();
+        change(&db, "DELETE FROM cats");
+        assert_eq!(audit.history("cats", 1).unwrap().len(), 1);
+
+        assert!(matches!(
+            audit.track("dogs"),
+            Err(DatabaseError::NotFound(_))
+        ));
+        db.connection()
+            .execute_batch("CREATE TABLE tags (name TEXT PRIMARY KEY) WITHOUT ROWID")
+            .unwrap();
+        assert!(matches!(
+            audit.track("tags"),
+            Err(DatabaseError::Unsupported(_))
+        ));
+    }
+}
@@ -0,0 +1,136 @@
+use database::*;
+use std::fs::{remove_file, write};
+
+fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests_config {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        let config = DatabaseConfig::from_toml("").unwrap();
+        assert_eq!(config, DatabaseConfig::default());
+        assert!(config.postgres.is_none() && config.sqlite.is_none());
+        assert_eq!(config.pool.max_size, 10);
+        assert!(config.pool.test_on_check_out);
+    }
+
+    #[test]
+    fn test_from_file() {
+        let path = std::env::temp_dir().join("tests_config_database.toml");
+        write(
+            &path,
+            r#"
+            [postgres]
+            host = "db.internal"
+            port = 6432
+            dbname = "cats"
+            user = "app"
+            password = "secret"
+            tls = "require"
+            connect_timeout_secs = 5
+
+            [sqlite]
+            path = "/var/lib/app/cats.db"
+            journal_mode = "wal"
+            synchronous = "normal"
+            foreign_keys = false
+            busy_timeout_ms = 250
+
+            [pool]
+            max_size = 20
+            min_idle = 2
+            idle_timeout_secs = 60
+            test_on_check_out = false
+            "#,
+        )
+        .unwrap();
+
+        let config = DatabaseConfig::from_file(&path).unwrap();
+        remove_file(&path).unwrap();
+
+        let postgres = config.postgres.unwrap();
+        assert_eq!(postgres.host, "db.internal");
+        assert_eq!(postgres.port, 6432);
+        assert_eq!(postgres.password.as_deref(), Some("secret"));
+        assert_eq!(postgres.tls, TlsMode::Require);
+        assert_eq!(postgres.connect_timeout_secs, Some(5));
+
+        let sqlite = config.sqlite.unwrap();
+        assert_eq!(sqlite.journal_mode, Some(JournalMode::Wal));
+        assert_eq!(sqlite.synchronous, Some(Synchronous::Normal));
+        assert!(!sqlite.foreign_keys);
+        assert_eq!(sqlite.busy_timeout_ms, 250);
+
+        assert_eq!(config.pool.max_size, 20);
+        assert_eq!(config.pool.min_idle, Some(2));
+        assert_eq!(config.pool.max_lifetime_secs, Some(1800));
+        assert!(!config.pool.test_on_check_out);
+    }
+
+    #[test]
+    fn test_invalid_toml() {
+        assert!(matches!(
+            DatabaseConfig::from_toml("[postgres]\ntls = \"sometimes\""),
+            Err(ConfigError::Toml(_))
+        ));
+        assert!(matches!(
+            DatabaseConfig::from_toml("[sqlite]\npath = \"a.db\"\nwal = true"),
+            Err(ConfigError::Toml(_))
+        ));
+        assert!(matches!(
+            DatabaseConfig::from_file("does_not_exist.toml"),
+            Err(ConfigError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_from_vars() {
+        let config = DatabaseConfig::from_vars(
+            "cats",
+            vars(&[
+                ("CATS_PG_DBNAME", "cats"),
+                ("CATS_PG_TLS", "verify_full"),
+                ("CATS_SQLITE_PATH", "cats.db"),
+                ("CATS_SQLITE_JOURNAL_MODE", "WAL"),
+                ("CATS_SQLITE_FOREIGN_KEYS", "off"),
+                ("CATS_POOL_MAX_SIZE", "3"),
+                ("CATS_POOL_TEST_ON_CHECK_OUT", "no"),
+                ("DOGS_PG_HOST", "elsewhere"),
+            ]),
+        )
+        .unwrap();
+
+        let postgres = config.postgres.unwrap();
+        assert_eq!(postgres.host, "localhost");
+        assert_eq!(postgres.dbname, "cats");
+        assert_eq!(postgres.tls, TlsMode::VerifyFull);
+
+        let sqlite = config.sqlite.unwrap();
+        assert_eq!(sqlite.path.to_str(), Some("cats.db"));
+        assert_eq!(sqlite.journal_mode, Some(JournalMode::Wal));
+        assert!(!sqlite.foreign_keys);
+
+        assert_eq!(config.pool.max_size, 3);
+        assert!(!config.pool.test_on_check_out);
+    }
+
+    #[test]
+    fn test_from_vars_errors() {
+        for pair in [
+            ("APP_PG_PORT", "eighty"),
+            ("APP_PG_HOSTNAME", "db"),
+            ("APP_SQLITE_FOREIGN_KEYS", "maybe"),
+            ("APP_POOL_MAX_SIZE", "-1"),
+        ] {
+            let result = DatabaseConfig::from_vars("APP", vars(&[pair]));
+            assert!(matches!(result, Err(ConfigError::Invalid(_))), "{:?}", pair);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use database::*;
+use rusqlite::Connection;
+use std::{
+    fs::{read_to_string, remove_file, write},
+    path::PathBuf,
+};
+
+const CATS_CSV: &str = "\u{feff}Name,Age,Weight (kg),Indoor,Born,Last Visit,Notes
+Tom,7,4.5,yes,2017-03-01,2024-01-10 09:30:00,\"Likes \"\"fish\"\", naps\"
+Felix,3,3,no,2021-06-15,2024-02-11T14:00:00,
+Garfield,5,,true,2019-01-20,2024-03-12,Lasagna
+Salem,two,3.8,f,2022-10-31,2024-04-01 08:00:00,Wrong age
+Luna,4,4.1,maybe,2020-05-05,2024-05-20 10:15:00.250,Wrong flag
+Kitty,1,2.2,no,2023-07-07
+";
+
+fn csv_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(name);
+    write(&path, contents).unwrap();
+    path
+}
+
+#[cfg(test)]
+mod tests_csv_import {
+    use super::*;
+
+    #[test]
+    fn test_infer_schema() {
+        let path = csv_file("tests_csv_import_schema.csv", CATS_CSV);
+        let columns = CsvImporter::new(&path)
+            .sample_rows(3)
+            .infer_schema()
+            .unwrap();
+
+        let summary: Vec<(&str, ColumnType, bool)> = columns
+            .iter()
+            .map(|column| (column.name.as_str(), column.column_type, column.nullable))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("name", ColumnType::Text, false),
+                ("age", ColumnType::Integer, false),
+                ("weight_(kg)", ColumnType::Numeric, true),
+                ("indoor", ColumnType::Boolean, false),
+                ("born", ColumnType::Date, false),
+                ("last_visit", ColumnType::Timestamp, false),
+                ("notes", ColumnType::Text, true),
+            ]
+        );
+
+        let columns = CsvImporter::new(&path).infer_schema().unwrap();
+        assert_eq!(columns[1].column_type, ColumnType::Text);
+        assert_eq!(columns[3].column_type, ColumnType::Text);
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_duplicate_and_empty_headers() {
+        let path = csv_file("tests_csv_import_headers.csv", "id,ID,,Id\n1,2,3,4\n");
+        let names: Vec<String> = CsvImporter::new(&path)
+            .infer_schema()
+            .unwrap()
+            .into_iter()
+            .map(|column| column.name)
+            .collect();
+        assert_eq!(names, vec!["id", "id_2", "column_3", "id_3"]);
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_import_sqlite_with_rejects() {
+        let path = csv_file("tests_csv_import_sqlite.csv", CATS_CSV);
+        let rejects = std::env::temp_dir().join("tests_csv_import_sqlite.rejects.csv");
+        let connection = Connection::open_in_memory().unwrap();
+
+        let report = CsvImporter::new(&path)
+            .sample_rows(3)
+            .rejects_file(&rejects)
+            .import_sqlite(&connection, "cat table")
+            .unwrap();
+        assert_eq!((report.imported, report.rejected), (3, 3));
+
+        let (age, weight, indoor, notes): (i64, f64, i64, String) = connection
+            .query_row(
+                "SELECT age, \"weight_(kg)\", indoor, notes FROM \"cat table\" WHERE name = 'Tom'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!((age, weight, indoor), (7, 4.5, 1));
+        assert_eq!(notes, "Likes \"fish\", naps");
+
+        let weight: Option<f64> = connection
+            .query_row(
+                "SELECT \"weight_(kg)\" FROM \"cat table\" WHERE name = 'Garfield'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(weight, None);
+
+        let rejected = read_to_string(&rejects).unwrap();
+        let lines: Vec<&str> = rejected.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("line,error,"));
+        assert!(lines[1].starts_with("5,") && lines[1].contains("age"));
+        assert!(lines[2].starts_with("6,") && lines[2].contains("indoor"));
+        assert!(lines[3].starts_with("7,") && lines[3].contains("expected 7 fields"));
+
+        remove_file(path).unwrap();
+        remove_file(rejects).unwrap();
+    }
+
+    #[test]
+    fn test_import_sqlite_constraint_rejects() {
+        let path = csv_file(
+            "tests_csv_import_unique.csv",
+            "code;label\n1;one\n2;two\n1;uno\n",
+        );
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch("CREATE TABLE codes (code INTEGER UNIQUE, label TEXT)")
+            .unwrap();
+
+        let report = CsvImporter::new(&path)
+            .delimiter(b';')
+            .import_sqlite(&connection, "codes")
+            .unwrap();
+        assert_eq!((report.imported, report.rejected), (2, 1));
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[ignore]
+    fn test_import_postgres() {
+        let connection_string = "postgresql://postgres:@localhost";
+        let db_name = "test_csv_import_db";
+        let _ = delete_db(connection_string, db_name);
+        create_db(connection_string, db_name).unwrap();
+
+        let path = csv_file("tests_csv_import_postgres.csv", CATS_CSV);
+        let mut client = postgres::Client::connect(
+            &format!("{}/{}", connection_string, db_name),
+            postgres::NoTls,
+        )
+        .unwrap();
+        let report = CsvImporter::new(&path)
+            .sample_rows(3)
+            .import_postgres(&mut client, "Cats")
+            .unwrap();
+        assert_eq!((report.imported, report.rejected), (3, 3));
+
+        let row = client
+            .query_one(
+                "SELECT age, indoor, born::text, notes FROM \"Cats\" WHERE name = 'Tom'",
+                &[],
+            )
+            .unwrap();
+        let (age, indoor, born, notes): (i64, bool, String, String) =
+            (row.get(0), row.get(1), row.get(2), row.get(3));
+        assert_eq!((age, indoor), (7, true));
+        assert_eq!(born, "2017-03-01");
+        assert_eq!(notes, "Likes \"fish\", naps");
+        drop(client);
+
+        remove_file(path).unwrap();
+        delete_db(connection_string, db_name).unwrap();
+    }
+}
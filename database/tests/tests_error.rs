@@ -0,0 +1,167 @@
+use database::*;
+use rusqlite::{Connection, TransactionBehavior};
+use std::{error::Error, fs::remove_file, time::Duration};
+
+mod common;
+use common::fixture_cats_connection;
+
+#[cfg(test)]
+mod tests_error {
+    use super::*;
+
+    fn error_of(connection: &Connection, sql: &str) -> DatabaseError {
+        connection.execute_batch(sql).unwrap_err().into()
+    }
+
+    #[test]
+    fn test_constraint_violations() {
+        let connection = fixture_cats_connection();
+
+        let error = error_of(&connection, "INSERT INTO colors (name) VALUES ('Black')");
+        assert!(matches!(error, DatabaseError::UniqueViolation(_)));
+        let error = error_of(
+            &connection,
+            "INSERT INTO colors (id, name) VALUES (1, 'White')",
+        );
+        assert!(matches!(error, DatabaseError::UniqueViolation(_)));
+
+        let error = error_of(
+            &connection,
+            "INSERT INTO cats (name, color_id) VALUES ('Tom', 9)",
+        );
+        assert!(matches!(error, DatabaseError::ForeignKeyViolation(_)));
+        assert_eq!(error.code().as_deref(), Some("787"));
+
+        let error = error_of(&connection, "INSERT INTO cats (name) VALUES (NULL)");
+        assert!(matches!(error, DatabaseError::ConstraintViolation(_)));
+        let error = error_of(&connection, "INSERT INTO cats (name) VALUES ('')");
+        assert!(matches!(error, DatabaseError::ConstraintViolation(_)));
+        assert!(error.to_string().starts_with("Constraint violated: "));
+        assert!(!error.is_retryable());
+    }
+
+    #[test]
+    fn test_missing_and_existing_objects() {
+        let connection = fixture_cats_connection();
+
+        assert!(matches!(
+            error_of(&connection, "SELECT * FROM dogs"),
+            DatabaseError::NotFound(_)
+        ));
+        assert!(matches!(
+            error_of(&connection, "SELECT breed FROM cats"),
+            DatabaseError::NotFound(_)
+        ));
+        let no_rows: DatabaseError = connection
+            .query_row("SELECT name FROM cats", [], |row| row.get::<_, String>(0))
+            .unwrap_err()
+            .into();
+        assert!(matches!(no_rows, DatabaseError::NotFound(_)));
+
+        assert!(matches!(
+            error_of(&connection, "CREATE TABLE cats (id INTEGER)"),
+            DatabaseError::AlreadyExists(_)
+        ));
+        assert!(matches!(
+            error_of(&connection, "ALTER TABLE cats ADD COLUMN name TEXT"),
+            DatabaseError::AlreadyExists(_)
+        ));
+        assert!(matches!(
+            error_of(&connection, "SELEC 1"),
+            DatabaseError::Other(_)
+        ));
+    }
+
+    #[test]
+    fn test_busy_is_serialization_failure() {
+        let path = std::env::temp_dir().join("tests_error_busy.db");
+        let _ = remove_file(&path);
+        let mut writer = Connection::open(&path).unwrap();
+        writer.execute_batch("CREATE TABLE t (x INTEGER)").unwrap();
+        let transaction = writer
+            .transaction_with_behavior(TransactionBehavior::Exclusive)
+            .unwrap();
+
+        let other = Connection::open(&path).unwrap();
+        other.busy_timeout(Duration::ZERO).unwrap();
+        let error = error_of(&other, "INSERT INTO t VALUES (1)");
+        assert!(matches!(error, DatabaseError::SerializationFailure(_)));
+        assert!(error.is_retryable());
+
+        let boxed: Box<dyn Error> = Box::new(error);
+        assert!(is_busy_error(boxed.as_ref()));
+
+        drop(transaction);
+        drop(other);
+        drop(writer);
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_connection_and_io_errors() {
+        let error: DatabaseError = Connection::open("/nonexistent/dir/cats.db")
+            .unwrap_err()
+            .into();
+        assert!(matches!(error, DatabaseError::Connection(_)));
+        assert!(error.source().is_some());
+
+        let error: DatabaseError = std::fs::read("/nonexistent/dir/cats.csv")
+            .unwrap_err()
+            .into();
+        assert!(matches!(error, DatabaseError::Io(_)));
+        assert_eq!(error.code(), None);
+    }
+
+    #[test]
+    fn test_sqlite_io_failure_keeps_code() {
+        let failure = rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_IOERR_WRITE);
+        let error: DatabaseError = rusqlite::Error::SqliteFailure(failure, None).into();
+        assert!(matches!(error, DatabaseError::Io(_)));
+        assert_eq!(error.code().as_deref(), Some("778"));
+        assert!(matches!(error.backend(), Some(BackendError::Sqlite(_))));
+    }
+
+    #[test]
+    fn test_crate_errors_convert() {
+        let error: DatabaseError =
+            MigrationError::Invalid("duplicate version 1".to_string()).into();
+        assert!(matches!(error, DatabaseError::Migration(_)));
+        let error: DatabaseError =
+            MigrationError::Sqlite(rusqlite::Error::QueryReturnedNoRows).into();
+        assert!(matches!(error, DatabaseError::NotFound(_)));
+        let error: DatabaseError = ConfigError::Invalid("bad port".to_string()).into();
+        assert!(matches!(error, DatabaseError::Config(_)));
+        assert!(error.source().is_some());
+
+        let error = CsvImporter::new("/nonexistent/dir/cats.csv")
+            .infer_schema()
+            .unwrap_err();
+        assert!(matches!(error, DatabaseError::Io(_)));
+        let error = export_sqlite_table_to_file(&fixture_cats_connection(), "cats", "cats.parquet")
+            .unwrap_err();
+        assert!(matches!(error, DatabaseError::Invalid(_)));
+    }
+
+    #[test]
+    fn test_sqlite_helpers_return_database_errors() {
+        let path = std::env::temp_dir().join("tests_error_cats.db");
+        let database = path.to_str().unwrap();
+        let _ = remove_file(&path);
+        create_sqlite_cats_database(database).unwrap();
+
+        let error = submit_db_transaction(
+            database,
+            "INSERT INTO cats (name, color_id) VALUES (NULL, 1)",
+            [],
+            TransactionType::Commit,
+        )
+        .unwrap_err();
+        assert!(matches!(error, DatabaseError::ConstraintViolation(_)));
+
+        assert!(matches!(
+            create_sqlite_cats_database("/nonexistent/dir/cats.db"),
+            Err(DatabaseError::Connection(_))
+        ));
+        remove_file(path).unwrap();
+    }
+}
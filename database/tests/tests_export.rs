@@ -0,0 +1,194 @@
+use database::*;
+use rusqlite::Connection;
+use std::fs::{read_to_string, remove_file};
+
+mod common;
+use common::{fixture_cats_connection, fixture_insert_cats};
+
+#[cfg(test)]
+mod tests_export {
+    use super::*;
+
+    fn fixture_cats() -> Connection {
+        let connection = fixture_cats_connection();
+        fixture_insert_cats(&connection);
+        connection
+    }
+
+    #[test]
+    fn test_export_csv() {
+        let connection = fixture_cats();
+        let mut output = Vec::new();
+        let rows =
+            export_sqlite_table(&connection, "cats", ExportFormat::Csv, &mut output).unwrap();
+
+        assert_eq!(rows, 3);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "name,age,weight,photo,color_id\n\
+             Tom,7,4.5,\\xcafe,1\n\
+             \"Felix, the \"\"cat\"\"\",,3,,\n\
+             Luna,4,,,\n"
+        );
+    }
+
+    #[test]
+    fn test_export_tsv_query_with_params() {
+        let connection = fixture_cats();
+        let mut output = Vec::new();
+        let rows = export_sqlite_query(
+            &connection,
+            "SELECT name, age FROM cats WHERE age > ?1 ORDER BY age",
+            [3],
+            ExportFormat::Tsv,
+            &mut output,
+        )
+        .unwrap();
+
+        assert_eq!(rows, 2);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "name\tage\nLuna\t4\nTom\t7\n"
+        );
+    }
+
+    #[test]
+    fn test_export_json_lines() {
+        let connection = fixture_cats();
+        let mut output = Vec::new();
+        export_sqlite_table(&connection, "cats", ExportFormat::JsonLines, &mut output).unwrap();
+
+        let lines: Vec<serde_json::Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            serde_json::json!({"name": "Tom", "age": 7, "weight": 4.5, "photo": "\\xcafe", "color_id": 1})
+        );
+        assert_eq!(lines[1]["name"], "Felix, the \"cat\"");
+        assert!(lines[1]["age"].is_null());
+    }
+
+    #[test]
+    fn test_export_to_file() {
+        let connection = fixture_cats();
+        let path = std::env::temp_dir().join("tests_export_cats.jsonl");
+        assert_eq!(
+            export_sqlite_table_to_file(&connection, "cats", &path).unwrap(),
+            3
+        );
+        assert_eq!(read_to_string(&path).unwrap().lines().count(), 3);
+        remove_file(&path).unwrap();
+
+        assert!(export_sqlite_table_to_file(&connection, "cats", "cats.parquet").is_err());
+        assert!(export_sqlite_table(&connection, "dogs", ExportFormat::Csv, Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_backup_and_restore() {
+        let connection = fixture_cats();
+        connection
+            .execute_batch(
+                "CREATE TABLE toys (name TEXT);
+                 WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 2000)
+                 INSERT INTO toys SELECT printf('toy number %d with a long name', i) FROM n;",
+            )
+            .unwrap();
+        let path = std::env::temp_dir().join("tests_export_backup.db");
+        let _ = remove_file(&path);
+
+        let mut reports = Vec::new();
+        backup_sqlite(&connection, &path, 4, |progress| {
+            reports.push((progress.remaining, progress.pagecount))
+        })
+        .unwrap();
+        assert!(reports.len() > 1);
+        assert_eq!(reports.last().unwrap().0, 0);
+
+        let backup = Connection::open(&path).unwrap();
+        let toys: i64 = backup
+            .query_row("SELECT COUNT(*) FROM toys", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(toys, 2000);
+        drop(backup);
+
+        let mut restored = Connection::open_in_memory().unwrap();
+        restore_sqlite(&mut restored, &path, -1, |_| {}).unwrap();
+        let cats: i64 = restored
+            .query_row("SELECT COUNT(*) FROM cats", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(cats, 3);
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_restore_missing_backup() {
+        let mut connection = fixture_cats();
+        let path = std::env::temp_dir().join("tests_export_missing.db");
+        let _ = remove_file(&path);
+
+        assert!(restore_sqlite(&mut connection, &path, -1, |_| {}).is_err());
+        assert!(!path.exists());
+        let cats: i64 = connection
+            .query_row("SELECT COUNT(*) FROM cats", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(cats, 3);
+    }
+
+    #[test]
+    fn test_restore_gives_up_on_locked_backup() {
+        let path = std::env::temp_dir().join("tests_export_locked.db");
+        let _ = remove_file(&path);
+        let mut connection = fixture_cats();
+        backup_sqlite(&connection, &path, -1, |_| {}).unwrap();
+
+        let locker = Connection::open(&path).unwrap();
+        locker.execute_batch("BEGIN EXCLUSIVE").unwrap();
+        assert!(restore_sqlite(&mut connection, &path, -1, |_| {}).is_err());
+
+        locker.execute_batch("ROLLBACK").unwrap();
+        drop(locker);
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[ignore]
+    fn test_export_postgres_round_trip() {
+        let connection_string = "postgresql://postgres:@localhost";
+        let db_name = "test_export_db";
+        let _ = delete_db(connection_string, db_name);
+        create_db_from_csv(connection_string, db_name, "artist.csv", "artist").unwrap();
+
+        let path = std::env::temp_dir().join("tests_export_artist.csv");
+        let bytes =
+            export_db_to_csv(connection_string, db_name, path.to_str().unwrap(), "artist").unwrap();
+        assert!(bytes > 0);
+
+        let exported = read_to_string(&path).unwrap();
+        assert!(exported.starts_with("displayname,nationality,gender\n"));
+        assert_eq!(exported.lines().count(), 21);
+        remove_file(&path).unwrap();
+
+        let mut client = postgres::Client::connect(
+            &format!("{}/{}", connection_string, db_name),
+            postgres::NoTls,
+        )
+        .unwrap();
+        let mut output = Vec::new();
+        export_postgres_query_csv(
+            &mut client,
+            "SELECT nationality, COUNT(*) FROM artist GROUP BY 1 ORDER BY 2 DESC LIMIT 1",
+            &mut output,
+        )
+        .unwrap();
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .starts_with("nationality,count\n"));
+        drop(client);
+
+        delete_db(connection_string, db_name).unwrap();
+    }
+}
@@ -0,0 +1,107 @@
+use database::*;
+use std::time::Duration;
+
+fn store() -> KeyValueStore {
+    KeyValueStore::open(&Database::open_in_memory().unwrap()).unwrap()
+}
+
+#[cfg(test)]
+mod tests_kv_store {
+    use super::*;
+
+    #[test]
+    fn test_set_get_delete() {
+        let store = store();
+        let cache = store.namespace("cache");
+        cache.set("name", "Tom").unwrap();
+        cache.set("bytes", vec![1u8, 2, 3]).unwrap();
+        cache.set("name", "Felix").unwrap();
+
+        assert_eq!(
+            cache.get::<String>("name").unwrap(),
+            Some("Felix".to_string())
+        );
+        assert_eq!(cache.get::<Vec<u8>>("bytes").unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(cache.get::<String>("missing").unwrap(), None);
+        assert_eq!(cache.keys().unwrap(), vec!["bytes", "name"]);
+        assert_eq!(cache.len().unwrap(), 2);
+
+        assert!(cache.delete("name").unwrap());
+        assert!(!cache.delete("name").unwrap());
+        assert!(!cache.contains("name").unwrap());
+    }
+
+    #[test]
+    fn test_namespaces_are_isolated() {
+        let store = store();
+        store.namespace("a").set("key", 1).unwrap();
+        store.namespace("b").set("key", 2).unwrap();
+
+        assert_eq!(store.namespace("a").get::<i64>("key").unwrap(), Some(1));
+        assert_eq!(store.namespace("b").get::<i64>("key").unwrap(), Some(2));
+        assert_eq!(store.namespaces().unwrap(), vec!["a", "b"]);
+
+        assert_eq!(store.namespace("a").clear().unwrap(), 1);
+        assert!(store.namespace("a").is_empty().unwrap());
+        assert_eq!(store.namespace("b").len().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_ttl_expiry() {
+        let store = store();
+        let sessions = store.namespace("sessions");
+        sessions.set_with_ttl("gone", "x", Duration::ZERO).unwrap();
+        sessions
+            .set_with_ttl("alive", "y", Duration::from_secs(60))
+            .unwrap();
+        sessions.set("forever", "z").unwrap();
+
+        assert_eq!(sessions.get::<String>("gone").unwrap(), None);
+        assert_eq!(sessions.keys().unwrap(), vec!["alive", "forever"]);
+        assert_eq!(sessions.ttl("gone").unwrap(), None);
+        assert_eq!(sessions.ttl("forever").unwrap(), Some(None));
+        let remaining = sessions.ttl("alive").unwrap().unwrap().unwrap();
+        assert!(remaining > Duration::from_secs(50) && remaining <= Duration::from_secs(60));
+        assert!(!sessions.delete("gone").unwrap());
+
+        sessions.set_with_ttl("gone", "x", Duration::ZERO).unwrap();
+        assert_eq!(store.purge_expired().unwrap(), 1);
+        assert_eq!(store.purge_expired().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_expire_and_persist() {
+        let store = store();
+        let cache = store.namespace("cache");
+        cache.set("key", 1).unwrap();
+
+        assert!(cache.expire("key", Some(Duration::from_secs(10))).unwrap());
+        assert!(cache.ttl("key").unwrap().unwrap().is_some());
+        assert!(cache.expire("key", None).unwrap());
+        assert_eq!(cache.ttl("key").unwrap(), Some(None));
+        assert!(cache.expire("key", Some(Duration::ZERO)).unwrap());
+        assert!(!cache.expire("key", None).unwrap());
+        assert!(!cache.expire("missing", None).unwrap());
+    }
+
+    #[test]
+    fn test_custom_table_shares_database() {
+        let db = Database::open_in_memory().unwrap();
+        let first = KeyValueStore::open_table(&db, "settings").unwrap();
+        first.namespace("app").set("theme", "dark").unwrap();
+
+        let second = KeyValueStore::open_table(&db, "settings").unwrap();
+        assert_eq!(
+            second.namespace("app").get::<String>("theme").unwrap(),
+            Some("dark".to_string())
+        );
+        assert_eq!(
+            KeyValueStore::open(&db)
+                .unwrap()
+                .namespaces()
+                .unwrap()
+                .len(),
+            0
+        );
+    }
+}
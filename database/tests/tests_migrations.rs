@@ -0,0 +1,199 @@
+use database::*;
+use rusqlite::Connection;
+use std::fs::{create_dir_all, remove_dir_all, write};
+
+fn cats_migrations() -> Vec<Migration> {
+    vec![
+        Migration::new(
+            1,
+            "create cats",
+            "CREATE TABLE cats (id INTEGER PRIMARY KEY, name TEXT NOT NULL)",
+        )
+        .with_down("DROP TABLE cats"),
+        Migration::new(2, "add color", "ALTER TABLE cats ADD COLUMN color TEXT")
+            .with_down("ALTER TABLE cats DROP COLUMN color"),
+        Migration::new(
+            3,
+            "create owners",
+            "CREATE TABLE owners (id INTEGER PRIMARY KEY, name TEXT NOT NULL);
+             INSERT INTO owners (id, name) VALUES (1, 'Ana');",
+        )
+        .with_down("DROP TABLE owners"),
+    ]
+}
+
+fn table_exists(connection: &Connection, table: &str) -> bool {
+    connection
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+            [table],
+            |row| row.get(0),
+        )
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests_migrations {
+    use super::*;
+
+    #[test]
+    fn test_migrate_and_rollback() {
+        let migrator = Migrator::new(cats_migrations()).unwrap();
+        let mut connection = Connection::open_in_memory().unwrap();
+        assert_eq!(migrator.current_version(&mut connection).unwrap(), None);
+
+        assert_eq!(migrator.migrate(&mut connection).unwrap(), vec![1, 2, 3]);
+        assert!(table_exists(&connection, "owners"));
+        assert!(migrator.migrate(&mut connection).unwrap().is_empty());
+
+        let applied = migrator.applied(&mut connection).unwrap();
+        assert_eq!(applied.len(), 3);
+        assert_eq!(applied[2].name, "create owners");
+        assert_eq!(applied[2].checksum, cats_migrations()[2].checksum());
+
+        assert_eq!(migrator.rollback(&mut connection, 2).unwrap(), vec![3, 2]);
+        assert_eq!(migrator.current_version(&mut connection).unwrap(), Some(1));
+        assert!(!table_exists(&connection, "owners"));
+
+        assert_eq!(migrator.migrate_to(&mut connection, 2).unwrap(), vec![2]);
+        assert_eq!(migrator.migrate_to(&mut connection, 0).unwrap(), vec![2, 1]);
+        assert!(!table_exists(&connection, "cats"));
+    }
+
+    #[test]
+    fn test_failed_migration_is_rolled_back() {
+        let mut migrations = cats_migrations();
+        migrations.push(Migration::new(
+            4,
+            "broken",
+            "CREATE TABLE toys (name TEXT); INSERT INTO missing VALUES (1);",
+        ));
+        let migrator = Migrator::new(migrations).unwrap();
+        let mut connection = Connection::open_in_memory().unwrap();
+
+        assert!(migrator.migrate(&mut connection).is_err());
+        assert_eq!(migrator.current_version(&mut connection).unwrap(), Some(3));
+        assert!(!table_exists(&connection, "toys"));
+    }
+
+    #[test]
+    fn test_checksum_mismatch() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        Migrator::new(cats_migrations())
+            .unwrap()
+            .migrate(&mut connection)
+            .unwrap();
+
+        let mut changed = cats_migrations();
+        changed[1].up = "ALTER TABLE cats ADD COLUMN colour TEXT".to_string();
+        let migrator = Migrator::new(changed).unwrap();
+        assert!(matches!(
+            migrator.migrate(&mut connection),
+            Err(MigrationError::ChecksumMismatch { version: 2, .. })
+        ));
+
+        let migrator = Migrator::new(cats_migrations()[..2].to_vec()).unwrap();
+        assert!(matches!(
+            migrator.migrate(&mut connection),
+            Err(MigrationError::UnknownApplied { version: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let migrator = Migrator::new(cats_migrations()).unwrap();
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrator.migrate_to(&mut connection, 1).unwrap();
+
+        let output = migrator.dry_run(&mut connection, 3).unwrap();
+        assert!(output.starts_with("-- 2 add color (up)\nALTER TABLE cats ADD COLUMN color TEXT\n"));
+        assert!(output.contains("-- 3 create owners (up)\n"));
+        assert_eq!(migrator.current_version(&mut connection).unwrap(), Some(1));
+
+        let output = migrator.dry_run(&mut connection, 0).unwrap();
+        assert_eq!(output, "-- 1 create cats (down)\nDROP TABLE cats\n\n");
+        assert!(table_exists(&connection, "cats"));
+
+        let fresh = &mut Connection::open_in_memory().unwrap();
+        migrator.dry_run(fresh, 3).unwrap();
+        assert!(!table_exists(fresh, MIGRATIONS_TABLE));
+    }
+
+    #[test]
+    fn test_irreversible_and_duplicate() {
+        let migrator = Migrator::new(vec![Migration::new(1, "seed", "CREATE TABLE seed (x)")])
+            .unwrap()
+            .table("history");
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrator.migrate(&mut connection).unwrap();
+        assert!(table_exists(&connection, "history"));
+        assert!(matches!(
+            migrator.rollback(&mut connection, 1),
+            Err(MigrationError::Irreversible { version: 1, .. })
+        ));
+
+        let duplicate = vec![
+            Migration::new(1, "a", "SELECT 1"),
+            Migration::new(1, "b", "SELECT 2"),
+        ];
+        assert!(matches!(
+            Migrator::new(duplicate),
+            Err(MigrationError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_from_dir() {
+        let dir = std::env::temp_dir().join("tests_migrations_from_dir");
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        write(
+            dir.join("0001_create_cats.up.sql"),
+            "CREATE TABLE cats (name TEXT);",
+        )
+        .unwrap();
+        write(dir.join("0001_create_cats.down.sql"), "DROP TABLE cats;").unwrap();
+        write(
+            dir.join("0002_add_color.up.sql"),
+            "ALTER TABLE cats ADD COLUMN color TEXT;",
+        )
+        .unwrap();
+        write(dir.join("README.md"), "Not a migration").unwrap();
+
+        let migrator = Migrator::from_dir(&dir).unwrap();
+        let migrations: Vec<_> = migrator.migrations().collect();
+        assert_eq!(migrations.len(), 2);
+        assert_eq!(migrations[0].name, "create cats");
+        assert_eq!(migrations[0].down.as_deref(), Some("DROP TABLE cats;"));
+        assert_eq!(migrations[1].down, None);
+
+        let mut connection = Connection::open_in_memory().unwrap();
+        assert_eq!(migrator.migrate(&mut connection).unwrap(), vec![1, 2]);
+
+        write(dir.join("0003.up.sql"), "SELECT 1;").unwrap();
+        assert!(Migrator::from_dir(&dir).is_err());
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[ignore]
+    fn test_postgres_migrations() {
+        let connection_string = "postgresql://postgres:@localhost";
+        let db_name = "test_migrations_db";
+        let _ = delete_db(connection_string, db_name);
+        create_db(connection_string, db_name).unwrap();
+
+        let mut client = postgres::Client::connect(
+            &format!("{}/{}", connection_string, db_name),
+            postgres::NoTls,
+        )
+        .unwrap();
+        let migrator = Migrator::new(cats_migrations()).unwrap();
+        assert_eq!(migrator.migrate(&mut client).unwrap(), vec![1, 2, 3]);
+        assert_eq!(migrator.current_version(&mut client).unwrap(), Some(3));
+        assert_eq!(migrator.migrate_to(&mut client, 0).unwrap(), vec![3, 2, 1]);
+        drop(client);
+
+        delete_db(connection_string, db_name).unwrap();
+    }
+}
@@ -0,0 +1,158 @@
+use database::*;
+use std::{fs::remove_file, path::Path, thread, time::Duration};
+
+fn sqlite_config(name: &str) -> SqliteConfig {
+    let path = std::env::temp_dir().join(name);
+    remove_files(&path);
+    SqliteConfig {
+        path,
+        journal_mode: Some(JournalMode::Wal),
+        synchronous: Some(Synchronous::Normal),
+        ..SqliteConfig::default()
+    }
+}
+
+fn remove_files(path: &Path) {
+    for suffix in ["", "-wal", "-shm"] {
+        let mut file = path.as_os_str().to_owned();
+        file.push(suffix);
+        let _ = remove_file(file);
+    }
+}
+
+#[cfg(test)]
+mod tests_pool {
+    use super::*;
+
+    #[test]
+    fn test_sqlite_pragmas() {
+        let config = sqlite_config("tests_pool_pragmas.db");
+        let connection = config.open().unwrap();
+
+        let journal_mode: String = connection
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        let synchronous: i64 = connection
+            .query_row("PRAGMA synchronous", [], |row| row.get(0))
+            .unwrap();
+        let foreign_keys: bool = connection
+            .query_row("PRAGMA foreign_keys", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+        assert_eq!(synchronous, 1);
+        assert!(foreign_keys);
+
+        drop(connection);
+        remove_files(&config.path);
+    }
+
+    #[test]
+    fn test_sqlite_pool_shares_database() {
+        let config = sqlite_config("tests_pool_shared.db");
+        let pool = config
+            .pool(&PoolConfig {
+                max_size: 4,
+                ..PoolConfig::default()
+            })
+            .unwrap();
+        pool.get()
+            .unwrap()
+            .execute_batch("CREATE TABLE cats (name TEXT)")
+            .unwrap();
+
+        let workers: Vec<_> = (0..8)
+            .map(|i| {
+                let pool = pool.clone();
+                thread::spawn(move || {
+                    let connection = pool.get().unwrap();
+                    connection
+                        .execute("INSERT INTO cats VALUES (?1)", [format!("cat {}", i)])
+                        .unwrap();
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let count: i64 = pool
+            .get()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM cats", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 8);
+        assert!(pool.state().connections <= 4);
+
+        drop(pool);
+        remove_files(&config.path);
+    }
+
+    #[test]
+    fn test_sqlite_pool_timeout_and_rollback() {
+        let config = sqlite_config("tests_pool_timeout.db");
+        let pool = config
+            .pool(&PoolConfig {
+                max_size: 1,
+                connection_timeout_secs: 1,
+                ..PoolConfig::default()
+            })
+            .unwrap();
+
+        let connection = pool.get().unwrap();
+        connection
+            .execute_batch("CREATE TABLE cats (name TEXT); BEGIN; INSERT INTO cats VALUES ('Tom');")
+            .unwrap();
+        assert!(pool.get_timeout(Duration::from_millis(50)).is_err());
+        drop(connection);
+
+        let connection = pool.get().unwrap();
+        assert!(connection.is_autocommit());
+        let count: i64 = connection
+            .query_row("SELECT COUNT(*) FROM cats", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+
+        drop(connection);
+        drop(pool);
+        remove_files(&config.path);
+    }
+
+    #[test]
+    fn test_postgres_config() {
+        let config = PostgresConfig {
+            host: "db.internal".to_string(),
+            password: Some("secret".to_string()),
+            tls: TlsMode::Disable,
+            connect_timeout_secs: Some(3),
+            ..PostgresConfig::default()
+        };
+        let postgres = config.to_postgres_config();
+        assert_eq!(postgres.get_dbname(), Some("postgres"));
+        assert_eq!(postgres.get_password(), Some(&b"secret"[..]));
+        assert_eq!(postgres.get_ssl_mode(), postgres::config::SslMode::Disable);
+        assert_eq!(
+            postgres.get_connect_timeout(),
+            Some(&Duration::from_secs(3))
+        );
+        assert!(config.tls_connector().is_ok());
+    }
+
+    #[test]
+    #[ignore]
+    fn test_postgres_pool() {
+        let config = PostgresConfig {
+            tls: TlsMode::Prefer,
+            ..PostgresConfig::default()
+        };
+        let pool = config
+            .pool(&PoolConfig {
+                max_size: 2,
+                ..PoolConfig::default()
+            })
+            .unwrap();
+
+        let mut client = pool.get().unwrap();
+        let row = client.query_one("SELECT 1::INT", &[]).unwrap();
+        assert_eq!(row.get::<_, i32>(0), 1);
+    }
+}
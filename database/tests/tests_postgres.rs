@@ -1,4 +1,4 @@
-use database::{add_tables, create_db, create_db_from_csv, delete_db, insert_data};
+use database::{add_tables, create_db, create_db_from_csv, delete_db, insert_data, DatabaseError};
 use postgres::{Client, Error, NoTls};
 use std::collections::HashMap;
 
@@ -16,6 +16,11 @@ mod tests_postgres {
 
     fn fixture_create_db() -> (String, String) {
         let (connection_string, db_name) = fixture_set_db();
+        // Drop the database an aborted run may have left behind.
+        match delete_db(&connection_string, &db_name) {
+            Ok(()) | Err(DatabaseError::NotFound(_)) => {}
+            Err(e) => panic!("{}", e),
+        }
         let result = create_db(&connection_string, &db_name);
         assert!(result.is_ok());
         (connection_string, db_name)
@@ -47,6 +52,21 @@ mod tests_postgres {
         let _ = fixture_delete_db(&connection_string, &db_name);
     }
 
+    #[test]
+    #[ignore]
+    fn test_create_existing_and_delete_missing_db() {
+        let (connection_string, db_name) = fixture_create_db();
+        assert!(matches!(
+            create_db(&connection_string, &db_name),
+            Err(DatabaseError::AlreadyExists(_))
+        ));
+        let _ = fixture_delete_db(&connection_string, &db_name);
+
+        let error = delete_db(&connection_string, &db_name).unwrap_err();
+        assert!(matches!(error, DatabaseError::NotFound(_)));
+        assert_eq!(error.code().as_deref(), Some("3D000"));
+    }
+
     #[test]
     #[ignore]
     fn test_add_tables() {
@@ -131,7 +151,9 @@ mod tests_postgres {
         let file_path = "artist.csv";
         let table_name = "artist";
 
-        let _ = fixture_delete_db(&connection_string, &db_name);
+        let _ = delete_db(connection_string, db_name);
+        // An existing database is reused, like create_db_from_csv_async does.
+        create_db(connection_string, db_name).unwrap();
 
         let result = create_db_from_csv(connection_string, db_name, file_path, table_name);
         assert!(result.is_ok());
@@ -0,0 +1,186 @@
+use database::*;
+use futures_util::StreamExt;
+use std::{fs::write, time::Duration};
+use tokio::time::timeout;
+
+const CONNECTION_STRING: &str = "postgresql://postgres:@localhost";
+
+const TEST_TABLES: [&str; 2] = [
+    "author (
+        id              SERIAL PRIMARY KEY,
+        name            VARCHAR NOT NULL UNIQUE,
+        country         VARCHAR NOT NULL
+    )",
+    "book  (
+        id              SERIAL PRIMARY KEY,
+        title           VARCHAR NOT NULL,
+        author_id       INTEGER NOT NULL REFERENCES author
+    )",
+];
+
+async fn fresh_db(db_name: &str) -> String {
+    let _ = delete_db_async(CONNECTION_STRING, db_name).await;
+    create_db_async(CONNECTION_STRING, db_name).await.unwrap();
+    format!("{}/{}", CONNECTION_STRING, db_name)
+}
+
+#[cfg(test)]
+mod tests_postgres_async {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_connection_refused() {
+        let result = connect_async("postgresql://postgres:@localhost:1").await;
+        assert!(matches!(result, Err(DatabaseError::Connection(_))));
+        let result = listen_async("postgresql://postgres:@localhost:1", &["cats"]).await;
+        assert!(matches!(result, Err(DatabaseError::Connection(_))));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_create_and_delete_db() {
+        let db_name = "test_async_create_db";
+        fresh_db(db_name).await;
+        assert!(matches!(
+            create_db_async(CONNECTION_STRING, db_name).await,
+            Err(DatabaseError::AlreadyExists(_))
+        ));
+        delete_db_async(CONNECTION_STRING, db_name).await.unwrap();
+        assert!(matches!(
+            delete_db_async(CONNECTION_STRING, db_name).await,
+            Err(DatabaseError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_tables_inserts_and_pipelined_queries() {
+        let db_name = "test_async_insert_db";
+        let connection_string = fresh_db(db_name).await;
+        add_tables_async(&connection_string, Vec::from(TEST_TABLES))
+            .await
+            .unwrap();
+        insert_data_async(
+            &connection_string,
+            vec![
+                "author (name, country) VALUES ('Octavio Paz', 'México')",
+                "author (name, country) VALUES ('Jorge Luis Borges', 'Argentina')",
+                "book (title, author_id) VALUES ('Ficciones', 2)",
+            ],
+        )
+        .await
+        .unwrap();
+
+        let error = insert_data_async(
+            &connection_string,
+            vec![
+                "author (name, country) VALUES ('Julio Cortázar', 'Argentina')",
+                "author (name, country) VALUES ('Octavio Paz', 'México')",
+            ],
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(error, DatabaseError::UniqueViolation(_)));
+
+        let client = connect_async(&connection_string).await.unwrap();
+        let country = "Argentina";
+        let results = query_pipelined(
+            &client,
+            &[
+                ("SELECT COUNT(*) FROM author", &[]),
+                ("SELECT name FROM author WHERE country = $1", &[&country]),
+                (
+                    "SELECT title FROM book JOIN author ON author.id = book.author_id",
+                    &[],
+                ),
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(results[0][0].get::<_, i64>(0), 2);
+        assert_eq!(results[1][0].get::<_, &str>(0), "Jorge Luis Borges");
+        assert_eq!(results[2][0].get::<_, &str>(0), "Ficciones");
+        drop(client);
+
+        delete_db_async(CONNECTION_STRING, db_name).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_import_csv_async() {
+        let db_name = "test_async_csv_db";
+        // The database already exists and is reused, like create_db_from_csv does.
+        fresh_db(db_name).await;
+        let path = std::env::temp_dir().join("tests_postgres_async.csv");
+        let mut contents = String::from("Name,Age,Indoor\n");
+        for index in 0..5000 {
+            contents.push_str(&format!(
+                "Cat {},{},{}\n",
+                index,
+                index % 20,
+                index % 2 == 0
+            ));
+        }
+        contents.push_str("Salem,two,yes\n");
+        write(&path, contents).unwrap();
+
+        // The import runs on another task, so its future and error are Send.
+        let file_path = path.to_str().unwrap().to_string();
+        let report = tokio::spawn(async move {
+            create_db_from_csv_async(CONNECTION_STRING, db_name, &file_path, "cats").await
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!((report.imported, report.rejected), (5000, 1));
+
+        let client = connect_async(&format!("{}/{}", CONNECTION_STRING, db_name))
+            .await
+            .unwrap();
+        let row = client
+            .query_one("SELECT COUNT(*), SUM(age) FROM cats WHERE indoor", &[])
+            .await
+            .unwrap();
+        assert_eq!(row.get::<_, i64>(0), 2500);
+        drop(client);
+
+        std::fs::remove_file(path).unwrap();
+        delete_db_async(CONNECTION_STRING, db_name).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_listen_notify() {
+        let mut notifications = listen_async(CONNECTION_STRING, &["cats", "Dogs"])
+            .await
+            .unwrap();
+        let client = connect_async(CONNECTION_STRING).await.unwrap();
+
+        notify_async(&client, "cats", "Tom was fed").await.unwrap();
+        notify_async(&client, "Dogs", "Rex barked").await.unwrap();
+        notifications.unlisten("cats").await.unwrap();
+        notify_async(&client, "cats", "ignored").await.unwrap();
+        notify_async(&client, "Dogs", "Rex slept").await.unwrap();
+
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            let notification = timeout(Duration::from_secs(5), notifications.next())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            received.push((
+                notification.channel().to_string(),
+                notification.payload().to_string(),
+            ));
+        }
+        assert_eq!(
+            received,
+            vec![
+                ("cats".to_string(), "Tom was fed".to_string()),
+                ("Dogs".to_string(), "Rex barked".to_string()),
+                ("Dogs".to_string(), "Rex slept".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use database::*;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Cat {
+    id: Option<i64>,
+    name: String,
+    color: String,
+    age: i64,
+    owner: Option<String>,
+}
+
+sqlite_entity!(Cat, "cats", id, {
+    name: "TEXT NOT NULL",
+    color: "TEXT NOT NULL",
+    age: "INTEGER NOT NULL",
+    owner: "TEXT",
+});
+
+fn cat(name: &str, color: &str, age: i64, owner: Option<&str>) -> Cat {
+    Cat {
+        id: None,
+        name: name.to_string(),
+        color: color.to_string(),
+        age,
+        owner: owner.map(str::to_string),
+    }
+}
+
+fn cats_repository() -> Repository<Cat> {
+    let cats = Repository::<Cat>::new(&Database::open_in_memory().unwrap());
+    cats.create_table().unwrap();
+    let mut rows = vec![
+        cat("Fluffy", "White", 3, Some("Ana")),
+        cat("Tom", "Grey", 7, None),
+        cat("Garfield", "Orange", 5, Some("Jon")),
+        cat("Salem", "Black", 2, None),
+        cat("Luna", "Black", 4, Some("Ana")),
+    ];
+    cats.insert_all(&mut rows).unwrap();
+    cats
+}
+
+fn names(cats: Vec<Cat>) -> Vec<String> {
+    cats.into_iter().map(|cat| cat.name).collect()
+}
+
+#[cfg(test)]
+mod tests_repository {
+    use super::*;
+
+    #[test]
+    fn test_crud() {
+        let cats = Repository::<Cat>::new(&Database::open_in_memory().unwrap());
+        cats.create_table().unwrap();
+
+        let mut tom = cat("Tom", "Grey", 7, None);
+        let id = cats.insert(&mut tom).unwrap();
+        assert_eq!(tom.id, Some(id));
+        assert_eq!(cats.get(id).unwrap(), Some(tom.clone()));
+
+        tom.owner = Some("Jerry".to_string());
+        assert!(cats.update(&tom).unwrap());
+        assert_eq!(
+            cats.get(id).unwrap().unwrap().owner.as_deref(),
+            Some("Jerry")
+        );
+
+        assert!(cats.delete(id).unwrap());
+        assert!(!cats.delete(id).unwrap());
+        assert_eq!(cats.get(id).unwrap(), None);
+        assert!(!cats.update(&tom).unwrap());
+    }
+
+    #[test]
+    fn test_insert_all_rolls_back_ids() {
+        let cats = cats_repository();
+        let mut duplicate = cat("Felix", "Black", 1, None);
+        duplicate.id = Some(1);
+        let mut rows = vec![cat("Nala", "Golden", 6, None), duplicate];
+
+        assert!(cats.insert_all(&mut rows).is_err());
+        assert_eq!(rows[0].id, None);
+        assert_eq!(rows[1].id, Some(1));
+        assert_eq!(cats.count(&Query::new()).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_update_requires_id() {
+        let cats = cats_repository();
+        assert!(cats.update(&cat("Nobody", "Clear", 1, None)).is_err());
+    }
+
+    #[test]
+    fn test_filter_and_sort() {
+        let cats = cats_repository();
+
+        let query = Query::new()
+            .filter("color", Comparison::Eq, "Black")
+            .order_by("age", SortOrder::Desc);
+        assert_eq!(names(cats.find(&query).unwrap()), vec!["Luna", "Salem"]);
+
+        let query = Query::new()
+            .filter("age", Comparison::Ge, 4)
+            .filter("name", Comparison::Like, "%a%")
+            .order_by("name", SortOrder::Asc);
+        assert_eq!(names(cats.find(&query).unwrap()), vec!["Garfield", "Luna"]);
+
+        let query = Query::new()
+            .filter_null("owner", true)
+            .order_by("name", SortOrder::Asc);
+        assert_eq!(names(cats.find(&query).unwrap()), vec!["Salem", "Tom"]);
+
+        let query = Query::new().filter_in("owner", ["Ana", "Jon"]);
+        assert_eq!(cats.count(&query).unwrap(), 3);
+        let query = Query::new().filter_in("owner", Vec::<String>::new());
+        assert_eq!(cats.count(&query).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_pagination() {
+        let cats = cats_repository();
+        let page = |page| {
+            let query = Query::new()
+                .order_by("color", SortOrder::Asc)
+                .order_by("name", SortOrder::Asc)
+                .page(page, 2);
+            names(cats.find(&query).unwrap())
+        };
+
+        assert_eq!(page(1), vec!["Luna", "Salem"]);
+        assert_eq!(page(2), vec!["Tom", "Garfield"]);
+        assert_eq!(page(3), vec!["Fluffy"]);
+        assert!(page(4).is_empty());
+
+        let query = Query::new().order_by("age", SortOrder::Asc).offset(3);
+        assert_eq!(names(cats.find(&query).unwrap()), vec!["Garfield", "Tom"]);
+    }
+
+    #[test]
+    fn test_find_one_count_and_delete_where() {
+        let cats = cats_repository();
+        let oldest = Query::new().order_by("age", SortOrder::Desc);
+        assert_eq!(cats.find_one(&oldest).unwrap().unwrap().name, "Tom");
+        assert_eq!(cats.count(&Query::new()).unwrap(), 5);
+
+        let black = Query::new().filter("color", Comparison::Eq, "Black");
+        assert_eq!(cats.delete_where(&black).unwrap(), 2);
+        assert_eq!(cats.count(&Query::new()).unwrap(), 3);
+        assert_eq!(cats.find_one(&black).unwrap(), None);
+    }
+
+    #[test]
+    fn test_unknown_column_is_rejected() {
+        let cats = cats_repository();
+        let query = Query::new().filter("name = name; DROP TABLE cats; --", Comparison::Eq, 1);
+        assert!(cats.find(&query).is_err());
+        assert!(cats
+            .find(&Query::new().order_by("weight", SortOrder::Asc))
+            .is_err());
+        assert_eq!(cats.all().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_shared_connection() {
+        let cats = cats_repository();
+        let same = Repository::<Cat>::new(cats.database());
+        same.insert(&mut cat("Kitty", "Pink", 1, None)).unwrap();
+        assert_eq!(cats.count(&Query::new()).unwrap(), 6);
+    }
+}
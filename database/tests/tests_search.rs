@@ -0,0 +1,128 @@
+use database::*;
+
+fn notes_index() -> (Database, SearchIndex) {
+    let db = Database::open_in_memory().unwrap();
+    db.connection()
+        .execute_batch(
+            "CREATE TABLE notes (id INTEGER PRIMARY KEY, title TEXT, body TEXT);
+             INSERT INTO notes (title, body) VALUES
+                 ('Groceries', 'Milk, eggs and food for the cat'),
+                 ('Cat care', 'Brush the cat every week'),
+                 ('Garden', 'Water the plants');",
+        )
+        .unwrap();
+    let index = SearchIndex::new(&db, "notes_search", "notes", &["title", "body"])
+        .content_rowid("id")
+        .tokenizer("porter unicode61");
+    index.create().unwrap();
+    (db, index)
+}
+
+#[cfg(test)]
+mod tests_search {
+    use super::*;
+
+    #[test]
+    fn test_search_ranks_and_highlights() {
+        let (_db, index) = notes_index();
+
+        let hits = index
+            .search(
+                &SearchQuery::new("cat")
+                    .markers("<b>", "</b>")
+                    .weights(&[10.0, 1.0]),
+            )
+            .unwrap();
+        let rowids: Vec<i64> = hits.iter().map(|hit| hit.rowid).collect();
+        assert_eq!(rowids, vec![2, 1]);
+        assert!(hits[0].score < hits[1].score);
+        assert_eq!(hits[0].highlights[0], "<b>Cat</b> care");
+        assert_eq!(hits[1].highlights[0], "Groceries");
+        assert!(hits[1].snippet.contains("<b>cat</b>"));
+
+        assert_eq!(index.count("plant").unwrap(), 1);
+        assert_eq!(index.count(&fts5_phrase("the cat")).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_snippet_and_paging() {
+        let (db, index) = notes_index();
+        db.connection()
+            .execute(
+                "INSERT INTO notes (title, body) VALUES ('Long', ?1)",
+                [format!("{} cat {}", "word ".repeat(30), "word ".repeat(30))],
+            )
+            .unwrap();
+
+        let hits = index
+            .search(&SearchQuery::new("cat").snippet_tokens(5).ellipsis("~"))
+            .unwrap();
+        let long = hits.iter().find(|hit| hit.rowid == 4).unwrap();
+        assert_eq!(long.snippet.split_whitespace().count(), 5);
+        assert!(long.snippet.starts_with('~') && long.snippet.ends_with('~'));
+
+        let page: Vec<i64> = index
+            .search(&SearchQuery::new("cat").page(2, 2))
+            .unwrap()
+            .iter()
+            .map(|hit| hit.rowid)
+            .collect();
+        assert_eq!(page.len(), 1);
+    }
+
+    #[test]
+    fn test_triggers_keep_index_in_sync() {
+        let (db, index) = notes_index();
+        {
+            let connection = db.connection();
+            connection
+                .execute(
+                    "INSERT INTO notes (title, body) VALUES ('Dog', 'Walk the dog')",
+                    [],
+                )
+                .unwrap();
+            connection
+                .execute("UPDATE notes SET body = 'Water the roses' WHERE id = 3", [])
+                .unwrap();
+            connection
+                .execute("DELETE FROM notes WHERE id = 2", [])
+                .unwrap();
+        }
+
+        assert_eq!(index.count("dog").unwrap(), 1);
+        assert_eq!(index.count("plants").unwrap(), 0);
+        assert_eq!(index.count("roses").unwrap(), 1);
+        assert_eq!(index.count("cat").unwrap(), 1);
+
+        index.optimize().unwrap();
+        index.rebuild().unwrap();
+        assert_eq!(index.count("cat").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_create_twice_and_drop() {
+        let (db, index) = notes_index();
+        index.create().unwrap();
+        assert_eq!(index.count("cat").unwrap(), 2);
+
+        index.drop_index().unwrap();
+        let objects: i64 = db
+            .connection()
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE name LIKE 'notes_search%'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(objects, 0);
+        db.connection()
+            .execute("INSERT INTO notes (title) VALUES ('Still writable')", [])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_invalid_query() {
+        let (_db, index) = notes_index();
+        assert!(index.search(&SearchQuery::new("cat AND")).is_err());
+    }
+}
@@ -1,11 +1,25 @@
 use database::*;
-use rusqlite::params;
+use rusqlite::{params, Connection};
 use shared::Cat;
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    thread,
+    time::Duration,
+};
+
+mod common;
+use common::fixture_cats_connection;
 
 #[cfg(test)]
 mod tests_sqlite {
     use super::*;
 
+    fn count_cats(connection: &Connection) -> i64 {
+        connection
+            .query_row("SELECT COUNT(*) FROM cats", [], |row| row.get(0))
+            .unwrap()
+    }
+
     #[test]
     #[ignore]
     fn test_create_sqlite_database() {
@@ -81,4 +95,129 @@ mod tests_sqlite {
 
         assert!(transaction.is_ok());
     }
+
+    #[test]
+    fn test_with_transaction_commit_and_rollback() {
+        let connection = fixture_cats_connection();
+        let retry = RetryPolicy::none();
+
+        let inserted = with_transaction(&connection, TransactionMode::Deferred, &retry, |tx| {
+            tx.execute("INSERT INTO cats (name) VALUES ('Tom')", [])?;
+            tx.execute("INSERT INTO cats (name) VALUES ('Felix')", [])?;
+            Ok(2)
+        });
+        assert_eq!(inserted.unwrap(), 2);
+        assert_eq!(count_cats(&connection), 2);
+
+        let failed: Result<(), _> =
+            with_transaction(&connection, TransactionMode::Immediate, &retry, |tx| {
+                tx.execute("INSERT INTO cats (name) VALUES ('Ghost')", [])?;
+                tx.execute("INSERT INTO cats (name) VALUES (NULL)", [])?;
+                Ok(())
+            });
+        assert!(matches!(failed, Err(DatabaseError::ConstraintViolation(_))));
+        assert_eq!(count_cats(&connection), 2);
+        assert!(connection.is_autocommit());
+    }
+
+    #[test]
+    fn test_with_transaction_rolls_back_on_panic() {
+        let connection = fixture_cats_connection();
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let _: Result<(), DatabaseError> = with_transaction(
+                &connection,
+                TransactionMode::Exclusive,
+                &RetryPolicy::none(),
+                |tx| {
+                    tx.execute("INSERT INTO cats (name) VALUES ('Tom')", [])?;
+                    panic!("boom");
+                },
+            );
+        }));
+        assert!(result.is_err());
+        assert_eq!(count_cats(&connection), 0);
+        assert!(connection.is_autocommit());
+    }
+
+    #[test]
+    fn test_nested_savepoints() {
+        let connection = fixture_cats_connection();
+        with_transaction(
+            &connection,
+            TransactionMode::Deferred,
+            &RetryPolicy::none(),
+            |tx| {
+                tx.execute("INSERT INTO cats (name) VALUES ('Tom')", [])?;
+                tx.savepoint(|outer| {
+                    assert_eq!(outer.depth(), 1);
+                    outer.execute("INSERT INTO cats (name) VALUES ('Felix')", [])?;
+                    let inner: Result<(), _> = outer.savepoint(|inner| {
+                        assert_eq!(inner.depth(), 2);
+                        inner.execute("INSERT INTO cats (name) VALUES ('Ghost')", [])?;
+                        Err(DatabaseError::Invalid("discard the ghost".to_string()))
+                    });
+                    assert!(inner.is_err());
+                    Ok(())
+                })?;
+                let discarded: Result<(), _> = tx.savepoint(|sp| {
+                    sp.execute("DELETE FROM cats", [])?;
+                    Err(DatabaseError::Invalid("keep everyone".to_string()))
+                });
+                assert!(discarded.is_err());
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        let names: Vec<String> = connection
+            .prepare("SELECT name FROM cats ORDER BY rowid")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(names, vec!["Tom", "Felix"]);
+    }
+
+    #[test]
+    fn test_with_transaction_retries_when_busy() {
+        let path = std::env::temp_dir().join("tests_sqlite_busy.db");
+        let _ = std::fs::remove_file(&path);
+        let connection = Connection::open(&path).unwrap();
+        connection
+            .execute_batch("CREATE TABLE cats (name TEXT NOT NULL)")
+            .unwrap();
+        connection.busy_timeout(Duration::ZERO).unwrap();
+
+        let locker = Connection::open(&path).unwrap();
+        locker.execute_batch("BEGIN IMMEDIATE").unwrap();
+
+        let no_retry: Result<(), _> = with_transaction(
+            &connection,
+            TransactionMode::Immediate,
+            &RetryPolicy::none(),
+            |_| Ok(()),
+        );
+        assert!(is_busy_error(&no_retry.unwrap_err()));
+
+        let holder = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            locker.execute_batch("COMMIT").unwrap();
+        });
+        let retry = RetryPolicy {
+            max_retries: 20,
+            initial_backoff: Duration::from_millis(5),
+            max_backoff: Duration::from_millis(50),
+        };
+        with_transaction(&connection, TransactionMode::Immediate, &retry, |tx| {
+            tx.execute("INSERT INTO cats (name) VALUES ('Tom')", [])?;
+            Ok(())
+        })
+        .unwrap();
+        holder.join().unwrap();
+
+        assert_eq!(count_cats(&connection), 1);
+        drop(connection);
+        std::fs::remove_file(&path).unwrap();
+    }
 }
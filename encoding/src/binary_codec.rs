@@ -0,0 +1,987 @@
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// The byte order of a multi-byte field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+/// An integer type of a field, a length prefix, an enum tag or a bit field
+/// container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntType {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+}
+
+impl IntType {
+    /// The size in bytes
+    pub fn size(&self) -> usize {
+        match self {
+            IntType::U8 | IntType::I8 => 1,
+            IntType::U16 | IntType::I16 => 2,
+            IntType::U32 | IntType::I32 => 4,
+            IntType::U64 | IntType::I64 => 8,
+        }
+    }
+
+    /// Whether the type is signed
+    pub fn signed(&self) -> bool {
+        matches!(
+            self,
+            IntType::I8 | IntType::I16 | IntType::I32 | IntType::I64
+        )
+    }
+
+    fn bits(&self) -> u32 {
+        self.size() as u32 * 8
+    }
+
+    fn range(&self) -> (i128, i128) {
+        match self.signed() {
+            true => (
+                -(1i128 << (self.bits() - 1)),
+                (1i128 << (self.bits() - 1)) - 1,
+            ),
+            false => (0, (1i128 << self.bits()) - 1),
+        }
+    }
+}
+
+/// The length of a byte string, text or array field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Length {
+    /// A fixed number of bytes or items. Text shorter than that is padded
+    /// with zero bytes, which are trimmed when decoding.
+    Fixed(usize),
+    /// A number of bytes or items written before them as an integer.
+    Prefixed(IntType, Endian),
+    /// Every remaining byte, or items up to the end of the input. Items of
+    /// arrays without a fixed length must take at least one byte.
+    Remaining,
+}
+
+/// The layout of a field of a `RecordLayout`.
+///
+/// Decoded values are JSON values: integers and floats are numbers, byte
+/// strings and arrays are arrays, text is a string, bit fields and records
+/// are objects, and enum variants are the variant name if they carry no
+/// fields or an object with the variant name as the only key otherwise, like
+/// serde does for enums.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BinaryField {
+    Int(IntType, Endian),
+    F32(Endian),
+    F64(Endian),
+    Bytes(Length),
+    Text(Length),
+    Array(Box<BinaryField>, Length),
+    /// Named bit fields packed in an integer, the first one in the most
+    /// significant bits. Every field takes at least one bit.
+    Bits(IntType, Endian, Vec<(String, u32)>),
+    Enum(EnumLayout),
+    Record(RecordLayout),
+}
+
+impl BinaryField {
+    /// An unsigned byte
+    pub fn u8() -> Self {
+        BinaryField::Int(IntType::U8, Endian::Little)
+    }
+
+    /// An unsigned 16-bit integer
+    pub fn u16(endian: Endian) -> Self {
+        BinaryField::Int(IntType::U16, endian)
+    }
+
+    /// An unsigned 32-bit integer
+    pub fn u32(endian: Endian) -> Self {
+        BinaryField::Int(IntType::U32, endian)
+    }
+
+    /// An unsigned 64-bit integer
+    pub fn u64(endian: Endian) -> Self {
+        BinaryField::Int(IntType::U64, endian)
+    }
+
+    /// A signed byte
+    pub fn i8() -> Self {
+        BinaryField::Int(IntType::I8, Endian::Little)
+    }
+
+    /// A signed 16-bit integer
+    pub fn i16(endian: Endian) -> Self {
+        BinaryField::Int(IntType::I16, endian)
+    }
+
+    /// A signed 32-bit integer
+    pub fn i32(endian: Endian) -> Self {
+        BinaryField::Int(IntType::I32, endian)
+    }
+
+    /// A signed 64-bit integer
+    pub fn i64(endian: Endian) -> Self {
+        BinaryField::Int(IntType::I64, endian)
+    }
+
+    /// A byte string
+    pub fn bytes(length: Length) -> Self {
+        BinaryField::Bytes(length)
+    }
+
+    /// UTF-8 text
+    pub fn text(length: Length) -> Self {
+        BinaryField::Text(length)
+    }
+
+    /// An array of items of the same layout
+    pub fn array(item: BinaryField, length: Length) -> Self {
+        BinaryField::Array(Box::new(item), length)
+    }
+
+    /// Bit fields packed in an unsigned integer, as `(name, width)` pairs
+    pub fn bits(container: IntType, endian: Endian, fields: &[(&str, u32)]) -> Self {
+        BinaryField::Bits(
+            container,
+            endian,
+            fields
+                .iter()
+                .map(|(name, width)| (name.to_string(), *width))
+                .collect(),
+        )
+    }
+}
+
+/// An enum tagged by an integer discriminant, each variant followed by its
+/// own fields.
+///
+/// # Example
+///
+/// ```
+/// use encoding::{BinaryField, Endian, EnumLayout, IntType, Length, RecordLayout};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// enum Message {
+///     Ping,
+///     Say { text: String },
+/// }
+///
+/// let layout = RecordLayout::new().field(
+///     "message",
+///     BinaryField::Enum(
+///         EnumLayout::new(IntType::U8, Endian::Little)
+///             .variant(0, "Ping", RecordLayout::new())
+///             .variant(
+///                 1,
+///                 "Say",
+///                 RecordLayout::new().field("text", BinaryField::text(Length::Prefixed(IntType::U8, Endian::Little))),
+///             ),
+///     ),
+/// );
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Packet {
+///     message: Message,
+/// }
+///
+/// let packet = Packet { message: Message::Say { text: "hi".to_string() } };
+/// let bytes = layout.encode(&packet).unwrap();
+///
+/// assert_eq!(bytes, vec![1, 2, b'h', b'i']);
+/// assert_eq!(layout.decode::<Packet>(&bytes).unwrap(), packet);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumLayout {
+    tag: IntType,
+    endian: Endian,
+    variants: Vec<(u64, String, RecordLayout)>,
+}
+
+impl EnumLayout {
+    /// Create an enum tagged by an integer without variants
+    pub fn new(tag: IntType, endian: Endian) -> Self {
+        EnumLayout {
+            tag,
+            endian,
+            variants: Vec::new(),
+        }
+    }
+
+    /// Add a variant with its discriminant and fields
+    pub fn variant(mut self, discriminant: u64, name: &str, fields: RecordLayout) -> Self {
+        self.variants.push((discriminant, name.to_string(), fields));
+        self
+    }
+}
+
+/// The layout of a binary record: named fields one after the other.
+///
+/// Records are encoded from and decoded into any serde type, or JSON values.
+///
+/// # Example
+///
+/// ```
+/// use encoding::{BinaryField, Endian, IntType, Length, Payload, RecordLayout};
+///
+/// let layout = RecordLayout::new()
+///     .field("kind", BinaryField::u8())
+///     .field("value", BinaryField::u16(Endian::Little));
+///
+/// let bytes = layout.encode(&Payload { kind: 0, value: 42 }).unwrap();
+/// assert_eq!(bytes, vec![0, 42, 0]);
+/// assert_eq!(layout.decode::<Payload>(&bytes).unwrap(), Payload { kind: 0, value: 42 });
+///
+/// let error = layout.decode::<Payload>(&[0, 42]).unwrap_err();
+/// assert_eq!(error.to_string(), "Unexpected end of input, 2 bytes needed and 1 left at byte 1 in value");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecordLayout {
+    fields: Vec<(String, BinaryField)>,
+}
+
+impl RecordLayout {
+    /// Create a layout without fields
+    pub fn new() -> Self {
+        RecordLayout::default()
+    }
+
+    /// Add a field after the previous ones
+    pub fn field(mut self, name: &str, field: BinaryField) -> Self {
+        self.fields.push((name.to_string(), field));
+        self
+    }
+
+    /// Encode a value into bytes
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - A value serializing to a map with every field of the layout.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the bytes, or an error with the offset and path of the
+    /// field that could not be encoded.
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        let value = serde_json::to_value(value).map_err(|e| CodecError {
+            offset: 0,
+            path: String::new(),
+            kind: CodecErrorKind::Serde(e.to_string()),
+        })?;
+        self.encode_value(&value)
+    }
+
+    /// Encode a JSON value into bytes
+    pub fn encode_value(&self, value: &Value) -> Result<Vec<u8>, CodecError> {
+        let mut encoder = Encoder::default();
+        self.write(value, &mut encoder)?;
+        Ok(encoder.bytes)
+    }
+
+    /// Decode bytes into a value
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The encoded record, without trailing bytes.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the value, or an error with the offset and path of the
+    /// field that could not be decoded.
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        let value = self.decode_value(bytes)?;
+        serde_json::from_value(value).map_err(|e| CodecError {
+            offset: 0,
+            path: String::new(),
+            kind: CodecErrorKind::Serde(e.to_string()),
+        })
+    }
+
+    /// Decode bytes into a JSON value
+    pub fn decode_value(&self, bytes: &[u8]) -> Result<Value, CodecError> {
+        let mut decoder = Decoder::new(bytes, 0);
+        let value = self.read(&mut decoder)?;
+        decoder.finish()?;
+        Ok(value)
+    }
+
+    fn write(&self, value: &Value, encoder: &mut Encoder) -> Result<(), CodecError> {
+        let object = value
+            .as_object()
+            .ok_or_else(|| encoder.error(CodecErrorKind::TypeMismatch("an object")))?;
+        for (name, field) in &self.fields {
+            encoder.path.push(name.clone());
+            let value = object
+                .get(name)
+                .ok_or_else(|| encoder.error(CodecErrorKind::MissingField))?;
+            write_field(field, value, encoder)?;
+            encoder.path.pop();
+        }
+        Ok(())
+    }
+
+    fn read(&self, decoder: &mut Decoder) -> Result<Value, CodecError> {
+        let mut object = Map::new();
+        for (name, field) in &self.fields {
+            decoder.path.push(name.clone());
+            object.insert(name.clone(), read_field(field, decoder)?);
+            decoder.path.pop();
+        }
+        Ok(Value::Object(object))
+    }
+}
+
+fn write_field(
+    field: &BinaryField,
+    value: &Value,
+    encoder: &mut Encoder,
+) -> Result<(), CodecError> {
+    match field {
+        BinaryField::Int(int, endian) => encoder.write_int(*int, *endian, value),
+        BinaryField::F32(endian) => {
+            let number = encoder.float(value)? as f32;
+            encoder.write_raw(IntType::U32, *endian, u64::from(number.to_bits()));
+            Ok(())
+        }
+        BinaryField::F64(endian) => {
+            let number = encoder.float(value)?;
+            encoder.write_raw(IntType::U64, *endian, number.to_bits());
+            Ok(())
+        }
+        BinaryField::Bytes(length) => {
+            let bytes = value
+                .as_array()
+                .and_then(|items| {
+                    items
+                        .iter()
+                        .map(|item| item.as_u64().and_then(|byte| u8::try_from(byte).ok()))
+                        .collect::<Option<Vec<u8>>>()
+                })
+                .ok_or_else(|| encoder.error(CodecErrorKind::TypeMismatch("an array of bytes")))?;
+            encoder.write_length(*length, bytes.len())?;
+            encoder.bytes.extend(bytes);
+            Ok(())
+        }
+        BinaryField::Text(length) => {
+            let text = value
+                .as_str()
+                .ok_or_else(|| encoder.error(CodecErrorKind::TypeMismatch("a string")))?;
+            match length {
+                Length::Fixed(size) if text.len() < *size => {
+                    encoder.bytes.extend(text.as_bytes());
+                    encoder
+                        .bytes
+                        .resize(encoder.bytes.len() + size - text.len(), 0);
+                }
+                _ => {
+                    encoder.write_length(*length, text.len())?;
+                    encoder.bytes.extend(text.as_bytes());
+                }
+            }
+            Ok(())
+        }
+        BinaryField::Array(item, length) => {
+            let items = value
+                .as_array()
+                .ok_or_else(|| encoder.error(CodecErrorKind::TypeMismatch("an array")))?;
+            encoder.write_length(*length, items.len())?;
+            for (index, value) in items.iter().enumerate() {
+                encoder.path.push(index.to_string());
+                write_field(item, value, encoder)?;
+                encoder.path.pop();
+            }
+            Ok(())
+        }
+        BinaryField::Bits(container, endian, parts) => {
+            check_bits(*container, parts).map_err(|kind| encoder.error(kind))?;
+            let object = value
+                .as_object()
+                .ok_or_else(|| encoder.error(CodecErrorKind::TypeMismatch("an object")))?;
+            let mut raw = 0u64;
+            for (name, width) in parts {
+                encoder.path.push(name.clone());
+                let bits = object
+                    .get(name)
+                    .ok_or_else(|| encoder.error(CodecErrorKind::MissingField))?
+                    .as_u64()
+                    .ok_or_else(|| {
+                        encoder.error(CodecErrorKind::TypeMismatch("an unsigned integer"))
+                    })?;
+                if bits > mask(*width) {
+                    return Err(encoder.error(CodecErrorKind::OutOfRange(bits.to_string())));
+                }
+                raw = raw.checked_shl(*width).unwrap_or(0) | bits;
+                encoder.path.pop();
+            }
+            let unused: u32 = container.bits() - parts.iter().map(|(_, width)| width).sum::<u32>();
+            encoder.write_raw(*container, *endian, raw.checked_shl(unused).unwrap_or(0));
+            Ok(())
+        }
+        BinaryField::Enum(layout) => {
+            let (name, fields) = match value {
+                Value::String(name) => (name, Value::Object(Map::new())),
+                Value::Object(object) if object.len() == 1 => {
+                    let (name, fields) = object.iter().next().unwrap();
+                    (name, fields.clone())
+                }
+                _ => {
+                    return Err(encoder.error(CodecErrorKind::TypeMismatch(
+                        "a variant name or an object with a variant name as key",
+                    )))
+                }
+            };
+            let (discriminant, _, variant) = layout
+                .variants
+                .iter()
+                .find(|(_, variant, _)| variant == name)
+                .ok_or_else(|| encoder.error(CodecErrorKind::UnknownVariant(name.clone())))?;
+            encoder.write_int(layout.tag, layout.endian, &Value::from(*discriminant))?;
+            encoder.path.push(name.clone());
+            variant.write(&fields, encoder)?;
+            encoder.path.pop();
+            Ok(())
+        }
+        BinaryField::Record(layout) => layout.write(value, encoder),
+    }
+}
+
+fn read_field(field: &BinaryField, decoder: &mut Decoder) -> Result<Value, CodecError> {
+    match field {
+        BinaryField::Int(int, endian) => decoder.read_int(*int, *endian),
+        BinaryField::F32(endian) => {
+            let bits = decoder.read_raw(IntType::U32, *endian)? as u32;
+            Ok(Value::from(f64::from(f32::from_bits(bits))))
+        }
+        BinaryField::F64(endian) => {
+            let bits = decoder.read_raw(IntType::U64, *endian)?;
+            Ok(Value::from(f64::from_bits(bits)))
+        }
+        BinaryField::Bytes(length) => {
+            let size = decoder.read_length(*length)?;
+            let bytes = decoder.take(size)?;
+            Ok(Value::Array(
+                bytes.iter().map(|&byte| Value::from(byte)).collect(),
+            ))
+        }
+        BinaryField::Text(length) => {
+            let size = decoder.read_length(*length)?;
+            let start = decoder.offset;
+            let mut bytes = decoder.take(size)?;
+            if let Length::Fixed(_) = length {
+                while let [rest @ .., 0] = bytes {
+                    bytes = rest;
+                }
+            }
+            match std::str::from_utf8(bytes) {
+                Ok(text) => Ok(Value::from(text)),
+                Err(e) => {
+                    decoder.offset = start + e.valid_up_to();
+                    Err(decoder.error(CodecErrorKind::InvalidUtf8))
+                }
+            }
+        }
+        BinaryField::Array(item, length) => {
+            let count = match length {
+                Length::Remaining => None,
+                length => Some(decoder.read_length(*length)?),
+            };
+            let mut items = Vec::new();
+            while count.map_or(decoder.remaining() > 0, |count| items.len() < count) {
+                let start = decoder.offset;
+                decoder.path.push(items.len().to_string());
+                items.push(read_field(item, decoder)?);
+                decoder.path.pop();
+                // Items without bytes would let the input ask for endless items
+                if decoder.offset == start && !matches!(length, Length::Fixed(_)) {
+                    return Err(decoder.error(CodecErrorKind::InvalidLayout(
+                        "array items of variable length arrays must take bytes".to_string(),
+                    )));
+                }
+            }
+            Ok(Value::Array(items))
+        }
+        BinaryField::Bits(container, endian, parts) => {
+            check_bits(*container, parts).map_err(|kind| decoder.error(kind))?;
+            let raw = decoder.read_raw(*container, *endian)?;
+            let mut shift = container.bits();
+            let mut object = Map::new();
+            for (name, width) in parts {
+                shift -= width;
+                let bits = raw.checked_shr(shift).unwrap_or(0) & mask(*width);
+                object.insert(name.clone(), Value::from(bits));
+            }
+            Ok(Value::Object(object))
+        }
+        BinaryField::Enum(layout) => {
+            let start = decoder.offset;
+            let discriminant = decoder.read_raw(layout.tag, layout.endian)?;
+            let Some((_, name, variant)) = layout
+                .variants
+                .iter()
+                .find(|(variant, _, _)| *variant == discriminant)
+            else {
+                decoder.offset = start;
+                return Err(decoder.error(CodecErrorKind::UnknownDiscriminant(discriminant)));
+            };
+            decoder.path.push(name.clone());
+            let fields = variant.read(decoder)?;
+            decoder.path.pop();
+            match variant.fields.is_empty() {
+                true => Ok(Value::from(name.as_str())),
+                false => Ok(Value::Object(Map::from_iter([(name.clone(), fields)]))),
+            }
+        }
+        BinaryField::Record(layout) => layout.read(decoder),
+    }
+}
+
+fn mask(width: u32) -> u64 {
+    match width {
+        64.. => u64::MAX,
+        width => (1 << width) - 1,
+    }
+}
+
+fn check_bits(container: IntType, parts: &[(String, u32)]) -> Result<(), CodecErrorKind> {
+    if let Some((name, _)) = parts.iter().find(|(_, width)| *width == 0) {
+        return Err(CodecErrorKind::InvalidLayout(format!(
+            "bit field {} has no bits",
+            name
+        )));
+    }
+    let width = parts
+        .iter()
+        .try_fold(0u32, |sum, (_, width)| sum.checked_add(*width))
+        .unwrap_or(u32::MAX);
+    match container.signed() || width > container.bits() {
+        true => Err(CodecErrorKind::InvalidLayout(format!(
+            "{} bits don't fit in {:?}",
+            width, container
+        ))),
+        false => Ok(()),
+    }
+}
+
+#[derive(Default)]
+struct Encoder {
+    bytes: Vec<u8>,
+    path: Vec<String>,
+}
+
+impl Encoder {
+    fn error(&self, kind: CodecErrorKind) -> CodecError {
+        CodecError {
+            offset: self.bytes.len(),
+            path: self.path.join("."),
+            kind,
+        }
+    }
+
+    fn float(&self, value: &Value) -> Result<f64, CodecError> {
+        value
+            .as_f64()
+            .ok_or_else(|| self.error(CodecErrorKind::TypeMismatch("a number")))
+    }
+
+    fn write_raw(&mut self, int: IntType, endian: Endian, raw: u64) {
+        let bytes = &raw.to_le_bytes()[..int.size()];
+        match endian {
+            Endian::Little => self.bytes.extend(bytes),
+            Endian::Big => self.bytes.extend(bytes.iter().rev()),
+        }
+    }
+
+    fn write_int(&mut self, int: IntType, endian: Endian, value: &Value) -> Result<(), CodecError> {
+        let number = match (value.as_u64(), value.as_i64()) {
+            (Some(number), _) => i128::from(number),
+            (None, Some(number)) => i128::from(number),
+            _ => return Err(self.error(CodecErrorKind::TypeMismatch("an integer"))),
+        };
+        let (min, max) = int.range();
+        if number < min || number > max {
+            return Err(self.error(CodecErrorKind::OutOfRange(number.to_string())));
+        }
+        self.write_raw(int, endian, number as u64);
+        Ok(())
+    }
+
+    fn write_length(&mut self, length: Length, actual: usize) -> Result<(), CodecError> {
+        match length {
+            Length::Fixed(expected) if expected != actual => {
+                Err(self.error(CodecErrorKind::LengthMismatch { expected, actual }))
+            }
+            Length::Prefixed(int, endian) => self.write_int(int, endian, &Value::from(actual)),
+            _ => Ok(()),
+        }
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    base: usize,
+    path: Vec<String>,
+}
+
+impl<'a> Decoder<'a> {
+    /// Decode `bytes` found at offset `base` of the input
+    fn new(bytes: &'a [u8], base: usize) -> Self {
+        Decoder {
+            bytes,
+            offset: 0,
+            base,
+            path: Vec::new(),
+        }
+    }
+
+    fn error(&self, kind: CodecErrorKind) -> CodecError {
+        CodecError {
+            offset: self.base + self.offset,
+            path: self.path.join("."),
+            kind,
+        }
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.offset
+    }
+
+    fn take(&mut self, size: usize) -> Result<&'a [u8], CodecError> {
+        if size > self.remaining() {
+            return Err(self.error(CodecErrorKind::UnexpectedEnd {
+                needed: size,
+                available: self.remaining(),
+            }));
+        }
+        let bytes = &self.bytes[self.offset..self.offset + size];
+        self.offset += size;
+        Ok(bytes)
+    }
+
+    fn read_raw(&mut self, int: IntType, endian: Endian) -> Result<u64, CodecError> {
+        let bytes = self.take(int.size())?;
+        let mut raw = [0u8; 8];
+        match endian {
+            Endian::Little => raw[..bytes.len()].copy_from_slice(bytes),
+            Endian::Big => raw[..bytes.len()]
+                .iter_mut()
+                .zip(bytes.iter().rev())
+                .for_each(|(raw, byte)| *raw = *byte),
+        }
+        Ok(u64::from_le_bytes(raw))
+    }
+
+    fn read_int(&mut self, int: IntType, endian: Endian) -> Result<Value, CodecError> {
+        let raw = self.read_raw(int, endian)?;
+        match int.signed() {
+            true => {
+                let unused = 64 - int.bits();
+                Ok(Value::from(((raw << unused) as i64) >> unused))
+            }
+            false => Ok(Value::from(raw)),
+        }
+    }
+
+    fn read_length(&mut self, length: Length) -> Result<usize, CodecError> {
+        match length {
+            Length::Fixed(size) => Ok(size),
+            Length::Remaining => Ok(self.remaining()),
+            Length::Prefixed(int, endian) => {
+                let start = self.offset;
+                let value = self.read_int(int, endian)?;
+                value
+                    .as_u64()
+                    .and_then(|size| usize::try_from(size).ok())
+                    .ok_or_else(|| {
+                        self.offset = start;
+                        self.error(CodecErrorKind::OutOfRange(value.to_string()))
+                    })
+            }
+        }
+    }
+
+    fn finish(&self) -> Result<(), CodecError> {
+        match self.remaining() {
+            0 => Ok(()),
+            remaining => Err(self.error(CodecErrorKind::TrailingBytes(remaining))),
+        }
+    }
+}
+
+/// A checksum of the payload of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    /// The wrapping sum of the bytes, in one byte.
+    Sum8,
+    /// The XOR of the bytes, in one byte.
+    Xor8,
+    /// CRC-16/CCITT-FALSE, in two bytes.
+    Crc16,
+    /// CRC-32 as used by zip and Ethernet, in four bytes.
+    Crc32,
+}
+
+impl Checksum {
+    /// Compute the checksum of some bytes
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use encoding::Checksum;
+    ///
+    /// assert_eq!(Checksum::Crc32.compute(b"123456789"), 0xCBF43926);
+    /// assert_eq!(Checksum::Crc16.compute(b"123456789"), 0x29B1);
+    /// ```
+    pub fn compute(&self, bytes: &[u8]) -> u64 {
+        match self {
+            Checksum::Sum8 => {
+                u64::from(bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)))
+            }
+            Checksum::Xor8 => u64::from(bytes.iter().fold(0u8, |xor, byte| xor ^ byte)),
+            Checksum::Crc16 => {
+                let crc = bytes.iter().fold(0xFFFFu16, |crc, byte| {
+                    (0..8).fold(crc ^ (u16::from(*byte) << 8), |crc, _| match crc & 0x8000 {
+                        0 => crc << 1,
+                        _ => (crc << 1) ^ 0x1021,
+                    })
+                });
+                u64::from(crc)
+            }
+            Checksum::Crc32 => {
+                let crc = bytes.iter().fold(0xFFFF_FFFFu32, |crc, byte| {
+                    (0..8).fold(crc ^ u32::from(*byte), |crc, _| match crc & 1 {
+                        0 => crc >> 1,
+                        _ => (crc >> 1) ^ 0xEDB8_8320,
+                    })
+                });
+                u64::from(!crc)
+            }
+        }
+    }
+
+    fn int_type(&self) -> IntType {
+        match self {
+            Checksum::Sum8 | Checksum::Xor8 => IntType::U8,
+            Checksum::Crc16 => IntType::U16,
+            Checksum::Crc32 => IntType::U32,
+        }
+    }
+}
+
+/// A record wrapped in a frame: an optional magic number, an optional length
+/// prefix, the record and an optional checksum of the record bytes.
+///
+/// # Example
+///
+/// ```
+/// use encoding::{BinaryField, Checksum, CodecErrorKind, Endian, FrameLayout, IntType, Payload, RecordLayout};
+///
+/// let frame = FrameLayout::new(
+///     RecordLayout::new()
+///         .field("kind", BinaryField::u8())
+///         .field("value", BinaryField::u16(Endian::Big)),
+/// )
+/// .magic(&[0xCA, 0xFE])
+/// .length_prefix(IntType::U8, Endian::Big)
+/// .checksum(Checksum::Xor8, Endian::Big);
+///
+/// let bytes = frame.encode(&Payload { kind: 1, value: 0x0203 }).unwrap();
+/// assert_eq!(bytes, vec![0xCA, 0xFE, 3, 1, 2, 3, 1 ^ 2 ^ 3]);
+///
+/// let mut corrupted = bytes.clone();
+/// corrupted[4] = 9;
+/// let error = frame.decode::<Payload>(&corrupted).unwrap_err();
+/// assert_eq!(error.offset, 6);
+/// assert!(matches!(error.kind, CodecErrorKind::ChecksumMismatch { .. }));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameLayout {
+    record: RecordLayout,
+    magic: Vec<u8>,
+    length: Option<(IntType, Endian)>,
+    checksum: Option<(Checksum, Endian)>,
+}
+
+impl FrameLayout {
+    /// Frame a record, without magic number, length prefix or checksum
+    pub fn new(record: RecordLayout) -> Self {
+        FrameLayout {
+            record,
+            magic: Vec::new(),
+            length: None,
+            checksum: None,
+        }
+    }
+
+    /// Start frames with a magic number
+    pub fn magic(mut self, magic: &[u8]) -> Self {
+        self.magic = magic.to_vec();
+        self
+    }
+
+    /// Write the record size before the record
+    pub fn length_prefix(mut self, int: IntType, endian: Endian) -> Self {
+        self.length = Some((int, endian));
+        self
+    }
+
+    /// End frames with a checksum of the record bytes
+    pub fn checksum(mut self, checksum: Checksum, endian: Endian) -> Self {
+        self.checksum = Some((checksum, endian));
+        self
+    }
+
+    /// Encode a value into a frame
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        let record = self.record.encode(value)?;
+        let mut encoder = Encoder::default();
+        encoder.bytes.extend(&self.magic);
+        if let Some((int, endian)) = self.length {
+            encoder.write_length(Length::Prefixed(int, endian), record.len())?;
+        }
+        encoder.bytes.extend(&record);
+        if let Some((checksum, endian)) = self.checksum {
+            encoder.write_raw(checksum.int_type(), endian, checksum.compute(&record));
+        }
+        Ok(encoder.bytes)
+    }
+
+    /// Decode a frame into a value
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        let value = self.decode_value(bytes)?;
+        serde_json::from_value(value).map_err(|e| CodecError {
+            offset: 0,
+            path: String::new(),
+            kind: CodecErrorKind::Serde(e.to_string()),
+        })
+    }
+
+    /// Decode a frame into a JSON value
+    ///
+    /// The magic number and checksum are checked before the record is decoded.
+    pub fn decode_value(&self, bytes: &[u8]) -> Result<Value, CodecError> {
+        let mut frame = Decoder::new(bytes, 0);
+        if frame.take(self.magic.len())? != self.magic.as_slice() {
+            frame.offset = 0;
+            return Err(frame.error(CodecErrorKind::BadMagic));
+        }
+        let checksum_size = self
+            .checksum
+            .map_or(0, |(checksum, _)| checksum.int_type().size());
+        let size = match self.length {
+            Some((int, endian)) => frame.read_length(Length::Prefixed(int, endian))?,
+            None => frame.remaining().saturating_sub(checksum_size),
+        };
+        let start = frame.offset;
+        let record = frame.take(size)?;
+
+        if let Some((checksum, endian)) = self.checksum {
+            let expected = frame.read_raw(checksum.int_type(), endian)?;
+            let actual = checksum.compute(record);
+            if expected != actual {
+                frame.offset -= checksum_size;
+                return Err(frame.error(CodecErrorKind::ChecksumMismatch { expected, actual }));
+            }
+        }
+        frame.finish()?;
+
+        let mut decoder = Decoder::new(record, start);
+        let value = self.record.read(&mut decoder)?;
+        decoder.finish()?;
+        Ok(value)
+    }
+}
+
+/// An error encoding or decoding a binary record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodecError {
+    /// The offset in bytes where decoding or encoding failed.
+    pub offset: usize,
+    /// The path of the field that failed, names and array indexes joined
+    /// with dots, empty for the whole record.
+    pub path: String,
+    /// What went wrong.
+    pub kind: CodecErrorKind,
+}
+
+/// The cause of a `CodecError`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CodecErrorKind {
+    /// The input ended before the field.
+    UnexpectedEnd { needed: usize, available: usize },
+    /// Bytes are left after the record.
+    TrailingBytes(usize),
+    /// Text is not valid UTF-8.
+    InvalidUtf8,
+    /// An enum tag has no variant.
+    UnknownDiscriminant(u64),
+    /// A value names no variant of an enum.
+    UnknownVariant(String),
+    /// The frame doesn't start with the magic number.
+    BadMagic,
+    /// The frame checksum doesn't match its record.
+    ChecksumMismatch { expected: u64, actual: u64 },
+    /// A number doesn't fit its field.
+    OutOfRange(String),
+    /// A value doesn't have the length of its fixed size field.
+    LengthMismatch { expected: usize, actual: usize },
+    /// A record field is missing from the value.
+    MissingField,
+    /// A value doesn't have the type of its field.
+    TypeMismatch(&'static str),
+    /// The layout can't be used, e.g. bit fields wider than their container.
+    InvalidLayout(String),
+    /// The value couldn't be converted from or to a serde type.
+    Serde(String),
+}
+
+impl fmt::Display for CodecErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CodecErrorKind::UnexpectedEnd { needed, available } => write!(
+                f,
+                "Unexpected end of input, {} bytes needed and {} left",
+                needed, available
+            ),
+            CodecErrorKind::TrailingBytes(count) => write!(f, "{} trailing bytes", count),
+            CodecErrorKind::InvalidUtf8 => write!(f, "Invalid UTF-8"),
+            CodecErrorKind::UnknownDiscriminant(tag) => write!(f, "Unknown discriminant {}", tag),
+            CodecErrorKind::UnknownVariant(name) => write!(f, "Unknown variant {}", name),
+            CodecErrorKind::BadMagic => write!(f, "Bad magic number"),
+            CodecErrorKind::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "Checksum mismatch, expected {:#x} and computed {:#x}",
+                expected, actual
+            ),
+            CodecErrorKind::OutOfRange(value) => write!(f, "{} is out of range", value),
+            CodecErrorKind::LengthMismatch { expected, actual } => {
+                write!(f, "Expected a length of {}, found {}", expected, actual)
+            }
+            CodecErrorKind::MissingField => write!(f, "Missing field"),
+            CodecErrorKind::TypeMismatch(expected) => write!(f, "Expected {}", expected),
+            CodecErrorKind::InvalidLayout(reason) => write!(f, "Invalid layout: {}", reason),
+            CodecErrorKind::Serde(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.kind, self.offset)?;
+        if !self.path.is_empty() {
+            write!(f, " in {}", self.path)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CodecError {}
@@ -1,5 +1,16 @@
+use crate::DataValue;
+use data_encoding::{
+    BASE32, BASE32HEX, BASE32_NOPAD, BASE64, BASE64URL, BASE64URL_NOPAD, BASE64_NOPAD, HEXUPPER,
+    HEXUPPER_PERMISSIVE,
+};
+use encoding_rs::{
+    DecoderResult, EncoderResult, Encoding, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252,
+};
 use percent_encoding::{percent_decode, utf8_percent_encode, AsciiSet, CONTROLS};
-use std::str::Utf8Error;
+use quoted_printable::ParseMode;
+use serde::Serialize;
+use std::{fmt, str::Utf8Error};
+use url::form_urlencoded::{self, Serializer};
 
 const FRAGMENT: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'<').add(b'>').add(b'`');
 
@@ -54,3 +65,596 @@ pub fn percent_decode_string(input: &str) -> Result<String, Utf8Error> {
     let iter = percent_decode(input.as_bytes());
     iter.decode_utf8().map(|cow| cow.to_string())
 }
+
+/// An error encoding or decoding text or binary data.
+#[derive(Debug)]
+pub enum CharsetError {
+    Base(data_encoding::DecodeError),
+    Base58(bs58::decode::Error),
+    Json(serde_json::Error),
+    /// The bytes at the offset are not valid in the charset.
+    Malformed {
+        charset: Charset,
+        offset: usize,
+    },
+    /// The character has no representation in the charset.
+    Unmappable {
+        charset: Charset,
+        character: char,
+    },
+    /// Only objects can be written as a form.
+    NotAnObject,
+}
+
+impl fmt::Display for CharsetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CharsetError::Base(e) => write!(f, "Base encoding error: {}", e),
+            CharsetError::Base58(e) => write!(f, "Base58 error: {}", e),
+            CharsetError::Json(e) => write!(f, "JSON error: {}", e),
+            CharsetError::Malformed { charset, offset } => {
+                write!(f, "Malformed {} at byte {}", charset.name(), offset)
+            }
+            CharsetError::Unmappable { charset, character } => {
+                write!(f, "{:?} can't be encoded in {}", character, charset.name())
+            }
+            CharsetError::NotAnObject => write!(f, "Only objects can be form-urlencoded"),
+        }
+    }
+}
+
+impl std::error::Error for CharsetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CharsetError::Base(e) => Some(e),
+            CharsetError::Base58(e) => Some(e),
+            CharsetError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<data_encoding::DecodeError> for CharsetError {
+    fn from(e: data_encoding::DecodeError) -> Self {
+        CharsetError::Base(e)
+    }
+}
+
+impl From<bs58::decode::Error> for CharsetError {
+    fn from(e: bs58::decode::Error) -> Self {
+        CharsetError::Base58(e)
+    }
+}
+
+impl From<serde_json::Error> for CharsetError {
+    fn from(e: serde_json::Error) -> Self {
+        CharsetError::Json(e)
+    }
+}
+
+/// A binary-to-text encoding.
+///
+/// Base16 is written in uppercase and read in either case. The other
+/// encodings are strict: padding must be present exactly when the variant
+/// has it.
+///
+/// # Example
+///
+/// ```
+/// use encoding::BaseEncoding;
+///
+/// let input = b"Hamburgerfontsiv";
+///
+/// assert_eq!(BaseEncoding::Base16.encode(input), "48616D627572676572666F6E74736976");
+/// assert_eq!(BaseEncoding::Base32.encode(input), "JBQW2YTVOJTWK4TGN5XHI43JOY======");
+/// assert_eq!(BaseEncoding::Base64.encode(input), "SGFtYnVyZ2VyZm9udHNpdg==");
+/// assert_eq!(BaseEncoding::Base64UrlNoPad.encode(input), "SGFtYnVyZ2VyZm9udHNpdg");
+/// assert_eq!(BaseEncoding::Base58.encode(input), "9wPy8kqgvHV4EpZajyGK81");
+///
+/// let decoded = BaseEncoding::Base58.decode("9wPy8kqgvHV4EpZajyGK81").unwrap();
+/// assert_eq!(decoded, input);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BaseEncoding {
+    Base16,
+    Base32,
+    Base32NoPad,
+    /// Base32 with the extended hex alphabet, which keeps the sort order.
+    Base32Hex,
+    Base64,
+    Base64NoPad,
+    /// Base64 with `-` and `_` instead of `+` and `/`, safe in URLs and file
+    /// names.
+    Base64Url,
+    Base64UrlNoPad,
+    /// Base58 with the Bitcoin alphabet, without `0`, `O`, `I` and `l`.
+    Base58,
+}
+
+impl BaseEncoding {
+    /// Encode bytes as text
+    pub fn encode(&self, input: &[u8]) -> String {
+        match self.specification(false) {
+            Some(specification) => specification.encode(input),
+            None => bs58::encode(input).into_string(),
+        }
+    }
+
+    /// Decode text to bytes
+    pub fn decode(&self, input: &str) -> Result<Vec<u8>, CharsetError> {
+        match self.specification(true) {
+            Some(specification) => Ok(specification.decode(input.as_bytes())?),
+            None => Ok(bs58::decode(input).into_vec()?),
+        }
+    }
+
+    /// The `data-encoding` specification, `None` for Base58
+    fn specification(&self, decoding: bool) -> Option<data_encoding::Encoding> {
+        let specification = match self {
+            BaseEncoding::Base16 if decoding => &HEXUPPER_PERMISSIVE,
+            BaseEncoding::Base16 => &HEXUPPER,
+            BaseEncoding::Base32 => &BASE32,
+            BaseEncoding::Base32NoPad => &BASE32_NOPAD,
+            BaseEncoding::Base32Hex => &BASE32HEX,
+            BaseEncoding::Base64 => &BASE64,
+            BaseEncoding::Base64NoPad => &BASE64_NOPAD,
+            BaseEncoding::Base64Url => &BASE64URL,
+            BaseEncoding::Base64UrlNoPad => &BASE64URL_NOPAD,
+            BaseEncoding::Base58 => return None,
+        };
+        Some(specification.clone())
+    }
+}
+
+/// Encode bytes as quoted-printable text, as in MIME bodies.
+///
+/// Line breaks are written as CRLF and lines are wrapped at 76 characters
+/// with soft line breaks.
+///
+/// # Arguments
+///
+/// * `input` - The bytes to encode, usually text in some charset.
+///
+/// # Returns
+///
+/// The quoted-printable text.
+///
+/// # Example
+///
+/// ```
+/// use encoding::quoted_printable_encode;
+///
+/// assert_eq!(quoted_printable_encode("Café = 3€".as_bytes()), "Caf=C3=A9 =3D 3=E2=82=AC");
+/// ```
+pub fn quoted_printable_encode(input: &[u8]) -> String {
+    quoted_printable::encode_to_str(input)
+}
+
+/// Decode quoted-printable text.
+///
+/// Decoding is lenient, like mail clients: lowercase hex digits and bare
+/// line feeds are accepted, and invalid escapes are kept as they are written.
+///
+/// # Arguments
+///
+/// * `input` - The quoted-printable text.
+///
+/// # Returns
+///
+/// The decoded bytes.
+///
+/// # Example
+///
+/// ```
+/// use encoding::quoted_printable_decode;
+///
+/// let decoded = quoted_printable_decode("Caf=C3=A9 =3D=\r\n 3=e2=82=ac");
+/// assert_eq!(String::from_utf8(decoded).unwrap(), "Café = 3€");
+/// ```
+pub fn quoted_printable_decode(input: &str) -> Vec<u8> {
+    // Robust parsing never fails.
+    quoted_printable::decode(input, ParseMode::Robust).unwrap_or_default()
+}
+
+/// Encode an object as `application/x-www-form-urlencoded`.
+///
+/// Nested objects and arrays are written with bracketed keys, like
+/// `user[name]=Tom&user[tags][0]=cat`, with the brackets percent-encoded.
+/// Nulls are written as empty values and empty arrays and objects are left
+/// out.
+///
+/// # Arguments
+///
+/// * `value` - Any serializable value that serializes as an object.
+///
+/// # Returns
+///
+/// A `Result` with the form, `CharsetError::NotAnObject` for other values.
+///
+/// # Example
+///
+/// ```
+/// use encoding::form_urlencode;
+/// use serde_json::json;
+///
+/// let form = json!({ "user": { "name": "Tom Cat", "tags": ["cat", "blue"] } });
+///
+/// assert_eq!(
+///     form_urlencode(&form).unwrap(),
+///     "user%5Bname%5D=Tom+Cat&user%5Btags%5D%5B0%5D=cat&user%5Btags%5D%5B1%5D=blue",
+/// );
+/// ```
+pub fn form_urlencode<T: Serialize>(value: &T) -> Result<String, CharsetError> {
+    // Go through the JSON text so fields keep their order.
+    let value: DataValue = serde_json::from_str(&serde_json::to_string(value)?)?;
+    let DataValue::Object(fields) = value else {
+        return Err(CharsetError::NotAnObject);
+    };
+
+    let mut serializer = Serializer::new(String::new());
+    for (key, value) in &fields {
+        append_form_field(&mut serializer, key, value);
+    }
+    Ok(serializer.finish())
+}
+
+/// Decode an `application/x-www-form-urlencoded` form, nesting bracketed keys.
+///
+/// `a[b]=1` sets the key `b` of the object `a`, `a[]=1` appends to the array
+/// `a`, and keys indexed `0`, `1`, ... in order become arrays. Values are
+/// always strings. A repeated key keeps its last value, and a key whose
+/// parent is already a string is kept as it is written. Keys nest at most
+/// 32 brackets deep, the brackets after that make up one literal key.
+///
+/// # Arguments
+///
+/// * `input` - The form.
+///
+/// # Returns
+///
+/// The form as an object.
+///
+/// # Example
+///
+/// ```
+/// use encoding::{form_urldecode, DataValue};
+///
+/// let form = form_urldecode("user[name]=Tom+Cat&user[tags][]=cat&user[tags][]=blue");
+/// let user = form.get("user").unwrap();
+///
+/// assert_eq!(user.get("name"), Some(&DataValue::String("Tom Cat".to_string())));
+/// assert_eq!(
+///     user.get("tags"),
+///     Some(&DataValue::Array(vec![
+///         DataValue::String("cat".to_string()),
+///         DataValue::String("blue".to_string()),
+///     ])),
+/// );
+/// ```
+pub fn form_urldecode(input: &str) -> DataValue {
+    let mut fields = Vec::new();
+    for (key, value) in form_urlencoded::parse(input.as_bytes()) {
+        let segments = form_key_segments(&key).unwrap_or_else(|| vec![&key]);
+        let value = DataValue::String(value.into_owned());
+        if let Err(value) = insert_form_field(&mut fields, &segments, value) {
+            // A string is in the way, keep the full key.
+            set_form_field(&mut fields, key.to_string(), value);
+        }
+    }
+
+    DataValue::Object(
+        fields
+            .into_iter()
+            .map(|(key, value)| (key, form_arrays(value)))
+            .collect(),
+    )
+}
+
+fn append_form_field(serializer: &mut Serializer<String>, key: &str, value: &DataValue) {
+    match value {
+        DataValue::Array(values) => {
+            for (index, value) in values.iter().enumerate() {
+                append_form_field(serializer, &format!("{}[{}]", key, index), value);
+            }
+        }
+        DataValue::Object(fields) => {
+            for (name, value) in fields {
+                append_form_field(serializer, &format!("{}[{}]", key, name), value);
+            }
+        }
+        scalar => {
+            serializer.append_pair(key, &form_text(scalar));
+        }
+    }
+}
+
+/// The number of bracketed segments a form key nests before the rest of
+/// it becomes one literal segment, so the input can't drive the recursion
+const MAX_FORM_DEPTH: usize = 32;
+
+/// Split `a[b][]` into `a`, `b` and an empty segment, `None` for plain keys
+fn form_key_segments(key: &str) -> Option<Vec<&str>> {
+    let root = &key[..key.find('[')?];
+    if root.is_empty() {
+        return None;
+    }
+
+    let mut segments = vec![root];
+    let mut rest = &key[root.len()..];
+    while !rest.is_empty() {
+        if segments.len() > MAX_FORM_DEPTH {
+            segments.push(rest);
+            break;
+        }
+        let (segment, after) = rest.strip_prefix('[')?.split_once(']')?;
+        segments.push(segment);
+        rest = after;
+    }
+    Some(segments)
+}
+
+/// Insert a value along the key segments, giving it back if a string is in
+/// the way
+fn insert_form_field(
+    fields: &mut Vec<(String, DataValue)>,
+    segments: &[&str],
+    value: DataValue,
+) -> Result<(), DataValue> {
+    let Some((segment, rest)) = segments.split_first() else {
+        return Ok(());
+    };
+    let name = match segment.is_empty() {
+        true => fields.len().to_string(),
+        false => segment.to_string(),
+    };
+    if rest.is_empty() {
+        set_form_field(fields, name, value);
+        return Ok(());
+    }
+
+    let position = match fields.iter().position(|(field, _)| *field == name) {
+        Some(position) => position,
+        None => {
+            fields.push((name, DataValue::Object(Vec::new())));
+            fields.len() - 1
+        }
+    };
+    match &mut fields[position].1 {
+        DataValue::Object(children) => insert_form_field(children, rest, value),
+        _ => Err(value),
+    }
+}
+
+fn set_form_field(fields: &mut Vec<(String, DataValue)>, name: String, value: DataValue) {
+    match fields.iter_mut().find(|(field, _)| *field == name) {
+        Some((_, existing)) => *existing = value,
+        None => fields.push((name, value)),
+    }
+}
+
+/// The text of a scalar form value, empty for null
+fn form_text(value: &DataValue) -> String {
+    match value {
+        DataValue::Null | DataValue::Array(_) | DataValue::Object(_) => String::new(),
+        DataValue::Bool(value) => value.to_string(),
+        DataValue::Integer(value) => value.to_string(),
+        DataValue::Float(value) => value.to_string(),
+        DataValue::String(value) => value.clone(),
+    }
+}
+
+/// Turn form objects keyed `0`, `1`, ... into arrays
+fn form_arrays(value: DataValue) -> DataValue {
+    let DataValue::Object(fields) = value else {
+        return value;
+    };
+    let fields: Vec<(String, DataValue)> = fields
+        .into_iter()
+        .map(|(key, value)| (key, form_arrays(value)))
+        .collect();
+    let is_array = !fields.is_empty()
+        && fields
+            .iter()
+            .enumerate()
+            .all(|(index, (key, _))| *key == index.to_string());
+    match is_array {
+        true => DataValue::Array(fields.into_iter().map(|(_, value)| value).collect()),
+        false => DataValue::Object(fields),
+    }
+}
+
+/// A character set of text files.
+///
+/// # Example
+///
+/// ```
+/// use encoding::Charset;
+///
+/// let charset = Charset::from_label("cp1252").unwrap();
+/// let bytes = charset.encode("Café – 3€").unwrap();
+///
+/// assert_eq!(bytes, b"Caf\xe9 \x96 3\x80");
+/// assert_eq!(charset.decode(&bytes).unwrap(), "Café – 3€");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    Utf8,
+    /// ISO-8859-1, every byte is the code point of the same value.
+    Latin1,
+    /// Latin-1 with printable characters instead of most C1 controls, which
+    /// is what most files labeled Latin-1 are.
+    Windows1252,
+    ShiftJis,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Charset {
+    /// The charset of a label such as `latin1`, `cp1252` or `sjis`, ignoring
+    /// case
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label.trim().to_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(Charset::Utf8),
+            "iso-8859-1" | "iso8859-1" | "latin1" | "latin-1" | "l1" => Some(Charset::Latin1),
+            "windows-1252" | "cp1252" => Some(Charset::Windows1252),
+            "shift_jis" | "shift-jis" | "sjis" | "cp932" | "windows-31j" => Some(Charset::ShiftJis),
+            "utf-16le" | "utf16le" => Some(Charset::Utf16Le),
+            "utf-16be" | "utf16be" => Some(Charset::Utf16Be),
+            _ => None,
+        }
+    }
+
+    /// The standard name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Charset::Utf8 => "UTF-8",
+            Charset::Latin1 => "ISO-8859-1",
+            Charset::Windows1252 => "windows-1252",
+            Charset::ShiftJis => "Shift_JIS",
+            Charset::Utf16Le => "UTF-16LE",
+            Charset::Utf16Be => "UTF-16BE",
+        }
+    }
+
+    /// Decode bytes without a byte order mark, failing at the first invalid
+    /// sequence
+    pub fn decode(&self, input: &[u8]) -> Result<String, CharsetError> {
+        match self {
+            Charset::Utf8 => std::str::from_utf8(input).map(str::to_string).map_err(|e| {
+                CharsetError::Malformed {
+                    charset: *self,
+                    offset: e.valid_up_to(),
+                }
+            }),
+            Charset::Latin1 => Ok(input.iter().map(|byte| *byte as char).collect()),
+            _ => self.decode_with(self.encoding(), input),
+        }
+    }
+
+    /// Encode text without a byte order mark, failing at the first character
+    /// the charset can't represent
+    pub fn encode(&self, input: &str) -> Result<Vec<u8>, CharsetError> {
+        match self {
+            Charset::Utf8 => Ok(input.as_bytes().to_vec()),
+            Charset::Latin1 => input
+                .chars()
+                .map(|character| {
+                    u8::try_from(character).map_err(|_| CharsetError::Unmappable {
+                        charset: *self,
+                        character,
+                    })
+                })
+                .collect(),
+            Charset::Utf16Le => Ok(input.encode_utf16().flat_map(u16::to_le_bytes).collect()),
+            Charset::Utf16Be => Ok(input.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+            Charset::Windows1252 | Charset::ShiftJis => self.encode_with(self.encoding(), input),
+        }
+    }
+
+    fn encoding(&self) -> &'static Encoding {
+        match self {
+            Charset::Utf8 | Charset::Latin1 => UTF_8,
+            Charset::Windows1252 => WINDOWS_1252,
+            Charset::ShiftJis => SHIFT_JIS,
+            Charset::Utf16Le => UTF_16LE,
+            Charset::Utf16Be => UTF_16BE,
+        }
+    }
+
+    fn decode_with(
+        &self,
+        encoding: &'static Encoding,
+        input: &[u8],
+    ) -> Result<String, CharsetError> {
+        let mut decoder = encoding.new_decoder_without_bom_handling();
+        let mut output = String::new();
+        let mut read = 0;
+        loop {
+            let remaining = &input[read..];
+            output.reserve(
+                decoder
+                    .max_utf8_buffer_length_without_replacement(remaining.len())
+                    .unwrap_or(remaining.len()),
+            );
+            let (result, consumed) =
+                decoder.decode_to_string_without_replacement(remaining, &mut output, true);
+            read += consumed;
+            match result {
+                DecoderResult::InputEmpty => return Ok(output),
+                DecoderResult::OutputFull => {}
+                DecoderResult::Malformed(length, after) => {
+                    return Err(CharsetError::Malformed {
+                        charset: *self,
+                        offset: read - after as usize - length as usize,
+                    })
+                }
+            }
+        }
+    }
+
+    fn encode_with(
+        &self,
+        encoding: &'static Encoding,
+        input: &str,
+    ) -> Result<Vec<u8>, CharsetError> {
+        let mut encoder = encoding.new_encoder();
+        let mut output = Vec::new();
+        let mut read = 0;
+        loop {
+            let remaining = &input[read..];
+            output.reserve(
+                encoder
+                    .max_buffer_length_from_utf8_without_replacement(remaining.len())
+                    .unwrap_or(remaining.len()),
+            );
+            let (result, consumed) =
+                encoder.encode_from_utf8_to_vec_without_replacement(remaining, &mut output, true);
+            read += consumed;
+            match result {
+                EncoderResult::InputEmpty => return Ok(output),
+                EncoderResult::OutputFull => {}
+                EncoderResult::Unmappable(character) => {
+                    return Err(CharsetError::Unmappable {
+                        charset: *self,
+                        character,
+                    })
+                }
+            }
+        }
+    }
+}
+
+/// Decode text, detecting UTF-8 and UTF-16 from a byte order mark.
+///
+/// Old exports often have no byte order mark, so the text is decoded with
+/// the fallback charset when there is none.
+///
+/// # Arguments
+///
+/// * `input` - The bytes of the text, such as a whole file.
+/// * `fallback` - The charset to use without a byte order mark.
+///
+/// # Returns
+///
+/// A `Result` with the text, without the byte order mark, and its charset.
+///
+/// # Example
+///
+/// ```
+/// use encoding::{decode_with_bom, Charset};
+///
+/// let utf16 = [0xff, 0xfe, b'h', 0, b'i', 0];
+/// assert_eq!(decode_with_bom(&utf16, Charset::Latin1).unwrap(), ("hi".to_string(), Charset::Utf16Le));
+///
+/// let latin1 = b"Jos\xe9";
+/// assert_eq!(decode_with_bom(latin1, Charset::Latin1).unwrap(), ("José".to_string(), Charset::Latin1));
+/// ```
+pub fn decode_with_bom(input: &[u8], fallback: Charset) -> Result<(String, Charset), CharsetError> {
+    let (charset, bom_length) = match Encoding::for_bom(input) {
+        Some((encoding, length)) if encoding == UTF_16LE => (Charset::Utf16Le, length),
+        Some((encoding, length)) if encoding == UTF_16BE => (Charset::Utf16Be, length),
+        Some((_, length)) => (Charset::Utf8, length),
+        None => (fallback, 0),
+    };
+    Ok((charset.decode(&input[bom_length..])?, charset))
+}
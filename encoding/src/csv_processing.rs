@@ -1,6 +1,7 @@
-use csv::{Error, ReaderBuilder};
-use serde::{de, Deserialize, Deserializer, Serialize};
-use std::str::FromStr;
+use csv::{ByteRecord, Error, ErrorKind, ReaderBuilder, StringRecord, Writer, WriterBuilder};
+use regex::Regex;
+use serde::{de, de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use std::{fmt, fs::File, io, path::Path, str::FromStr};
 
 /// Read CSV records from a string.
 ///
@@ -10,7 +11,9 @@ use std::str::FromStr;
 ///
 /// # Returns
 ///
-/// A vector of strings, where each string is a record from the CSV data.
+/// A vector of strings, where each string is a record from the CSV data with
+/// its fields joined without a separator. Use a `CsvPipeline` to read typed
+/// and validated records.
 ///
 /// # Example
 ///
@@ -117,6 +120,7 @@ pub fn read_csv_records_custom_delimiter(
 /// # Returns
 ///
 /// A vector of strings, where each string is a record from the CSV data that matches the query.
+/// Records that can't be read are skipped; use a `CsvPipeline` to report them.
 ///
 /// # Example
 ///
@@ -134,8 +138,7 @@ pub fn filter_csv_records_matching_predicate(csv_data: &str, query: &str) -> Vec
     let mut reader = ReaderBuilder::new().from_reader(csv_data.as_bytes());
     let mut filtered_records: Vec<String> = Vec::new();
 
-    for result in reader.records() {
-        let record = result.unwrap();
+    for record in reader.records().flatten() {
         let record_str = record
             .iter()
             .map(|field| field.to_string())
@@ -262,3 +265,623 @@ impl<'de> Deserialize<'de> for RgbColor {
         FromStr::from_str(&s).map_err(de::Error::custom)
     }
 }
+
+/// The type a CSV column must parse as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    /// Any text.
+    Text,
+    /// A signed or unsigned integer.
+    Integer,
+    /// A floating point or integer number.
+    Float,
+    /// `true` or `false`.
+    Boolean,
+}
+
+/// A rule on one column of a `CsvSchema`.
+///
+/// Empty values of optional columns pass every check.
+///
+/// # Example
+///
+/// ```
+/// use encoding::{ColumnRule, ColumnType};
+/// use regex::Regex;
+///
+/// let ticker = ColumnRule::new("ticker", ColumnType::Text)
+///     .required()
+///     .pattern(Regex::new("^[A-Z]{2,5}$").unwrap());
+///
+/// assert!(ticker.check("ETH").is_ok());
+/// assert_eq!(
+///     ticker.check("eth").unwrap_err(),
+///     "ticker: \"eth\" does not match ^[A-Z]{2,5}$"
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct ColumnRule {
+    name: String,
+    column_type: ColumnType,
+    required: bool,
+    min: Option<f64>,
+    max: Option<f64>,
+    pattern: Option<Regex>,
+}
+
+impl ColumnRule {
+    /// Create an optional column rule of a type
+    pub fn new(name: &str, column_type: ColumnType) -> Self {
+        ColumnRule {
+            name: name.to_string(),
+            column_type,
+            required: false,
+            min: None,
+            max: None,
+            pattern: None,
+        }
+    }
+
+    /// Require the column in the header and a non-empty value in every row
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    /// Require a finite number greater than or equal to `min`
+    pub fn min(mut self, min: f64) -> Self {
+        self.min = Some(min);
+        self
+    }
+
+    /// Require a finite number lower than or equal to `max`
+    pub fn max(mut self, max: f64) -> Self {
+        self.max = Some(max);
+        self
+    }
+
+    /// Require a finite number between `min` and `max`, both included
+    pub fn range(self, min: f64, max: f64) -> Self {
+        self.min(min).max(max)
+    }
+
+    /// Require a value matching a regular expression, anchored with `^` and `$`
+    /// to match the whole value
+    pub fn pattern(mut self, pattern: Regex) -> Self {
+        self.pattern = Some(pattern);
+        self
+    }
+
+    /// The column name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Check a value against the rule
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value of the column in a row.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the reason the value was rejected.
+    pub fn check(&self, value: &str) -> Result<(), String> {
+        let name = &self.name;
+        if value.is_empty() {
+            return match self.required {
+                true => Err(format!("{}: missing value", name)),
+                false => Ok(()),
+            };
+        }
+
+        let valid_type = match self.column_type {
+            ColumnType::Text => true,
+            ColumnType::Integer => value.parse::<i128>().is_ok(),
+            ColumnType::Float => value.parse::<f64>().is_ok(),
+            ColumnType::Boolean => value.parse::<bool>().is_ok(),
+        };
+        if !valid_type {
+            let expected = match self.column_type {
+                ColumnType::Text => "text",
+                ColumnType::Integer => "an integer",
+                ColumnType::Float => "a number",
+                ColumnType::Boolean => "a boolean",
+            };
+            return Err(format!("{}: \"{}\" is not {}", name, value, expected));
+        }
+
+        if self.min.is_some() || self.max.is_some() {
+            let number = value
+                .parse::<f64>()
+                .ok()
+                .filter(|number| number.is_finite())
+                .ok_or_else(|| format!("{}: \"{}\" is not a finite number", name, value))?;
+            if let Some(min) = self.min.filter(|min| number < *min) {
+                return Err(format!("{}: {} is below the minimum {}", name, value, min));
+            }
+            if let Some(max) = self.max.filter(|max| number > *max) {
+                return Err(format!("{}: {} is above the maximum {}", name, value, max));
+            }
+        }
+
+        match &self.pattern {
+            Some(pattern) if !pattern.is_match(value) => Err(format!(
+                "{}: \"{}\" does not match {}",
+                name, value, pattern
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The declared columns of a CSV file.
+///
+/// Columns without a rule are accepted as they are.
+///
+/// # Example
+///
+/// ```
+/// use encoding::{ColumnRule, ColumnType, CsvSchema};
+///
+/// let schema = CsvSchema::new()
+///     .column(ColumnRule::new("name", ColumnType::Text).required())
+///     .column(ColumnRule::new("price", ColumnType::Float).min(0.0));
+///
+/// let headers = csv::StringRecord::from(vec!["name", "price"]);
+/// let row = csv::StringRecord::from(vec!["", "-1"]);
+///
+/// assert_eq!(
+///     schema.validate(&headers, &row).unwrap_err(),
+///     vec!["name: missing value", "price: -1 is below the minimum 0"]
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CsvSchema {
+    columns: Vec<ColumnRule>,
+}
+
+impl CsvSchema {
+    /// Create a schema without columns
+    pub fn new() -> Self {
+        CsvSchema::default()
+    }
+
+    /// Add a column rule
+    pub fn column(mut self, rule: ColumnRule) -> Self {
+        self.columns.push(rule);
+        self
+    }
+
+    /// The column rules, in declaration order
+    pub fn columns(&self) -> &[ColumnRule] {
+        &self.columns
+    }
+
+    /// Check that the header has every required column
+    pub fn check_headers(&self, headers: &StringRecord) -> Result<(), CsvPipelineError> {
+        match self
+            .columns
+            .iter()
+            .find(|rule| rule.required && column_index(headers, &rule.name).is_none())
+        {
+            Some(rule) => Err(CsvPipelineError::MissingColumn(rule.name.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Check a row against every column rule
+    ///
+    /// # Arguments
+    ///
+    /// * `headers` - The header of the CSV file.
+    /// * `record` - The row to check.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the reasons the row was rejected, one per failing column.
+    pub fn validate(
+        &self,
+        headers: &StringRecord,
+        record: &StringRecord,
+    ) -> Result<(), Vec<String>> {
+        let reasons: Vec<String> = self
+            .columns
+            .iter()
+            .filter_map(|rule| {
+                let value = column_index(headers, &rule.name)
+                    .and_then(|index| record.get(index))
+                    .unwrap_or_default();
+                rule.check(value).err()
+            })
+            .collect();
+
+        match reasons.is_empty() {
+            true => Ok(()),
+            false => Err(reasons),
+        }
+    }
+}
+
+fn column_index(headers: &StringRecord, name: &str) -> Option<usize> {
+    headers.iter().position(|header| header == name)
+}
+
+/// An error that stops a `CsvPipeline` run.
+///
+/// Invalid rows don't stop a run, they are rejected.
+#[derive(Debug)]
+pub enum CsvPipelineError {
+    /// Reading or writing CSV data failed.
+    Csv(Error),
+    /// A required column is missing from the header.
+    MissingColumn(String),
+    /// A filter, map or projection uses a column missing from the header.
+    UnknownColumn(String),
+}
+
+impl fmt::Display for CsvPipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CsvPipelineError::Csv(e) => write!(f, "CSV error: {}", e),
+            CsvPipelineError::MissingColumn(name) => write!(f, "Missing required column {}", name),
+            CsvPipelineError::UnknownColumn(name) => write!(f, "Unknown column {}", name),
+        }
+    }
+}
+
+impl std::error::Error for CsvPipelineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CsvPipelineError::Csv(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<Error> for CsvPipelineError {
+    fn from(e: Error) -> Self {
+        CsvPipelineError::Csv(e)
+    }
+}
+
+impl From<io::Error> for CsvPipelineError {
+    fn from(e: io::Error) -> Self {
+        CsvPipelineError::Csv(e.into())
+    }
+}
+
+/// A row rejected by a `CsvPipeline`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RejectedRow {
+    /// The line the row starts on, the header being line 1.
+    pub line: u64,
+    /// Why the row was rejected.
+    pub reason: String,
+}
+
+/// The outcome of a `CsvPipeline` run.
+#[derive(Debug)]
+pub struct PipelineReport<T> {
+    /// The rows written to the output, deserialized, empty for `run_with`.
+    pub records: Vec<T>,
+    /// The number of rows written to the output.
+    pub written: u64,
+    /// The number of valid rows dropped by a filter.
+    pub filtered: u64,
+    /// The rows written to the rejects file.
+    pub rejected: Vec<RejectedRow>,
+}
+
+enum PipelineStep {
+    Map(String, Box<dyn Fn(&str) -> String>),
+    Filter(String, Box<dyn Fn(&str) -> bool>),
+}
+
+/// A CSV pipeline that validates, transforms and deserializes rows.
+///
+/// Every row goes through these stages:
+///
+/// 1. It is checked against the schema and rejected if it doesn't match.
+/// 2. The maps and filters run in the order they were added.
+/// 3. The projection picks the output columns.
+/// 4. The output row is deserialized into `T`, or rejected if that fails.
+///
+/// Valid rows are written to the output and rejected rows to a rejects file
+/// with the columns `line` and `reason` followed by the original fields.
+///
+/// # Example
+///
+/// ```
+/// use encoding::{ColumnRule, ColumnType, CsvPipeline, CsvSchema, Token};
+///
+/// let csv_data = "chain,name,ticker,price
+/// Ethereum,Ether,eth,3000.0
+/// Bitcoin,Bitcoin,BTC,-1
+/// Solana,Solana,SOL,150.0
+/// ";
+///
+/// let pipeline = CsvPipeline::new(
+///     CsvSchema::new()
+///         .column(ColumnRule::new("ticker", ColumnType::Text).required())
+///         .column(ColumnRule::new("price", ColumnType::Float).required().min(0.0)),
+/// )
+/// .map("ticker", |ticker| ticker.to_uppercase())
+/// .filter("chain", |chain| chain != "Solana");
+///
+/// let (mut output, mut rejects) = (Vec::new(), Vec::new());
+/// let report = pipeline
+///     .run::<Token, _, _, _>(csv_data.as_bytes(), &mut output, &mut rejects)
+///     .unwrap();
+///
+/// assert_eq!(report.records[0].ticker, "ETH");
+/// assert_eq!(report.filtered, 1);
+/// assert_eq!(report.rejected[0].line, 3);
+/// assert_eq!(
+///     String::from_utf8(output).unwrap(),
+///     "chain,name,ticker,price\nEthereum,Ether,ETH,3000.0\n"
+/// );
+/// assert_eq!(
+///     String::from_utf8(rejects).unwrap(),
+///     "line,reason,chain,name,ticker,price\n\
+///      3,price: -1 is below the minimum 0,Bitcoin,Bitcoin,BTC,-1\n"
+/// );
+/// ```
+pub struct CsvPipeline {
+    schema: CsvSchema,
+    delimiter: u8,
+    steps: Vec<PipelineStep>,
+    projection: Option<Vec<String>>,
+}
+
+impl CsvPipeline {
+    /// Create a pipeline validating rows against a schema
+    pub fn new(schema: CsvSchema) -> Self {
+        CsvPipeline {
+            schema,
+            delimiter: b',',
+            steps: Vec::new(),
+            projection: None,
+        }
+    }
+
+    /// Read and write fields separated by `delimiter`, `,` by default
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Replace the value of a column
+    pub fn map<F>(mut self, column: &str, map: F) -> Self
+    where
+        F: Fn(&str) -> String + 'static,
+    {
+        self.steps
+            .push(PipelineStep::Map(column.to_string(), Box::new(map)));
+        self
+    }
+
+    /// Keep only the rows whose column value matches a predicate
+    pub fn filter<F>(mut self, column: &str, predicate: F) -> Self
+    where
+        F: Fn(&str) -> bool + 'static,
+    {
+        self.steps.push(PipelineStep::Filter(
+            column.to_string(),
+            Box::new(predicate),
+        ));
+        self
+    }
+
+    /// Write only these columns, in this order, instead of every column
+    pub fn select(mut self, columns: &[&str]) -> Self {
+        self.projection = Some(columns.iter().map(|column| column.to_string()).collect());
+        self
+    }
+
+    /// Run the pipeline from a reader to an output and a rejects writer
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The CSV data, with a header.
+    /// * `output` - Where valid rows are written, with a header.
+    /// * `rejects` - Where rejected rows are written, with a header.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the report of the run, or an error if the header lacks a
+    /// column or reading or writing failed.
+    pub fn run<T, R, W, E>(
+        &self,
+        input: R,
+        output: W,
+        rejects: E,
+    ) -> Result<PipelineReport<T>, CsvPipelineError>
+    where
+        T: DeserializeOwned,
+        R: io::Read,
+        W: io::Write,
+        E: io::Write,
+    {
+        let mut records = Vec::new();
+        let mut report = self.run_with(input, output, rejects, |record| records.push(record))?;
+        report.records = records;
+        Ok(report)
+    }
+
+    /// Run the pipeline like `run`, handing each deserialized row to a
+    /// callback instead of keeping it in the report
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The CSV data, with a header.
+    /// * `output` - Where valid rows are written, with a header.
+    /// * `rejects` - Where rejected rows are written, with a header.
+    /// * `on_record` - Called with every row written to the output, in order.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the report of the run, without records.
+    pub fn run_with<T, R, W, E, F>(
+        &self,
+        input: R,
+        output: W,
+        rejects: E,
+        mut on_record: F,
+    ) -> Result<PipelineReport<T>, CsvPipelineError>
+    where
+        T: DeserializeOwned,
+        R: io::Read,
+        W: io::Write,
+        E: io::Write,
+        F: FnMut(T),
+    {
+        let mut reader = ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .flexible(true)
+            .from_reader(input);
+        let headers = reader.headers()?.clone();
+        self.schema.check_headers(&headers)?;
+
+        let mut steps = Vec::with_capacity(self.steps.len());
+        for step in &self.steps {
+            let column = match step {
+                PipelineStep::Map(column, _) | PipelineStep::Filter(column, _) => column,
+            };
+            steps.push((self.index_of(&headers, column)?, step));
+        }
+        let projection = match &self.projection {
+            Some(columns) => columns
+                .iter()
+                .map(|column| self.index_of(&headers, column))
+                .collect::<Result<Vec<_>, _>>()?,
+            None => (0..headers.len()).collect(),
+        };
+        let output_headers: StringRecord =
+            projection.iter().map(|&index| &headers[index]).collect();
+
+        let mut output = self.writer(output);
+        let mut rejects = self.writer(rejects);
+        output.write_record(&output_headers)?;
+        rejects.write_record(["line", "reason"].iter().copied().chain(headers.iter()))?;
+
+        let mut report = PipelineReport {
+            records: Vec::new(),
+            written: 0,
+            filtered: 0,
+            rejected: Vec::new(),
+        };
+        let mut raw = ByteRecord::new();
+        while reader.read_byte_record(&mut raw)? {
+            match self.process_row(&headers, &steps, &projection, &output_headers, &raw) {
+                Ok(Some((row, value))) => {
+                    output.write_record(&row)?;
+                    report.written += 1;
+                    on_record(value);
+                }
+                Ok(None) => report.filtered += 1,
+                Err(reason) => {
+                    let line = raw.position().map_or(0, |position| position.line());
+                    let mut fields = ByteRecord::new();
+                    fields.push_field(line.to_string().as_bytes());
+                    fields.push_field(reason.as_bytes());
+                    fields.extend(raw.iter());
+                    rejects.write_byte_record(&fields)?;
+                    report.rejected.push(RejectedRow { line, reason });
+                }
+            }
+        }
+
+        output.flush()?;
+        rejects.flush()?;
+        Ok(report)
+    }
+
+    /// Run the pipeline from a CSV file to an output file and a rejects file
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The path of the CSV file to read.
+    /// * `output` - The path of the file to write valid rows to.
+    /// * `rejects` - The path of the file to write rejected rows to.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the report of the run.
+    pub fn run_files<T: DeserializeOwned>(
+        &self,
+        input: &Path,
+        output: &Path,
+        rejects: &Path,
+    ) -> Result<PipelineReport<T>, CsvPipelineError> {
+        self.run(
+            File::open(input)?,
+            File::create(output)?,
+            File::create(rejects)?,
+        )
+    }
+
+    /// Validate and transform a row, `Ok(None)` if a filter dropped it
+    fn process_row<T: DeserializeOwned>(
+        &self,
+        headers: &StringRecord,
+        steps: &[(usize, &PipelineStep)],
+        projection: &[usize],
+        output_headers: &StringRecord,
+        raw: &ByteRecord,
+    ) -> Result<Option<(StringRecord, T)>, String> {
+        if raw.len() != headers.len() {
+            return Err(format!(
+                "expected {} fields, found {}",
+                headers.len(),
+                raw.len()
+            ));
+        }
+        let record =
+            StringRecord::from_byte_record(raw.clone()).map_err(|_| "invalid UTF-8".to_string())?;
+        self.schema
+            .validate(headers, &record)
+            .map_err(|reasons| reasons.join("; "))?;
+
+        let mut fields: Vec<String> = record.iter().map(str::to_string).collect();
+        for (index, step) in steps {
+            match step {
+                PipelineStep::Map(_, map) => fields[*index] = map(&fields[*index]),
+                PipelineStep::Filter(_, predicate) => {
+                    if !predicate(&fields[*index]) {
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+
+        let row: StringRecord = projection.iter().map(|&index| &fields[index]).collect();
+        let value = row
+            .deserialize(Some(output_headers))
+            .map_err(|e| match e.kind() {
+                ErrorKind::Deserialize { err, .. } => {
+                    match err
+                        .field()
+                        .and_then(|field| output_headers.get(field as usize))
+                    {
+                        Some(column) => format!("{}: {}", column, err.kind()),
+                        None => err.kind().to_string(),
+                    }
+                }
+                _ => e.to_string(),
+            })?;
+        Ok(Some((row, value)))
+    }
+
+    fn index_of(&self, headers: &StringRecord, column: &str) -> Result<usize, CsvPipelineError> {
+        column_index(headers, column)
+            .ok_or_else(|| CsvPipelineError::UnknownColumn(column.to_string()))
+    }
+
+    fn writer<W: io::Write>(&self, writer: W) -> Writer<W> {
+        WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .flexible(true)
+            .from_writer(writer)
+    }
+}
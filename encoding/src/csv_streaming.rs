@@ -0,0 +1,1069 @@
+use csv::{Reader, ReaderBuilder, StringRecord, StringRecordsIntoIter, Writer, WriterBuilder};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+    fmt,
+    fs::File,
+    io::{self, Seek},
+    path::{Path, PathBuf},
+};
+use tempfile::NamedTempFile;
+
+type Records<'a> = Box<dyn Iterator<Item = Result<StringRecord, CsvStreamError>> + 'a>;
+
+/// A stream of CSV records with their header.
+///
+/// Records are read one at a time, so a stream can go through files larger
+/// than memory. Operators consume a stream and return a new one, and the
+/// streaming ones (`dedup`, `merge_join`, `group_by_sorted`, `rename`) only
+/// hold the current record or group, so a chain of them runs in a single pass
+/// over the data. `sort` reads its whole input, spilling sorted chunks to
+/// temporary files, before it yields the first record. `distinct`,
+/// `hash_join` and `group_by` keep their keys, the right input or the groups
+/// in memory.
+///
+/// # Example
+///
+/// ```
+/// use encoding::{Aggregate, CsvStream, SortKey};
+///
+/// let csv_data = "chain,ticker,price
+/// Solana,SOL,150
+/// Ethereum,ETH,3000
+/// Ethereum,PEPE,0.00001
+/// Solana,SOL,150
+/// ";
+///
+/// let mut output = Vec::new();
+/// CsvStream::from_reader(csv_data.as_bytes())
+///     .unwrap()
+///     .sort(&[SortKey::new("chain"), SortKey::new("ticker")])
+///     .unwrap()
+///     .dedup(&[])
+///     .unwrap()
+///     .group_by_sorted(&["chain"], &[Aggregate::Count, Aggregate::max("price")])
+///     .unwrap()
+///     .write(&mut output)
+///     .unwrap();
+///
+/// assert_eq!(
+///     String::from_utf8(output).unwrap(),
+///     "chain,count,max_price\nEthereum,2,3000\nSolana,1,150\n"
+/// );
+/// ```
+pub struct CsvStream<'a> {
+    headers: StringRecord,
+    records: Records<'a>,
+}
+
+impl<'a> CsvStream<'a> {
+    /// Stream comma separated records from a reader, with a header
+    pub fn from_reader<R: io::Read + 'a>(reader: R) -> Result<Self, CsvStreamError> {
+        CsvStream::from_csv_reader(Reader::from_reader(reader))
+    }
+
+    /// Stream records from a CSV reader, e.g. one with a custom delimiter
+    pub fn from_csv_reader<R: io::Read + 'a>(
+        mut reader: Reader<R>,
+    ) -> Result<Self, CsvStreamError> {
+        let headers = reader.headers()?.clone();
+        Ok(CsvStream {
+            headers,
+            records: Box::new(
+                reader
+                    .into_records()
+                    .map(|record| record.map_err(CsvStreamError::from)),
+            ),
+        })
+    }
+
+    /// Stream comma separated records from a file, with a header
+    pub fn from_path(path: &Path) -> Result<Self, CsvStreamError> {
+        CsvStream::from_reader(File::open(path)?)
+    }
+
+    /// Stream records already in memory
+    pub fn from_records<I>(headers: StringRecord, records: I) -> Self
+    where
+        I: IntoIterator<Item = StringRecord>,
+        I::IntoIter: 'a,
+    {
+        CsvStream {
+            headers,
+            records: Box::new(records.into_iter().map(Ok)),
+        }
+    }
+
+    /// The header of the records
+    pub fn headers(&self) -> &StringRecord {
+        &self.headers
+    }
+
+    /// Write the header and every record as CSV
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Where the CSV data is written.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the number of records written.
+    pub fn write<W: io::Write>(self, writer: W) -> Result<u64, CsvStreamError> {
+        let mut writer = Writer::from_writer(writer);
+        writer.write_record(&self.headers)?;
+        let mut written = 0;
+        for record in self.records {
+            writer.write_record(&record?)?;
+            written += 1;
+        }
+        writer.flush()?;
+        Ok(written)
+    }
+
+    /// Rename a column
+    pub fn rename(mut self, column: &str, name: &str) -> Result<Self, CsvStreamError> {
+        let index = column_indexes(&self.headers, &[column])?[0];
+        self.headers = self
+            .headers
+            .iter()
+            .enumerate()
+            .map(|(i, header)| if i == index { name } else { header })
+            .collect();
+        Ok(self)
+    }
+
+    /// Sort by key columns with the default `SortOptions`
+    pub fn sort(self, keys: &[SortKey]) -> Result<Self, CsvStreamError> {
+        self.sort_with(keys, &SortOptions::default())
+    }
+
+    /// Sort by key columns with an external merge sort
+    ///
+    /// The input is read in chunks of at most `options.max_rows_in_memory`
+    /// records. Each chunk is sorted and written to a temporary file, and the
+    /// files are merged while the sorted stream is read. The files are deleted
+    /// when the stream is dropped. The sort is stable.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - The key columns, most significant first.
+    /// * `options` - The chunk size and the directory of the temporary files.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the sorted stream.
+    pub fn sort_with(
+        self,
+        keys: &[SortKey],
+        options: &SortOptions,
+    ) -> Result<Self, CsvStreamError> {
+        let keys = resolve_keys(&self.headers, keys)?;
+        let max_rows = options.max_rows_in_memory.max(1);
+        let mut chunk: Vec<(Vec<SortValue>, StringRecord)> = Vec::new();
+        let mut spills = Vec::new();
+
+        for record in self.records {
+            let record = record?;
+            chunk.push((sort_values(&keys, &record), record));
+            if chunk.len() == max_rows {
+                chunk.sort_by(|a, b| a.0.cmp(&b.0));
+                spills.push(SpillFile::write(&options.temp_dir, &chunk)?);
+                chunk.clear();
+            }
+        }
+        chunk.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let records: Records<'a> = if spills.is_empty() {
+            Box::new(chunk.into_iter().map(|(_, record)| Ok(record)))
+        } else {
+            if !chunk.is_empty() {
+                spills.push(SpillFile::write(&options.temp_dir, &chunk)?);
+            }
+            Box::new(SpillMerge::new(keys, spills)?)
+        };
+        Ok(CsvStream {
+            headers: self.headers,
+            records,
+        })
+    }
+
+    /// Drop records whose key columns equal those of the previous record
+    ///
+    /// On a stream sorted by the key columns this removes every duplicate
+    /// while holding only one record.
+    ///
+    /// # Arguments
+    ///
+    /// * `columns` - The key columns, or every column if empty.
+    pub fn dedup(self, columns: &[&str]) -> Result<Self, CsvStreamError> {
+        let indexes = column_indexes(&self.headers, columns)?;
+        let mut previous: Option<Vec<String>> = None;
+        let records = self.records.filter(move |record| match record {
+            Ok(record) => {
+                let key = project(&indexes, record);
+                let duplicate = previous.as_ref() == Some(&key);
+                previous = Some(key);
+                !duplicate
+            }
+            Err(_) => true,
+        });
+        Ok(CsvStream {
+            headers: self.headers,
+            records: Box::new(records),
+        })
+    }
+
+    /// Keep the first record of every distinct key
+    ///
+    /// Every distinct key is kept in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `columns` - The key columns, or every column if empty.
+    pub fn distinct(self, columns: &[&str]) -> Result<Self, CsvStreamError> {
+        let indexes = column_indexes(&self.headers, columns)?;
+        let mut seen = HashSet::new();
+        let records = self.records.filter(move |record| match record {
+            Ok(record) => seen.insert(project(&indexes, record)),
+            Err(_) => true,
+        });
+        Ok(CsvStream {
+            headers: self.headers,
+            records: Box::new(records),
+        })
+    }
+
+    /// Join with another stream by loading it in a hash table
+    ///
+    /// The right stream is read into memory and this stream is streamed, so
+    /// the smaller input should be on the right. Keys match when their values
+    /// are equal text. Output records have the columns of this stream followed
+    /// by the columns of the right stream other than the key columns.
+    ///
+    /// # Arguments
+    ///
+    /// * `right` - The stream to join with.
+    /// * `on` - The key columns, with the same names in both streams.
+    /// * `kind` - Whether records without a match are kept.
+    pub fn hash_join(
+        self,
+        right: CsvStream<'a>,
+        on: &[&str],
+        kind: JoinKind,
+    ) -> Result<Self, CsvStreamError> {
+        let left_keys = column_indexes(&self.headers, on)?;
+        let right_keys = column_indexes(&right.headers, on)?;
+        let right_rest = other_indexes(&right.headers, &right_keys);
+        let headers = joined_headers(&self.headers, &right.headers, &right_rest);
+
+        let mut table: HashMap<Vec<String>, Vec<StringRecord>> = HashMap::new();
+        for record in right.records {
+            let record = record?;
+            table
+                .entry(project(&right_keys, &record))
+                .or_default()
+                .push(project(&right_rest, &record).into());
+        }
+
+        let unmatched = vec![StringRecord::from(vec![""; right_rest.len()])];
+        let records = self.records.flat_map(move |record| {
+            let left = match record {
+                Ok(left) => left,
+                Err(e) => return vec![Err(e)],
+            };
+            let matches = match table.get(&project(&left_keys, &left)) {
+                Some(matches) => matches,
+                None if kind == JoinKind::Left => &unmatched,
+                None => return Vec::new(),
+            };
+            matches
+                .iter()
+                .map(|right| Ok(join_records(&left, right)))
+                .collect()
+        });
+        Ok(CsvStream {
+            headers,
+            records: Box::new(records),
+        })
+    }
+
+    /// Join with another stream when both are sorted by the key columns
+    ///
+    /// Both streams are read once, side by side, holding only the right
+    /// records of the current key. The streams must be sorted by `on`, e.g.
+    /// with `sort`, or the join fails with `CsvStreamError::Unsorted`. Output
+    /// records have the columns of this stream followed by the columns of the
+    /// right stream other than the key columns.
+    ///
+    /// # Arguments
+    ///
+    /// * `right` - The stream to join with.
+    /// * `on` - The key columns, with the same names in both streams.
+    /// * `kind` - Whether records without a match are kept.
+    pub fn merge_join(
+        self,
+        right: CsvStream<'a>,
+        on: &[SortKey],
+        kind: JoinKind,
+    ) -> Result<Self, CsvStreamError> {
+        let left_keys = resolve_keys(&self.headers, on)?;
+        let right_keys = resolve_keys(&right.headers, on)?;
+        let key_indexes: Vec<usize> = right_keys.iter().map(|key| key.index).collect();
+        let right_rest = other_indexes(&right.headers, &key_indexes);
+        let headers = joined_headers(&self.headers, &right.headers, &right_rest);
+
+        let join = MergeJoin {
+            left: self.records,
+            right: right.records,
+            left_keys,
+            right_keys,
+            unmatched: StringRecord::from(vec![""; right_rest.len()]),
+            right_rest,
+            kind,
+            previous_left: None,
+            lookahead: None,
+            group_key: None,
+            group: Vec::new(),
+            right_exhausted: false,
+            pending: VecDeque::new(),
+        };
+        Ok(CsvStream {
+            headers,
+            records: Box::new(join),
+        })
+    }
+
+    /// Aggregate the records of every group, holding all groups in memory
+    ///
+    /// Groups come out in the order of their first record. Output records
+    /// have the group columns followed by a column per aggregate.
+    ///
+    /// # Arguments
+    ///
+    /// * `columns` - The group columns.
+    /// * `aggregates` - The aggregates computed for every group.
+    pub fn group_by(
+        self,
+        columns: &[&str],
+        aggregates: &[Aggregate],
+    ) -> Result<Self, CsvStreamError> {
+        let indexes = column_indexes_or_none(&self.headers, columns)?;
+        let aggregates = resolve_aggregates(&self.headers, aggregates)?;
+        let headers = group_headers(&self.headers, &indexes, &aggregates);
+
+        let mut positions: HashMap<Vec<String>, usize> = HashMap::new();
+        let mut groups: Vec<Group> = Vec::new();
+        for record in self.records {
+            let record = record?;
+            let key = project(&indexes, &record);
+            let position = *positions.entry(key.clone()).or_insert_with(|| {
+                groups.push(Group::new(key, &aggregates));
+                groups.len() - 1
+            });
+            groups[position].update(&aggregates, &record)?;
+        }
+
+        Ok(CsvStream {
+            headers,
+            records: Box::new(groups.into_iter().map(|group| Ok(group.finish()))),
+        })
+    }
+
+    /// Aggregate the records of every group of a stream sorted by the group
+    /// columns, holding only the current group
+    ///
+    /// A group ends when the group columns change, so on an unsorted stream
+    /// a key shows up once per run of consecutive records. Output records
+    /// have the group columns followed by a column per aggregate.
+    ///
+    /// # Arguments
+    ///
+    /// * `columns` - The group columns.
+    /// * `aggregates` - The aggregates computed for every group.
+    pub fn group_by_sorted(
+        self,
+        columns: &[&str],
+        aggregates: &[Aggregate],
+    ) -> Result<Self, CsvStreamError> {
+        let indexes = column_indexes_or_none(&self.headers, columns)?;
+        let aggregates = resolve_aggregates(&self.headers, aggregates)?;
+        let headers = group_headers(&self.headers, &indexes, &aggregates);
+
+        let mut records = self.records;
+        let mut current: Option<Group> = None;
+        let groups = std::iter::from_fn(move || loop {
+            let record = match records.next() {
+                Some(Ok(record)) => record,
+                Some(Err(e)) => return Some(Err(e)),
+                None => return current.take().map(|group| Ok(group.finish())),
+            };
+            let key = project(&indexes, &record);
+            let finished = match current.as_ref().is_some_and(|group| group.key == key) {
+                true => None,
+                false => current.replace(Group::new(key, &aggregates)),
+            };
+            if let Some(group) = current.as_mut() {
+                if let Err(e) = group.update(&aggregates, &record) {
+                    return Some(Err(e));
+                }
+            }
+            if let Some(finished) = finished {
+                return Some(Ok(finished.finish()));
+            }
+        });
+        Ok(CsvStream {
+            headers,
+            records: Box::new(groups),
+        })
+    }
+}
+
+impl Iterator for CsvStream<'_> {
+    type Item = Result<StringRecord, CsvStreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.records.next()
+    }
+}
+
+/// An error of a `CsvStream` operator.
+#[derive(Debug)]
+pub enum CsvStreamError {
+    /// Reading or writing CSV data or a temporary file failed.
+    Csv(csv::Error),
+    /// A column is missing from the header.
+    UnknownColumn(String),
+    /// An input of a merge join is not sorted by the join keys.
+    Unsorted(String),
+    /// A value to aggregate is not a number.
+    InvalidNumber { column: String, value: String },
+}
+
+impl fmt::Display for CsvStreamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CsvStreamError::Csv(e) => write!(f, "CSV error: {}", e),
+            CsvStreamError::UnknownColumn(name) => write!(f, "Unknown column {}", name),
+            CsvStreamError::Unsorted(input) => {
+                write!(f, "The {} input is not sorted by the join keys", input)
+            }
+            CsvStreamError::InvalidNumber { column, value } => {
+                write!(f, "{}: \"{}\" is not a number", column, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CsvStreamError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CsvStreamError::Csv(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<csv::Error> for CsvStreamError {
+    fn from(e: csv::Error) -> Self {
+        CsvStreamError::Csv(e)
+    }
+}
+
+impl From<io::Error> for CsvStreamError {
+    fn from(e: io::Error) -> Self {
+        CsvStreamError::Csv(e.into())
+    }
+}
+
+fn column_indexes(headers: &StringRecord, columns: &[&str]) -> Result<Vec<usize>, CsvStreamError> {
+    if columns.is_empty() {
+        return Ok((0..headers.len()).collect());
+    }
+    column_indexes_or_none(headers, columns)
+}
+
+fn column_indexes_or_none(
+    headers: &StringRecord,
+    columns: &[&str],
+) -> Result<Vec<usize>, CsvStreamError> {
+    columns
+        .iter()
+        .map(|column| {
+            headers
+                .iter()
+                .position(|header| header == *column)
+                .ok_or_else(|| CsvStreamError::UnknownColumn(column.to_string()))
+        })
+        .collect()
+}
+
+fn other_indexes(headers: &StringRecord, indexes: &[usize]) -> Vec<usize> {
+    (0..headers.len())
+        .filter(|index| !indexes.contains(index))
+        .collect()
+}
+
+fn project(indexes: &[usize], record: &StringRecord) -> Vec<String> {
+    indexes
+        .iter()
+        .map(|&index| record.get(index).unwrap_or_default().to_string())
+        .collect()
+}
+
+fn joined_headers(left: &StringRecord, right: &StringRecord, right_rest: &[usize]) -> StringRecord {
+    left.iter()
+        .chain(right_rest.iter().map(|&index| &right[index]))
+        .collect()
+}
+
+fn join_records(left: &StringRecord, right: &StringRecord) -> StringRecord {
+    left.iter().chain(right.iter()).collect()
+}
+
+/// A column to sort or merge join by.
+///
+/// Values compare as text by default. Numeric keys compare as numbers, with
+/// values that aren't numbers sorted after them as text.
+///
+/// # Example
+///
+/// ```
+/// use encoding::SortKey;
+///
+/// let newest_first = SortKey::new("year").numeric().descending();
+///
+/// assert_eq!(newest_first.column(), "year");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    column: String,
+    numeric: bool,
+    descending: bool,
+}
+
+impl SortKey {
+    /// Sort by a column as text, in ascending order
+    pub fn new(column: &str) -> Self {
+        SortKey {
+            column: column.to_string(),
+            numeric: false,
+            descending: false,
+        }
+    }
+
+    /// Compare values as numbers
+    pub fn numeric(mut self) -> Self {
+        self.numeric = true;
+        self
+    }
+
+    /// Sort in descending order
+    pub fn descending(mut self) -> Self {
+        self.descending = true;
+        self
+    }
+
+    /// The column name
+    pub fn column(&self) -> &str {
+        &self.column
+    }
+}
+
+/// Options of an external merge sort.
+#[derive(Debug, Clone)]
+pub struct SortOptions {
+    /// The most records sorted in memory at once, 100 000 by default.
+    pub max_rows_in_memory: usize,
+    /// Where sorted chunks are written, the system temporary directory by
+    /// default.
+    pub temp_dir: PathBuf,
+}
+
+impl Default for SortOptions {
+    fn default() -> Self {
+        SortOptions {
+            max_rows_in_memory: 100_000,
+            temp_dir: std::env::temp_dir(),
+        }
+    }
+}
+
+struct ResolvedKey {
+    index: usize,
+    numeric: bool,
+    descending: bool,
+}
+
+fn resolve_keys(
+    headers: &StringRecord,
+    keys: &[SortKey],
+) -> Result<Vec<ResolvedKey>, CsvStreamError> {
+    let columns: Vec<&str> = keys.iter().map(|key| key.column.as_str()).collect();
+    let indexes = column_indexes_or_none(headers, &columns)?;
+    Ok(keys
+        .iter()
+        .zip(indexes)
+        .map(|(key, index)| ResolvedKey {
+            index,
+            numeric: key.numeric,
+            descending: key.descending,
+        })
+        .collect())
+}
+
+#[derive(Debug, Clone)]
+enum KeyPart {
+    Number(f64),
+    Text(String),
+}
+
+/// A key value ordered by its `SortKey`
+#[derive(Debug, Clone)]
+struct SortValue {
+    part: KeyPart,
+    descending: bool,
+}
+
+impl Ord for SortValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let ordering = match (&self.part, &other.part) {
+            (KeyPart::Number(a), KeyPart::Number(b)) => a.total_cmp(b),
+            (KeyPart::Number(_), KeyPart::Text(_)) => Ordering::Less,
+            (KeyPart::Text(_), KeyPart::Number(_)) => Ordering::Greater,
+            (KeyPart::Text(a), KeyPart::Text(b)) => a.cmp(b),
+        };
+        match self.descending {
+            true => ordering.reverse(),
+            false => ordering,
+        }
+    }
+}
+
+impl PartialOrd for SortValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for SortValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for SortValue {}
+
+fn sort_values(keys: &[ResolvedKey], record: &StringRecord) -> Vec<SortValue> {
+    keys.iter()
+        .map(|key| {
+            let value = record.get(key.index).unwrap_or_default();
+            let part = match value.parse::<f64>() {
+                Ok(number) if key.numeric => KeyPart::Number(number),
+                _ => KeyPart::Text(value.to_string()),
+            };
+            SortValue {
+                part,
+                descending: key.descending,
+            }
+        })
+        .collect()
+}
+
+/// A sorted chunk written to a temporary file, deleted on drop
+///
+/// The file gets a random name and is created exclusively, so it can't be
+/// redirected through a symlink planted in a shared temporary directory.
+struct SpillFile {
+    file: NamedTempFile,
+}
+
+impl SpillFile {
+    fn write(dir: &Path, chunk: &[(Vec<SortValue>, StringRecord)]) -> Result<Self, CsvStreamError> {
+        let file = tempfile::Builder::new()
+            .prefix("csv-sort-")
+            .suffix(".csv")
+            .tempfile_in(dir)?;
+        let mut writer = WriterBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_writer(file.as_file());
+        for (_, record) in chunk {
+            writer.write_record(record)?;
+        }
+        writer.flush()?;
+        drop(writer);
+        Ok(SpillFile { file })
+    }
+
+    fn records(&self) -> Result<StringRecordsIntoIter<File>, CsvStreamError> {
+        let mut file = self.file.as_file().try_clone()?;
+        file.rewind()?;
+        Ok(ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(file)
+            .into_records())
+    }
+}
+
+/// The next record of a sorted chunk, ordered by key then chunk so that
+/// equal keys keep their input order
+struct MergeEntry {
+    values: Vec<SortValue>,
+    chunk: usize,
+    record: StringRecord,
+}
+
+impl Ord for MergeEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.values, self.chunk).cmp(&(&other.values, other.chunk))
+    }
+}
+
+impl PartialOrd for MergeEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for MergeEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for MergeEntry {}
+
+/// A k-way merge of sorted chunks
+struct SpillMerge {
+    keys: Vec<ResolvedKey>,
+    chunks: Vec<StringRecordsIntoIter<File>>,
+    heap: BinaryHeap<Reverse<MergeEntry>>,
+    _spills: Vec<SpillFile>,
+}
+
+impl SpillMerge {
+    fn new(keys: Vec<ResolvedKey>, spills: Vec<SpillFile>) -> Result<Self, CsvStreamError> {
+        let mut merge = SpillMerge {
+            keys,
+            chunks: spills
+                .iter()
+                .map(SpillFile::records)
+                .collect::<Result<_, _>>()?,
+            heap: BinaryHeap::with_capacity(spills.len()),
+            _spills: spills,
+        };
+        for chunk in 0..merge.chunks.len() {
+            merge.pull(chunk)?;
+        }
+        Ok(merge)
+    }
+
+    fn pull(&mut self, chunk: usize) -> Result<(), CsvStreamError> {
+        if let Some(record) = self.chunks[chunk].next() {
+            let record = record?;
+            self.heap.push(Reverse(MergeEntry {
+                values: sort_values(&self.keys, &record),
+                chunk,
+                record,
+            }));
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for SpillMerge {
+    type Item = Result<StringRecord, CsvStreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse(entry) = self.heap.pop()?;
+        if let Err(e) = self.pull(entry.chunk) {
+            return Some(Err(e));
+        }
+        Some(Ok(entry.record))
+    }
+}
+
+/// Which records a join keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinKind {
+    /// Only records with a match on both sides.
+    Inner,
+    /// Every record of the left stream, with empty right columns if there
+    /// is no match.
+    Left,
+}
+
+/// A sort-merge join of two sorted record streams
+struct MergeJoin<'a> {
+    left: Records<'a>,
+    right: Records<'a>,
+    left_keys: Vec<ResolvedKey>,
+    right_keys: Vec<ResolvedKey>,
+    right_rest: Vec<usize>,
+    unmatched: StringRecord,
+    kind: JoinKind,
+    previous_left: Option<Vec<SortValue>>,
+    lookahead: Option<(Vec<SortValue>, StringRecord)>,
+    group_key: Option<Vec<SortValue>>,
+    group: Vec<StringRecord>,
+    right_exhausted: bool,
+    pending: VecDeque<StringRecord>,
+}
+
+impl MergeJoin<'_> {
+    fn read_right(&mut self) -> Result<Option<(Vec<SortValue>, StringRecord)>, CsvStreamError> {
+        match self.right.next() {
+            Some(record) => {
+                let record = record?;
+                Ok(Some((sort_values(&self.right_keys, &record), record)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Read the right records of the next key
+    fn next_group(&mut self) -> Result<(), CsvStreamError> {
+        self.group.clear();
+        let first = match self.lookahead.take() {
+            Some(first) => Some(first),
+            None => self.read_right()?,
+        };
+        let Some((key, record)) = first else {
+            self.group_key = None;
+            self.right_exhausted = true;
+            return Ok(());
+        };
+
+        self.group.push(project(&self.right_rest, &record).into());
+        while let Some((next_key, next)) = self.read_right()? {
+            match next_key.cmp(&key) {
+                Ordering::Equal => self.group.push(project(&self.right_rest, &next).into()),
+                Ordering::Greater => {
+                    self.lookahead = Some((next_key, next));
+                    break;
+                }
+                Ordering::Less => return Err(CsvStreamError::Unsorted("right".to_string())),
+            }
+        }
+        self.group_key = Some(key);
+        Ok(())
+    }
+
+    fn join(&mut self, left: StringRecord) -> Result<(), CsvStreamError> {
+        let key = sort_values(&self.left_keys, &left);
+        if self
+            .previous_left
+            .as_ref()
+            .is_some_and(|previous| *previous > key)
+        {
+            return Err(CsvStreamError::Unsorted("left".to_string()));
+        }
+
+        while !self.right_exhausted && self.group_key.as_ref().is_none_or(|group| *group < key) {
+            self.next_group()?;
+        }
+        if self.group_key.as_ref() == Some(&key) {
+            for right in &self.group {
+                self.pending.push_back(join_records(&left, right));
+            }
+        } else if self.kind == JoinKind::Left {
+            self.pending.push_back(join_records(&left, &self.unmatched));
+        }
+        self.previous_left = Some(key);
+        Ok(())
+    }
+}
+
+impl Iterator for MergeJoin<'_> {
+    type Item = Result<StringRecord, CsvStreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.pending.pop_front() {
+                return Some(Ok(record));
+            }
+            let left = match self.left.next()? {
+                Ok(left) => left,
+                Err(e) => return Some(Err(e)),
+            };
+            if let Err(e) = self.join(left) {
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+/// An aggregate computed for every group of a `group_by`.
+///
+/// Empty values are skipped, other values must be numbers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Aggregate {
+    /// The number of records, in a `count` column.
+    Count,
+    /// The sum of a column, in a `sum_<column>` column.
+    Sum(String),
+    /// The smallest value of a column, in a `min_<column>` column.
+    Min(String),
+    /// The largest value of a column, in a `max_<column>` column.
+    Max(String),
+    /// The mean of a column, in a `mean_<column>` column.
+    Mean(String),
+}
+
+impl Aggregate {
+    /// The sum of a column
+    pub fn sum(column: &str) -> Self {
+        Aggregate::Sum(column.to_string())
+    }
+
+    /// The smallest value of a column
+    pub fn min(column: &str) -> Self {
+        Aggregate::Min(column.to_string())
+    }
+
+    /// The largest value of a column
+    pub fn max(column: &str) -> Self {
+        Aggregate::Max(column.to_string())
+    }
+
+    /// The mean of a column
+    pub fn mean(column: &str) -> Self {
+        Aggregate::Mean(column.to_string())
+    }
+
+    /// The name of the output column
+    pub fn output_name(&self) -> String {
+        match self {
+            Aggregate::Count => "count".to_string(),
+            Aggregate::Sum(column) => format!("sum_{}", column),
+            Aggregate::Min(column) => format!("min_{}", column),
+            Aggregate::Max(column) => format!("max_{}", column),
+            Aggregate::Mean(column) => format!("mean_{}", column),
+        }
+    }
+
+    fn column(&self) -> Option<&str> {
+        match self {
+            Aggregate::Count => None,
+            Aggregate::Sum(column)
+            | Aggregate::Min(column)
+            | Aggregate::Max(column)
+            | Aggregate::Mean(column) => Some(column),
+        }
+    }
+}
+
+/// An aggregate with the index of its column
+type ResolvedAggregate = (Aggregate, Option<usize>);
+
+fn resolve_aggregates(
+    headers: &StringRecord,
+    aggregates: &[Aggregate],
+) -> Result<Vec<ResolvedAggregate>, CsvStreamError> {
+    aggregates
+        .iter()
+        .map(|aggregate| {
+            let index = match aggregate.column() {
+                Some(column) => Some(column_indexes_or_none(headers, &[column])?[0]),
+                None => None,
+            };
+            Ok((aggregate.clone(), index))
+        })
+        .collect()
+}
+
+fn group_headers(
+    headers: &StringRecord,
+    indexes: &[usize],
+    aggregates: &[ResolvedAggregate],
+) -> StringRecord {
+    let mut group_headers: StringRecord = indexes.iter().map(|&index| &headers[index]).collect();
+    for (aggregate, _) in aggregates {
+        group_headers.push_field(&aggregate.output_name());
+    }
+    group_headers
+}
+
+#[derive(Debug, Clone)]
+enum Accumulator {
+    Count(u64),
+    Sum(f64),
+    Min(Option<f64>),
+    Max(Option<f64>),
+    Mean(f64, u64),
+}
+
+struct Group {
+    key: Vec<String>,
+    accumulators: Vec<Accumulator>,
+}
+
+impl Group {
+    fn new(key: Vec<String>, aggregates: &[ResolvedAggregate]) -> Self {
+        let accumulators = aggregates
+            .iter()
+            .map(|(aggregate, _)| match aggregate {
+                Aggregate::Count => Accumulator::Count(0),
+                Aggregate::Sum(_) => Accumulator::Sum(0.0),
+                Aggregate::Min(_) => Accumulator::Min(None),
+                Aggregate::Max(_) => Accumulator::Max(None),
+                Aggregate::Mean(_) => Accumulator::Mean(0.0, 0),
+            })
+            .collect();
+        Group { key, accumulators }
+    }
+
+    fn update(
+        &mut self,
+        aggregates: &[ResolvedAggregate],
+        record: &StringRecord,
+    ) -> Result<(), CsvStreamError> {
+        for (accumulator, (aggregate, index)) in self.accumulators.iter_mut().zip(aggregates) {
+            if let Accumulator::Count(count) = accumulator {
+                *count += 1;
+                continue;
+            }
+            let value = index
+                .and_then(|index| record.get(index))
+                .unwrap_or_default();
+            if value.is_empty() {
+                continue;
+            }
+            let number = value
+                .parse::<f64>()
+                .map_err(|_| CsvStreamError::InvalidNumber {
+                    column: aggregate.column().unwrap_or_default().to_string(),
+                    value: value.to_string(),
+                })?;
+            match accumulator {
+                Accumulator::Count(_) => {}
+                Accumulator::Sum(sum) => *sum += number,
+                Accumulator::Min(min) => *min = Some(min.map_or(number, |min| min.min(number))),
+                Accumulator::Max(max) => *max = Some(max.map_or(number, |max| max.max(number))),
+                Accumulator::Mean(sum, count) => {
+                    *sum += number;
+                    *count += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> StringRecord {
+        let mut record: StringRecord = self.key.iter().collect();
+        for accumulator in self.accumulators {
+            let value = match accumulator {
+                Accumulator::Count(count) => count.to_string(),
+                Accumulator::Sum(sum) => sum.to_string(),
+                Accumulator::Min(value) | Accumulator::Max(value) => {
+                    value.map(|value| value.to_string()).unwrap_or_default()
+                }
+                Accumulator::Mean(_, 0) => String::new(),
+                Accumulator::Mean(sum, count) => (sum / count as f64).to_string(),
+            };
+            record.push_field(&value);
+        }
+        record
+    }
+}
@@ -0,0 +1,906 @@
+use crate::ColumnType;
+use csv::{ReaderBuilder, WriterBuilder};
+use serde::{
+    de::{self, MapAccess, SeqAccess, Visitor},
+    ser::{SerializeMap, SerializeSeq},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{collections::HashMap, fmt, fs, io, path::Path};
+
+/// The key `toml` uses to pass a datetime through serde.
+const TOML_DATETIME_KEY: &str = "$__toml_private_datetime";
+
+/// A value of any supported data format.
+///
+/// Objects keep the order of their keys, so CSV columns and TOML, YAML and
+/// JSON fields are written in the order they were read.
+///
+/// # Example
+///
+/// ```
+/// use encoding::DataValue;
+///
+/// let value: DataValue = serde_json::from_str(r#"{"name": "Tom", "age": 3}"#).unwrap();
+///
+/// assert_eq!(value.get("age"), Some(&DataValue::Integer(3)));
+/// assert_eq!(serde_json::to_string(&value).unwrap(), r#"{"name":"Tom","age":3}"#);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataValue {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<DataValue>),
+    Object(Vec<(String, DataValue)>),
+}
+
+impl DataValue {
+    /// The value of a key of an object
+    pub fn get(&self, key: &str) -> Option<&DataValue> {
+        match self {
+            DataValue::Object(fields) => fields
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut DataValue> {
+        match self {
+            DataValue::Object(fields) => fields
+                .iter_mut()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// The text of a scalar as written to a CSV field, `None` for arrays and
+    /// objects
+    fn scalar_text(&self) -> Option<String> {
+        match self {
+            DataValue::Null => Some(String::new()),
+            DataValue::Bool(value) => Some(value.to_string()),
+            DataValue::Integer(value) => Some(value.to_string()),
+            // Keep a decimal point so the value reads back as a float.
+            DataValue::Float(value) if value.fract() == 0.0 && value.abs() < 1e16 => {
+                Some(format!("{:.1}", value))
+            }
+            DataValue::Float(value) => Some(value.to_string()),
+            DataValue::String(value) => Some(value.clone()),
+            DataValue::Array(_) | DataValue::Object(_) => None,
+        }
+    }
+
+    /// Guess the type of a CSV field
+    fn infer(text: &str) -> DataValue {
+        let digits = text.strip_prefix('-').unwrap_or(text);
+        let leading_zero =
+            digits.len() > 1 && digits.starts_with('0') && digits.as_bytes()[1].is_ascii_digit();
+        match text {
+            "" => DataValue::Null,
+            "true" => DataValue::Bool(true),
+            "false" => DataValue::Bool(false),
+            // Zip codes, phone numbers and such stay text.
+            _ if leading_zero || !digits.starts_with(|c: char| c.is_ascii_digit()) => {
+                DataValue::String(text.to_string())
+            }
+            _ => match (text.parse::<i64>(), text.parse::<f64>()) {
+                (Ok(integer), _) => DataValue::Integer(integer),
+                (_, Ok(float)) => DataValue::Float(float),
+                _ => DataValue::String(text.to_string()),
+            },
+        }
+    }
+
+    /// Convert a scalar to a type, `None` if it doesn't fit
+    fn coerce(&self, column_type: ColumnType) -> Option<DataValue> {
+        let value = match (self, column_type) {
+            (DataValue::Null, _) => DataValue::Null,
+            (
+                DataValue::String(text),
+                ColumnType::Integer | ColumnType::Float | ColumnType::Boolean,
+            ) if text.is_empty() => DataValue::Null,
+            (_, ColumnType::Text) => DataValue::String(self.scalar_text()?),
+            (DataValue::Integer(_), ColumnType::Integer) => self.clone(),
+            (DataValue::Float(float), ColumnType::Integer)
+                if float.fract() == 0.0 && float.abs() < i64::MAX as f64 =>
+            {
+                DataValue::Integer(*float as i64)
+            }
+            (DataValue::String(text), ColumnType::Integer) => {
+                DataValue::Integer(text.parse().ok()?)
+            }
+            (DataValue::Integer(integer), ColumnType::Float) => DataValue::Float(*integer as f64),
+            (DataValue::Float(_), ColumnType::Float) => self.clone(),
+            (DataValue::String(text), ColumnType::Float) => DataValue::Float(text.parse().ok()?),
+            (DataValue::Bool(_), ColumnType::Boolean) => self.clone(),
+            (DataValue::String(text), ColumnType::Boolean) => {
+                DataValue::Bool(text.to_lowercase().parse().ok()?)
+            }
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Remove nulls from objects, as TOML has no null
+    fn without_nulls(self) -> DataValue {
+        match self {
+            DataValue::Array(values) => {
+                DataValue::Array(values.into_iter().map(DataValue::without_nulls).collect())
+            }
+            DataValue::Object(fields) => DataValue::Object(
+                fields
+                    .into_iter()
+                    .filter(|(_, value)| *value != DataValue::Null)
+                    .map(|(key, value)| (key, value.without_nulls()))
+                    .collect(),
+            ),
+            value => value,
+        }
+    }
+}
+
+impl Serialize for DataValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            DataValue::Null => serializer.serialize_unit(),
+            DataValue::Bool(value) => serializer.serialize_bool(*value),
+            DataValue::Integer(value) => serializer.serialize_i64(*value),
+            DataValue::Float(value) => serializer.serialize_f64(*value),
+            DataValue::String(value) => serializer.serialize_str(value),
+            DataValue::Array(values) => {
+                let mut seq = serializer.serialize_seq(Some(values.len()))?;
+                for value in values {
+                    seq.serialize_element(value)?;
+                }
+                seq.end()
+            }
+            DataValue::Object(fields) => {
+                let mut map = serializer.serialize_map(Some(fields.len()))?;
+                for (key, value) in fields {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DataValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(DataValueVisitor)
+    }
+}
+
+struct DataValueVisitor;
+
+impl<'de> Visitor<'de> for DataValueVisitor {
+    type Value = DataValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "any JSON, TOML or YAML value")
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<DataValue, E> {
+        Ok(DataValue::Bool(value))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<DataValue, E> {
+        Ok(DataValue::Integer(value))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<DataValue, E> {
+        Ok(i64::try_from(value).map_or(DataValue::Float(value as f64), DataValue::Integer))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<DataValue, E> {
+        Ok(DataValue::Float(value))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<DataValue, E> {
+        Ok(DataValue::String(value.to_string()))
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<DataValue, E> {
+        Ok(DataValue::String(value))
+    }
+
+    fn visit_unit<E>(self) -> Result<DataValue, E> {
+        Ok(DataValue::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<DataValue, E> {
+        Ok(DataValue::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<DataValue, D::Error> {
+        DataValue::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<DataValue, A::Error> {
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(DataValue::Array(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<DataValue, A::Error> {
+        let mut fields = Vec::new();
+        // YAML keys can be any scalar, they become text.
+        while let Some((key, value)) = map.next_entry::<DataValue, DataValue>()? {
+            let key = key
+                .scalar_text()
+                .ok_or_else(|| de::Error::custom("object keys must be scalars"))?;
+            fields.push((key, value));
+        }
+        match fields.as_slice() {
+            [(key, DataValue::String(datetime))] if key == TOML_DATETIME_KEY => {
+                Ok(DataValue::String(datetime.clone()))
+            }
+            _ => Ok(DataValue::Object(fields)),
+        }
+    }
+}
+
+/// A supported data format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+    /// Comma separated values, one record per row.
+    Csv,
+    /// A JSON document.
+    Json,
+    /// One JSON document per line, one record per line.
+    JsonLines,
+    /// A TOML document, always a table.
+    Toml,
+    /// A YAML document.
+    Yaml,
+}
+
+impl DataFormat {
+    /// The format of a file from its extension
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use encoding::DataFormat;
+    /// use std::path::Path;
+    ///
+    /// assert_eq!(DataFormat::from_path(Path::new("cats.yml")), Some(DataFormat::Yaml));
+    /// assert_eq!(DataFormat::from_path(Path::new("cats.txt")), None);
+    /// ```
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "csv" => Some(DataFormat::Csv),
+            "json" => Some(DataFormat::Json),
+            "jsonl" | "ndjson" => Some(DataFormat::JsonLines),
+            "toml" => Some(DataFormat::Toml),
+            "yaml" | "yml" => Some(DataFormat::Yaml),
+            _ => None,
+        }
+    }
+}
+
+/// An error converting between data formats.
+#[derive(Debug)]
+pub enum ConvertError {
+    Csv(csv::Error),
+    Json(serde_json::Error),
+    TomlRead(toml::de::Error),
+    TomlWrite(toml::ser::Error),
+    Yaml(serde_norway::Error),
+    Io(io::Error),
+    /// The file extension is not a supported format.
+    UnknownFormat(String),
+    /// The root key is missing from the input document.
+    MissingRootKey(String),
+    /// A value could not be coerced to the type of its path.
+    Coercion {
+        path: String,
+        value: String,
+        expected: ColumnType,
+    },
+    /// The value can't be written in the output format.
+    Unsupported(String),
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConvertError::Csv(e) => write!(f, "CSV error: {}", e),
+            ConvertError::Json(e) => write!(f, "JSON error: {}", e),
+            ConvertError::TomlRead(e) => write!(f, "TOML error: {}", e),
+            ConvertError::TomlWrite(e) => write!(f, "TOML error: {}", e),
+            ConvertError::Yaml(e) => write!(f, "YAML error: {}", e),
+            ConvertError::Io(e) => write!(f, "I/O error: {}", e),
+            ConvertError::UnknownFormat(path) => write!(f, "Unknown data format of {}", path),
+            ConvertError::MissingRootKey(key) => write!(f, "Missing root key {}", key),
+            ConvertError::Coercion {
+                path,
+                value,
+                expected,
+            } => write!(f, "{}: {} can't be coerced to {:?}", path, value, expected),
+            ConvertError::Unsupported(reason) => write!(f, "Unsupported: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ConvertError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConvertError::Csv(e) => Some(e),
+            ConvertError::Json(e) => Some(e),
+            ConvertError::TomlRead(e) => Some(e),
+            ConvertError::TomlWrite(e) => Some(e),
+            ConvertError::Yaml(e) => Some(e),
+            ConvertError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<csv::Error> for ConvertError {
+    fn from(e: csv::Error) -> Self {
+        ConvertError::Csv(e)
+    }
+}
+
+impl From<serde_json::Error> for ConvertError {
+    fn from(e: serde_json::Error) -> Self {
+        ConvertError::Json(e)
+    }
+}
+
+impl From<toml::de::Error> for ConvertError {
+    fn from(e: toml::de::Error) -> Self {
+        ConvertError::TomlRead(e)
+    }
+}
+
+impl From<toml::ser::Error> for ConvertError {
+    fn from(e: toml::ser::Error) -> Self {
+        ConvertError::TomlWrite(e)
+    }
+}
+
+impl From<serde_norway::Error> for ConvertError {
+    fn from(e: serde_norway::Error) -> Self {
+        ConvertError::Yaml(e)
+    }
+}
+
+impl From<io::Error> for ConvertError {
+    fn from(e: io::Error) -> Self {
+        ConvertError::Io(e)
+    }
+}
+
+/// A converter between CSV, JSON, JSON Lines, TOML and YAML.
+///
+/// Documents are read into a `DataValue`. CSV and JSON Lines hold records:
+/// reading them gives an array with a value per row or line, and writing them
+/// takes the elements of an array, or the value itself, as the records.
+///
+/// CSV columns named like `owner.name` or `tags.0` are paths into nested
+/// objects and arrays, and nested records are flattened into such columns
+/// when written to CSV. CSV fields are typed by inference by default: empty
+/// fields are null and `true`, `false` and numbers are parsed.
+///
+/// # Example
+///
+/// ```
+/// use encoding::{DataFormat, FormatConverter};
+///
+/// let csv_data = "name,owner.name,owner.age,zip\nGarfield,Jon,30,02134\n";
+///
+/// let json = FormatConverter::new()
+///     .convert(csv_data, DataFormat::Csv, DataFormat::Json)
+///     .unwrap();
+/// assert_eq!(
+///     json,
+///     r#"[{"name":"Garfield","owner":{"name":"Jon","age":30},"zip":"02134"}]"#
+/// );
+///
+/// let toml = FormatConverter::new()
+///     .root_key("cats")
+///     .convert(&json, DataFormat::Json, DataFormat::Toml)
+///     .unwrap();
+/// assert!(toml.starts_with("[[cats]]\nname = \"Garfield\"\n"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct FormatConverter {
+    delimiter: u8,
+    has_headers: bool,
+    write_headers: bool,
+    separator: Option<String>,
+    flatten: bool,
+    infer_types: bool,
+    column_types: HashMap<String, ColumnType>,
+    root_key: Option<String>,
+    pretty: bool,
+}
+
+impl Default for FormatConverter {
+    fn default() -> Self {
+        FormatConverter {
+            delimiter: b',',
+            has_headers: true,
+            write_headers: true,
+            separator: Some(".".to_string()),
+            flatten: false,
+            infer_types: true,
+            column_types: HashMap::new(),
+            root_key: None,
+            pretty: false,
+        }
+    }
+}
+
+impl FormatConverter {
+    /// Create a converter with the default options
+    pub fn new() -> Self {
+        FormatConverter::default()
+    }
+
+    /// Read and write CSV fields separated by `delimiter`, `,` by default
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Whether CSV input starts with a header, `true` by default
+    ///
+    /// Without one, columns are named `column_1`, `column_2` and so on.
+    pub fn has_headers(mut self, has_headers: bool) -> Self {
+        self.has_headers = has_headers;
+        self
+    }
+
+    /// Whether CSV output starts with a header, `true` by default
+    pub fn write_headers(mut self, write_headers: bool) -> Self {
+        self.write_headers = write_headers;
+        self
+    }
+
+    /// Separate the keys of nested fields in CSV columns with `separator`,
+    /// `.` by default
+    ///
+    /// An empty separator splits nothing and works like `no_nesting`.
+    pub fn separator(mut self, separator: &str) -> Self {
+        self.separator = Some(separator.to_string()).filter(|separator| !separator.is_empty());
+        self
+    }
+
+    /// Keep CSV column names as they are and write nested values to CSV as
+    /// JSON text
+    pub fn no_nesting(mut self) -> Self {
+        self.separator = None;
+        self
+    }
+
+    /// Flatten nested records for every output format, not only CSV
+    pub fn flatten(mut self, flatten: bool) -> Self {
+        self.flatten = flatten;
+        self
+    }
+
+    /// Whether CSV fields are typed by inference, `true` by default
+    ///
+    /// Otherwise every field is text.
+    pub fn infer_types(mut self, infer_types: bool) -> Self {
+        self.infer_types = infer_types;
+        self
+    }
+
+    /// Coerce the values at a path of every record to a type when reading
+    ///
+    /// Empty text becomes null, and a value that doesn't fit the type fails
+    /// the conversion.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the field, with keys joined by the separator.
+    /// * `column_type` - The type of the field.
+    pub fn coerce(mut self, path: &str, column_type: ColumnType) -> Self {
+        self.column_types.insert(path.to_string(), column_type);
+        self
+    }
+
+    /// Read the records from a key of the root object, if the input is an
+    /// object, and write JSON, TOML and YAML documents with the records under
+    /// that key
+    ///
+    /// TOML documents must be tables, so writing an array to TOML needs a
+    /// root key.
+    pub fn root_key(mut self, key: &str) -> Self {
+        self.root_key = Some(key.to_string());
+        self
+    }
+
+    /// Write indented JSON and TOML, `false` by default
+    pub fn pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+
+    /// Convert a document between formats
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The document to convert.
+    /// * `from` - The format of the input.
+    /// * `to` - The format of the output.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the converted document.
+    pub fn convert(
+        &self,
+        input: &str,
+        from: DataFormat,
+        to: DataFormat,
+    ) -> Result<String, ConvertError> {
+        self.write(&self.read(input, from)?, to)
+    }
+
+    /// Convert a file to another file, with formats taken from their
+    /// extensions
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The path of the file to convert.
+    /// * `output` - The path of the converted file.
+    pub fn convert_file(&self, input: &Path, output: &Path) -> Result<(), ConvertError> {
+        let format_of = |path: &Path| {
+            DataFormat::from_path(path)
+                .ok_or_else(|| ConvertError::UnknownFormat(path.display().to_string()))
+        };
+        let (from, to) = (format_of(input)?, format_of(output)?);
+        let converted = self.convert(&fs::read_to_string(input)?, from, to)?;
+        fs::write(output, converted)?;
+        Ok(())
+    }
+
+    /// Read a document into a value
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The document to read.
+    /// * `format` - The format of the document.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the value, or with the records for CSV and JSON Lines.
+    pub fn read(&self, input: &str, format: DataFormat) -> Result<DataValue, ConvertError> {
+        let mut value = match format {
+            DataFormat::Csv => self.read_csv(input)?,
+            DataFormat::Json => serde_json::from_str(input)?,
+            DataFormat::JsonLines => DataValue::Array(
+                input
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(serde_json::from_str)
+                    .collect::<Result<_, _>>()?,
+            ),
+            DataFormat::Toml => toml::from_str(input)?,
+            DataFormat::Yaml => serde_norway::from_str(input)?,
+        };
+
+        if let (Some(key), DataValue::Object(fields)) = (&self.root_key, &mut value) {
+            let position = fields
+                .iter()
+                .position(|(name, _)| name == key)
+                .ok_or_else(|| ConvertError::MissingRootKey(key.clone()))?;
+            value = fields.swap_remove(position).1;
+        }
+
+        match &mut value {
+            DataValue::Array(records) => {
+                for record in records {
+                    self.coerce_record(record)?;
+                }
+            }
+            record => self.coerce_record(record)?,
+        }
+        Ok(value)
+    }
+
+    /// Write a value as a document
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value to write.
+    /// * `format` - The format of the document.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` with the document.
+    pub fn write(&self, value: &DataValue, format: DataFormat) -> Result<String, ConvertError> {
+        let flattened;
+        let mut value = value;
+        if self.flatten && format != DataFormat::Csv {
+            flattened = match value {
+                DataValue::Array(records) => DataValue::Array(
+                    records
+                        .iter()
+                        .map(|record| self.flatten_record(record))
+                        .collect(),
+                ),
+                record => self.flatten_record(record),
+            };
+            value = &flattened;
+        }
+
+        let wrapped;
+        if let Some(key) = &self.root_key {
+            if matches!(
+                format,
+                DataFormat::Json | DataFormat::Toml | DataFormat::Yaml
+            ) {
+                wrapped = DataValue::Object(vec![(key.clone(), value.clone())]);
+                value = &wrapped;
+            }
+        }
+
+        match format {
+            DataFormat::Csv => self.write_csv(value),
+            DataFormat::Json if self.pretty => Ok(serde_json::to_string_pretty(value)?),
+            DataFormat::Json => Ok(serde_json::to_string(value)?),
+            DataFormat::JsonLines => {
+                let mut lines = String::new();
+                for record in records(value) {
+                    lines.push_str(&serde_json::to_string(record)?);
+                    lines.push('\n');
+                }
+                Ok(lines)
+            }
+            DataFormat::Toml => {
+                if !matches!(value, DataValue::Object(_)) {
+                    return Err(ConvertError::Unsupported(
+                        "a TOML document must be a table, set a root key".to_string(),
+                    ));
+                }
+                let value = value.clone().without_nulls();
+                match self.pretty {
+                    true => Ok(toml::to_string_pretty(&value)?),
+                    false => Ok(toml::to_string(&value)?),
+                }
+            }
+            DataFormat::Yaml => Ok(serde_norway::to_string(value)?),
+        }
+    }
+
+    fn read_csv(&self, input: &str) -> Result<DataValue, ConvertError> {
+        let mut reader = ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .has_headers(self.has_headers)
+            .flexible(true)
+            .from_reader(input.as_bytes());
+        let headers: Vec<String> = match self.has_headers {
+            true => reader.headers()?.iter().map(str::to_string).collect(),
+            false => Vec::new(),
+        };
+
+        let mut records = Vec::new();
+        for record in reader.records() {
+            let fields = record?
+                .iter()
+                .enumerate()
+                .map(|(index, field)| {
+                    let name = headers
+                        .get(index)
+                        .cloned()
+                        .unwrap_or_else(|| format!("column_{}", index + 1));
+                    let value = match self.infer_types {
+                        true => DataValue::infer(field),
+                        false => DataValue::String(field.to_string()),
+                    };
+                    (name, value)
+                })
+                .collect();
+            records.push(match &self.separator {
+                Some(separator) => unflatten(fields, separator),
+                None => DataValue::Object(fields),
+            });
+        }
+        Ok(DataValue::Array(records))
+    }
+
+    fn write_csv(&self, value: &DataValue) -> Result<String, ConvertError> {
+        let rows: Vec<Vec<(String, String)>> = records(value)
+            .map(|record| {
+                let mut fields = Vec::new();
+                match record {
+                    DataValue::Object(_) | DataValue::Array(_) => {
+                        self.flatten_into(record, None, &mut fields)
+                    }
+                    scalar => self.flatten_into(scalar, Some("value".to_string()), &mut fields),
+                }
+                fields
+                    .into_iter()
+                    .map(|(path, value)| Ok((path, csv_field(&value)?)))
+                    .collect::<Result<_, ConvertError>>()
+            })
+            .collect::<Result<_, _>>()?;
+
+        let mut columns: Vec<&str> = Vec::new();
+        for row in &rows {
+            for (column, _) in row {
+                if !columns.contains(&column.as_str()) {
+                    columns.push(column);
+                }
+            }
+        }
+
+        let mut writer = WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .from_writer(Vec::new());
+        if self.write_headers {
+            writer.write_record(&columns)?;
+        }
+        for row in &rows {
+            writer.write_record(columns.iter().map(|column| {
+                row.iter()
+                    .find(|(name, _)| name == column)
+                    .map_or("", |(_, value)| value.as_str())
+            }))?;
+        }
+        let bytes = writer
+            .into_inner()
+            .map_err(|e| ConvertError::Io(e.into_error()))?;
+        String::from_utf8(bytes).map_err(|e| ConvertError::Unsupported(e.to_string()))
+    }
+
+    /// Flatten a record into an object of paths to scalars
+    fn flatten_record(&self, record: &DataValue) -> DataValue {
+        let mut fields = Vec::new();
+        self.flatten_into(record, None, &mut fields);
+        DataValue::Object(fields)
+    }
+
+    fn flatten_into(
+        &self,
+        value: &DataValue,
+        path: Option<String>,
+        fields: &mut Vec<(String, DataValue)>,
+    ) {
+        let Some(separator) = &self.separator else {
+            match (value, path) {
+                (DataValue::Object(object), None) => fields.extend(object.iter().cloned()),
+                (value, path) => fields.push((path.unwrap_or_default(), value.clone())),
+            }
+            return;
+        };
+
+        let children: Vec<(String, &DataValue)> = match value {
+            DataValue::Object(object) => object
+                .iter()
+                .map(|(key, value)| (key.clone(), value))
+                .collect(),
+            DataValue::Array(values) => values
+                .iter()
+                .enumerate()
+                .map(|(index, value)| (index.to_string(), value))
+                .collect(),
+            scalar => {
+                fields.push((path.unwrap_or_default(), scalar.clone()));
+                return;
+            }
+        };
+        if children.is_empty() {
+            if let Some(path) = path {
+                fields.push((path, DataValue::Null));
+            }
+            return;
+        }
+        for (key, child) in children {
+            let child_path = match &path {
+                Some(path) => format!("{}{}{}", path, separator, key),
+                None => key,
+            };
+            self.flatten_into(child, Some(child_path), fields);
+        }
+    }
+
+    fn coerce_record(&self, record: &mut DataValue) -> Result<(), ConvertError> {
+        for (path, column_type) in &self.column_types {
+            let keys: Vec<&str> = match &self.separator {
+                Some(separator) => path.split(separator.as_str()).collect(),
+                None => vec![path.as_str()],
+            };
+            let mut target = Some(&mut *record);
+            for key in keys {
+                target = target.and_then(|value| match value {
+                    DataValue::Array(values) => key
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|index| values.get_mut(index)),
+                    object => object.get_mut(key),
+                });
+            }
+            if let Some(value) = target {
+                *value = value
+                    .coerce(*column_type)
+                    .ok_or_else(|| ConvertError::Coercion {
+                        path: path.clone(),
+                        value: serde_json::to_string(value).unwrap_or_default(),
+                        expected: *column_type,
+                    })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The records of a value: the elements of an array or the value itself
+fn records(value: &DataValue) -> Box<dyn Iterator<Item = &DataValue> + '_> {
+    match value {
+        DataValue::Array(values) => Box::new(values.iter()),
+        value => Box::new(std::iter::once(value)),
+    }
+}
+
+/// The text of a CSV field, with nested values as JSON
+fn csv_field(value: &DataValue) -> Result<String, ConvertError> {
+    match value.scalar_text() {
+        Some(text) => Ok(text),
+        None => Ok(serde_json::to_string(value)?),
+    }
+}
+
+/// Nest the fields of a CSV row along the paths of their column names
+fn unflatten(fields: Vec<(String, DataValue)>, separator: &str) -> DataValue {
+    let mut root = DataValue::Object(Vec::new());
+    for (path, value) in fields {
+        insert_path(&mut root, &path, separator, value);
+    }
+    into_arrays(root)
+}
+
+fn insert_path(object: &mut DataValue, path: &str, separator: &str, value: DataValue) {
+    let DataValue::Object(fields) = object else {
+        return;
+    };
+    let Some((key, rest)) = path.split_once(separator) else {
+        fields.push((path.to_string(), value));
+        return;
+    };
+    let position = match fields.iter().position(|(name, _)| name == key) {
+        Some(position) => position,
+        None => {
+            fields.push((key.to_string(), DataValue::Object(Vec::new())));
+            fields.len() - 1
+        }
+    };
+    match &mut fields[position].1 {
+        child @ DataValue::Object(_) => insert_path(child, rest, separator, value),
+        // A scalar column is in the way, keep the full column name.
+        _ => fields.push((path.to_string(), value)),
+    }
+}
+
+/// Turn objects keyed `0`, `1`, ... into arrays
+fn into_arrays(value: DataValue) -> DataValue {
+    let DataValue::Object(fields) = value else {
+        return value;
+    };
+    let fields: Vec<(String, DataValue)> = fields
+        .into_iter()
+        .map(|(key, value)| (key, into_arrays(value)))
+        .collect();
+    let is_array = !fields.is_empty()
+        && fields
+            .iter()
+            .enumerate()
+            .all(|(index, (key, _))| *key == index.to_string());
+    match is_array {
+        true => DataValue::Array(fields.into_iter().map(|(_, value)| value).collect()),
+        false => DataValue::Object(fields),
+    }
+}
@@ -1,8 +1,19 @@
+pub mod binary_codec;
+pub use binary_codec::*;
+
 pub mod character_sets;
 pub use character_sets::*;
 
 pub mod csv_processing;
 pub use csv_processing::*;
 
+pub mod csv_streaming;
+pub use csv_streaming::*;
+
+pub mod format_conversion;
+pub use format_conversion::*;
+
 pub mod structured_data;
 pub use structured_data::*;
+
+pub use regex;
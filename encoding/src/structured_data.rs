@@ -1,5 +1,5 @@
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::io::Error;
 
@@ -70,7 +70,7 @@ pub struct Package {
 /// assert_eq!(payload.kind, 0);
 /// assert_eq!(payload.value, 42);
 /// ```
-#[derive(Default, PartialEq, Debug)]
+#[derive(Default, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Payload {
     pub kind: u8,
     pub value: u16,
@@ -78,6 +78,8 @@ pub struct Payload {
 
 /// Encodes a payload into a byte vector using little-endian encoding.
 ///
+/// Other binary layouts can be described with a `RecordLayout`.
+///
 /// # Arguments
 ///
 /// * `payload` - A reference to a `Payload` struct.
@@ -0,0 +1,336 @@
+use encoding::{
+    BinaryField, Checksum, CodecErrorKind, Endian, EnumLayout, FrameLayout, IntType, Length,
+    RecordLayout,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[cfg(test)]
+mod tests_binary_codec {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Reading {
+        sensor: String,
+        id: u32,
+        offset: i16,
+        temperature: f32,
+        samples: Vec<u16>,
+        raw: Vec<u8>,
+    }
+
+    fn reading_layout() -> RecordLayout {
+        RecordLayout::new()
+            .field("sensor", BinaryField::text(Length::Fixed(4)))
+            .field("id", BinaryField::u32(Endian::Big))
+            .field("offset", BinaryField::i16(Endian::Little))
+            .field("temperature", BinaryField::F32(Endian::Big))
+            .field(
+                "samples",
+                BinaryField::array(
+                    BinaryField::u16(Endian::Little),
+                    Length::Prefixed(IntType::U8, Endian::Little),
+                ),
+            )
+            .field("raw", BinaryField::bytes(Length::Remaining))
+    }
+
+    #[test]
+    fn test_record_round_trip() {
+        let reading = Reading {
+            sensor: "T1".to_string(),
+            id: 0x01020304,
+            offset: -2,
+            temperature: 21.5,
+            samples: vec![1, 256],
+            raw: vec![0xAA, 0xBB],
+        };
+
+        let bytes = reading_layout().encode(&reading).unwrap();
+        assert_eq!(
+            bytes,
+            vec![
+                b'T', b'1', 0, 0, // sensor
+                1, 2, 3, 4, // id
+                0xFE, 0xFF, // offset
+                0x41, 0xAC, 0, 0, // temperature
+                2, 1, 0, 0, 1, // samples
+                0xAA, 0xBB, // raw
+            ]
+        );
+        assert_eq!(reading_layout().decode::<Reading>(&bytes).unwrap(), reading);
+    }
+
+    #[test]
+    fn test_decode_errors_report_offset_and_path() {
+        let layout = reading_layout();
+        let mut bytes = layout
+            .encode_value(&json!({
+                "sensor": "T1", "id": 1, "offset": 0, "temperature": 0.0,
+                "samples": [1, 2, 3], "raw": []
+            }))
+            .unwrap();
+
+        let error = layout.decode_value(&bytes[..17]).unwrap_err();
+        assert_eq!(error.offset, 17);
+        assert_eq!(error.path, "samples.1");
+        assert_eq!(
+            error.kind,
+            CodecErrorKind::UnexpectedEnd {
+                needed: 2,
+                available: 0
+            }
+        );
+
+        bytes[1] = 0xFF;
+        let error = layout.decode_value(&bytes).unwrap_err();
+        assert_eq!(error.kind, CodecErrorKind::InvalidUtf8);
+        assert_eq!(error.to_string(), "Invalid UTF-8 at byte 1 in sensor");
+
+        let error = RecordLayout::new()
+            .field("kind", BinaryField::u8())
+            .decode_value(&[1, 2, 3])
+            .unwrap_err();
+        assert_eq!(
+            (error.offset, error.kind),
+            (1, CodecErrorKind::TrailingBytes(2))
+        );
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        let layout = reading_layout();
+        let mut value = json!({
+            "sensor": "T1", "id": 1, "offset": 0, "temperature": 0.0,
+            "samples": [1, 2, 70000], "raw": []
+        });
+
+        let error = layout.encode_value(&value).unwrap_err();
+        assert_eq!(error.path, "samples.2");
+        assert_eq!(error.offset, 19);
+        assert_eq!(error.kind, CodecErrorKind::OutOfRange("70000".to_string()));
+
+        value["sensor"] = json!("THERMO");
+        let error = layout.encode_value(&value).unwrap_err();
+        assert_eq!(
+            error.kind,
+            CodecErrorKind::LengthMismatch {
+                expected: 4,
+                actual: 6
+            }
+        );
+
+        value["sensor"] = json!(7);
+        assert_eq!(
+            layout.encode_value(&value).unwrap_err().kind,
+            CodecErrorKind::TypeMismatch("a string")
+        );
+
+        value.as_object_mut().unwrap().remove("id");
+        value["sensor"] = json!("T1");
+        let error = layout.encode_value(&value).unwrap_err();
+        assert_eq!(
+            (error.path.as_str(), error.kind),
+            ("id", CodecErrorKind::MissingField)
+        );
+    }
+
+    #[test]
+    fn test_bit_fields() {
+        let layout = RecordLayout::new().field(
+            "header",
+            BinaryField::bits(
+                IntType::U16,
+                Endian::Big,
+                &[("version", 4), ("length", 4), ("flags", 3)],
+            ),
+        );
+
+        let header = json!({"header": {"version": 4, "length": 5, "flags": 2}});
+        let bytes = layout.encode_value(&header).unwrap();
+        assert_eq!(bytes, vec![0x45, 0b0100_0000]);
+        assert_eq!(layout.decode_value(&bytes).unwrap(), header);
+
+        let error = layout
+            .encode_value(&json!({"header": {"version": 16, "length": 5, "flags": 2}}))
+            .unwrap_err();
+        assert_eq!(error.path, "header.version");
+        assert_eq!(error.kind, CodecErrorKind::OutOfRange("16".to_string()));
+
+        let error = RecordLayout::new()
+            .field(
+                "flags",
+                BinaryField::bits(IntType::U8, Endian::Big, &[("a", 5), ("b", 4)]),
+            )
+            .decode_value(&[0])
+            .unwrap_err();
+        assert!(matches!(error.kind, CodecErrorKind::InvalidLayout(_)));
+    }
+
+    #[test]
+    fn test_bit_field_edge_widths() {
+        let layout = RecordLayout::new()
+            .field(
+                "whole",
+                BinaryField::bits(IntType::U64, Endian::Little, &[("value", 64)]),
+            )
+            .field("none", BinaryField::bits(IntType::U8, Endian::Little, &[]));
+        let value = json!({"whole": {"value": u64::MAX}, "none": {}});
+        let bytes = layout.encode_value(&value).unwrap();
+        assert_eq!(bytes, [vec![0xFF; 8], vec![0]].concat());
+        assert_eq!(layout.decode_value(&bytes).unwrap(), value);
+
+        let error = RecordLayout::new()
+            .field(
+                "flags",
+                BinaryField::bits(IntType::U8, Endian::Big, &[("a", 0), ("b", 4)]),
+            )
+            .encode_value(&json!({"flags": {"a": 0, "b": 1}}))
+            .unwrap_err();
+        assert_eq!(
+            error.kind,
+            CodecErrorKind::InvalidLayout("bit field a has no bits".to_string())
+        );
+    }
+
+    #[test]
+    fn test_variable_arrays_of_empty_items() {
+        for length in [
+            Length::Remaining,
+            Length::Prefixed(IntType::U8, Endian::Big),
+        ] {
+            let error = RecordLayout::new()
+                .field(
+                    "items",
+                    BinaryField::array(BinaryField::Record(RecordLayout::new()), length),
+                )
+                .decode_value(&[5])
+                .unwrap_err();
+            assert!(matches!(error.kind, CodecErrorKind::InvalidLayout(_)));
+        }
+
+        let layout = RecordLayout::new().field(
+            "items",
+            BinaryField::array(BinaryField::Record(RecordLayout::new()), Length::Fixed(2)),
+        );
+        assert_eq!(
+            layout.decode_value(&[]).unwrap(),
+            json!({"items": [{}, {}]})
+        );
+    }
+
+    #[test]
+    fn test_enum_discriminants() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        enum Shape {
+            Empty,
+            Circle { radius: u16 },
+            Rectangle { width: u16, height: u16 },
+        }
+
+        let layout = RecordLayout::new().field(
+            "shapes",
+            BinaryField::array(
+                BinaryField::Enum(
+                    EnumLayout::new(IntType::U16, Endian::Big)
+                        .variant(0, "Empty", RecordLayout::new())
+                        .variant(
+                            10,
+                            "Circle",
+                            RecordLayout::new().field("radius", BinaryField::u16(Endian::Big)),
+                        )
+                        .variant(
+                            20,
+                            "Rectangle",
+                            RecordLayout::new()
+                                .field("width", BinaryField::u16(Endian::Big))
+                                .field("height", BinaryField::u16(Endian::Big)),
+                        ),
+                ),
+                Length::Remaining,
+            ),
+        );
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Drawing {
+            shapes: Vec<Shape>,
+        }
+
+        let drawing = Drawing {
+            shapes: vec![
+                Shape::Circle { radius: 3 },
+                Shape::Empty,
+                Shape::Rectangle {
+                    width: 1,
+                    height: 2,
+                },
+            ],
+        };
+        let bytes = layout.encode(&drawing).unwrap();
+        assert_eq!(bytes, vec![0, 10, 0, 3, 0, 0, 0, 20, 0, 1, 0, 2]);
+        assert_eq!(layout.decode::<Drawing>(&bytes).unwrap(), drawing);
+
+        let error = layout.decode_value(&[0, 10, 0, 3, 0, 30]).unwrap_err();
+        assert_eq!(error.offset, 4);
+        assert_eq!(error.path, "shapes.1");
+        assert_eq!(error.kind, CodecErrorKind::UnknownDiscriminant(30));
+
+        let error = layout
+            .encode_value(&json!({"shapes": ["Triangle"]}))
+            .unwrap_err();
+        assert_eq!(
+            error.kind,
+            CodecErrorKind::UnknownVariant("Triangle".to_string())
+        );
+    }
+
+    #[test]
+    fn test_checksummed_frames() {
+        let record = RecordLayout::new()
+            .field("id", BinaryField::u16(Endian::Little))
+            .field(
+                "name",
+                BinaryField::text(Length::Prefixed(IntType::U8, Endian::Little)),
+            );
+        let value = json!({"id": 7, "name": "Tom"});
+
+        let frame = FrameLayout::new(record.clone())
+            .magic(b"CAT")
+            .length_prefix(IntType::U16, Endian::Big)
+            .checksum(Checksum::Crc32, Endian::Little);
+        let bytes = frame.encode(&value).unwrap();
+        assert_eq!(&bytes[..5], &[b'C', b'A', b'T', 0, 6]);
+        assert_eq!(bytes.len(), 3 + 2 + 6 + 4);
+        assert_eq!(frame.decode_value(&bytes).unwrap(), value);
+
+        let error = frame.decode_value(b"DOG\0\x06").unwrap_err();
+        assert_eq!((error.offset, error.kind), (0, CodecErrorKind::BadMagic));
+
+        let error = frame.decode_value(&bytes[..12]).unwrap_err();
+        assert_eq!(error.offset, 11);
+        assert!(matches!(error.kind, CodecErrorKind::UnexpectedEnd { .. }));
+
+        // Without a length prefix the record runs up to the checksum, and
+        // record errors are reported at their offset in the frame.
+        let frame = FrameLayout::new(record)
+            .magic(b"CAT")
+            .checksum(Checksum::Crc16, Endian::Big);
+        let mut bytes = frame.encode(&value).unwrap();
+        assert_eq!(bytes.len(), 3 + 6 + 2);
+        assert_eq!(frame.decode_value(&bytes).unwrap(), value);
+
+        bytes[5] = 9;
+        let len = bytes.len();
+        let checksum = Checksum::Crc16.compute(&bytes[3..len - 2]) as u16;
+        bytes[len - 2..].copy_from_slice(&checksum.to_be_bytes());
+        let error = frame.decode_value(&bytes).unwrap_err();
+        assert_eq!(error.offset, 6);
+        assert_eq!(error.path, "name");
+    }
+
+    #[test]
+    fn test_simple_checksums() {
+        assert_eq!(Checksum::Sum8.compute(&[200, 100]), 44);
+        assert_eq!(Checksum::Xor8.compute(&[0b1100, 0b1010]), 0b0110);
+    }
+}
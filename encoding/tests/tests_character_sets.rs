@@ -1,6 +1,11 @@
 use base64::{engine::general_purpose, Engine as _};
 use data_encoding::HEXUPPER;
-use encoding::{percent_decode_string, percent_encode_string};
+use encoding::{
+    decode_with_bom, form_urldecode, form_urlencode, percent_decode_string, percent_encode_string,
+    quoted_printable_decode, quoted_printable_encode, BaseEncoding, Charset, CharsetError,
+    DataValue,
+};
+use serde::Serialize;
 use url::form_urlencoded::{byte_serialize, parse};
 
 #[cfg(test)]
@@ -64,4 +69,261 @@ mod tests_character_sets {
         print!("decoded: {:?}\n", decoded);
         assert_eq!(decoded, original);
     }
+
+    #[test]
+    fn test_base_encodings_round_trip() {
+        let original = b"\xfb\xff\x00 rust?";
+
+        for (base, expected) in [
+            (BaseEncoding::Base16, "FBFF0020727573743F"),
+            (BaseEncoding::Base32, "7P7QAIDSOVZXIPY="),
+            (BaseEncoding::Base32NoPad, "7P7QAIDSOVZXIPY"),
+            (BaseEncoding::Base32Hex, "VFVG083IELPN8FO="),
+            (BaseEncoding::Base64, "+/8AIHJ1c3Q/"),
+            (BaseEncoding::Base64NoPad, "+/8AIHJ1c3Q/"),
+            (BaseEncoding::Base64Url, "-_8AIHJ1c3Q_"),
+            (BaseEncoding::Base64UrlNoPad, "-_8AIHJ1c3Q_"),
+            (BaseEncoding::Base58, "4D3JWqzpJmKYa"),
+        ] {
+            let encoded = base.encode(original);
+            assert_eq!(encoded, expected, "{:?}", base);
+            assert_eq!(base.decode(&encoded).unwrap(), original, "{:?}", base);
+        }
+
+        assert_eq!(
+            BaseEncoding::Base16.decode("fbff00").unwrap(),
+            vec![0xfb, 0xff, 0x00]
+        );
+        assert_eq!(BaseEncoding::Base64.encode(b"ab"), "YWI=");
+        assert_eq!(BaseEncoding::Base64NoPad.encode(b"ab"), "YWI");
+        assert!(matches!(
+            BaseEncoding::Base64.decode("YWI"),
+            Err(CharsetError::Base(_))
+        ));
+        assert!(matches!(
+            BaseEncoding::Base58.decode("0OIl"),
+            Err(CharsetError::Base58(_))
+        ));
+    }
+
+    #[test]
+    fn test_quoted_printable() {
+        let text = "Grüße aus Köln, ".repeat(6);
+
+        let encoded = quoted_printable_encode(text.as_bytes());
+        println!("encoded: {}", encoded);
+        assert!(encoded.starts_with("Gr=C3=BC=C3=9Fe aus K=C3=B6ln,"));
+        assert!(encoded.lines().all(|line| line.len() <= 76));
+
+        let decoded = quoted_printable_decode(&encoded);
+        assert_eq!(String::from_utf8(decoded).unwrap(), text);
+
+        assert_eq!(quoted_printable_decode("100=ZZ =3d=\n ok"), b"100=ZZ = ok");
+    }
+
+    #[derive(Serialize)]
+    struct Order {
+        id: u32,
+        customer: Customer,
+        items: Vec<Item>,
+        note: Option<String>,
+    }
+
+    #[derive(Serialize)]
+    struct Customer {
+        name: String,
+        email: String,
+    }
+
+    #[derive(Serialize)]
+    struct Item {
+        sku: String,
+        quantity: u32,
+    }
+
+    #[test]
+    fn test_nested_form_urlencoded() {
+        let order = Order {
+            id: 7,
+            customer: Customer {
+                name: "Ana & Tom".to_string(),
+                email: "ana@example.com".to_string(),
+            },
+            items: vec![
+                Item {
+                    sku: "A-1".to_string(),
+                    quantity: 2,
+                },
+                Item {
+                    sku: "B 2".to_string(),
+                    quantity: 1,
+                },
+            ],
+            note: None,
+        };
+
+        let encoded = form_urlencode(&order).unwrap();
+        println!("encoded: {}", encoded);
+        assert_eq!(
+            encoded,
+            "id=7&customer%5Bname%5D=Ana+%26+Tom&customer%5Bemail%5D=ana%40example.com\
+             &items%5B0%5D%5Bsku%5D=A-1&items%5B0%5D%5Bquantity%5D=2\
+             &items%5B1%5D%5Bsku%5D=B+2&items%5B1%5D%5Bquantity%5D=1&note="
+        );
+
+        let decoded = form_urldecode(&encoded);
+        let text = |value: &str| DataValue::String(value.to_string());
+        assert_eq!(
+            decoded,
+            DataValue::Object(vec![
+                ("id".to_string(), text("7")),
+                (
+                    "customer".to_string(),
+                    DataValue::Object(vec![
+                        ("name".to_string(), text("Ana & Tom")),
+                        ("email".to_string(), text("ana@example.com")),
+                    ])
+                ),
+                (
+                    "items".to_string(),
+                    DataValue::Array(vec![
+                        DataValue::Object(vec![
+                            ("sku".to_string(), text("A-1")),
+                            ("quantity".to_string(), text("2")),
+                        ]),
+                        DataValue::Object(vec![
+                            ("sku".to_string(), text("B 2")),
+                            ("quantity".to_string(), text("1")),
+                        ]),
+                    ])
+                ),
+                ("note".to_string(), text("")),
+            ])
+        );
+
+        assert!(matches!(
+            form_urlencode(&vec![1, 2]),
+            Err(CharsetError::NotAnObject)
+        ));
+    }
+
+    #[test]
+    fn test_form_urldecode_edge_cases() {
+        let text = |value: &str| DataValue::String(value.to_string());
+
+        let decoded = form_urldecode("a=1&a=2&b[]=x&b[]=y&c=3&c[d]=4&e[=5&f[x]y=6");
+        assert_eq!(decoded.get("a"), Some(&text("2")));
+        assert_eq!(
+            decoded.get("b"),
+            Some(&DataValue::Array(vec![text("x"), text("y")]))
+        );
+        assert_eq!(decoded.get("c"), Some(&text("3")));
+        assert_eq!(decoded.get("c[d]"), Some(&text("4")));
+        assert_eq!(decoded.get("e["), Some(&text("5")));
+        assert_eq!(decoded.get("f[x]y"), Some(&text("6")));
+
+        let decoded = form_urldecode("m[1]=b&m[0]=a");
+        assert_eq!(
+            decoded.get("m"),
+            Some(&DataValue::Object(vec![
+                ("1".to_string(), text("b")),
+                ("0".to_string(), text("a")),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_form_urldecode_caps_nesting_depth() {
+        let decoded = form_urldecode(&format!("a{}=1", "[b]".repeat(200_000)));
+        let mut value = decoded.get("a").unwrap();
+        for _ in 0..32 {
+            value = value.get("b").unwrap();
+        }
+        assert_eq!(
+            value,
+            &DataValue::Object(vec![(
+                "[b]".repeat(200_000 - 32),
+                DataValue::String("1".to_string())
+            )])
+        );
+
+        let decoded = form_urldecode(&format!("a{}=1", "[0]".repeat(32)));
+        let mut value = decoded.get("a").unwrap();
+        for _ in 0..32 {
+            let DataValue::Array(values) = value else {
+                panic!("expected an array, found {:?}", value);
+            };
+            value = &values[0];
+        }
+        assert_eq!(value, &DataValue::String("1".to_string()));
+    }
+
+    #[test]
+    fn test_legacy_charsets() {
+        assert_eq!(Charset::from_label(" Latin1 "), Some(Charset::Latin1));
+        assert_eq!(Charset::from_label("SJIS"), Some(Charset::ShiftJis));
+        assert_eq!(Charset::from_label("ebcdic"), None);
+
+        // Latin-1 keeps C1 controls that windows-1252 maps to punctuation.
+        assert_eq!(
+            Charset::Latin1.decode(b"\x93ok\x94").unwrap(),
+            "\u{93}ok\u{94}"
+        );
+        assert_eq!(
+            Charset::Windows1252.decode(b"\x93ok\x94").unwrap(),
+            "\u{201c}ok\u{201d}"
+        );
+        assert!(matches!(
+            Charset::Latin1.encode("3€"),
+            Err(CharsetError::Unmappable {
+                charset: Charset::Latin1,
+                character: '€'
+            })
+        ));
+
+        let japanese = "東京タワー, ｶﾀｶﾅ";
+        let bytes = Charset::ShiftJis.encode(japanese).unwrap();
+        assert_eq!(&bytes[..4], b"\x93\x8c\x8b\x9e");
+        assert_eq!(Charset::ShiftJis.decode(&bytes).unwrap(), japanese);
+
+        let error = Charset::ShiftJis.decode(b"ok\x82\xff").unwrap_err();
+        assert_eq!(error.to_string(), "Malformed Shift_JIS at byte 2");
+        let error = Charset::Utf8.decode(b"caf\xe9").unwrap_err();
+        assert_eq!(error.to_string(), "Malformed UTF-8 at byte 3");
+        let error = Charset::ShiftJis.encode("café").unwrap_err();
+        assert_eq!(error.to_string(), "'é' can't be encoded in Shift_JIS");
+
+        let text = "naïve 🦀";
+        for charset in [Charset::Utf8, Charset::Utf16Le, Charset::Utf16Be] {
+            let bytes = charset.encode(text).unwrap();
+            assert_eq!(charset.decode(&bytes).unwrap(), text, "{:?}", charset);
+        }
+        assert!(Charset::Utf16Le.decode(b"a\x00b").is_err());
+    }
+
+    #[test]
+    fn test_decode_with_bom() {
+        let text = "Niño";
+
+        let mut utf16_be = vec![0xfe, 0xff];
+        utf16_be.extend(Charset::Utf16Be.encode(text).unwrap());
+        assert_eq!(
+            decode_with_bom(&utf16_be, Charset::Windows1252).unwrap(),
+            (text.to_string(), Charset::Utf16Be)
+        );
+
+        let mut utf8 = vec![0xef, 0xbb, 0xbf];
+        utf8.extend(text.as_bytes());
+        assert_eq!(
+            decode_with_bom(&utf8, Charset::Windows1252).unwrap(),
+            (text.to_string(), Charset::Utf8)
+        );
+
+        let windows_1252 = Charset::Windows1252.encode(text).unwrap();
+        assert_eq!(
+            decode_with_bom(&windows_1252, Charset::Windows1252).unwrap(),
+            (text.to_string(), Charset::Windows1252)
+        );
+        assert!(decode_with_bom(&windows_1252, Charset::Utf8).is_err());
+    }
 }
@@ -1,9 +1,11 @@
 use csv::{Reader, Writer};
 use encoding::{
     filter_csv_records_matching_predicate, read_csv_records, read_csv_records_custom_delimiter,
-    MemeCoin, Row, Steak, Token,
+    regex::Regex, ColumnRule, ColumnType, CsvPipeline, CsvPipelineError, CsvSchema, MemeCoin,
+    RejectedRow, Row, Steak, Token,
 };
-use std::io::stdout;
+use serde::Deserialize;
+use std::{fs, io::stdout};
 
 #[cfg(test)]
 mod tests_csv_processing {
@@ -148,4 +150,226 @@ mod tests_csv_processing {
         print!("{:?}", written);
         assert_eq!(Some("Blue,0,0,255"), written.lines().last(),);
     }
+
+    fn token_pipeline() -> CsvPipeline {
+        CsvPipeline::new(
+            CsvSchema::new()
+                .column(ColumnRule::new("chain", ColumnType::Text).required())
+                .column(
+                    ColumnRule::new("ticker", ColumnType::Text)
+                        .required()
+                        .pattern(Regex::new("^[A-Z]{2,5}$").unwrap()),
+                )
+                .column(
+                    ColumnRule::new("price", ColumnType::Float)
+                        .required()
+                        .range(0.0, 100000.0),
+                ),
+        )
+    }
+
+    #[test]
+    fn test_csv_pipeline_rejects_invalid_rows() {
+        let csv_data = "chain,name,ticker,price
+Ethereum,Ether,ETH,3000.0
+Bitcoin,Bitcoin,BTC,free
+Solana,Solana,sol,-5
+,Dogecoin,DOGE,0.1
+Polygon,POL
+Cardano,Ada,ADA,0.35
+";
+        let (mut output, mut rejects) = (Vec::new(), Vec::new());
+        let report = token_pipeline()
+            .run::<Token, _, _, _>(csv_data.as_bytes(), &mut output, &mut rejects)
+            .unwrap();
+
+        let tickers: Vec<&str> = report.records.iter().map(|t| t.ticker.as_str()).collect();
+        assert_eq!(tickers, vec!["ETH", "ADA"]);
+        assert_eq!(report.written, 2);
+        assert_eq!(report.filtered, 0);
+        assert_eq!(
+            report.rejected,
+            vec![
+                RejectedRow {
+                    line: 3,
+                    reason: "price: \"free\" is not a number".to_string()
+                },
+                RejectedRow {
+                    line: 4,
+                    reason: "ticker: \"sol\" does not match ^[A-Z]{2,5}$; \
+                             price: -5 is below the minimum 0"
+                        .to_string()
+                },
+                RejectedRow {
+                    line: 5,
+                    reason: "chain: missing value".to_string()
+                },
+                RejectedRow {
+                    line: 6,
+                    reason: "expected 4 fields, found 2".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "chain,name,ticker,price\nEthereum,Ether,ETH,3000.0\nCardano,Ada,ADA,0.35\n"
+        );
+
+        let rejects = String::from_utf8(rejects).unwrap();
+        let lines: Vec<&str> = rejects.lines().collect();
+        assert_eq!(lines[0], "line,reason,chain,name,ticker,price");
+        assert_eq!(
+            lines[1],
+            "3,\"price: \"\"free\"\" is not a number\",Bitcoin,Bitcoin,BTC,free"
+        );
+        assert_eq!(lines[4], "6,\"expected 4 fields, found 2\",Polygon,POL");
+    }
+
+    #[test]
+    fn test_csv_pipeline_run_with_rejects_non_finite_numbers() {
+        let csv_data = "chain,name,ticker,price
+Ethereum,Ether,ETH,3000.0
+Bitcoin,Bitcoin,BTC,NaN
+Solana,Solana,SOL,inf
+Cardano,Ada,ADA,0.35
+";
+        let mut tickers = Vec::new();
+        let report = token_pipeline()
+            .run_with::<Token, _, _, _, _>(csv_data.as_bytes(), Vec::new(), Vec::new(), |token| {
+                tickers.push(token.ticker)
+            })
+            .unwrap();
+
+        assert_eq!(tickers, vec!["ETH", "ADA"]);
+        assert!(report.records.is_empty());
+        assert_eq!(report.written, 2);
+        let reasons: Vec<&str> = report
+            .rejected
+            .iter()
+            .map(|row| row.reason.as_str())
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                "price: \"NaN\" is not a finite number",
+                "price: \"inf\" is not a finite number"
+            ]
+        );
+        assert!(ColumnRule::new("price", ColumnType::Float)
+            .max(1.0)
+            .check("-inf")
+            .is_err());
+        assert!(ColumnRule::new("price", ColumnType::Float)
+            .check("NaN")
+            .is_ok());
+    }
+
+    #[test]
+    fn test_csv_pipeline_map_filter_select() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Listing {
+            ticker: String,
+            price: f64,
+        }
+
+        let csv_data = "chain;name;ticker;price
+Ethereum;Ether;eth;3000.0
+Bitcoin;Bitcoin;btc;60000.0
+Ethereum;Pepe;pepe;0.00001
+";
+        let (mut output, mut rejects) = (Vec::new(), Vec::new());
+        let report = CsvPipeline::new(CsvSchema::new())
+            .delimiter(b';')
+            .filter("chain", |chain| chain == "Ethereum")
+            .map("ticker", |ticker| ticker.to_uppercase())
+            .filter("price", |price| price.parse::<f64>().unwrap() >= 1.0)
+            .select(&["ticker", "price"])
+            .run::<Listing, _, _, _>(csv_data.as_bytes(), &mut output, &mut rejects)
+            .unwrap();
+
+        assert_eq!(
+            report.records,
+            vec![Listing {
+                ticker: "ETH".to_string(),
+                price: 3000.0
+            }]
+        );
+        assert_eq!(report.filtered, 2);
+        assert!(report.rejected.is_empty());
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "ticker;price\nETH;3000.0\n"
+        );
+        assert_eq!(
+            String::from_utf8(rejects).unwrap(),
+            "line;reason;chain;name;ticker;price\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_pipeline_rejects_rows_that_fail_to_deserialize() {
+        let csv_data = "name,price,id\nT-bone,20.0,1\nPicanha,cheap,2\nRibeye,30.0,none\n";
+        let report = CsvPipeline::new(CsvSchema::new())
+            .run::<Steak, _, _, _>(csv_data.as_bytes(), Vec::new(), Vec::new())
+            .unwrap();
+
+        assert_eq!(report.records.len(), 2);
+        assert_eq!(report.records[0].id, Some(1));
+        assert_eq!(report.records[1].name, "Ribeye");
+        assert_eq!(report.records[1].id, None);
+        assert_eq!(report.rejected.len(), 1);
+        assert_eq!(report.rejected[0].line, 3);
+        assert_eq!(
+            report.rejected[0].reason,
+            "price: invalid float literal".to_string()
+        );
+    }
+
+    #[test]
+    fn test_csv_pipeline_header_errors() {
+        let csv_data = "chain,name,price\nEthereum,Ether,3000.0\n";
+        let result =
+            token_pipeline().run::<Token, _, _, _>(csv_data.as_bytes(), Vec::new(), Vec::new());
+        assert!(
+            matches!(result, Err(CsvPipelineError::MissingColumn(column)) if column == "ticker")
+        );
+
+        let result = CsvPipeline::new(CsvSchema::new())
+            .select(&["name", "volume"])
+            .run::<Token, _, _, _>(csv_data.as_bytes(), Vec::new(), Vec::new());
+        assert!(
+            matches!(result, Err(CsvPipelineError::UnknownColumn(column)) if column == "volume")
+        );
+    }
+
+    #[test]
+    fn test_csv_pipeline_run_files() {
+        let dir = std::env::temp_dir().join("encoding_csv_pipeline");
+        fs::create_dir_all(&dir).unwrap();
+        let (input, output, rejects) = (
+            dir.join("tokens.csv"),
+            dir.join("valid.csv"),
+            dir.join("rejects.csv"),
+        );
+        fs::write(
+            &input,
+            "chain,name,ticker,price\nEthereum,Ether,ETH,3000.0\nBitcoin,Bitcoin,BTC,\n",
+        )
+        .unwrap();
+
+        let report = token_pipeline()
+            .run_files::<Token>(&input, &output, &rejects)
+            .unwrap();
+
+        assert_eq!(report.records.len(), 1);
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            "chain,name,ticker,price\nEthereum,Ether,ETH,3000.0\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&rejects).unwrap(),
+            "line,reason,chain,name,ticker,price\n3,price: missing value,Bitcoin,Bitcoin,BTC,\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,262 @@
+use csv::{ReaderBuilder, StringRecord};
+use encoding::{Aggregate, CsvStream, CsvStreamError, JoinKind, SortKey, SortOptions};
+use std::fs;
+
+#[cfg(test)]
+mod tests_csv_streaming {
+    use super::*;
+
+    const TOKENS: &str = "chain,ticker,price
+Solana,SOL,150
+Ethereum,ETH,3000
+Bitcoin,BTC,60000
+Ethereum,PEPE,0.00001
+Solana,WIF,2.5
+Ethereum,ETH,3000
+";
+
+    fn to_csv(stream: CsvStream) -> String {
+        let mut output = Vec::new();
+        stream.write(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_sort_in_memory() {
+        let stream = CsvStream::from_reader(TOKENS.as_bytes())
+            .unwrap()
+            .sort(&[SortKey::new("price").numeric().descending()])
+            .unwrap();
+
+        assert_eq!(
+            to_csv(stream),
+            "chain,ticker,price
+Bitcoin,BTC,60000
+Ethereum,ETH,3000
+Ethereum,ETH,3000
+Solana,SOL,150
+Solana,WIF,2.5
+Ethereum,PEPE,0.00001
+"
+        );
+    }
+
+    #[test]
+    fn test_external_sort_is_stable_and_cleans_up() {
+        let temp_dir = std::env::temp_dir().join("encoding_external_sort");
+        fs::create_dir_all(&temp_dir).unwrap();
+        let options = SortOptions {
+            max_rows_in_memory: 2,
+            temp_dir: temp_dir.clone(),
+        };
+
+        let stream = CsvStream::from_reader(TOKENS.as_bytes())
+            .unwrap()
+            .sort_with(&[SortKey::new("chain")], &options)
+            .unwrap();
+        assert_eq!(fs::read_dir(&temp_dir).unwrap().count(), 3);
+
+        let tickers: Vec<String> = stream
+            .map(|record| record.unwrap()[1].to_string())
+            .collect();
+        assert_eq!(tickers, vec!["BTC", "ETH", "PEPE", "ETH", "SOL", "WIF"]);
+        assert_eq!(fs::read_dir(&temp_dir).unwrap().count(), 0);
+        fs::remove_dir(&temp_dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_external_sort_ignores_planted_symlinks() {
+        let temp_dir = std::env::temp_dir().join("encoding_external_sort_symlinks");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let victim = std::env::temp_dir().join("encoding_external_sort_victim.txt");
+        fs::write(&victim, "keep me").unwrap();
+        for index in 0..8 {
+            let name = format!("csv-sort-{}-{}.csv", std::process::id(), index);
+            std::os::unix::fs::symlink(&victim, temp_dir.join(name)).unwrap();
+        }
+        let options = SortOptions {
+            max_rows_in_memory: 2,
+            temp_dir: temp_dir.clone(),
+        };
+
+        let stream = CsvStream::from_reader(TOKENS.as_bytes())
+            .unwrap()
+            .sort_with(&[SortKey::new("chain")], &options)
+            .unwrap();
+        assert_eq!(stream.count(), 6);
+        assert_eq!(fs::read_to_string(&victim).unwrap(), "keep me");
+        assert_eq!(fs::read_dir(&temp_dir).unwrap().count(), 8);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+        fs::remove_file(&victim).unwrap();
+    }
+
+    #[test]
+    fn test_sort_unknown_column() {
+        let result = CsvStream::from_reader(TOKENS.as_bytes())
+            .unwrap()
+            .sort(&[SortKey::new("volume")]);
+        assert!(matches!(result, Err(CsvStreamError::UnknownColumn(column)) if column == "volume"));
+    }
+
+    #[test]
+    fn test_dedup_and_distinct() {
+        let sorted = CsvStream::from_reader(TOKENS.as_bytes())
+            .unwrap()
+            .sort(&[SortKey::new("ticker")])
+            .unwrap()
+            .dedup(&[])
+            .unwrap();
+        assert_eq!(sorted.count(), 5);
+
+        let distinct = CsvStream::from_reader(TOKENS.as_bytes())
+            .unwrap()
+            .distinct(&["chain"])
+            .unwrap();
+        assert_eq!(
+            to_csv(distinct),
+            "chain,ticker,price\nSolana,SOL,150\nEthereum,ETH,3000\nBitcoin,BTC,60000\n"
+        );
+    }
+
+    #[test]
+    fn test_hash_join() {
+        let chains = "name,consensus\nEthereum,Proof of Stake\nBitcoin,Proof of Work\n";
+        let joined = |kind| {
+            CsvStream::from_reader(TOKENS.as_bytes())
+                .unwrap()
+                .hash_join(
+                    CsvStream::from_reader(chains.as_bytes())
+                        .unwrap()
+                        .rename("name", "chain")
+                        .unwrap(),
+                    &["chain"],
+                    kind,
+                )
+                .unwrap()
+        };
+
+        let inner = to_csv(joined(JoinKind::Inner));
+        let lines: Vec<&str> = inner.lines().collect();
+        assert_eq!(lines[0], "chain,ticker,price,consensus");
+        assert_eq!(lines[1], "Ethereum,ETH,3000,Proof of Stake");
+        assert_eq!(lines.len(), 5);
+
+        let left = to_csv(joined(JoinKind::Left));
+        assert_eq!(left.lines().count(), 7);
+        assert_eq!(left.lines().nth(1), Some("Solana,SOL,150,"));
+    }
+
+    #[test]
+    fn test_merge_join_many_to_many() {
+        let left = "id,name\n1,Tom\n2,Garfield\n2,Nermal\n4,Felix\n";
+        let right = "id,toy\n1,ball\n2,lasagna\n2,mouse\n3,yarn\n";
+
+        let stream = CsvStream::from_reader(left.as_bytes())
+            .unwrap()
+            .merge_join(
+                CsvStream::from_reader(right.as_bytes()).unwrap(),
+                &[SortKey::new("id").numeric()],
+                JoinKind::Left,
+            )
+            .unwrap();
+
+        assert_eq!(
+            to_csv(stream),
+            "id,name,toy
+1,Tom,ball
+2,Garfield,lasagna
+2,Garfield,mouse
+2,Nermal,lasagna
+2,Nermal,mouse
+4,Felix,
+"
+        );
+    }
+
+    #[test]
+    fn test_merge_join_unsorted_input() {
+        let left = "id,name\n2,Garfield\n1,Tom\n";
+        let right = "id,toy\n1,ball\n2,lasagna\n";
+
+        let result: Result<Vec<StringRecord>, _> = CsvStream::from_reader(left.as_bytes())
+            .unwrap()
+            .merge_join(
+                CsvStream::from_reader(right.as_bytes()).unwrap(),
+                &[SortKey::new("id")],
+                JoinKind::Inner,
+            )
+            .unwrap()
+            .collect();
+
+        assert!(matches!(result, Err(CsvStreamError::Unsorted(input)) if input == "left"));
+    }
+
+    #[test]
+    fn test_group_by() {
+        let aggregates = [
+            Aggregate::Count,
+            Aggregate::sum("price"),
+            Aggregate::min("price"),
+            Aggregate::max("price"),
+            Aggregate::mean("price"),
+        ];
+
+        let grouped = CsvStream::from_reader(TOKENS.as_bytes())
+            .unwrap()
+            .group_by(&["chain"], &aggregates)
+            .unwrap();
+        assert_eq!(
+            to_csv(grouped),
+            "chain,count,sum_price,min_price,max_price,mean_price
+Solana,2,152.5,2.5,150,76.25
+Ethereum,3,6000.00001,0.00001,3000,2000.0000033333333
+Bitcoin,1,60000,60000,60000,60000
+"
+        );
+
+        let totals = CsvStream::from_reader(TOKENS.as_bytes())
+            .unwrap()
+            .group_by(&[], &[Aggregate::Count])
+            .unwrap();
+        assert_eq!(to_csv(totals), "count\n6\n");
+    }
+
+    #[test]
+    fn test_group_by_sorted_with_delimiter() {
+        let csv_data = "city;temperature\nOslo;-3\nOslo;\nOslo;1\nLima;18\nOslo;5\n";
+        let reader = ReaderBuilder::new()
+            .delimiter(b';')
+            .from_reader(csv_data.as_bytes());
+
+        let grouped = CsvStream::from_csv_reader(reader)
+            .unwrap()
+            .group_by_sorted(
+                &["city"],
+                &[Aggregate::Count, Aggregate::mean("temperature")],
+            )
+            .unwrap();
+
+        assert_eq!(
+            to_csv(grouped),
+            "city,count,mean_temperature\nOslo,3,-1\nLima,1,18\nOslo,1,5\n"
+        );
+    }
+
+    #[test]
+    fn test_group_by_invalid_number() {
+        let result: Result<Vec<StringRecord>, _> =
+            CsvStream::from_reader("chain,price\nSolana,cheap\n".as_bytes())
+                .unwrap()
+                .group_by_sorted(&["chain"], &[Aggregate::sum("price")])
+                .unwrap()
+                .collect();
+
+        assert!(matches!(
+            result,
+            Err(CsvStreamError::InvalidNumber { column, value }) if column == "price" && value == "cheap"
+        ));
+    }
+}
@@ -0,0 +1,258 @@
+use encoding::{ColumnType, ConvertError, DataFormat, DataValue, FormatConverter};
+use std::{fs, path::Path};
+
+#[cfg(test)]
+mod tests_format_conversion {
+    use super::*;
+
+    const CATS_JSON: &str = r#"[
+        {"name": "Garfield", "age": 45, "owner": {"name": "Jon", "city": "Muncie"}, "toys": ["lasagna", "teddy"]},
+        {"name": "Tom", "age": 84.5, "owner": {"name": "Mammy"}, "indoor": false}
+    ]"#;
+
+    #[test]
+    fn test_json_to_csv_flattens_nested_fields() {
+        let csv = FormatConverter::new()
+            .convert(CATS_JSON, DataFormat::Json, DataFormat::Csv)
+            .unwrap();
+
+        assert_eq!(
+            csv,
+            "name,age,owner.name,owner.city,toys.0,toys.1,indoor
+Garfield,45,Jon,Muncie,lasagna,teddy,
+Tom,84.5,Mammy,,,,false
+"
+        );
+    }
+
+    #[test]
+    fn test_csv_to_json_rebuilds_nested_fields() {
+        let csv = FormatConverter::new()
+            .convert(CATS_JSON, DataFormat::Json, DataFormat::Csv)
+            .unwrap();
+        let cats = FormatConverter::new().read(&csv, DataFormat::Csv).unwrap();
+
+        let DataValue::Array(cats) = cats else {
+            panic!("CSV records are an array");
+        };
+        assert_eq!(cats[0].get("age"), Some(&DataValue::Integer(45)));
+        assert_eq!(
+            cats[0].get("toys"),
+            Some(&DataValue::Array(vec![
+                DataValue::String("lasagna".to_string()),
+                DataValue::String("teddy".to_string()),
+            ]))
+        );
+        assert_eq!(
+            cats[1].get("owner").and_then(|owner| owner.get("city")),
+            Some(&DataValue::Null)
+        );
+        assert_eq!(cats[1].get("indoor"), Some(&DataValue::Bool(false)));
+    }
+
+    #[test]
+    fn test_no_nesting_writes_json_fields() {
+        let csv = FormatConverter::new()
+            .no_nesting()
+            .delimiter(b';')
+            .convert(CATS_JSON, DataFormat::Json, DataFormat::Csv)
+            .unwrap();
+
+        let header = csv.lines().next().unwrap();
+        assert_eq!(header, "name;age;owner;toys;indoor");
+        assert!(csv.contains(r#"Garfield;45;"{""name"":""Jon"",""city"":""Muncie""}";"#));
+    }
+
+    #[test]
+    fn test_empty_separator_means_no_nesting() {
+        let converter = FormatConverter::new()
+            .separator("")
+            .coerce("owner.name", ColumnType::Text);
+        let csv = converter
+            .convert(CATS_JSON, DataFormat::Json, DataFormat::Csv)
+            .unwrap();
+        assert_eq!(
+            csv,
+            FormatConverter::new()
+                .no_nesting()
+                .convert(CATS_JSON, DataFormat::Json, DataFormat::Csv)
+                .unwrap()
+        );
+
+        let cats = converter
+            .read("owner.name,age\nJon,45\n", DataFormat::Csv)
+            .unwrap();
+        let DataValue::Array(cats) = cats else {
+            panic!("CSV records are an array");
+        };
+        assert_eq!(
+            cats[0].get("owner.name"),
+            Some(&DataValue::String("Jon".to_string()))
+        );
+        assert_eq!(cats[0].get("age"), Some(&DataValue::Integer(45)));
+    }
+
+    #[test]
+    fn test_json_lines_round_trip() {
+        let converter = FormatConverter::new();
+        let lines = converter
+            .convert(CATS_JSON, DataFormat::Json, DataFormat::JsonLines)
+            .unwrap();
+        assert_eq!(lines.lines().count(), 2);
+        assert!(lines.starts_with(r#"{"name":"Garfield","age":45,"owner":"#));
+
+        let json = converter
+            .convert(&lines, DataFormat::JsonLines, DataFormat::Json)
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+            serde_json::from_str::<serde_json::Value>(CATS_JSON).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_toml_to_yaml_and_json() {
+        let toml_data = r#"
+            [package]
+            name = "encoding"
+            version = "0.1.0"
+            authors = ["Jane Doe"]
+            released = 2024-08-01T10:00:00Z
+
+            [dependencies]
+            serde = "1.0"
+        "#;
+        let converter = FormatConverter::new();
+
+        let yaml = converter
+            .convert(toml_data, DataFormat::Toml, DataFormat::Yaml)
+            .unwrap();
+        assert_eq!(
+            yaml,
+            "package:
+  name: encoding
+  version: 0.1.0
+  authors:
+  - Jane Doe
+  released: 2024-08-01T10:00:00Z
+dependencies:
+  serde: '1.0'
+"
+        );
+
+        let json = FormatConverter::new()
+            .root_key("package")
+            .flatten(true)
+            .convert(toml_data, DataFormat::Toml, DataFormat::Json)
+            .unwrap();
+        assert_eq!(
+            json,
+            r#"{"package":{"name":"encoding","version":"0.1.0","authors.0":"Jane Doe","released":"2024-08-01T10:00:00Z"}}"#
+        );
+    }
+
+    #[test]
+    fn test_yaml_to_toml_with_root_key() {
+        let yaml = "- name: Garfield\n  owner: null\n  age: 45\n- name: Tom\n  age: 84\n";
+
+        let toml = FormatConverter::new()
+            .root_key("cats")
+            .convert(yaml, DataFormat::Yaml, DataFormat::Toml)
+            .unwrap();
+        assert_eq!(
+            toml,
+            "[[cats]]\nname = \"Garfield\"\nage = 45\n\n[[cats]]\nname = \"Tom\"\nage = 84\n"
+        );
+
+        let cats = FormatConverter::new()
+            .root_key("cats")
+            .read(&toml, DataFormat::Toml)
+            .unwrap();
+        assert!(matches!(cats, DataValue::Array(cats) if cats.len() == 2));
+
+        let result = FormatConverter::new().convert(yaml, DataFormat::Yaml, DataFormat::Toml);
+        assert!(matches!(result, Err(ConvertError::Unsupported(_))));
+    }
+
+    #[test]
+    fn test_type_coercion() {
+        let csv_data = "id,price,zip,active\n007,20,02134,YES\n8,25.5,,true\n";
+
+        let converter = FormatConverter::new()
+            .infer_types(false)
+            .coerce("id", ColumnType::Integer)
+            .coerce("price", ColumnType::Float)
+            .coerce("zip", ColumnType::Integer);
+        let json = converter
+            .convert(
+                &csv_data.replace("YES", "false"),
+                DataFormat::Csv,
+                DataFormat::Json,
+            )
+            .unwrap();
+        assert_eq!(
+            json,
+            r#"[{"id":7,"price":20.0,"zip":2134,"active":"false"},{"id":8,"price":25.5,"zip":null,"active":"true"}]"#
+        );
+
+        let result = converter
+            .coerce("active", ColumnType::Boolean)
+            .read(csv_data, DataFormat::Csv);
+        assert!(matches!(
+            result,
+            Err(ConvertError::Coercion { path, value, expected: ColumnType::Boolean })
+                if path == "active" && value == "\"YES\""
+        ));
+
+        let json = FormatConverter::new()
+            .coerce("owner.name", ColumnType::Text)
+            .coerce("age", ColumnType::Integer)
+            .convert(
+                r#"{"age": 45.0, "owner": {"name": 42}}"#,
+                DataFormat::Json,
+                DataFormat::Json,
+            )
+            .unwrap();
+        assert_eq!(json, r#"{"age":45,"owner":{"name":"42"}}"#);
+    }
+
+    #[test]
+    fn test_csv_header_handling() {
+        let converter = FormatConverter::new()
+            .has_headers(false)
+            .write_headers(false);
+
+        let value = converter
+            .read("Garfield,45\nTom,84\n", DataFormat::Csv)
+            .unwrap();
+        let DataValue::Array(rows) = &value else {
+            panic!("CSV records are an array");
+        };
+        assert_eq!(
+            rows[1].get("column_1"),
+            Some(&DataValue::String("Tom".to_string()))
+        );
+
+        assert_eq!(
+            converter.write(&value, DataFormat::Csv).unwrap(),
+            "Garfield,45\nTom,84\n"
+        );
+    }
+
+    #[test]
+    fn test_convert_file() {
+        let dir = std::env::temp_dir().join("encoding_format_conversion");
+        fs::create_dir_all(&dir).unwrap();
+        let (input, output) = (dir.join("cats.json"), dir.join("cats.yml"));
+        fs::write(&input, r#"[{"name": "Garfield"}]"#).unwrap();
+
+        FormatConverter::new()
+            .convert_file(&input, &output)
+            .unwrap();
+        assert_eq!(fs::read_to_string(&output).unwrap(), "- name: Garfield\n");
+
+        let result = FormatConverter::new().convert_file(&input, Path::new("cats.txt"));
+        assert!(matches!(result, Err(ConvertError::UnknownFormat(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -29,7 +29,7 @@ use std::{
 pub fn compute_digest<P: AsRef<Path>>(filepath: P) -> Result<(Digest, P), Error> {
     let mut buf_reader = BufReader::new(File::open(&filepath)?);
     let mut context = Context::new(&SHA256);
-    let mut buffer = [0; 1024];
+    let mut buffer = vec![0; 64 * 1024];
 
     loop {
         let count = buf_reader.read(&mut buffer)?;